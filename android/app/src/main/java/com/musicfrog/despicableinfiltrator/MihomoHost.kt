@@ -6,14 +6,21 @@ import android.security.keystore.KeyGenParameterSpec
 import android.security.keystore.KeyProperties
 import android.util.Base64
 import android.util.Log
+import infiltrator_android.FfiErrorCode
+import infiltrator_android.proxySelectionsRestore
 import java.io.File
 import java.io.IOException
 import java.nio.ByteBuffer
 import java.security.KeyStore
+import java.util.concurrent.TimeUnit
 import javax.crypto.Cipher
 import javax.crypto.KeyGenerator
 import javax.crypto.SecretKey
 import javax.crypto.spec.GCMParameterSpec
+import kotlinx.coroutines.CoroutineScope
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.SupervisorJob
+import kotlinx.coroutines.launch
 
 private const val TAG = "MihomoHost"
 private const val ASSET_NAME = "mihomo/mihomo-android-arm64-v8"
@@ -27,15 +34,31 @@ private const val CREDENTIALS_PREFS = "credentials"
 private const val CREDENTIALS_KEY_ALIAS = "mfdi_credentials_key"
 private const val CREDENTIALS_FORMAT_PREFIX = "v1:"
 private const val CREDENTIALS_GCM_TAG_BITS = 128
+private const val CONFIG_TEST_TIMEOUT_SECONDS = 30L
 
 class MihomoHost(private val context: Context) : BridgeHost {
     private val processManager = MihomoProcessManager(context)
     private val credentialStore = AndroidCredentialStore(context)
+    private val scope = CoroutineScope(SupervisorJob() + Dispatchers.IO)
 
     override fun coreStart(): Boolean {
         ensureCoreAssets()
         val configFile = processManager.ensureConfigFile()
-        return processManager.start(configFile)
+        val started = processManager.start(configFile)
+        if (started) {
+            restoreProxySelections()
+        }
+        return started
+    }
+
+    // A fresh core starts on each group's default node; put the user's picks back.
+    private fun restoreProxySelections() {
+        scope.launch {
+            val status = proxySelectionsRestore()
+            if (status.code != FfiErrorCode.OK) {
+                Log.w(TAG, "Failed to restore proxy selections: ${status.message}")
+            }
+        }
     }
 
     private fun ensureCoreAssets() {
@@ -69,6 +92,10 @@ class MihomoHost(private val context: Context) : BridgeHost {
         return processManager.readControllerUrl() ?: CONTROLLER_URL
     }
 
+    override fun coreTestConfig(path: String): String? {
+        return processManager.testConfig(File(path))
+    }
+
     override fun credentialGet(service: String, key: String): String? {
         return credentialStore.get(service, key)
     }
@@ -190,6 +217,47 @@ private class MihomoProcessManager(private val context: Context) {
         return process?.isAlive == true
     }
 
+    fun testConfig(configFile: File): String? {
+        val binary = ensureBinary() ?: return "mihomo binary not found"
+        // Output goes to a file: reading the pipe would block as long as a hung core keeps it open
+        val outputFile = try {
+            File.createTempFile("mihomo-config-test", ".log", context.cacheDir)
+        } catch (err: IOException) {
+            return "test config failed: ${err.message}"
+        }
+        return try {
+            val p = ProcessBuilder(
+                binary.absolutePath,
+                "-t",
+                "-d",
+                context.filesDir.absolutePath,
+                "-f",
+                configFile.absolutePath
+            )
+                .directory(context.filesDir)
+                .redirectErrorStream(true)
+                .redirectOutput(outputFile)
+                .start()
+            if (!p.waitFor(CONFIG_TEST_TIMEOUT_SECONDS, TimeUnit.SECONDS)) {
+                p.destroyForcibly()
+                Log.w(TAG, "config test timed out after ${CONFIG_TEST_TIMEOUT_SECONDS}s")
+                return "config test timed out after ${CONFIG_TEST_TIMEOUT_SECONDS}s"
+            }
+            val exitCode = p.exitValue()
+            if (exitCode == 0) {
+                null
+            } else {
+                val output = outputFile.readText()
+                output.trim().ifEmpty { "config test exited with code $exitCode" }
+            }
+        } catch (err: Exception) {
+            Log.w(TAG, "test config failed: ${err.message}")
+            "test config failed: ${err.message}"
+        } finally {
+            outputFile.delete()
+        }
+    }
+
     fun ensureConfigFile(): File {
         val configDir = File(context.filesDir, CONFIG_DIR)
         if (!configDir.exists()) {
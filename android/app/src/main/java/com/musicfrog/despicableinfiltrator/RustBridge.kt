@@ -5,6 +5,8 @@ interface BridgeHost {
     fun coreStop(): Boolean
     fun coreIsRunning(): Boolean
     fun coreControllerUrl(): String?
+    /** Returns null when the config passes `mihomo -t`, otherwise the error output */
+    fun coreTestConfig(path: String): String?
     fun credentialGet(service: String, key: String): String?
     fun credentialSet(service: String, key: String, value: String): Boolean
     fun credentialDelete(service: String, key: String): Boolean
@@ -96,7 +96,7 @@ class RulesViewModel : ViewModel() {
             return
         }
         val entry = current[index]
-        current[index] = RuleEntryRecord(entry.rule, enabled)
+        current[index] = entry.copy(enabled = enabled)
         _state.value = _state.value.copy(rules = current, rulesSaved = false)
     }
 
@@ -9,8 +9,11 @@ import androidx.compose.foundation.layout.fillMaxWidth
 import androidx.compose.foundation.layout.height
 import androidx.compose.foundation.layout.padding
 import androidx.compose.foundation.layout.width
+import androidx.compose.foundation.rememberScrollState
+import androidx.compose.foundation.verticalScroll
 import androidx.compose.material3.Button
 import androidx.compose.material3.CircularProgressIndicator
+import androidx.compose.material3.FilterChip
 import androidx.compose.material3.MaterialTheme
 import androidx.compose.material3.OutlinedTextField
 import androidx.compose.material3.Switch
@@ -34,10 +37,11 @@ fun SyncScreen() {
     Column(
         modifier = Modifier
             .fillMaxSize()
+            .verticalScroll(rememberScrollState())
             .padding(16.dp),
         verticalArrangement = Arrangement.spacedBy(12.dp)
     ) {
-        Text(text = "WebDAV Sync", style = MaterialTheme.typography.titleLarge)
+        Text(text = "Sync", style = MaterialTheme.typography.titleLarge)
 
         if (state.isLoading) {
             Row(verticalAlignment = Alignment.CenterVertically) {
@@ -55,36 +59,95 @@ fun SyncScreen() {
         }
 
         ToggleRow(
-            title = "Enable WebDAV Sync",
+            title = "Enable Sync",
             checked = state.enabled,
             onCheckedChange = { viewModel.updateEnabled(it) },
             enabled = !state.isLoading
         )
 
-        OutlinedTextField(
-            value = state.url,
-            onValueChange = { viewModel.updateUrl(it) },
-            label = { Text("WebDAV URL") },
-            modifier = Modifier.fillMaxWidth(),
-            enabled = !state.isLoading
-        )
+        Row(horizontalArrangement = Arrangement.spacedBy(8.dp)) {
+            SyncBackendKind.values().forEach { kind ->
+                FilterChip(
+                    selected = state.backend == kind,
+                    onClick = { viewModel.updateBackend(kind) },
+                    label = { Text(kind.label) },
+                    enabled = !state.isLoading
+                )
+            }
+        }
 
-        OutlinedTextField(
-            value = state.username,
-            onValueChange = { viewModel.updateUsername(it) },
-            label = { Text("Username") },
-            modifier = Modifier.fillMaxWidth(),
-            enabled = !state.isLoading
-        )
+        when (state.backend) {
+            SyncBackendKind.WEBDAV -> OutlinedTextField(
+                value = state.url,
+                onValueChange = { viewModel.updateUrl(it) },
+                label = { Text("WebDAV URL") },
+                modifier = Modifier.fillMaxWidth(),
+                enabled = !state.isLoading
+            )
+            SyncBackendKind.LOCAL -> OutlinedTextField(
+                value = state.localPath,
+                onValueChange = { viewModel.updateLocalPath(it) },
+                label = { Text("Folder path") },
+                modifier = Modifier.fillMaxWidth(),
+                enabled = !state.isLoading
+            )
+            SyncBackendKind.S3 -> {
+                OutlinedTextField(
+                    value = state.s3Endpoint,
+                    onValueChange = { viewModel.updateS3Endpoint(it) },
+                    label = { Text("Endpoint") },
+                    modifier = Modifier.fillMaxWidth(),
+                    enabled = !state.isLoading
+                )
+                OutlinedTextField(
+                    value = state.s3Region,
+                    onValueChange = { viewModel.updateS3Region(it) },
+                    label = { Text("Region") },
+                    modifier = Modifier.fillMaxWidth(),
+                    enabled = !state.isLoading
+                )
+                OutlinedTextField(
+                    value = state.s3Bucket,
+                    onValueChange = { viewModel.updateS3Bucket(it) },
+                    label = { Text("Bucket") },
+                    modifier = Modifier.fillMaxWidth(),
+                    enabled = !state.isLoading
+                )
+                OutlinedTextField(
+                    value = state.s3Prefix,
+                    onValueChange = { viewModel.updateS3Prefix(it) },
+                    label = { Text("Prefix") },
+                    modifier = Modifier.fillMaxWidth(),
+                    enabled = !state.isLoading
+                )
+                ToggleRow(
+                    title = "Path-style addressing",
+                    checked = state.s3PathStyle,
+                    onCheckedChange = { viewModel.updateS3PathStyle(it) },
+                    enabled = !state.isLoading
+                )
+            }
+        }
 
-        OutlinedTextField(
-            value = state.password,
-            onValueChange = { viewModel.updatePassword(it) },
-            label = { Text("Password") },
-            modifier = Modifier.fillMaxWidth(),
-            enabled = !state.isLoading,
-            visualTransformation = PasswordVisualTransformation()
-        )
+        if (state.backend != SyncBackendKind.LOCAL) {
+            val s3 = state.backend == SyncBackendKind.S3
+            OutlinedTextField(
+                value = state.username,
+                onValueChange = { viewModel.updateUsername(it) },
+                label = { Text(if (s3) "Access key" else "Username") },
+                modifier = Modifier.fillMaxWidth(),
+                enabled = !state.isLoading
+            )
+
+            OutlinedTextField(
+                value = state.password,
+                onValueChange = { viewModel.updatePassword(it) },
+                label = { Text(if (s3) "Secret key" else "Password") },
+                modifier = Modifier.fillMaxWidth(),
+                enabled = !state.isLoading,
+                visualTransformation = PasswordVisualTransformation()
+            )
+        }
 
         OutlinedTextField(
             value = state.syncInterval,
@@ -101,6 +164,42 @@ fun SyncScreen() {
             enabled = !state.isLoading
         )
 
+        ToggleRow(
+            title = "End-to-end encryption",
+            checked = state.encryptionEnabled,
+            onCheckedChange = { viewModel.updateEncryptionEnabled(it) },
+            enabled = !state.isLoading
+        )
+
+        if (state.encryptionEnabled) {
+            ToggleRow(
+                title = "Hide file names",
+                checked = state.obfuscateNames,
+                onCheckedChange = { viewModel.updateObfuscateNames(it) },
+                enabled = !state.isLoading
+            )
+
+            Row(
+                modifier = Modifier.fillMaxWidth(),
+                verticalAlignment = Alignment.CenterVertically
+            ) {
+                OutlinedTextField(
+                    value = state.passphrase,
+                    onValueChange = { viewModel.updatePassphrase(it) },
+                    label = { Text("Passphrase") },
+                    modifier = Modifier.weight(1f),
+                    enabled = !state.isLoading,
+                    visualTransformation = PasswordVisualTransformation()
+                )
+                TextButton(
+                    onClick = { viewModel.setPassphrase() },
+                    enabled = !state.isLoading && state.passphrase.isNotEmpty()
+                ) {
+                    Text(text = "Set")
+                }
+            }
+        }
+
         Row(
             modifier = Modifier.fillMaxWidth(),
             horizontalArrangement = Arrangement.spacedBy(12.dp)
@@ -7,8 +7,10 @@ import com.musicfrog.despicableinfiltrator.ui.common.LONG_FFI_TIMEOUT_MS
 import com.musicfrog.despicableinfiltrator.ui.common.runFfiCall
 import com.musicfrog.despicableinfiltrator.ui.common.userMessage
 import infiltrator_android.FfiErrorCode
+import infiltrator_android.SyncBackendSettings
 import infiltrator_android.WebDavSettings
 import infiltrator_android.webdavSettings
+import infiltrator_android.webdavSetPassphrase
 import infiltrator_android.webdavSettingsSave
 import infiltrator_android.webdavSyncNow
 import infiltrator_android.webdavTest
@@ -17,13 +19,29 @@ import kotlinx.coroutines.flow.StateFlow
 import kotlinx.coroutines.flow.asStateFlow
 import kotlinx.coroutines.launch
 
+enum class SyncBackendKind(val label: String) {
+    WEBDAV("WebDAV"),
+    LOCAL("Local folder"),
+    S3("S3")
+}
+
 data class SyncUiState(
     val enabled: Boolean = false,
+    val backend: SyncBackendKind = SyncBackendKind.WEBDAV,
     val url: String = "",
+    val localPath: String = "",
+    val s3Endpoint: String = "",
+    val s3Region: String = "",
+    val s3Bucket: String = "",
+    val s3Prefix: String = "",
+    val s3PathStyle: Boolean = false,
     val username: String = "",
     val password: String = "",
     val syncInterval: String = "60",
     val syncOnStartup: Boolean = false,
+    val encryptionEnabled: Boolean = false,
+    val obfuscateNames: Boolean = false,
+    val passphrase: String = "",
     val isLoading: Boolean = false,
     val error: String? = null,
     val saved: Boolean = false,
@@ -60,7 +78,7 @@ class SyncViewModel : ViewModel() {
             } else {
                 _state.value = _state.value.copy(
                     isLoading = false,
-                    error = result.status.userMessage("Failed to load WebDAV settings")
+                    error = result.status.userMessage("Failed to load sync settings")
                 )
             }
         }
@@ -70,10 +88,38 @@ class SyncViewModel : ViewModel() {
         _state.value = _state.value.copy(enabled = value, saved = false)
     }
 
+    fun updateBackend(value: SyncBackendKind) {
+        _state.value = _state.value.copy(backend = value, saved = false)
+    }
+
     fun updateUrl(value: String) {
         _state.value = _state.value.copy(url = value, saved = false)
     }
 
+    fun updateLocalPath(value: String) {
+        _state.value = _state.value.copy(localPath = value, saved = false)
+    }
+
+    fun updateS3Endpoint(value: String) {
+        _state.value = _state.value.copy(s3Endpoint = value, saved = false)
+    }
+
+    fun updateS3Region(value: String) {
+        _state.value = _state.value.copy(s3Region = value, saved = false)
+    }
+
+    fun updateS3Bucket(value: String) {
+        _state.value = _state.value.copy(s3Bucket = value, saved = false)
+    }
+
+    fun updateS3Prefix(value: String) {
+        _state.value = _state.value.copy(s3Prefix = value, saved = false)
+    }
+
+    fun updateS3PathStyle(value: Boolean) {
+        _state.value = _state.value.copy(s3PathStyle = value, saved = false)
+    }
+
     fun updateUsername(value: String) {
         _state.value = _state.value.copy(username = value, saved = false)
     }
@@ -90,6 +136,43 @@ class SyncViewModel : ViewModel() {
         _state.value = _state.value.copy(syncOnStartup = value, saved = false)
     }
 
+    fun updateEncryptionEnabled(value: Boolean) {
+        _state.value = _state.value.copy(encryptionEnabled = value, saved = false)
+    }
+
+    fun updateObfuscateNames(value: Boolean) {
+        _state.value = _state.value.copy(obfuscateNames = value, saved = false)
+    }
+
+    fun updatePassphrase(value: String) {
+        _state.value = _state.value.copy(passphrase = value)
+    }
+
+    fun setPassphrase() {
+        viewModelScope.launch {
+            val current = _state.value
+            _state.value = current.copy(isLoading = true, error = null, testMessage = null)
+            val call = runFfiCall(timeoutMs = LONG_FFI_TIMEOUT_MS) { webdavSetPassphrase(current.passphrase) }
+            if (call.error != null) {
+                _state.value = _state.value.copy(isLoading = false, error = call.error)
+                return@launch
+            }
+            val status = call.value!!
+            if (status.code == FfiErrorCode.OK) {
+                _state.value = _state.value.copy(
+                    isLoading = false,
+                    passphrase = "",
+                    testMessage = "Passphrase saved"
+                )
+            } else {
+                _state.value = _state.value.copy(
+                    isLoading = false,
+                    error = status.userMessage("Failed to set passphrase")
+                )
+            }
+        }
+    }
+
     fun save() {
         viewModelScope.launch {
             val current = _state.value
@@ -104,14 +187,7 @@ class SyncViewModel : ViewModel() {
                 return@launch
             }
 
-            val settingsToSave = WebDavSettings(
-                current.enabled,
-                current.url,
-                current.username,
-                current.password,
-                interval,
-                current.syncOnStartup
-            )
+            val settingsToSave = current.toSettings(interval)
 
             val call = runFfiCall(timeoutMs = LONG_FFI_TIMEOUT_MS) { webdavSettingsSave(settingsToSave) }
             if (call.error != null) {
@@ -126,7 +202,7 @@ class SyncViewModel : ViewModel() {
             } else {
                 _state.value = _state.value.copy(
                     isLoading = false,
-                    error = result.status.userMessage("Failed to save WebDAV settings")
+                    error = result.status.userMessage("Failed to save sync settings")
                 )
             }
         }
@@ -146,14 +222,7 @@ class SyncViewModel : ViewModel() {
                 return@launch
             }
 
-            val settings = WebDavSettings(
-                current.enabled,
-                current.url,
-                current.username,
-                current.password,
-                interval,
-                current.syncOnStartup
-            )
+            val settings = current.toSettings(interval)
 
             val call = runFfiCall(timeoutMs = LONG_FFI_TIMEOUT_MS) { webdavTest(settings) }
             if (call.error != null) {
@@ -169,7 +238,7 @@ class SyncViewModel : ViewModel() {
             } else {
                 _state.value = _state.value.copy(
                     isLoading = false,
-                    error = status.userMessage("WebDAV connection failed")
+                    error = status.userMessage("Sync connection failed")
                 )
             }
         }
@@ -193,7 +262,7 @@ class SyncViewModel : ViewModel() {
             } else {
                 _state.value = _state.value.copy(
                     isLoading = false,
-                    error = result.status.userMessage("WebDAV sync failed")
+                    error = result.status.userMessage("Sync failed")
                 )
             }
         }
@@ -204,19 +273,59 @@ class SyncViewModel : ViewModel() {
     }
 
     private fun applySettings(settings: WebDavSettings) {
+        val backend = settings.backend
+        val local = backend as? SyncBackendSettings.Local
+        val s3 = backend as? SyncBackendSettings.S3
         _state.value = SyncUiState(
             enabled = settings.enabled,
+            backend = when (backend) {
+                is SyncBackendSettings.Local -> SyncBackendKind.LOCAL
+                is SyncBackendSettings.S3 -> SyncBackendKind.S3
+                else -> SyncBackendKind.WEBDAV
+            },
             url = settings.url,
+            localPath = local?.path ?: "",
+            s3Endpoint = s3?.endpoint ?: "",
+            s3Region = s3?.region ?: "",
+            s3Bucket = s3?.bucket ?: "",
+            s3Prefix = s3?.prefix ?: "",
+            s3PathStyle = s3?.pathStyle ?: false,
             username = settings.username,
             password = settings.password,
             syncInterval = settings.syncIntervalMins.toString(),
             syncOnStartup = settings.syncOnStartup,
+            encryptionEnabled = settings.encryptionEnabled,
+            obfuscateNames = settings.encryptionObfuscateNames,
             isLoading = false,
             error = null,
             saved = false
         )
     }
 
+    private fun SyncUiState.toSettings(interval: UInt): WebDavSettings {
+        return WebDavSettings(
+            enabled = enabled,
+            url = url,
+            username = username,
+            password = password,
+            syncIntervalMins = interval,
+            syncOnStartup = syncOnStartup,
+            encryptionEnabled = encryptionEnabled,
+            encryptionObfuscateNames = obfuscateNames,
+            backend = when (backend) {
+                SyncBackendKind.WEBDAV -> SyncBackendSettings.WebDav
+                SyncBackendKind.LOCAL -> SyncBackendSettings.Local(path = localPath)
+                SyncBackendKind.S3 -> SyncBackendSettings.S3(
+                    endpoint = s3Endpoint,
+                    region = s3Region,
+                    bucket = s3Bucket,
+                    prefix = s3Prefix,
+                    pathStyle = s3PathStyle
+                )
+            }
+        )
+    }
+
     private fun parseInterval(raw: String): UInt? {
         val trimmed = raw.trim()
         if (trimmed.isEmpty()) {
@@ -26,12 +26,47 @@ pub fn router<C: AdminApiContext>(state: AdminApiState<C>) -> Router {
             post(set_profile_subscription_http::<C>)
                 .delete(clear_profile_subscription_http::<C>),
         )
+        .route(
+            "/admin/api/profiles/{name}/overlay",
+            get(get_profile_overlay_http::<C>)
+                .put(save_profile_overlay_http::<C>)
+                .delete(clear_profile_overlay_http::<C>),
+        )
+        .route(
+            "/admin/api/profiles/{name}/history",
+            get(list_profile_history_http::<C>),
+        )
+        .route(
+            "/admin/api/profiles/{name}/history/diff",
+            get(diff_profile_history_http::<C>),
+        )
+        .route(
+            "/admin/api/profiles/{name}/history/{id}",
+            get(get_profile_revision_http::<C>),
+        )
+        .route(
+            "/admin/api/profiles/{name}/history/{id}/restore",
+            post(restore_profile_revision_http::<C>),
+        )
+        .route(
+            "/admin/api/profiles/{name}/share",
+            get(get_profile_share_links_http::<C>),
+        )
+        .route(
+            "/admin/api/profiles/{name}/share/subscription",
+            get(get_profile_share_subscription_http::<C>),
+        )
+        .route(
+            "/admin/api/profiles/{name}/share/qr",
+            get(get_profile_share_qr_http::<C>),
+        )
         .route(
             "/admin/api/profiles/{name}/update-now",
             post(update_profile_now_http::<C>),
         )
         .route("/admin/api/profiles/switch", post(switch_profile_http::<C>))
         .route("/admin/api/profiles/save", post(save_profile_http::<C>))
+        .route("/admin/api/profiles/validate", post(validate_profile_http::<C>))
         .route("/admin/api/profiles/import", post(import_profile_http::<C>))
         .route("/admin/api/profiles/clear", post(clear_profiles_http::<C>))
         .route("/admin/api/profiles/open", post(open_profile_in_editor_http::<C>))
@@ -63,16 +98,110 @@ pub fn router<C: AdminApiContext>(state: AdminApiState<C>) -> Router {
             "/admin/api/rule-providers",
             get(get_rule_providers_http::<C>).post(save_rule_providers_http::<C>),
         )
+        .route(
+            "/admin/api/rule-providers/cache",
+            get(list_rule_provider_cache_http::<C>),
+        )
+        .route(
+            "/admin/api/rule-providers/cache/refresh",
+            post(refresh_rule_provider_cache_http::<C>),
+        )
+        .route(
+            "/admin/api/rule-providers/{name}/cache",
+            post(download_rule_provider_http::<C>),
+        )
+        .route(
+            "/admin/api/rule-providers/{name}/search",
+            get(search_rule_provider_http::<C>),
+        )
+        .route(
+            "/admin/api/rule-providers/{name}/offline",
+            post(set_rule_provider_offline_http::<C>),
+        )
         .route(
             "/admin/api/rules",
             get(get_rules_http::<C>).post(save_rules_http::<C>),
         )
+        .route("/admin/api/rules/test", post(test_rules_http::<C>))
         .route(
             "/admin/api/tun",
             get(get_tun_config_http::<C>).post(save_tun_config_http::<C>),
         )
+        .route(
+            "/admin/api/proxies",
+            get(list_proxies_http::<C>).post(create_proxy_http::<C>),
+        )
+        .route(
+            "/admin/api/proxies/{name}",
+            get(get_proxy_http::<C>)
+                .put(update_proxy_http::<C>)
+                .delete(delete_proxy_http::<C>),
+        )
+        .route(
+            "/admin/api/proxy-groups",
+            get(list_proxy_groups_http::<C>).post(create_proxy_group_http::<C>),
+        )
+        .route(
+            "/admin/api/proxy-groups/{name}",
+            get(get_proxy_group_http::<C>)
+                .put(update_proxy_group_http::<C>)
+                .delete(delete_proxy_group_http::<C>),
+        )
+        .route(
+            "/admin/api/providers/proxies",
+            get(list_proxy_providers_http::<C>),
+        )
+        .route(
+            "/admin/api/providers/proxies/{name}/update",
+            post(update_proxy_provider_http::<C>),
+        )
+        .route(
+            "/admin/api/providers/proxies/{name}/healthcheck",
+            post(healthcheck_proxy_provider_http::<C>),
+        )
+        .route(
+            "/admin/api/providers/rules",
+            get(list_rule_provider_status_http::<C>),
+        )
+        .route(
+            "/admin/api/providers/rules/{name}/update",
+            post(update_rule_provider_http::<C>),
+        )
+        .route("/admin/api/proxies/delay", post(test_proxy_delay_http::<C>))
+        .route(
+            "/admin/api/proxies/delay/history",
+            get(get_proxy_delay_history_http::<C>),
+        )
+        .route("/admin/api/proxies/select", post(select_proxy_http::<C>))
+        .route(
+            "/admin/api/proxies/auto-select",
+            post(run_auto_select_http::<C>),
+        )
+        .route(
+            "/admin/api/connections/stats",
+            get(get_connection_stats_http::<C>).delete(reset_connection_stats_http::<C>),
+        )
+        .route(
+            "/admin/api/connections/stats/timeline",
+            get(get_connection_timeline_http::<C>),
+        )
+        .route("/admin/api/usage", get(get_usage_summary_http::<C>))
+        .route("/admin/api/usage/{profile}", get(get_profile_usage_http::<C>))
+        .route(
+            "/admin/api/usage/{profile}/quota",
+            post(set_usage_quota_http::<C>).delete(clear_usage_quota_http::<C>),
+        )
         .route("/admin/api/webdav/sync", post(sync_webdav_now_http::<C>))
         .route("/admin/api/webdav/test", post(test_webdav_conn_http::<C>))
+        .route(
+            "/admin/api/webdav/encryption",
+            get(get_sync_encryption_http::<C>)
+                .post(set_sync_passphrase_http::<C>)
+                .delete(clear_sync_passphrase_http::<C>),
+        )
+        .route("/admin/api/webdav/conflicts", get(list_webdav_conflicts_http::<C>))
+        .route("/admin/api/webdav/conflicts/detail", get(get_webdav_conflict_http::<C>))
+        .route("/admin/api/webdav/conflicts/resolve", post(resolve_webdav_conflict_http::<C>))
         .route("/admin/api/events", get(stream_admin_events_http::<C>))
         .route("/admin/api/rebuild/status", get(get_rebuild_status_http::<C>))
         .route("/admin/api/core/versions", get(list_core_versions_http::<C>))
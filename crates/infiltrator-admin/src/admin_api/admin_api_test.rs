@@ -9,10 +9,14 @@ mod tests {
     use crate::admin_api::*;
     use infiltrator_core::AppSettings;
     use infiltrator_http::HttpClient;
+    use mihomo_api::{ConnectionAnalytics, DelayHistoryStore, MihomoClient};
 
     #[derive(Clone)]
     struct MockContext {
         rebuild_count: Arc<Mutex<usize>>,
+        analytics: ConnectionAnalytics,
+        controller: Option<String>,
+        hot_applied: Arc<Mutex<Vec<String>>>,
     }
 
     #[async_trait::async_trait]
@@ -31,11 +35,37 @@ mod tests {
         async fn open_profile_in_editor(&self, _name: &str) -> anyhow::Result<()> { Ok(()) }
         async fn get_app_settings(&self) -> AppSettings { AppSettings::default() }
         async fn save_app_settings(&self, _s: AppSettings) -> anyhow::Result<()> { Ok(()) }
+        async fn controller_client(&self) -> anyhow::Result<MihomoClient> {
+            match &self.controller {
+                Some(url) => Ok(MihomoClient::new(url, None)?),
+                None => Err(anyhow::anyhow!("core not running")),
+            }
+        }
+        async fn usage_ledger(&self) -> anyhow::Result<infiltrator_core::usage::UsageLedger> {
+            infiltrator_core::usage::UsageLedger::open_default().await
+        }
+        fn runtime_generation(&self) -> u64 { *self.rebuild_count.lock().unwrap() as u64 }
+        async fn profile_hot_applied(&self, content: String) {
+            self.hot_applied.lock().unwrap().push(content);
+        }
+        fn delay_history(&self) -> DelayHistoryStore { DelayHistoryStore::default() }
+        fn connection_analytics(&self) -> ConnectionAnalytics { self.analytics.clone() }
+        fn emit_admin_event(&self, _event: AdminEvent) {}
     }
 
+    /// Serialises tests that redirect the global home dir override.
+    static HOME_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
     fn setup_app() -> axum::Router {
+        setup_app_with_analytics(ConnectionAnalytics::default())
+    }
+
+    fn setup_app_with_analytics(analytics: ConnectionAnalytics) -> axum::Router {
         let ctx = MockContext {
             rebuild_count: Arc::new(Mutex::new(0)),
+            analytics,
+            controller: None,
+            hot_applied: Arc::default(),
         };
         let bus = events::AdminEventBus::new();
         let state = AdminApiState::new(ctx, bus);
@@ -77,6 +107,196 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_providers_route_without_core() {
+        let app = setup_app();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/api/providers/proxies")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_connection_stats_routes() {
+        let analytics = ConnectionAnalytics::default();
+        let snapshot: mihomo_api::ConnectionSnapshot = serde_json::from_value(serde_json::json!({
+            "downloadTotal": 0,
+            "uploadTotal": 0,
+            "connections": [
+                {"id": "1", "metadata": {"host": "a.com", "processPath": "/usr/bin/curl"},
+                 "upload": 10, "download": 90, "chains": ["HK", "Proxy"], "rule": "Match"},
+                {"id": "2", "metadata": {"host": "b.com", "processPath": "/usr/bin/curl"},
+                 "upload": 1, "download": 2, "chains": ["DIRECT"], "rule": "Match"}
+            ]
+        }))
+        .unwrap();
+        analytics.ingest(&snapshot);
+        let app = setup_app_with_analytics(analytics);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/api/connections/stats?by=process")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["entries"][0]["key"], "curl");
+        assert_eq!(value["entries"][0]["download"], 92);
+        assert_eq!(value["totals"]["connections"], 2);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/api/connections/stats?by=nope")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/api/connections/stats/timeline?by=host&key=a.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["buckets"][0]["upload"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_usage_routes() {
+        use infiltrator_core::usage::UsageLedger;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+
+        let day = chrono::NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+        let ledger = UsageLedger::open_default().await.unwrap();
+        ledger.add_usage("work", day, 100, 900).await.unwrap();
+        drop(ledger);
+
+        let app = setup_app();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/api/usage/work/quota")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"monthly_bytes": 2000}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/api/usage?month=2026-03")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["month"], "2026-03");
+        assert_eq!(value["profiles"][0]["profile"], "work");
+        assert_eq!(value["profiles"][0]["download"], 900);
+        assert_eq!(value["profiles"][0]["quota"]["monthly_bytes"], 2000);
+        assert_eq!(value["profiles"][0]["used_percent"], 50.0);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/api/usage/work?from=2026-03-01&to=2026-03-31")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["days"][0]["day"], "2026-03-14");
+        assert_eq!(value["days"][0]["upload"], 100);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/api/usage?month=March")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_profile_validation_gates_save() {
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let content = "proxy-groups:\n  - { name: G, type: select, proxies: [missing] }\nrules:\n  - MATCH,G\n";
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/admin/api/profiles/validate")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::json!({ "content": content }).to_string()))
+            .unwrap();
+        let response = setup_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["diagnostics"][0]["path"], "proxy-groups[0].proxies[0]");
+        assert_eq!(body["diagnostics"][0]["severity"], "error");
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/admin/api/profiles/save")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({ "name": "broken", "content": content }).to_string(),
+            ))
+            .unwrap();
+        let response = setup_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(!temp_dir.path().join("configs").join("broken.yaml").exists());
+        mihomo_platform::clear_home_dir_override();
+    }
+
     #[tokio::test]
     async fn test_import_profile_integration() {
         let mut server = mockito::Server::new_async().await;
@@ -86,6 +306,7 @@ mod tests {
             .with_body(mock_yaml)
             .create_async().await;
 
+        let _home = HOME_LOCK.lock().await;
         let temp_dir = tempfile::tempdir().unwrap();
         mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
 
@@ -116,4 +337,706 @@ mod tests {
         
         mihomo_platform::clear_home_dir_override();
     }
+
+    #[tokio::test]
+    async fn test_import_share_link_subscription() {
+        use base64::Engine;
+
+        let links = "trojan://pw@a.example.com:443#A\nvless://id@b.example.com:443?security=tls#B\nssr://nope\n";
+        let body = base64::engine::general_purpose::STANDARD.encode(links);
+        let mut server = mockito::Server::new_async().await;
+        let _m = server.mock("GET", "/links")
+            .with_status(200)
+            .with_body(body)
+            .create_async().await;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        // Subscription URLs live in the OS keyring, which outlives the temp dir.
+        let name = format!(
+            "share-links-{}",
+            temp_dir.path().file_name().unwrap().to_string_lossy()
+        );
+
+        let payload = ImportProfilePayload {
+            name: name.clone(),
+            url: format!("{}/links", server.url()),
+            activate: Some(false),
+        };
+        let response = setup_app()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/api/profiles/import")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&payload).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result["unparsed_links"].as_array().unwrap().len(), 1);
+        assert_eq!(result["unparsed_links"][0]["line"], 3);
+        assert_eq!(result["unparsed_links"][0]["scheme"], "ssr");
+
+        let saved = std::fs::read_to_string(
+            temp_dir.path().join("configs").join(format!("{name}.yaml")),
+        )
+        .unwrap();
+        let doc: serde_yaml::Value = serde_yaml::from_str(&saved).unwrap();
+        assert_eq!(doc["proxies"].as_sequence().unwrap().len(), 2);
+        assert_eq!(doc["proxy-groups"][0]["name"], "Proxy");
+
+        mihomo_config::ConfigManager::new().unwrap().delete_profile(&name).await.unwrap();
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_profile_overlay_survives_subscription_update() {
+        use tower::Service;
+
+        let mut server = mockito::Server::new_async().await;
+        let first = server.mock("GET", "/sub")
+            .with_status(200)
+            .with_body("mode: rule\nproxies:\n  - { name: a, type: socks5, server: a.example.com, port: 1080 }\nrules:\n  - MATCH,DIRECT\n")
+            .create_async().await;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let name = format!(
+            "overlay-{}",
+            temp_dir.path().file_name().unwrap().to_string_lossy()
+        );
+        let mut app = setup_app();
+        let mut send = async |method: &str, uri: String, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, bytes)
+        };
+        let load = |name: &str| -> serde_yaml::Value {
+            let path = temp_dir.path().join("configs").join(format!("{name}.yaml"));
+            serde_yaml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+        };
+
+        let payload = serde_json::json!({
+            "name": name,
+            "url": format!("{}/sub", server.url()),
+            "activate": true,
+        });
+        let (status, _) = send("POST", "/admin/api/profiles/import".into(), Some(payload)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(
+            "POST",
+            "/admin/api/dns".into(),
+            Some(serde_json::json!({ "enable": true, "nameserver": ["223.5.5.5"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let rules = serde_json::json!({ "rules": [
+            { "rule": "DOMAIN-SUFFIX,lan,DIRECT", "enabled": true },
+            { "rule": "MATCH,DIRECT", "enabled": true },
+        ]});
+        let (status, _) = send("POST", "/admin/api/rules".into(), Some(rules)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        first.remove_async().await;
+        let _second = server.mock("GET", "/sub")
+            .with_status(200)
+            .with_body("mode: rule\nproxies:\n  - { name: b, type: socks5, server: b.example.com, port: 1080 }\nrules:\n  - GEOIP,CN,DIRECT\n  - MATCH,DIRECT\n")
+            .create_async().await;
+        let (status, _) = send("POST", format!("/admin/api/profiles/{name}/update-now"), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let doc = load(&name);
+        assert_eq!(doc["proxies"][0]["name"], "b");
+        assert_eq!(doc["dns"]["nameserver"][0], "223.5.5.5");
+        let rules: Vec<&str> = doc["rules"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|rule| rule.as_str())
+            .collect();
+        assert_eq!(rules, vec!["DOMAIN-SUFFIX,lan,DIRECT", "GEOIP,CN,DIRECT", "MATCH,DIRECT"]);
+
+        let (status, body) = send("GET", format!("/admin/api/profiles/{name}/overlay"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let overlay: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(overlay["prepend-rules"][0], "DOMAIN-SUFFIX,lan,DIRECT");
+        assert_eq!(overlay["dns"]["nameserver"][0], "223.5.5.5");
+
+        let (status, _) = send("DELETE", format!("/admin/api/profiles/{name}/overlay"), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let doc = load(&name);
+        assert!(doc.get("dns").is_none());
+        assert_eq!(doc["rules"].as_sequence().unwrap().len(), 2);
+
+        let (status, _) = send("DELETE", format!("/admin/api/profiles/{name}/subscription"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_restoring_subscription_revision_keeps_later_refreshes() {
+        use infiltrator_core::overlay;
+        use mihomo_config::{ConfigManager, RevisionSource};
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let manager = ConfigManager::new().unwrap();
+        let field = |content: &str, key: &str| {
+            serde_yaml::from_str::<serde_yaml::Value>(content).unwrap()[key].clone()
+        };
+        let port = |content: &str| field(content, "port").as_u64();
+        let mode = |content: &str| field(content, "mode").as_str().map(str::to_string);
+
+        overlay::save_profile_base(&manager, "sub", "mode: rule\nport: 7890\n", RevisionSource::SubscriptionUpdate)
+            .await
+            .unwrap();
+        overlay::save_profile_base(&manager, "sub", "mode: rule\nport: 7891\n", RevisionSource::SubscriptionUpdate)
+            .await
+            .unwrap();
+        overlay::save_profile_edit(&manager, "sub", "mode: global\nport: 7891\n", RevisionSource::AdminSave)
+            .await
+            .unwrap();
+        let revisions = manager.list_revisions("sub").await.unwrap();
+        let first = revisions.last().unwrap();
+        assert_eq!(first.source, RevisionSource::SubscriptionUpdate);
+
+        let restored = overlay::restore_profile_revision(&manager, "sub", &first.id).await.unwrap();
+        assert_eq!(port(&restored), Some(7890));
+        assert_eq!(mode(&restored).as_deref(), Some("global"));
+        assert_eq!(manager.load("sub").await.unwrap(), restored);
+        assert!(!overlay::load_overlay("sub").await.unwrap().is_empty());
+
+        overlay::save_profile_base(&manager, "sub", "mode: rule\nport: 7892\n", RevisionSource::SubscriptionUpdate)
+            .await
+            .unwrap();
+        let refreshed = manager.load("sub").await.unwrap();
+        assert_eq!(port(&refreshed), Some(7892));
+        assert_eq!(mode(&refreshed).as_deref(), Some("global"));
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_profile_history_diff_and_restore() {
+        use tower::Service;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let mut app = setup_app();
+        let mut send = async |method: &str, uri: String, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let value = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default();
+            (status, value)
+        };
+
+        for content in ["mode: rule\nport: 7890\n", "mode: global\nport: 7890\n"] {
+            let payload = serde_json::json!({ "name": "audit", "content": content });
+            let (status, _) = send("POST", "/admin/api/profiles/save".into(), Some(payload)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let (status, revisions) = send("GET", "/admin/api/profiles/audit/history".into(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let revisions = revisions.as_array().unwrap().clone();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0]["source"], "admin-save");
+        let first = revisions[1]["id"].as_str().unwrap().to_string();
+
+        let (status, diff) = send(
+            "GET",
+            format!("/admin/api/profiles/audit/history/diff?from={first}"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(diff["changes"][0]["path"], "mode");
+        assert_eq!(diff["changes"][0]["before"], "rule");
+        assert_eq!(diff["changes"][0]["after"], "global");
+
+        let (status, _) = send(
+            "POST",
+            format!("/admin/api/profiles/audit/history/{first}/restore"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let restored =
+            std::fs::read_to_string(temp_dir.path().join("configs").join("audit.yaml")).unwrap();
+        assert_eq!(restored, "mode: rule\nport: 7890\n");
+
+        let (status, _) = send(
+            "GET",
+            "/admin/api/profiles/audit/history/../../secret".into(),
+            None,
+        )
+        .await;
+        assert_ne!(status, StatusCode::OK);
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_profile_edits_apply_without_restart() {
+        use tower::Service;
+
+        let mut server = mockito::Server::new_async().await;
+        let patch = server
+            .mock("PATCH", "/configs")
+            .match_body(mockito::Matcher::Json(serde_json::json!({ "mode": "global" })))
+            .with_status(204)
+            .create_async()
+            .await;
+        let reload = server
+            .mock("PUT", "/configs")
+            .match_query(mockito::Matcher::UrlEncoded("force".into(), "true".into()))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let rebuild_count = Arc::new(Mutex::new(0));
+        let hot_applied = Arc::new(Mutex::new(vec![]));
+        let ctx = MockContext {
+            rebuild_count: Arc::clone(&rebuild_count),
+            analytics: ConnectionAnalytics::default(),
+            controller: Some(server.url()),
+            hot_applied: Arc::clone(&hot_applied),
+        };
+        let mut app = router(AdminApiState::new(ctx, events::AdminEventBus::new()));
+        let mut save = async |content: &str, activate: bool| {
+            let payload = serde_json::json!({ "name": "hot", "content": content, "activate": activate });
+            let request = Request::builder()
+                .method("POST")
+                .uri("/admin/api/profiles/save")
+                .header("content-type", "application/json")
+                .body(Body::from(payload.to_string()))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let mut status = serde_json::Value::Null;
+            for _ in 0..100 {
+                let request = Request::builder()
+                    .uri("/admin/api/rebuild/status")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.call(request).await.unwrap();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                status = serde_json::from_slice(&bytes).unwrap();
+                if status["in_progress"] == false {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+            status
+        };
+
+        let base = "mode: rule\nmixed-port: 7890\nrules:\n  - MATCH,DIRECT\n";
+        let status = save(base, true).await;
+        assert_eq!(status["last_method"], "rebuild");
+
+        let status = save(&base.replace("mode: rule", "mode: global"), false).await;
+        assert_eq!(status["last_method"], "patch");
+        assert_eq!(status["last_detail"], "mode");
+        patch.assert_async().await;
+
+        let status = save(&base.replace("mode: rule", "mode: global").replace("DIRECT", "REJECT"), false).await;
+        assert_eq!(status["last_method"], "reload");
+        reload.assert_async().await;
+        assert_eq!(*rebuild_count.lock().unwrap(), 1);
+        // The runtime learns about hot-applied content, so a rejected rebuild
+        // falls back to the latest edit instead of the bootstrap config
+        assert_eq!(
+            *hot_applied.lock().unwrap(),
+            vec![
+                base.replace("mode: rule", "mode: global"),
+                base.replace("mode: rule", "mode: global").replace("DIRECT", "REJECT"),
+            ]
+        );
+
+        let status = save(&base.replace("7890", "7891"), false).await;
+        assert_eq!(status["last_method"], "rebuild");
+        assert_eq!(status["last_detail"], "mixed-port");
+        assert_eq!(*rebuild_count.lock().unwrap(), 2);
+
+        // A rebuild started outside the admin API leaves the snapshot stale.
+        *rebuild_count.lock().unwrap() += 1;
+        let status = save(&base.replace("7890", "7891").replace("mode: rule", "mode: global"), false).await;
+        assert_eq!(status["last_method"], "rebuild");
+        assert_eq!(status["last_detail"], "running profile unknown");
+        assert_eq!(*rebuild_count.lock().unwrap(), 4);
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_rules_test_route() {
+        use tower::Service;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let mut app = setup_app();
+        let mut send = async |uri: &str, body: serde_json::Value| {
+            let request = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default())
+        };
+
+        let content = "mode: rule\nrules:\n  - DOMAIN-SUFFIX,example.com,REJECT\n  - GEOIP,CN,DIRECT\n  - MATCH,GLOBAL\n";
+        let payload = serde_json::json!({ "name": "rule-test", "content": content, "activate": true });
+        let (status, _) = send("/admin/api/profiles/save", payload).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, result) = send(
+            "/admin/api/rules/test",
+            serde_json::json!({ "host": "www.example.com", "port": 443 }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result["index"], 0);
+        assert_eq!(result["policy"], "REJECT");
+
+        let (status, result) = send("/admin/api/rules/test", serde_json::json!({ "ip": "1.1.1.1" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result["index"], 2);
+        assert_eq!(result["policy"], "GLOBAL");
+        assert_eq!(result["skipped"][0]["index"], 1);
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_rules_save_rejects_invalid_rules() {
+        use tower::Service;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let mut app = setup_app();
+        let mut send = async |uri: &str, body: serde_json::Value| {
+            let request = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, String::from_utf8_lossy(&bytes).to_string())
+        };
+
+        let payload = serde_json::json!({ "name": "rule-save", "content": "mode: rule\nrules:\n  - MATCH,GLOBAL\n", "activate": true });
+        let (status, _) = send("/admin/api/profiles/save", payload).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let rules = serde_json::json!({ "rules": [
+            { "rule": "MATCH,DIRECT", "enabled": true },
+            { "rule": "IP-CIDR,10.0.0.0/40,DIRECT", "enabled": true },
+        ]});
+        let (status, body) = send("/admin/api/rules", rules).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("rules[1]"), "{body}");
+
+        // Rule types this build does not know are kept verbatim and reported.
+        let rules = serde_json::json!({ "rules": [{ "rule": "HOST,a.com,DIRECT", "enabled": true }] });
+        let (status, body) = send("/admin/api/rules", rules).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let saved: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(saved["rules"][0]["rule"], "HOST,a.com,DIRECT");
+        assert_eq!(saved["unrecognized"][0], "rules[0] `HOST,a.com,DIRECT`: unknown rule type `HOST`");
+
+        let rules = serde_json::json!({ "rules": [
+            { "rule": "domain-suffix, lan ,DIRECT", "enabled": true },
+            { "rule": "MATCH,DIRECT", "enabled": false },
+        ]});
+        let (status, body) = send("/admin/api/rules", rules).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let saved: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(saved["rules"][0]["rule"], "DOMAIN-SUFFIX,lan,DIRECT");
+        let content =
+            std::fs::read_to_string(temp_dir.path().join("configs").join("rule-save.yaml")).unwrap();
+        assert!(content.contains("'# MATCH,DIRECT'"), "{content}");
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_rule_provider_cache_routes() {
+        use tower::Service;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let mut server = mockito::Server::new_async().await;
+        let _ads = server
+            .mock("GET", "/ads.txt")
+            .with_status(200)
+            .with_body("# ads\n+.ads.example\ntrack.example.com\n")
+            .create_async()
+            .await;
+        let mut app = setup_app();
+        let mut send = async |method: &str, uri: &str, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default())
+        };
+
+        let content = format!(
+            "mode: rule\nrule-providers:\n  ads: {{ type: http, behavior: domain, format: text, url: '{}/ads.txt', path: ./ruleset/ads.txt }}\nrules:\n  - RULE-SET,ads,REJECT\n  - MATCH,DIRECT\n",
+            server.url()
+        );
+        let payload = serde_json::json!({ "name": "providers", "content": content, "activate": true });
+        let (status, _) = send("POST", "/admin/api/profiles/save", Some(payload)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send("GET", "/admin/api/rule-providers/cache", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["providers"][0]["name"], "ads");
+        assert!(body["providers"][0]["entries"].is_null());
+
+        let (status, body) = send("POST", "/admin/api/rule-providers/ads/cache", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["entries"], 2);
+        assert!(body["updated_at"].is_string());
+        assert!(temp_dir.path().join("configs/rule-providers/ads.txt").is_file());
+
+        let (status, body) = send("GET", "/admin/api/rule-providers/ads/search?q=TRACK", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["matches"][0], "track.example.com");
+
+        let offline = serde_json::json!({ "offline": true });
+        let (status, body) = send("POST", "/admin/api/rule-providers/ads/offline", Some(offline)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["offline"], true);
+        let profile = temp_dir.path().join("configs/providers.yaml");
+        let doc: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&profile).unwrap()).unwrap();
+        assert_eq!(doc["rule-providers"]["ads"]["type"], "file");
+        assert_eq!(doc["rule-providers"]["ads"]["path"], "./rule-providers/ads.txt");
+
+        let (status, result) = send(
+            "POST",
+            "/admin/api/rules/test",
+            Some(serde_json::json!({ "host": "x.ads.example" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result["policy"], "REJECT");
+
+        let online = serde_json::json!({ "offline": false });
+        let (status, _) = send("POST", "/admin/api/rule-providers/ads/offline", Some(online)).await;
+        assert_eq!(status, StatusCode::OK);
+        let doc: serde_yaml::Value =
+            serde_yaml::from_str(&std::fs::read_to_string(&profile).unwrap()).unwrap();
+        assert_eq!(doc["rule-providers"]["ads"]["type"], "http");
+        assert_eq!(doc["rule-providers"]["ads"]["path"], "./ruleset/ads.txt");
+
+        let (status, _) = send("POST", "/admin/api/rule-providers/missing/cache", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_proxy_editor_routes() {
+        use tower::Service;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let mut app = setup_app();
+        let mut send = async |method: &str, uri: &str, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, String::from_utf8_lossy(&bytes).to_string())
+        };
+
+        let content = "mode: rule\nproxies:\n  - { name: hk, type: ss, server: a.example, port: 8388, cipher: aes-128-gcm, password: p }\nproxy-groups:\n  - { name: auto, type: select, proxies: [hk, DIRECT] }\nrules:\n  - DOMAIN,hk.example,hk\n  - MATCH,auto\n";
+        let payload = serde_json::json!({ "name": "proxy-edit", "content": content, "activate": true });
+        let (status, body) = send("POST", "/admin/api/profiles/save", Some(payload)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        let trojan = serde_json::json!({ "name": "jp", "type": "trojan", "server": "b.example", "port": 443, "password": "p" });
+        let (status, body) = send("POST", "/admin/api/proxies", Some(trojan.clone())).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = send("POST", "/admin/api/proxies", Some(trojan)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("already exists"), "{body}");
+
+        let renamed = serde_json::json!({ "name": "hk-2", "type": "ss", "server": "a.example", "port": 8388, "cipher": "aes-128-gcm", "password": "q" });
+        let (status, body) = send("PUT", "/admin/api/proxies/hk", Some(renamed)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = send("GET", "/admin/api/proxy-groups/auto", None).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let group: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(group["proxies"], serde_json::json!(["hk-2", "DIRECT"]));
+
+        let (status, body) = send("DELETE", "/admin/api/proxies/hk-2", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("hk-2"), "{body}");
+
+        let group = serde_json::json!({ "name": "fast", "type": "url-test", "proxies": ["jp", "missing"], "url": "https://cp.example/generate_204", "interval": 300 });
+        let (status, body) = send("POST", "/admin/api/proxy-groups", Some(group)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("missing"), "{body}");
+        let group = serde_json::json!({ "name": "fast", "type": "url-test", "proxies": ["jp", "auto"], "url": "https://cp.example/generate_204", "interval": 300 });
+        let (status, body) = send("POST", "/admin/api/proxy-groups", Some(group)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        let (status, body) = send("GET", "/admin/api/proxies", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let listed: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(listed["proxies"].as_array().unwrap().len(), 2);
+        let saved =
+            std::fs::read_to_string(temp_dir.path().join("configs").join("proxy-edit.yaml")).unwrap();
+        assert!(saved.contains("DOMAIN,hk.example,hk-2"), "{saved}");
+        assert!(saved.contains("url-test"), "{saved}");
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_profile_share_routes() {
+        use tower::Service;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let mut app = setup_app();
+        let mut send = async |method: &str, uri: &str, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let content_type = response
+                .headers()
+                .get("content-type")
+                .map(|value| value.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, content_type, bytes.to_vec())
+        };
+
+        let content = "mode: rule\nproxies:\n  - { name: jp, type: trojan, server: b.example, port: 443, password: p }\n  - { name: wg, type: wireguard, server: w.example, port: 51820, ip: 10.0.0.2, private-key: k, public-key: k }\nrules:\n  - MATCH,jp\n";
+        let payload = serde_json::json!({ "name": "share", "content": content, "activate": true });
+        let (status, _, body) = send("POST", "/admin/api/profiles/save", Some(payload)).await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+
+        let (status, _, body) = send("GET", "/admin/api/profiles/share/share", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["links"][0]["link"], "trojan://p@b.example:443#jp");
+        assert!(value["links"][1]["link"].is_null());
+        assert!(value["links"][1]["error"].as_str().unwrap().contains("wireguard"));
+
+        let (status, content_type, body) =
+            send("GET", "/admin/api/profiles/share/share/subscription", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/plain"));
+        assert_eq!(body, value["subscription"].as_str().unwrap().as_bytes());
+
+        let (status, content_type, body) =
+            send("GET", "/admin/api/profiles/share/share/qr?proxy=jp", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "image/svg+xml");
+        assert!(body.starts_with(b"<svg"));
+        let (status, content_type, body) =
+            send("GET", "/admin/api/profiles/share/share/qr?proxy=jp&format=png&scale=4", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "image/png");
+        assert!(body.starts_with(b"\x89PNG"));
+        let (status, _, _) = send("GET", "/admin/api/profiles/share/share/qr?proxy=wg", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_webdav_conflict_routes() {
+        use tower::Service;
+
+        let _home = HOME_LOCK.lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let mut app = setup_app();
+        let mut send = async |method: &str, uri: &str, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = app.call(request).await.unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, bytes.to_vec())
+        };
+
+        let (status, body) = send("GET", "/admin/api/webdav/conflicts", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["conflicts"], serde_json::json!([]));
+
+        let (status, _) = send("GET", "/admin/api/webdav/conflicts/detail?path=a.yaml", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        for payload in [
+            serde_json::json!({ "path": "../a.yaml", "keep": "local" }),
+            serde_json::json!({ "path": "a.yaml", "keep": "merged" }),
+            serde_json::json!({ "path": "a.yaml", "keep": "both" }),
+        ] {
+            let (status, _) = send("POST", "/admin/api/webdav/conflicts/resolve", Some(payload)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        let payload = serde_json::json!({ "passphrase": "" });
+        let (status, _) = send("POST", "/admin/api/webdav/encryption", Some(payload)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        mihomo_platform::clear_home_dir_override();
+    }
 }
@@ -12,8 +12,12 @@ pub const EVENT_DNS_CHANGED: &str = "dns-changed";
 pub const EVENT_FAKE_IP_CHANGED: &str = "fake-ip-changed";
 pub const EVENT_RULES_CHANGED: &str = "rules-changed";
 pub const EVENT_RULE_PROVIDERS_CHANGED: &str = "rule-providers-changed";
+pub const EVENT_PROXIES_CHANGED: &str = "proxies-changed";
 pub const EVENT_TUN_CHANGED: &str = "tun-changed";
 pub const EVENT_WEBDAV_SYNCED: &str = "webdav-synced";
+pub const EVENT_PROVIDERS_UPDATED: &str = "providers-updated";
+pub const EVENT_PROXY_AUTO_SWITCHED: &str = "proxy-auto-switched";
+pub const EVENT_USAGE_QUOTA_REACHED: &str = "usage-quota-reached";
 
 const EVENT_CHANNEL_SIZE: usize = 64;
 
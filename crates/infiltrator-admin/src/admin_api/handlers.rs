@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     convert::Infallible,
     sync::Arc,
     time::{Duration, Instant},
@@ -7,30 +8,45 @@ use std::{
 use anyhow::anyhow;
 use axum::{
     body::Body,
-    extract::{Path as AxumPath, State as AxumState},
-    http::{Request, StatusCode},
+    extract::{Path as AxumPath, Query, State as AxumState},
+    http::{header, Request, StatusCode},
     middleware::Next,
-    response::{sse::{Event, KeepAlive, Sse}, Response},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response},
     Json,
 };
-use chrono::Utc;
+use chrono::{Datelike, Local, NaiveDate, Utc};
 use log::{info, warn};
 use infiltrator_http::HttpClient;
 use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 
 use infiltrator_core::{
     config as core_config,
+    converter::{self, UnparsedLink},
     dns,
     fake_ip,
+    hot_reload::{self, ApplyMethod, ApplyPlan},
+    overlay::{self, ProfileOverlay},
     profiles as core_profiles,
+    proxies,
+    proxy_selection,
+    rule_providers,
+    rule_test,
     rules,
     settings::WebDavConfig,
+    share,
     subscription as core_subscription,
+    sync_backend,
+    sync_crypto,
     tun,
+    usage::{self, UsageLedger, UsageQuota},
     ProfileDetail,
     ProfileInfo,
 };
-use mihomo_config::ConfigManager;
+use mihomo_api::{Dimension, MihomoClient, ProxyManager};
+use sync_engine::executor::ConflictResolution;
+use mihomo_config::{
+    history::CURRENT_REVISION, ConfigManager, ProfileRevision, RevisionSource, Severity,
+};
 use mihomo_version::VersionManager;
 
 use super::events::{
@@ -39,6 +55,8 @@ use super::events::{
     EVENT_DNS_CHANGED,
     EVENT_FAKE_IP_CHANGED,
     EVENT_PROFILES_CHANGED,
+    EVENT_PROVIDERS_UPDATED,
+    EVENT_PROXIES_CHANGED,
     EVENT_RULE_PROVIDERS_CHANGED,
     EVENT_RULES_CHANGED,
     EVENT_SETTINGS_CHANGED,
@@ -47,6 +65,7 @@ use super::events::{
 };
 use super::models::*;
 use super::state::{AdminApiContext, AdminApiState, RebuildStatus};
+use crate::scheduler::{auto_select, update_lock};
 
 pub async fn list_profiles_http<C: AdminApiContext>(
     AxumState(_state): AxumState<AdminApiState<C>>,
@@ -110,6 +129,7 @@ pub async fn switch_profile_http<C: AdminApiContext>(
     Ok(Json(ProfileActionResponse {
         profile,
         rebuild_scheduled: true,
+        unparsed_links: Vec::new(),
     }))
 }
 
@@ -123,7 +143,7 @@ pub async fn import_profile_http<C: AdminApiContext>(
             "订阅链接不能为空",
         ));
     }
-    let (profile, rebuild_scheduled) = import_profile_from_url_internal(
+    let (profile, rebuild_scheduled, unparsed_links) = import_profile_from_url_internal(
         &state.ctx,
         &state.rebuild_status,
         &state.http_client,
@@ -137,16 +157,39 @@ pub async fn import_profile_http<C: AdminApiContext>(
     Ok(Json(ProfileActionResponse {
         profile,
         rebuild_scheduled,
+        unparsed_links,
     }))
 }
 
+pub async fn validate_profile_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<ValidateProfilePayload>,
+) -> Result<Json<ProfileValidationResponse>, ApiError> {
+    let diagnostics = core_config::check_profile(&payload.content)
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let valid = diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity != Severity::Error);
+    Ok(Json(ProfileValidationResponse { valid, diagnostics }))
+}
+
 pub async fn save_profile_http<C: AdminApiContext>(
     AxumState(state): AxumState<AdminApiState<C>>,
     Json(payload): Json<SaveProfilePayload>,
 ) -> Result<Json<ProfileActionResponse>, ApiError> {
     let name = ensure_valid_profile_name(&payload.name)?;
-    if let Err(err) = core_config::validate_yaml(&payload.content) {
-        return Err(ApiError::bad_request(err.to_string()));
+    let diagnostics = core_config::check_profile(&payload.content)
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let errors: Vec<String> = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(ApiError::bad_request(format!(
+            "配置校验失败: {}",
+            errors.join("; ")
+        )));
     }
 
     let manager = ConfigManager::new().map_err(|e| ApiError::internal(e.to_string()))?;
@@ -158,8 +201,7 @@ pub async fn save_profile_http<C: AdminApiContext>(
         None
     };
 
-    manager
-        .save(&name, &payload.content)
+    overlay::save_profile_edit(&manager, &name, &payload.content, RevisionSource::AdminSave)
         .await
         .map_err(|e| ApiError::bad_request(e.to_string()))?;
 
@@ -176,7 +218,7 @@ pub async fn save_profile_http<C: AdminApiContext>(
         rebuild_scheduled = true;
         controller_url = manager.get_external_controller().await.ok();
     } else if manager.get_current().await.ok().as_deref() == Some(&name) {
-        schedule_rebuild(&state.ctx, &state.rebuild_status, "save-current");
+        schedule_apply(&state.ctx, &state.rebuild_status, "save-current");
         rebuild_scheduled = true;
         controller_url = manager.get_external_controller().await.ok();
     }
@@ -191,6 +233,7 @@ pub async fn save_profile_http<C: AdminApiContext>(
     Ok(Json(ProfileActionResponse {
         profile: info,
         rebuild_scheduled,
+        unparsed_links: Vec::new(),
     }))
 }
 
@@ -208,6 +251,7 @@ pub async fn clear_profiles_http<C: AdminApiContext>(
     Ok(Json(ProfileActionResponse {
         profile: info,
         rebuild_scheduled: true,
+        unparsed_links: Vec::new(),
     }))
 }
 
@@ -221,6 +265,9 @@ pub async fn delete_profile_http<C: AdminApiContext>(
         .delete_profile(&profile_name)
         .await
         .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    if let Err(err) = overlay::remove_profile_overlay(&profile_name).await {
+        warn!("failed to remove overlay of profile {profile_name}: {err:#}");
+    }
     state.events.publish(AdminEvent::new(EVENT_PROFILES_CHANGED));
     Ok(StatusCode::NO_CONTENT)
 }
@@ -293,6 +340,132 @@ pub async fn clear_profile_subscription_http<C: AdminApiContext>(
     Ok(Json(info))
 }
 
+pub async fn get_profile_overlay_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<Json<ProfileOverlay>, ApiError> {
+    let profile_name = ensure_valid_profile_name(&name)?;
+    core_profiles::load_profile_info(&profile_name)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let overlay = overlay::load_overlay(&profile_name).await?;
+    Ok(Json(overlay))
+}
+
+pub async fn save_profile_overlay_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+    Json(payload): Json<ProfileOverlay>,
+) -> Result<Json<ProfileOverlay>, ApiError> {
+    let profile_name = ensure_valid_profile_name(&name)?;
+    apply_profile_overlay(&state, &profile_name, &payload).await?;
+    Ok(Json(payload))
+}
+
+pub async fn clear_profile_overlay_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let profile_name = ensure_valid_profile_name(&name)?;
+    apply_profile_overlay(&state, &profile_name, &ProfileOverlay::default()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn apply_profile_overlay<C: AdminApiContext>(
+    state: &AdminApiState<C>,
+    profile_name: &str,
+    profile_overlay: &ProfileOverlay,
+) -> Result<(), ApiError> {
+    core_profiles::load_profile_info(profile_name)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let manager = ConfigManager::new().map_err(|e| ApiError::internal(e.to_string()))?;
+    overlay::save_overlay(&manager, profile_name, profile_overlay)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    if manager.get_current().await.ok().as_deref() == Some(profile_name) {
+        schedule_apply(&state.ctx, &state.rebuild_status, "overlay-update");
+    }
+    state.events.publish(
+        AdminEvent::new(EVENT_PROFILES_CHANGED).with_detail(format!("overlay: {profile_name}")),
+    );
+    Ok(())
+}
+
+pub async fn list_profile_history_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<Json<Vec<ProfileRevision>>, ApiError> {
+    let profile_name = ensure_valid_profile_name(&name)?;
+    let manager = ConfigManager::new().map_err(|e| ApiError::internal(e.to_string()))?;
+    let revisions = manager
+        .list_revisions(&profile_name)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(revisions))
+}
+
+pub async fn get_profile_revision_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath((name, id)): AxumPath<(String, String)>,
+) -> Result<Json<ProfileRevisionContent>, ApiError> {
+    let profile_name = ensure_valid_profile_name(&name)?;
+    let manager = ConfigManager::new().map_err(|e| ApiError::internal(e.to_string()))?;
+    let content = manager
+        .load_revision(&profile_name, &id)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    Ok(Json(ProfileRevisionContent { id, content }))
+}
+
+pub async fn diff_profile_history_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+    Query(query): Query<ProfileHistoryDiffQuery>,
+) -> Result<Json<ProfileHistoryDiffResponse>, ApiError> {
+    let profile_name = ensure_valid_profile_name(&name)?;
+    let to = query.to.unwrap_or_else(|| CURRENT_REVISION.to_string());
+    let manager = ConfigManager::new().map_err(|e| ApiError::internal(e.to_string()))?;
+    let changes = manager
+        .diff_revisions(&profile_name, &query.from, &to)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    Ok(Json(ProfileHistoryDiffResponse {
+        from: query.from,
+        to,
+        changes,
+    }))
+}
+
+pub async fn restore_profile_revision_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath((name, id)): AxumPath<(String, String)>,
+) -> Result<Json<ProfileActionResponse>, ApiError> {
+    let profile_name = ensure_valid_profile_name(&name)?;
+    let manager = ConfigManager::new().map_err(|e| ApiError::internal(e.to_string()))?;
+    overlay::restore_profile_revision(&manager, &profile_name, &id)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+
+    let mut rebuild_scheduled = false;
+    if manager.get_current().await.ok().as_deref() == Some(profile_name.as_str()) {
+        schedule_apply(&state.ctx, &state.rebuild_status, "profile-restore");
+        rebuild_scheduled = true;
+    }
+    state.events.publish(
+        AdminEvent::new(EVENT_PROFILES_CHANGED)
+            .with_detail(format!("restore: {profile_name} -> {id}")),
+    );
+    let profile = core_profiles::load_profile_info(&profile_name)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(ProfileActionResponse {
+        profile,
+        rebuild_scheduled,
+        unparsed_links: Vec::new(),
+    }))
+}
+
 pub async fn update_profile_now_http<C: AdminApiContext>(
     AxumState(state): AxumState<AdminApiState<C>>,
     AxumPath(name): AxumPath<String>,
@@ -311,28 +484,24 @@ pub async fn update_profile_now_http<C: AdminApiContext>(
         .as_deref()
         .ok_or_else(|| ApiError::bad_request("未找到订阅链接"))?;
 
-    let content =
+    let fetched =
         core_subscription::fetch_subscription_text(&state.http_client, &state.raw_http_client, url)
             .await
             .map_err(|e| ApiError::internal(e.to_string()))?;
-    let content = core_subscription::strip_utf8_bom(&content);
-    if core_config::validate_yaml(&content).is_err() {
-        return Err(ApiError::bad_request("订阅内容不是有效的 YAML"));
-    }
-    manager
-        .save(&profile_name, &content)
-        .await
-        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let converted =
+        converter::normalize_subscription(&core_subscription::strip_utf8_bom(&fetched.content))
+            .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    overlay::save_profile_base(
+        &manager,
+        &profile_name,
+        &converted.content,
+        RevisionSource::SubscriptionUpdate,
+    )
+    .await
+    .map_err(|e| ApiError::bad_request(e.to_string()))?;
 
-    let now = Utc::now();
-    metadata.last_updated = Some(now);
-    metadata.next_update = if metadata.auto_update_enabled {
-        metadata
-            .update_interval_hours
-            .map(|hours| now + chrono::Duration::hours(hours as i64))
-    } else {
-        None
-    };
+    metadata.apply_subscription_metadata(&fetched.metadata);
+    metadata.mark_updated(Utc::now());
     manager
         .update_profile_metadata(&profile_name, &metadata)
         .await
@@ -345,13 +514,14 @@ pub async fn update_profile_now_http<C: AdminApiContext>(
         .as_deref()
         == Some(&profile_name);
     if rebuild_scheduled {
-        schedule_rebuild(&state.ctx, &state.rebuild_status, "subscription-update-now");
+        schedule_apply(&state.ctx, &state.rebuild_status, "subscription-update-now");
     }
     let profile = core_profiles::load_profile_info(&profile_name).await?;
     state.events.publish(AdminEvent::new(EVENT_PROFILES_CHANGED));
     Ok(Json(ProfileActionResponse {
         profile,
         rebuild_scheduled,
+        unparsed_links: converted.unparsed,
     }))
 }
 
@@ -448,6 +618,7 @@ pub async fn get_app_settings_http<C: AdminApiContext>(
         language: Some(settings.language),
         theme: Some(settings.theme),
         webdav: Some(settings.webdav),
+        auto_select: Some(settings.auto_select),
     }))
 }
 
@@ -455,6 +626,8 @@ pub async fn save_app_settings_http<C: AdminApiContext>(
     AxumState(state): AxumState<AdminApiState<C>>,
     Json(payload): Json<AppSettingsPayload>,
 ) -> Result<StatusCode, ApiError> {
+    // 与调度任务互斥，避免其按旧设置运行时被覆盖
+    let _guard = update_lock().lock().await;
     let mut settings = state.ctx.get_app_settings().await;
     
     if let Some(val) = payload.open_webui_on_startup {
@@ -474,8 +647,13 @@ pub async fn save_app_settings_http<C: AdminApiContext>(
         settings.theme = val;
     }
     if let Some(val) = payload.webdav {
+        val.conflict_policy.validate().map_err(|e| ApiError::bad_request(e.to_string()))?;
         settings.webdav = val;
     }
+    if let Some(val) = payload.auto_select {
+        auto_select::validate_policies(&val).map_err(|e| ApiError::bad_request(e.to_string()))?;
+        settings.auto_select = val;
+    }
 
     state.ctx.save_app_settings(settings).await.map_err(|e| ApiError::internal(e.to_string()))?;
     state.events.publish(AdminEvent::new(EVENT_SETTINGS_CHANGED));
@@ -494,7 +672,7 @@ pub async fn save_dns_config_http<C: AdminApiContext>(
     Json(payload): Json<dns::DnsConfigPatch>,
 ) -> Result<Json<dns::DnsConfig>, ApiError> {
     let config = dns::save_dns_config(payload).await?;
-    schedule_rebuild(&state.ctx, &state.rebuild_status, "dns-update");
+    schedule_apply(&state.ctx, &state.rebuild_status, "dns-update");
     state.events.publish(AdminEvent::new(EVENT_DNS_CHANGED));
     Ok(Json(config))
 }
@@ -511,7 +689,7 @@ pub async fn save_fake_ip_config_http<C: AdminApiContext>(
     Json(payload): Json<fake_ip::FakeIpConfigPatch>,
 ) -> Result<Json<fake_ip::FakeIpConfig>, ApiError> {
     let config = fake_ip::save_fake_ip_config(payload).await?;
-    schedule_rebuild(&state.ctx, &state.rebuild_status, "fake-ip-update");
+    schedule_apply(&state.ctx, &state.rebuild_status, "fake-ip-update");
     state.events.publish(AdminEvent::new(EVENT_FAKE_IP_CHANGED));
     Ok(Json(config))
 }
@@ -535,26 +713,248 @@ pub async fn save_rule_providers_http<C: AdminApiContext>(
     Json(payload): Json<rules::RuleProvidersPayload>,
 ) -> Result<Json<rules::RuleProvidersPayload>, ApiError> {
     let providers = rules::save_rule_providers(payload.providers).await?;
-    schedule_rebuild(&state.ctx, &state.rebuild_status, "rule-providers-update");
+    schedule_apply(&state.ctx, &state.rebuild_status, "rule-providers-update");
     state.events.publish(AdminEvent::new(EVENT_RULE_PROVIDERS_CHANGED));
     Ok(Json(rules::RuleProvidersPayload { providers }))
 }
 
+pub async fn list_rule_provider_cache_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+) -> Result<Json<RuleProviderCacheResponse>, ApiError> {
+    let providers = rule_providers::list_rule_provider_cache().await?;
+    Ok(Json(RuleProviderCacheResponse { providers }))
+}
+
+pub async fn refresh_rule_provider_cache_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<Json<RuleProviderCacheResponse>, ApiError> {
+    let providers = rule_providers::refresh_rule_providers(&state.http_client).await?;
+    state.events.publish(AdminEvent::new(EVENT_PROVIDERS_UPDATED));
+    Ok(Json(RuleProviderCacheResponse { providers }))
+}
+
+pub async fn download_rule_provider_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<Json<rule_providers::RuleProviderStatus>, ApiError> {
+    let status = rule_providers::refresh_rule_provider(&state.http_client, &name)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_PROVIDERS_UPDATED).with_detail(name));
+    Ok(Json(status))
+}
+
+pub async fn search_rule_provider_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+    Query(query): Query<RuleProviderSearchQuery>,
+) -> Result<Json<rule_providers::RuleProviderSearch>, ApiError> {
+    let limit = query.limit.unwrap_or(200);
+    let result = rule_providers::search_rule_provider(&name, &query.q, limit)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    Ok(Json(result))
+}
+
+pub async fn set_rule_provider_offline_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+    Json(payload): Json<RuleProviderOfflinePayload>,
+) -> Result<Json<rule_providers::RuleProviderStatus>, ApiError> {
+    let status = rule_providers::set_rule_provider_offline(&name, payload.offline)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    schedule_apply(&state.ctx, &state.rebuild_status, "rule-providers-update");
+    state.events.publish(AdminEvent::new(EVENT_RULE_PROVIDERS_CHANGED));
+    Ok(Json(status))
+}
+
 pub async fn get_rules_http<C: AdminApiContext>(
     AxumState(_state): AxumState<AdminApiState<C>>,
 ) -> Result<Json<rules::RulesPayload>, ApiError> {
     let rules_list = rules::load_rules().await?;
-    Ok(Json(rules::RulesPayload { rules: rules_list }))
+    Ok(Json(rules::RulesPayload::new(rules_list)))
 }
 
 pub async fn save_rules_http<C: AdminApiContext>(
     AxumState(state): AxumState<AdminApiState<C>>,
     Json(payload): Json<rules::RulesPayload>,
 ) -> Result<Json<rules::RulesPayload>, ApiError> {
+    rules::validate_rules(&payload.rules).map_err(|e| ApiError::bad_request(e.to_string()))?;
     let rules_list = rules::save_rules(payload.rules).await?;
-    schedule_rebuild(&state.ctx, &state.rebuild_status, "rules-update");
+    schedule_apply(&state.ctx, &state.rebuild_status, "rules-update");
     state.events.publish(AdminEvent::new(EVENT_RULES_CHANGED));
-    Ok(Json(rules::RulesPayload { rules: rules_list }))
+    Ok(Json(rules::RulesPayload::new(rules_list)))
+}
+
+pub async fn test_rules_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<rule_test::RuleTestQuery>,
+) -> Result<Json<rule_test::RuleTestResult>, ApiError> {
+    let result = rule_test::test_rules(payload).await?;
+    Ok(Json(result))
+}
+
+pub async fn get_profile_share_links_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<Json<ShareLinksResponse>, ApiError> {
+    let links = share::load_share_links(&name).await?;
+    let subscription = share::subscription_body(&links);
+    Ok(Json(ShareLinksResponse {
+        links,
+        subscription,
+    }))
+}
+
+/// Plain base64 body, so the URL itself works as a subscription on the LAN
+pub async fn get_profile_share_subscription_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<Response, ApiError> {
+    let links = share::load_share_links(&name).await?;
+    let body = share::subscription_body(&links);
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response())
+}
+
+pub async fn get_profile_share_qr_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+    Query(query): Query<ShareQrQuery>,
+) -> Result<Response, ApiError> {
+    let links = share::load_share_links(&name).await?;
+    let entry = links
+        .into_iter()
+        .find(|link| link.name == query.proxy)
+        .ok_or_else(|| ApiError::bad_request(format!("代理不存在: {}", query.proxy)))?;
+    let link = match (entry.link, entry.error) {
+        (Some(link), _) => link,
+        (None, error) => return Err(ApiError::bad_request(error.unwrap_or_default())),
+    };
+    let matrix = share::QrMatrix::encode(&link).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    match query.format.as_deref().unwrap_or("svg") {
+        "svg" => Ok(([(header::CONTENT_TYPE, "image/svg+xml")], matrix.to_svg()).into_response()),
+        "png" => {
+            let png = matrix.to_png(query.scale.unwrap_or(8))?;
+            Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+        }
+        other => Err(ApiError::bad_request(format!("不支持的二维码格式: {other}"))),
+    }
+}
+
+pub async fn list_proxies_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+) -> Result<Json<proxies::ProxiesPayload>, ApiError> {
+    let list = proxies::load_proxies().await?;
+    Ok(Json(proxies::ProxiesPayload { proxies: list }))
+}
+
+pub async fn get_proxy_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<Json<proxies::ProxyDefinition>, ApiError> {
+    let proxy = proxies::load_proxies()
+        .await?
+        .into_iter()
+        .find(|proxy| proxy.name() == name)
+        .ok_or_else(|| ApiError::bad_request(format!("代理不存在: {name}")))?;
+    Ok(Json(proxy))
+}
+
+pub async fn create_proxy_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<proxies::ProxyDefinition>,
+) -> Result<Json<proxies::ProxyDefinition>, ApiError> {
+    let proxy = proxies::create_proxy(payload)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    publish_proxies_changed(&state, proxy.name());
+    Ok(Json(proxy))
+}
+
+pub async fn update_proxy_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+    Json(payload): Json<proxies::ProxyDefinition>,
+) -> Result<Json<proxies::ProxyDefinition>, ApiError> {
+    let proxy = proxies::update_proxy(&name, payload)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    publish_proxies_changed(&state, proxy.name());
+    Ok(Json(proxy))
+}
+
+pub async fn delete_proxy_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    proxies::delete_proxy(&name)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    publish_proxies_changed(&state, &name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_proxy_groups_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+) -> Result<Json<proxies::ProxyGroupsPayload>, ApiError> {
+    let groups = proxies::load_proxy_groups().await?;
+    Ok(Json(proxies::ProxyGroupsPayload { groups }))
+}
+
+pub async fn get_proxy_group_http<C: AdminApiContext>(
+    AxumState(_state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<Json<proxies::ProxyGroupDefinition>, ApiError> {
+    let group = proxies::load_proxy_groups()
+        .await?
+        .into_iter()
+        .find(|group| group.name == name)
+        .ok_or_else(|| ApiError::bad_request(format!("策略组不存在: {name}")))?;
+    Ok(Json(group))
+}
+
+pub async fn create_proxy_group_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<proxies::ProxyGroupDefinition>,
+) -> Result<Json<proxies::ProxyGroupDefinition>, ApiError> {
+    let group = proxies::create_proxy_group(payload)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    publish_proxies_changed(&state, &group.name);
+    Ok(Json(group))
+}
+
+pub async fn update_proxy_group_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+    Json(payload): Json<proxies::ProxyGroupDefinition>,
+) -> Result<Json<proxies::ProxyGroupDefinition>, ApiError> {
+    let group = proxies::update_proxy_group(&name, payload)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    publish_proxies_changed(&state, &group.name);
+    Ok(Json(group))
+}
+
+pub async fn delete_proxy_group_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    proxies::delete_proxy_group(&name)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    publish_proxies_changed(&state, &name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn publish_proxies_changed<C: AdminApiContext>(state: &AdminApiState<C>, name: &str) {
+    schedule_apply(&state.ctx, &state.rebuild_status, "proxies-update");
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_PROXIES_CHANGED).with_detail(name));
 }
 
 pub async fn get_tun_config_http<C: AdminApiContext>(
@@ -569,11 +969,304 @@ pub async fn save_tun_config_http<C: AdminApiContext>(
     Json(payload): Json<tun::TunConfigPatch>,
 ) -> Result<Json<tun::TunConfig>, ApiError> {
     let config = tun::save_tun_config(payload).await?;
-    schedule_rebuild(&state.ctx, &state.rebuild_status, "tun-update");
+    schedule_apply(&state.ctx, &state.rebuild_status, "tun-update");
     state.events.publish(AdminEvent::new(EVENT_TUN_CHANGED));
     Ok(Json(config))
 }
 
+pub async fn list_proxy_providers_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<Json<ProxyProvidersResponse>, ApiError> {
+    let client = controller_client(&state.ctx).await?;
+    let providers = ProxyManager::new(client)
+        .list_providers()
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(ProxyProvidersResponse { providers }))
+}
+
+pub async fn update_proxy_provider_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let client = controller_client(&state.ctx).await?;
+    ProxyManager::new(client)
+        .update_provider(&name)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_PROVIDERS_UPDATED).with_detail(name));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn healthcheck_proxy_provider_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let client = controller_client(&state.ctx).await?;
+    ProxyManager::new(client)
+        .healthcheck_provider(&name)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_rule_provider_status_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<Json<RuleProvidersStatusResponse>, ApiError> {
+    let client = controller_client(&state.ctx).await?;
+    let mut providers: Vec<_> = client
+        .get_rule_providers()
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .into_values()
+        .collect();
+    providers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(RuleProvidersStatusResponse { providers }))
+}
+
+pub async fn update_rule_provider_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(name): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let client = controller_client(&state.ctx).await?;
+    client
+        .update_rule_provider(&name)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_PROVIDERS_UPDATED).with_detail(name));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn test_proxy_delay_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<DelayTestPayload>,
+) -> Result<Json<DelayTestResponse>, ApiError> {
+    if payload.options.url.trim().is_empty() {
+        return Err(ApiError::bad_request("测速地址不能为空"));
+    }
+    let client = controller_client(&state.ctx).await?;
+    let manager = ProxyManager::with_history(client, state.ctx.delay_history());
+    let groups = match payload.group {
+        Some(group) => {
+            let results = manager
+                .test_group(&group, &payload.options)
+                .await
+                .map_err(|e| ApiError::bad_request(e.to_string()))?;
+            HashMap::from([(group, results)])
+        }
+        None => manager
+            .test_all_groups(&payload.options)
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?,
+    };
+    Ok(Json(DelayTestResponse { groups }))
+}
+
+pub async fn select_proxy_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<ProxySelectPayload>,
+) -> Result<StatusCode, ApiError> {
+    let group = payload.group.trim();
+    let node = payload.node.trim();
+    if group.is_empty() || node.is_empty() {
+        return Err(ApiError::bad_request("代理组和节点名称不能为空"));
+    }
+    let client = controller_client(&state.ctx).await?;
+    client
+        .switch_proxy(group, node)
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let profile = current_profile_name()
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    if let Err(err) = proxy_selection::record_selection(&profile, group, node) {
+        warn!("failed to remember proxy selection: {err:#}");
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn run_auto_select_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<Json<AutoSelectRunResponse>, ApiError> {
+    let _guard = update_lock().lock().await;
+    let settings = state.ctx.get_app_settings().await;
+    if settings.auto_select.groups.is_empty() {
+        return Err(ApiError::bad_request("未配置自动选择策略"));
+    }
+    let switches = auto_select::run_auto_select_tick(&state.ctx, &settings.auto_select)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(AutoSelectRunResponse { switches }))
+}
+
+pub async fn get_proxy_delay_history_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<Json<HashMap<String, NodeDelayHistory>>, ApiError> {
+    let history = state.ctx.delay_history();
+    let nodes = history
+        .snapshot()
+        .into_iter()
+        .map(|(name, samples)| {
+            let stats = history.stats(&name);
+            (name, NodeDelayHistory { stats, samples })
+        })
+        .collect();
+    Ok(Json(nodes))
+}
+
+const DEFAULT_CONNECTION_STATS_LIMIT: usize = 50;
+
+pub async fn get_connection_stats_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Query(query): Query<ConnectionStatsQuery>,
+) -> Result<Json<ConnectionStatsResponse>, ApiError> {
+    let dimension = parse_dimension(query.by.as_deref())?.unwrap_or(Dimension::Host);
+    let limit = query.limit.unwrap_or(DEFAULT_CONNECTION_STATS_LIMIT);
+    let analytics = state.ctx.connection_analytics();
+    let entries = match query.since {
+        Some(since) => analytics.aggregate_since(dimension, since, limit),
+        None => analytics.aggregate(dimension, limit),
+    };
+    Ok(Json(ConnectionStatsResponse {
+        dimension,
+        totals: analytics.totals(),
+        active: analytics.active_connections(),
+        closed: analytics.closed_connections(),
+        entries,
+    }))
+}
+
+pub async fn get_connection_timeline_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Query(query): Query<ConnectionStatsQuery>,
+) -> Result<Json<ConnectionTimelineResponse>, ApiError> {
+    let dimension = parse_dimension(query.by.as_deref())?;
+    let filter = match (dimension, query.key.as_deref()) {
+        (Some(dimension), Some(key)) => Some((dimension, key)),
+        (None, Some(_)) => return Err(ApiError::bad_request("按 key 过滤时必须指定 by")),
+        _ => None,
+    };
+    let analytics = state.ctx.connection_analytics();
+    Ok(Json(ConnectionTimelineResponse {
+        bucket_secs: analytics.bucket_width().as_secs(),
+        buckets: analytics.timeline(query.since, filter),
+    }))
+}
+
+pub async fn reset_connection_stats_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<StatusCode, ApiError> {
+    state.ctx.connection_analytics().reset();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn parse_dimension(value: Option<&str>) -> Result<Option<Dimension>, ApiError> {
+    match value.map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(raw) => serde_json::from_value(serde_json::Value::String(raw.to_ascii_lowercase()))
+            .map(Some)
+            .map_err(|_| ApiError::bad_request(format!("不支持的统计维度: {raw}"))),
+    }
+}
+
+async fn open_usage_ledger<C: AdminApiContext>(
+    state: &AdminApiState<C>,
+) -> Result<UsageLedger, ApiError> {
+    state
+        .ctx
+        .usage_ledger()
+        .await
+        .map_err(|e| ApiError::internal(format!("打开流量账本失败: {e}")))
+}
+
+fn parse_usage_day(value: &str) -> Result<NaiveDate, ApiError> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map_err(|_| ApiError::bad_request(format!("无效的日期: {value}，应为 YYYY-MM-DD")))
+}
+
+pub async fn get_usage_summary_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageSummaryResponse>, ApiError> {
+    let day = match query.month.as_deref() {
+        Some(month) => usage::parse_month(month).map_err(|e| ApiError::bad_request(e.to_string()))?,
+        None => Local::now().date_naive(),
+    };
+    let ledger = open_usage_ledger(&state).await?;
+    let profiles = ledger
+        .month_summary(day)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(UsageSummaryResponse {
+        month: usage::month_key(day),
+        profiles,
+    }))
+}
+
+pub async fn get_profile_usage_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(profile): AxumPath<String>,
+    Query(query): Query<ProfileUsageQuery>,
+) -> Result<Json<ProfileUsageResponse>, ApiError> {
+    let today = Local::now().date_naive();
+    let to = match query.to.as_deref() {
+        Some(value) => parse_usage_day(value)?,
+        None => today,
+    };
+    let from = match query.from.as_deref() {
+        Some(value) => parse_usage_day(value)?,
+        None => to.with_day(1).unwrap_or(to),
+    };
+    if from > to {
+        return Err(ApiError::bad_request("开始日期不能晚于结束日期"));
+    }
+    let ledger = open_usage_ledger(&state).await?;
+    let usage = ledger
+        .profile_usage(&profile, to)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    let days = ledger
+        .daily_usage(&profile, from, to)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(ProfileUsageResponse { usage, days }))
+}
+
+pub async fn set_usage_quota_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(profile): AxumPath<String>,
+    Json(quota): Json<UsageQuota>,
+) -> Result<StatusCode, ApiError> {
+    let ledger = open_usage_ledger(&state).await?;
+    ledger
+        .set_quota(&profile, Some(quota))
+        .await
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_SETTINGS_CHANGED).with_detail(format!("usage-quota: {profile}")));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn clear_usage_quota_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    AxumPath(profile): AxumPath<String>,
+) -> Result<StatusCode, ApiError> {
+    let ledger = open_usage_ledger(&state).await?;
+    ledger
+        .set_quota(&profile, None)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_SETTINGS_CHANGED).with_detail(format!("usage-quota: {profile}")));
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn sync_webdav_now_http<C: AdminApiContext>(
     AxumState(state): AxumState<AdminApiState<C>>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
@@ -593,17 +1286,100 @@ pub async fn sync_webdav_now_http<C: AdminApiContext>(
         "success_count": summary.success_count,
         "failed_count": summary.failed_count,
         "total_actions": summary.total_actions,
+        "conflict_count": summary.conflict_count,
     })))
 }
 
+pub async fn get_sync_encryption_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Json<SyncEncryptionStatus> {
+    let settings = state.ctx.get_app_settings().await;
+    Json(SyncEncryptionStatus {
+        enabled: settings.webdav.encryption.enabled,
+        obfuscate_names: settings.webdav.encryption.obfuscate_names,
+        passphrase_set: sync_crypto::has_passphrase().await,
+    })
+}
+
+pub async fn set_sync_passphrase_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<SyncPassphraseRequest>,
+) -> Result<StatusCode, ApiError> {
+    if payload.passphrase.is_empty() {
+        return Err(ApiError::bad_request("同步口令不能为空"));
+    }
+    let settings = state.ctx.get_app_settings().await;
+    let dav = sync_backend::connect(&settings.webdav)
+        .map_err(|e| ApiError::bad_request(format!("无效的配置: {e}")))?;
+    // 远端已有校验文件时口令不一致会在这里被拒绝
+    sync_crypto::set_passphrase(&*dav, &payload.passphrase)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("设置同步口令失败: {e:#}")))?;
+
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_SETTINGS_CHANGED).with_detail("webdav-encryption"));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn clear_sync_passphrase_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<StatusCode, ApiError> {
+    sync_crypto::clear_passphrase()
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_SETTINGS_CHANGED).with_detail("webdav-encryption"));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_webdav_conflicts_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+) -> Result<Json<SyncConflictsResponse>, ApiError> {
+    let settings = state.ctx.get_app_settings().await;
+    let conflicts = crate::scheduler::sync::list_conflicts(&settings.webdav).await?;
+    Ok(Json(SyncConflictsResponse { conflicts }))
+}
+
+pub async fn get_webdav_conflict_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Query(query): Query<SyncConflictQuery>,
+) -> Result<Json<crate::scheduler::sync::ConflictDetail>, ApiError> {
+    let settings = state.ctx.get_app_settings().await;
+    let detail = crate::scheduler::sync::conflict_detail(&settings.webdav, &query.path)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("{e:#}")))?;
+    Ok(Json(detail))
+}
+
+pub async fn resolve_webdav_conflict_http<C: AdminApiContext>(
+    AxumState(state): AxumState<AdminApiState<C>>,
+    Json(payload): Json<ResolveConflictRequest>,
+) -> Result<StatusCode, ApiError> {
+    let resolution = match (payload.keep.as_str(), payload.content) {
+        ("local", _) => ConflictResolution::KeepLocal,
+        ("remote", _) => ConflictResolution::KeepRemote,
+        ("merged", Some(content)) => ConflictResolution::Merged(content.into_bytes()),
+        ("merged", None) => return Err(ApiError::bad_request("合并结果不能为空")),
+        (other, _) => return Err(ApiError::bad_request(format!("未知的冲突处理方式: {other}"))),
+    };
+    let settings = state.ctx.get_app_settings().await;
+    crate::scheduler::sync::resolve_conflict(&settings.webdav, &payload.path, resolution)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("处理冲突失败: {e:#}")))?;
+
+    state
+        .events
+        .publish(AdminEvent::new(EVENT_WEBDAV_SYNCED).with_detail(payload.path));
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn test_webdav_conn_http<C: AdminApiContext>(
     AxumState(_state): AxumState<AdminApiState<C>>,
     Json(payload): Json<WebDavConfig>,
 ) -> Result<StatusCode, ApiError> {
-    use dav_client::client::WebDavClient;
-    use dav_client::DavClient;
-
-    let dav = WebDavClient::new(&payload.url, &payload.username, &payload.password)
+    let dav = sync_backend::connect(&payload)
         .map_err(|e| ApiError::bad_request(format!("无效的配置: {e}")))?;
     
     // 尝试 list 根目录来测试连接
@@ -612,6 +1388,16 @@ pub async fn test_webdav_conn_http<C: AdminApiContext>(
     Ok(StatusCode::OK)
 }
 
+async fn controller_client<C: AdminApiContext>(ctx: &C) -> Result<MihomoClient, ApiError> {
+    ctx.controller_client()
+        .await
+        .map_err(|e| ApiError::internal(format!("内核控制接口不可用: {e}")))
+}
+
+async fn current_profile_name() -> anyhow::Result<String> {
+    Ok(ConfigManager::new()?.get_current().await?)
+}
+
 fn ensure_valid_profile_name(name: &str) -> Result<String, ApiError> {
     core_profiles::sanitize_profile_name(name).map_err(|e| ApiError::bad_request(e.to_string()))
 }
@@ -636,7 +1422,7 @@ async fn import_profile_from_url_internal<C: AdminApiContext>(
     name: &str,
     url: &str,
     activate: bool,
-) -> anyhow::Result<(ProfileInfo, bool)> {
+) -> anyhow::Result<(ProfileInfo, bool, Vec<UnparsedLink>)> {
     let profile_name = core_profiles::sanitize_profile_name(name)?;
     let source_url = url.trim();
     if source_url.is_empty() {
@@ -650,22 +1436,24 @@ async fn import_profile_from_url_internal<C: AdminApiContext>(
         "admin import profile start: name={} url={}",
         profile_name, masked_url
     );
-    let content =
+    let fetched =
         core_subscription::fetch_subscription_text(client, raw_client, source_url).await?;
-    if content.trim().is_empty() {
+    if fetched.content.trim().is_empty() {
         return Err(anyhow!(
             "订阅返回内容为空"
         ));
     }
-    let content = core_subscription::strip_utf8_bom(&content);
-    if core_config::validate_yaml(&content).is_err() {
-        return Err(anyhow!(
-            "订阅内容不是有效的 YAML"
-        ));
-    }
+    let converted =
+        converter::normalize_subscription(&core_subscription::strip_utf8_bom(&fetched.content))?;
 
     let manager = ConfigManager::new()?;
-    manager.save(&profile_name, &content).await?;
+    overlay::save_profile_base(
+        &manager,
+        &profile_name,
+        &converted.content,
+        RevisionSource::SubscriptionUpdate,
+    )
+    .await?;
 
     let mut rebuild_scheduled = false;
     if activate {
@@ -674,24 +1462,17 @@ async fn import_profile_from_url_internal<C: AdminApiContext>(
         rebuild_scheduled = true;
     }
 
-    let now = Utc::now();
     let mut metadata = manager.get_profile_metadata(&profile_name).await?;
     metadata.subscription_url = Some(source_url.to_string());
-    metadata.last_updated = Some(now);
-    metadata.next_update = if metadata.auto_update_enabled {
-        metadata
-            .update_interval_hours
-            .map(|hours| now + chrono::Duration::hours(hours as i64))
-    } else {
-        None
-    };
+    metadata.apply_subscription_metadata(&fetched.metadata);
+    metadata.mark_updated(Utc::now());
     manager.update_profile_metadata(&profile_name, &metadata).await?;
 
     let mut info = core_profiles::load_profile_info(&profile_name).await?;
     if activate {
         info.controller_url = manager.get_external_controller().await.ok();
     }
-    Ok((info, rebuild_scheduled))
+    Ok((info, rebuild_scheduled, converted.unparsed))
 }
 
 pub async fn log_admin_request(req: Request<Body>, next: Next) -> Response {
@@ -741,14 +1522,101 @@ fn schedule_rebuild<C: AdminApiContext>(
     tokio::spawn(async move {
         if let Err(err) = ctx.rebuild_runtime().await {
             warn!("runtime rebuild failed ({reason}): {err}");
+            rebuild_status.clear_applied_profile();
             rebuild_status.mark_error(err.to_string());
         } else {
             info!("runtime rebuild completed ({reason})");
+            match read_current_profile().await {
+                Ok((path, content)) => {
+                    rebuild_status.set_applied_profile(ctx.runtime_generation(), path, content)
+                }
+                Err(_) => rebuild_status.clear_applied_profile(),
+            }
+            rebuild_status.mark_method(ApplyMethod::Rebuild, reason);
             rebuild_status.mark_success();
         }
     });
 }
 
+/// Push an edit of the running profile to the core, restarting it only when
+/// the change cannot be applied through the controller
+fn schedule_apply<C: AdminApiContext>(
+    ctx: &C,
+    rebuild_status: &Arc<RebuildStatus>,
+    reason: &str,
+) {
+    let ctx = ctx.clone();
+    let reason = reason.to_string();
+    let rebuild_status = Arc::clone(rebuild_status);
+    info!("schedule runtime apply: {reason}");
+    rebuild_status.mark_start(&reason);
+    tokio::spawn(async move {
+        match apply_current_profile(&ctx, &rebuild_status).await {
+            Ok(method) => {
+                info!("runtime apply completed ({reason}): {method:?}");
+                rebuild_status.mark_success();
+            }
+            Err(err) => {
+                warn!("runtime apply failed ({reason}): {err}");
+                rebuild_status.clear_applied_profile();
+                rebuild_status.mark_error(err.to_string());
+            }
+        }
+    });
+}
+
+async fn apply_current_profile<C: AdminApiContext>(
+    ctx: &C,
+    rebuild_status: &RebuildStatus,
+) -> anyhow::Result<ApplyMethod> {
+    let (path, content) = read_current_profile().await?;
+    let plan = match rebuild_status.applied_profile(ctx.runtime_generation()) {
+        Some((applied_path, applied)) if applied_path == path => {
+            hot_reload::classify(&applied, &content)
+                .unwrap_or_else(|err| ApplyPlan::rebuild(format!("{err:#}")))
+        }
+        _ => ApplyPlan::rebuild("running profile unknown"),
+    };
+    let mut method = plan.method;
+    let mut detail = plan.reasons.join(", ");
+    if matches!(method, ApplyMethod::Patch | ApplyMethod::Reload)
+        && let Err(err) = hot_apply(ctx, &plan, &path).await
+    {
+        warn!("hot apply failed, falling back to rebuild: {err:#}");
+        method = ApplyMethod::Rebuild;
+        detail = format!("{detail} ({err})");
+    }
+    let content = if method == ApplyMethod::Rebuild {
+        ctx.rebuild_runtime().await?;
+        read_current_profile().await?.1
+    } else {
+        ctx.profile_hot_applied(content.clone()).await;
+        content
+    };
+    rebuild_status.set_applied_profile(ctx.runtime_generation(), path, content);
+    rebuild_status.mark_method(method, detail);
+    Ok(method)
+}
+
+async fn hot_apply<C: AdminApiContext>(
+    ctx: &C,
+    plan: &ApplyPlan,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    let client = ctx.controller_client().await?;
+    match (&plan.method, &plan.patch) {
+        (ApplyMethod::Patch, Some(patch)) => client.patch_config(patch.clone()).await?,
+        _ => client.reload_config(Some(&path.to_string_lossy())).await?,
+    }
+    Ok(())
+}
+
+async fn read_current_profile() -> anyhow::Result<(std::path::PathBuf, String)> {
+    let path = ConfigManager::new()?.get_current_path().await?;
+    let content = tokio::fs::read_to_string(&path).await?;
+    Ok((path, content))
+}
+
 fn sort_versions_desc(list: &mut [String]) {
     list.sort_by(|a, b| compare_versions_desc(a, b));
 }
@@ -7,7 +7,22 @@ use log::warn;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use infiltrator_core::{ProfileInfo, settings::WebDavConfig};
+use infiltrator_core::{
+    converter::UnparsedLink,
+    hot_reload::ApplyMethod,
+    rule_providers::RuleProviderStatus,
+    share::ShareLink,
+    ProfileInfo,
+    settings::{AutoSelectConfig, WebDavConfig},
+    usage::{DailyUsage, ProfileUsage},
+};
+use std::collections::HashMap;
+
+use crate::scheduler::auto_select::AutoSwitch;
+use state_store::SyncConflictRow;
+use mihomo_config::{Diagnostic, YamlChange};
+use mihomo_api::{AggregateEntry, BucketRollup, Dimension, TrafficTotals};
+use mihomo_api::{DelayResult, DelaySample, DelayStats, DelayTestOptions, ProxyProvider, RuleProvider};
 
 #[derive(Serialize, Deserialize)]
 pub struct SwitchProfilePayload {
@@ -61,6 +76,8 @@ pub struct RebuildStatusResponse {
     pub in_progress: bool,
     pub last_error: Option<String>,
     pub last_reason: Option<String>,
+    pub last_method: Option<ApplyMethod>,
+    pub last_detail: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -68,6 +85,163 @@ pub struct CacheFlushResponse {
     pub removed: bool,
 }
 
+#[derive(Serialize)]
+pub struct ProxyProvidersResponse {
+    pub providers: Vec<ProxyProvider>,
+}
+
+#[derive(Serialize)]
+pub struct RuleProvidersStatusResponse {
+    pub providers: Vec<RuleProvider>,
+}
+
+#[derive(Serialize)]
+pub struct RuleProviderCacheResponse {
+    pub providers: Vec<RuleProviderStatus>,
+}
+
+#[derive(Deserialize)]
+pub struct RuleProviderSearchQuery {
+    #[serde(default)]
+    pub q: String,
+    /// Defaults to 200
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct ShareLinksResponse {
+    pub links: Vec<ShareLink>,
+    /// Base64 body of every exportable link
+    pub subscription: String,
+}
+
+#[derive(Deserialize)]
+pub struct SyncPassphraseRequest {
+    pub passphrase: String,
+}
+
+#[derive(Serialize)]
+pub struct SyncEncryptionStatus {
+    pub enabled: bool,
+    pub obfuscate_names: bool,
+    /// Whether this device holds a key for the remote
+    pub passphrase_set: bool,
+}
+
+#[derive(Serialize)]
+pub struct SyncConflictsResponse {
+    pub conflicts: Vec<SyncConflictRow>,
+}
+
+#[derive(Deserialize)]
+pub struct SyncConflictQuery {
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResolveConflictRequest {
+    pub path: String,
+    /// `local`, `remote` or `merged`
+    pub keep: String,
+    /// Hand-merged content, required when `keep` is `merged`
+    pub content: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ShareQrQuery {
+    pub proxy: String,
+    /// `svg` (default) or `png`
+    pub format: Option<String>,
+    /// Pixels per module for PNG, defaults to 8
+    pub scale: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct RuleProviderOfflinePayload {
+    pub offline: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DelayTestPayload {
+    /// Tests every group when omitted.
+    pub group: Option<String>,
+    #[serde(flatten)]
+    pub options: DelayTestOptions,
+}
+
+#[derive(Serialize)]
+pub struct DelayTestResponse {
+    pub groups: HashMap<String, Vec<DelayResult>>,
+}
+
+#[derive(Deserialize)]
+pub struct ProxySelectPayload {
+    pub group: String,
+    pub node: String,
+}
+
+#[derive(Serialize)]
+pub struct AutoSelectRunResponse {
+    pub switches: Vec<AutoSwitch>,
+}
+
+#[derive(Deserialize)]
+pub struct ConnectionStatsQuery {
+    pub by: Option<String>,
+    pub key: Option<String>,
+    /// Unix timestamp in milliseconds; limits the result to recent buckets.
+    pub since: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct ConnectionStatsResponse {
+    pub dimension: Dimension,
+    pub totals: TrafficTotals,
+    pub active: usize,
+    pub closed: u64,
+    pub entries: Vec<AggregateEntry>,
+}
+
+#[derive(Serialize)]
+pub struct ConnectionTimelineResponse {
+    pub bucket_secs: u64,
+    pub buckets: Vec<BucketRollup>,
+}
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    /// `YYYY-MM`; defaults to the current month.
+    pub month: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ProfileUsageQuery {
+    /// `YYYY-MM-DD`, inclusive; defaults to the first day of the current month.
+    pub from: Option<String>,
+    /// `YYYY-MM-DD`, inclusive; defaults to today.
+    pub to: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UsageSummaryResponse {
+    pub month: String,
+    pub profiles: Vec<ProfileUsage>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileUsageResponse {
+    #[serde(flatten)]
+    pub usage: ProfileUsage,
+    pub days: Vec<DailyUsage>,
+}
+
+#[derive(Serialize)]
+pub struct NodeDelayHistory {
+    pub stats: Option<DelayStats>,
+    pub samples: Vec<DelaySample>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TunConfigPayload {
     pub enable: Option<bool>,
@@ -83,6 +257,40 @@ pub struct TunConfigPayload {
 pub struct ProfileActionResponse {
     pub profile: ProfileInfo,
     pub rebuild_scheduled: bool,
+    /// Share links the subscription contained but could not be converted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unparsed_links: Vec<UnparsedLink>,
+}
+
+#[derive(Deserialize)]
+pub struct ValidateProfilePayload {
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct ProfileValidationResponse {
+    pub valid: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileRevisionContent {
+    pub id: String,
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct ProfileHistoryDiffQuery {
+    pub from: String,
+    /// Revision id or `current`; defaults to `current`.
+    pub to: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileHistoryDiffResponse {
+    pub from: String,
+    pub to: String,
+    pub changes: Vec<YamlChange>,
 }
 
 #[derive(Deserialize)]
@@ -98,6 +306,7 @@ pub struct AppSettingsPayload {
     pub language: Option<String>,
     pub theme: Option<String>,
     pub webdav: Option<WebDavConfig>,
+    pub auto_select: Option<AutoSelectConfig>,
 }
 
 pub struct ApiError {
@@ -1,14 +1,18 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use infiltrator_http::{build_http_client, build_raw_http_client, HttpClient};
 
 use super::models::RebuildStatusResponse;
-use super::events::AdminEventBus;
+use super::events::{AdminEvent, AdminEventBus};
 
-use infiltrator_core::AppSettings;
+use infiltrator_core::{hot_reload::ApplyMethod, usage::UsageLedger, AppSettings};
+use mihomo_api::{ConnectionAnalytics, DelayHistoryStore, MihomoClient};
 
 #[async_trait::async_trait]
 pub trait AdminApiContext: Clone + Send + Sync + 'static {
@@ -27,6 +31,16 @@ pub trait AdminApiContext: Clone + Send + Sync + 'static {
     async fn open_profile_in_editor(&self, profile_name: &str) -> anyhow::Result<()>;
     async fn get_app_settings(&self) -> AppSettings;
     async fn save_app_settings(&self, settings: AppSettings) -> anyhow::Result<()>;
+    async fn controller_client(&self) -> anyhow::Result<MihomoClient>;
+    async fn usage_ledger(&self) -> anyhow::Result<UsageLedger>;
+    /// Changes every time a new core runtime is started, whoever started it
+    fn runtime_generation(&self) -> u64;
+    /// The running core picked up `content` without a restart; a rejected
+    /// rebuild later falls back to it
+    async fn profile_hot_applied(&self, content: String);
+    fn delay_history(&self) -> DelayHistoryStore;
+    fn connection_analytics(&self) -> ConnectionAnalytics;
+    fn emit_admin_event(&self, event: AdminEvent);
 }
 
 #[derive(Default)]
@@ -34,6 +48,8 @@ pub struct RebuildStatus {
     in_progress: AtomicBool,
     last_error: Mutex<Option<String>>,
     last_reason: Mutex<Option<String>>,
+    last_method: Mutex<Option<(ApplyMethod, String)>>,
+    applied: Mutex<Option<(u64, PathBuf, String)>>,
 }
 
 impl RebuildStatus {
@@ -48,10 +64,18 @@ impl RebuildStatus {
             .lock()
             .unwrap_or_else(|poisoned| poisoned.into_inner())
             .clone();
+        let (last_method, last_detail) = self
+            .last_method
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+            .unzip();
         RebuildStatusResponse {
             in_progress: self.in_progress.load(Ordering::SeqCst),
             last_error,
             last_reason,
+            last_method,
+            last_detail,
         }
     }
 
@@ -63,6 +87,40 @@ impl RebuildStatus {
         if let Ok(mut guard) = self.last_reason.lock() {
             *guard = Some(reason.to_string());
         }
+        if let Ok(mut guard) = self.last_method.lock() {
+            *guard = None;
+        }
+    }
+
+    /// Record how the last change reached the core
+    pub fn mark_method(&self, method: ApplyMethod, detail: String) {
+        if let Ok(mut guard) = self.last_method.lock() {
+            *guard = Some((method, detail));
+        }
+    }
+
+    /// Profile path and content the core is known to be running. A snapshot
+    /// taken under an older runtime generation is stale: the core was rebuilt
+    /// elsewhere (scheduler, tray) from whatever was on disk at the time.
+    pub fn applied_profile(&self, generation: u64) -> Option<(PathBuf, String)> {
+        self.applied
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_ref()
+            .filter(|(applied_generation, _, _)| *applied_generation == generation)
+            .map(|(_, path, content)| (path.clone(), content.clone()))
+    }
+
+    pub fn set_applied_profile(&self, generation: u64, path: PathBuf, content: String) {
+        if let Ok(mut guard) = self.applied.lock() {
+            *guard = Some((generation, path, content));
+        }
+    }
+
+    pub fn clear_applied_profile(&self) {
+        if let Ok(mut guard) = self.applied.lock() {
+            *guard = None;
+        }
     }
 
     pub fn mark_success(&self) {
@@ -104,6 +162,8 @@ impl<C: AdminApiContext> AdminApiState<C> {
 #[cfg(test)]
 mod tests {
     use super::RebuildStatus;
+    use std::path::PathBuf;
+    use infiltrator_core::hot_reload::ApplyMethod;
 
     #[test]
     fn rebuild_status_transitions() {
@@ -124,5 +184,26 @@ mod tests {
         assert!(!snapshot.in_progress);
         assert_eq!(snapshot.last_error.as_deref(), Some("boom"));
         assert_eq!(snapshot.last_reason.as_deref(), Some("import-activate"));
+
+        status.mark_start("rules-update");
+        status.mark_method(ApplyMethod::Reload, "rules".to_string());
+        status.mark_success();
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.last_method, Some(ApplyMethod::Reload));
+        assert_eq!(snapshot.last_detail.as_deref(), Some("rules"));
+    }
+
+    #[test]
+    fn applied_profile_expires_with_runtime_generation() {
+        let status = RebuildStatus::default();
+        status.set_applied_profile(3, PathBuf::from("a.yaml"), "mode: rule".to_string());
+        assert_eq!(
+            status.applied_profile(3),
+            Some((PathBuf::from("a.yaml"), "mode: rule".to_string()))
+        );
+        assert!(status.applied_profile(4).is_none());
+
+        status.clear_applied_profile();
+        assert!(status.applied_profile(3).is_none());
     }
 }
@@ -7,9 +7,11 @@ use tokio::time::{interval, Duration, Instant};
 use infiltrator_http::{build_http_client, build_raw_http_client};
 
 use crate::admin_api::AdminApiContext;
+use self::auto_select::run_auto_select_tick;
 use self::subscription::run_subscription_tick;
 use self::sync::run_sync_tick;
 
+pub mod auto_select;
 pub mod subscription;
 pub mod sync;
 
@@ -43,17 +45,20 @@ impl SubscriptionScheduler {
                 Some(instant) => (instant, false),
                 None => (now, true),
             };
+            let (mut last_auto_select, mut force_auto_select) = match initial_instant {
+                Some(instant) => (instant, false),
+                None => (now, true),
+            };
             
             loop {
                 tokio::select! {
                     _ = ticker.tick() => {
-                        let settings = ctx_clone.get_app_settings().await;
-
-                        // 锁保护，防止多个调度任务重叠
+                        // 锁保护，防止多个调度任务重叠；设置在持锁后读取
                         let _guard = match update_lock().try_lock() {
                             Ok(guard) => guard,
                             Err(_) => continue,
                         };
+                        let settings = ctx_clone.get_app_settings().await;
 
                         // 1. 订阅更新 (每小时一次)
                         if force_sub_update || last_sub_update.elapsed() >= Duration::from_secs(3600) {
@@ -85,6 +90,18 @@ impl SubscriptionScheduler {
                                 force_sync_update = false;
                             }
                         }
+
+                        // 3. 自动选择最优节点
+                        if settings.auto_select.enabled {
+                            let interval = Duration::from_secs(settings.auto_select.interval_mins.max(1) as u64 * 60);
+                            if force_auto_select || last_auto_select.elapsed() >= interval {
+                                if let Err(err) = run_auto_select_tick(&ctx_clone, &settings.auto_select).await {
+                                    warn!("auto-select scheduler failed: {err:#}");
+                                }
+                                last_auto_select = Instant::now();
+                                force_auto_select = false;
+                            }
+                        }
                     }
                     _ = stop_rx.changed() => {
                         if *stop_rx.borrow() {
@@ -0,0 +1,317 @@
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use regex::Regex;
+use serde::Serialize;
+
+use infiltrator_core::proxy_selection;
+use infiltrator_core::settings::{AutoSelectConfig, GroupPolicy, SelectionStrategy};
+use mihomo_api::{DelayResult, DelayTestOptions, ProxyManager};
+use mihomo_config::ConfigManager;
+
+use crate::admin_api::{AdminApiContext, AdminEvent, EVENT_PROXY_AUTO_SWITCHED};
+
+/// A switch performed by the auto-select engine.
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSwitch {
+    pub group: String,
+    pub from: Option<String>,
+    pub to: String,
+    pub delay: u32,
+}
+
+/// Checks that every policy names a group and has a valid exclusion regex.
+pub fn validate_policies(config: &AutoSelectConfig) -> Result<()> {
+    for policy in &config.groups {
+        if policy.group.trim().is_empty() {
+            return Err(anyhow!("自动选择策略的代理组名称不能为空"));
+        }
+        compile_exclude(policy)?;
+    }
+    Ok(())
+}
+
+fn compile_exclude(policy: &GroupPolicy) -> Result<Option<Regex>> {
+    match policy.exclude.as_deref().map(str::trim) {
+        Some(pattern) if !pattern.is_empty() => Regex::new(pattern)
+            .map(Some)
+            .map_err(|e| anyhow!("代理组 {} 的排除规则无效: {e}", policy.group)),
+        _ => Ok(None),
+    }
+}
+
+/// Picks the node a group should be on, given fresh delay results.
+///
+/// The strategy decides among the reachable, non-excluded nodes. A live
+/// manual pick is kept unless that choice beats it by more than
+/// `manual_margin_ms`. Returns `None` when nothing is reachable, in which
+/// case the group is left alone.
+pub fn choose_node(
+    strategy: &SelectionStrategy,
+    current: Option<&str>,
+    manual: Option<&str>,
+    manual_margin_ms: u32,
+    results: &[DelayResult],
+    exclude: Option<&Regex>,
+) -> Option<(String, u32)> {
+    let alive: Vec<(&str, u32)> = results
+        .iter()
+        .filter(|result| !exclude.is_some_and(|re| re.is_match(&result.name)))
+        .filter_map(|result| result.delay.map(|delay| (result.name.as_str(), delay)))
+        .collect();
+    let delay_of = |name: &str| alive.iter().find(|(n, _)| *n == name).map(|(_, d)| *d);
+    let fastest = alive
+        .iter()
+        .min_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(b.0)))
+        .copied()?;
+
+    let chosen = match strategy {
+        SelectionStrategy::LowestLatency => fastest,
+        SelectionStrategy::Hysteresis { tolerance_ms } => {
+            match current.and_then(|name| delay_of(name).map(|delay| (name, delay))) {
+                Some(current) if current.1 <= fastest.1.saturating_add(*tolerance_ms) => current,
+                _ => fastest,
+            }
+        }
+        SelectionStrategy::Failover { order } => order
+            .iter()
+            .find_map(|name| delay_of(name).map(|delay| (name.as_str(), delay)))
+            .unwrap_or(fastest),
+    };
+    if let Some(manual) = manual
+        && let Some(delay) = delay_of(manual)
+        && delay <= chosen.1.saturating_add(manual_margin_ms)
+    {
+        return Some((manual.to_string(), delay));
+    }
+    Some((chosen.0.to_string(), chosen.1))
+}
+
+/// Tests every configured group and switches selectors to the node their
+/// policy prefers. Groups that are missing or not selectors are skipped.
+pub async fn run_auto_select_tick<C: AdminApiContext>(
+    ctx: &C,
+    config: &AutoSelectConfig,
+) -> Result<Vec<AutoSwitch>> {
+    if config.groups.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let client = ctx.controller_client().await?;
+    let profile = ConfigManager::new()?
+        .get_current()
+        .await
+        .context("failed to resolve current profile")?;
+    let selections = proxy_selection::load_proxy_selections().unwrap_or_else(|err| {
+        warn!("failed to load proxy selections: {err:#}");
+        Default::default()
+    });
+    let proxies = client.get_proxies().await?;
+    let manager = ProxyManager::with_history(client.clone(), ctx.delay_history());
+    let options = DelayTestOptions {
+        url: config.test_url.clone(),
+        expected_status: config.expected_status.clone(),
+        timeout_ms: config.timeout_ms,
+        ..DelayTestOptions::default()
+    };
+
+    let mut switches = Vec::new();
+    for policy in &config.groups {
+        let Some(group) = proxies.get(&policy.group) else {
+            warn!("auto-select: group {} not found", policy.group);
+            continue;
+        };
+        if group.proxy_type != "Selector" {
+            warn!(
+                "auto-select: group {} is {}, only selectors can be switched",
+                policy.group, group.proxy_type
+            );
+            continue;
+        }
+        let exclude = match compile_exclude(policy) {
+            Ok(exclude) => exclude,
+            Err(err) => {
+                warn!("auto-select: {err:#}");
+                continue;
+            }
+        };
+        let results = match manager.test_group(&policy.group, &options).await {
+            Ok(results) => results,
+            Err(err) => {
+                warn!("auto-select: delay test for {} failed: {err}", policy.group);
+                continue;
+            }
+        };
+
+        let current = group.now.as_deref();
+        let manual = selections.get(&profile, &policy.group);
+        let Some((target, delay)) = choose_node(
+            &policy.strategy,
+            current,
+            manual,
+            config.manual_margin_ms,
+            &results,
+            exclude.as_ref(),
+        ) else {
+            warn!("auto-select: no reachable node in {}", policy.group);
+            continue;
+        };
+        if let Some(manual) = manual
+            && manual != target
+        {
+            info!("auto-select: {} outgrew manual pick {manual}", policy.group);
+            if let Err(err) = proxy_selection::forget_selection(&profile, &policy.group) {
+                warn!("auto-select: failed to forget manual pick: {err:#}");
+            }
+        }
+        if current == Some(target.as_str()) {
+            continue;
+        }
+
+        if let Err(err) = client.switch_proxy(&policy.group, &target).await {
+            warn!("auto-select: failed to switch {} to {target}: {err}", policy.group);
+            continue;
+        }
+        info!(
+            "auto-select: {} {} -> {target} ({delay} ms)",
+            policy.group,
+            current.unwrap_or("-")
+        );
+        ctx.emit_admin_event(
+            AdminEvent::new(EVENT_PROXY_AUTO_SWITCHED).with_detail(format!(
+                "{}: {} -> {target}",
+                policy.group,
+                current.unwrap_or("-")
+            )),
+        );
+        switches.push(AutoSwitch {
+            group: policy.group.clone(),
+            from: current.map(str::to_string),
+            to: target,
+            delay,
+        });
+    }
+    Ok(switches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(entries: &[(&str, Option<u32>)]) -> Vec<DelayResult> {
+        entries
+            .iter()
+            .map(|(name, delay)| DelayResult {
+                name: name.to_string(),
+                delay: *delay,
+                error: delay.is_none().then(|| "timeout".to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lowest_latency_picks_fastest_alive() {
+        let results = results(&[("A", Some(300)), ("B", None), ("C", Some(120))]);
+        let chosen = choose_node(&SelectionStrategy::LowestLatency, Some("A"), None, 0, &results, None);
+        assert_eq!(chosen, Some(("C".to_string(), 120)));
+    }
+
+    #[test]
+    fn test_nothing_alive_keeps_group() {
+        let results = results(&[("A", None), ("B", None)]);
+        assert!(choose_node(&SelectionStrategy::LowestLatency, Some("A"), None, 0, &results, None).is_none());
+    }
+
+    #[test]
+    fn test_hysteresis_keeps_current_within_tolerance() {
+        let strategy = SelectionStrategy::Hysteresis { tolerance_ms: 50 };
+        let close = results(&[("A", Some(140)), ("B", Some(100))]);
+        assert_eq!(
+            choose_node(&strategy, Some("A"), None, 0, &close, None),
+            Some(("A".to_string(), 140))
+        );
+
+        let far = results(&[("A", Some(200)), ("B", Some(100))]);
+        assert_eq!(
+            choose_node(&strategy, Some("A"), None, 0, &far, None),
+            Some(("B".to_string(), 100))
+        );
+
+        let down = results(&[("A", None), ("B", Some(100))]);
+        assert_eq!(
+            choose_node(&strategy, Some("A"), None, 0, &down, None),
+            Some(("B".to_string(), 100))
+        );
+    }
+
+    #[test]
+    fn test_failover_follows_order() {
+        let strategy = SelectionStrategy::Failover {
+            order: vec!["A".to_string(), "B".to_string()],
+        };
+        let all_up = results(&[("A", Some(400)), ("B", Some(100)), ("C", Some(50))]);
+        assert_eq!(
+            choose_node(&strategy, None, None, 0, &all_up, None),
+            Some(("A".to_string(), 400))
+        );
+
+        let first_down = results(&[("A", None), ("B", Some(100)), ("C", Some(50))]);
+        assert_eq!(
+            choose_node(&strategy, None, None, 0, &first_down, None),
+            Some(("B".to_string(), 100))
+        );
+
+        let listed_down = results(&[("A", None), ("B", None), ("C", Some(50)), ("D", Some(80))]);
+        assert_eq!(
+            choose_node(&strategy, None, None, 0, &listed_down, None),
+            Some(("C".to_string(), 50))
+        );
+    }
+
+    #[test]
+    fn test_exclusion_regex_filters_nodes() {
+        let exclude = Regex::new("(?i)expire|剩余").unwrap();
+        let results = results(&[("Expire: 2026-01-01", Some(1)), ("剩余流量", Some(2)), ("HK", Some(90))]);
+        assert_eq!(
+            choose_node(&SelectionStrategy::LowestLatency, None, None, 0, &results, Some(&exclude)),
+            Some(("HK".to_string(), 90))
+        );
+    }
+
+    #[test]
+    fn test_manual_pick_kept_within_margin() {
+        let close = results(&[("A", Some(250)), ("B", Some(100))]);
+        assert_eq!(
+            choose_node(&SelectionStrategy::LowestLatency, Some("A"), Some("A"), 200, &close, None),
+            Some(("A".to_string(), 250))
+        );
+
+        let far = results(&[("A", Some(400)), ("B", Some(100))]);
+        assert_eq!(
+            choose_node(&SelectionStrategy::LowestLatency, Some("A"), Some("A"), 200, &far, None),
+            Some(("B".to_string(), 100))
+        );
+
+        let dead = results(&[("A", None), ("B", Some(100))]);
+        assert_eq!(
+            choose_node(&SelectionStrategy::LowestLatency, Some("A"), Some("A"), 200, &dead, None),
+            Some(("B".to_string(), 100))
+        );
+    }
+
+    #[test]
+    fn test_validate_policies() {
+        let mut config = AutoSelectConfig::default();
+        config.groups.push(GroupPolicy {
+            group: "Proxy".to_string(),
+            strategy: SelectionStrategy::LowestLatency,
+            exclude: Some("[".to_string()),
+        });
+        assert!(validate_policies(&config).is_err());
+
+        config.groups[0].exclude = Some("^(DIRECT|REJECT)$".to_string());
+        assert!(validate_policies(&config).is_ok());
+
+        config.groups[0].group = " ".to_string();
+        assert!(validate_policies(&config).is_err());
+    }
+}
@@ -1,14 +1,14 @@
-use anyhow::anyhow;
 use chrono::{Duration as ChronoDuration, Utc};
 use log::{info, warn};
-use mihomo_config::{ConfigManager, Profile};
+use mihomo_config::{ConfigManager, Profile, RevisionSource};
 use infiltrator_http::HttpClient;
 use tokio::time::{sleep, Duration};
 use tokio::task::JoinSet;
 
 use crate::admin_api::AdminApiContext;
 use infiltrator_core::{
-    config as core_config,
+    converter,
+    overlay,
     subscription::{fetch_subscription_text, mask_subscription_url, strip_utf8_bom},
 };
 
@@ -23,6 +23,8 @@ pub struct SubscriptionUpdateSummary {
 pub(crate) struct SubscriptionUpdateResult {
     profile_name: String,
     needs_rebuild: bool,
+    /// Summary of share links dropped during conversion, shown in the notification
+    note: Option<String>,
 }
 
 pub(super) async fn run_subscription_tick<C: AdminApiContext>(
@@ -42,9 +44,8 @@ pub(super) async fn run_subscription_tick<C: AdminApiContext>(
             Some(url) if !url.trim().is_empty() => url.trim().to_string(),
             _ => continue,
         };
-        let interval_hours = match profile.update_interval_hours {
-            Some(hours) if hours > 0 => Some(hours),
-            _ => continue,
+        let Some(effective_hours) = profile.effective_update_interval_hours() else {
+            continue;
         };
         let due = profile.next_update.map(|next| next <= now).unwrap_or(true);
         if !due {
@@ -56,7 +57,7 @@ pub(super) async fn run_subscription_tick<C: AdminApiContext>(
                 manager: &manager,
                 profile: &profile,
                 url: &url,
-                interval_hours,
+                interval_hours: profile.update_interval_hours,
                 auto_update_enabled: true,
                 now,
                 client,
@@ -66,11 +67,11 @@ pub(super) async fn run_subscription_tick<C: AdminApiContext>(
         )
         .await;
         match result {
-            Ok(needs_rebuild) => {
-                if needs_rebuild {
+            Ok(update_result) => {
+                if update_result.needs_rebuild {
                     rebuild_needed = true;
                 }
-                ctx.notify_subscription_update(profile.name.clone(), true, None)
+                ctx.notify_subscription_update(profile.name.clone(), true, update_result.note)
                     .await;
             }
             Err(err) => {
@@ -86,9 +87,7 @@ pub(super) async fn run_subscription_tick<C: AdminApiContext>(
                     Some(err.to_string()),
                 )
                 .await;
-                if let Some(hours) = interval_hours {
-                    let _ = schedule_next_attempt(&manager, &profile, hours, now).await;
-                }
+                let _ = schedule_next_attempt(&manager, &profile, effective_hours, now).await;
             }
         }
     }
@@ -151,9 +150,9 @@ pub async fn update_all_subscriptions<C: AdminApiContext>(
                         }
                         summary.updated += 1;
                         ctx.notify_subscription_update(
-                            update_result.profile_name.clone(),
+                            update_result.profile_name,
                             true,
-                            None,
+                            update_result.note,
                         )
                         .await;
                     }
@@ -195,10 +194,7 @@ pub async fn update_all_subscriptions<C: AdminApiContext>(
             .await;
 
             match result {
-                Ok(needs_rebuild) => Ok(SubscriptionUpdateResult {
-                    profile_name: profile_name.clone(),
-                    needs_rebuild,
-                }),
+                Ok(update_result) => Ok(update_result),
                 Err(err) => {
                     warn!(
                         "subscription update failed: profile={} url={} err={:#}",
@@ -221,9 +217,9 @@ pub async fn update_all_subscriptions<C: AdminApiContext>(
                 }
                 summary.updated += 1;
                 ctx.notify_subscription_update(
-                    update_result.profile_name.clone(),
+                    update_result.profile_name,
                     true,
-                    None,
+                    update_result.note,
                 )
                 .await;
             }
@@ -259,42 +255,42 @@ struct ProfileUpdateParams<'a> {
 
 async fn update_profile_subscription(
     params: ProfileUpdateParams<'_>,
-) -> anyhow::Result<bool> {
+) -> anyhow::Result<SubscriptionUpdateResult> {
     info!(
         "subscription update: profile={} url={}",
         params.profile.name,
         mask_subscription_url(params.url)
     );
-    let content = fetch_subscription_text(params.client, params.raw_client, params.url).await?;
-    let content = strip_utf8_bom(&content);
-    if core_config::validate_yaml(&content).is_err() {
-        return Err(anyhow!("订阅内容不是有效的 YAML"));
-    }
-    params.manager
-        .save(&params.profile.name, &content)
-        .await
-        .map_err(|err| anyhow!(err.to_string()))?;
+    let fetched = fetch_subscription_text(params.client, params.raw_client, params.url).await?;
+    let converted = converter::normalize_subscription(&strip_utf8_bom(&fetched.content))?;
+    overlay::save_profile_base(
+        params.manager,
+        &params.profile.name,
+        &converted.content,
+        RevisionSource::SubscriptionUpdate,
+    )
+    .await?;
 
-    let next_update = if params.auto_update_enabled {
-        params.interval_hours.map(|hours| params.now + ChronoDuration::hours(hours as i64))
-    } else {
-        None
-    };
     let mut updated = params.profile.clone();
     updated.subscription_url = Some(params.url.to_string());
     updated.auto_update_enabled = params.auto_update_enabled;
     updated.update_interval_hours = params.interval_hours;
-    updated.last_updated = Some(params.now);
-    updated.next_update = next_update;
+    // A provider-suggested interval takes over scheduling from here on.
+    updated.apply_subscription_metadata(&fetched.metadata);
+    updated.mark_updated(params.now);
     params.manager.update_profile_metadata(&params.profile.name, &updated).await?;
 
-    Ok(params.profile.active)
+    Ok(SubscriptionUpdateResult {
+        profile_name: params.profile.name.clone(),
+        needs_rebuild: params.profile.active,
+        note: converter::describe_unparsed(&converted.unparsed),
+    })
 }
 
 async fn update_profile_subscription_with_retry(
     params: ProfileUpdateParams<'_>,
     max_attempts: usize,
-) -> anyhow::Result<bool> {
+) -> anyhow::Result<SubscriptionUpdateResult> {
     let mut attempt = 0usize;
     let mut delay = Duration::from_secs(2);
     loop {
@@ -311,7 +307,7 @@ async fn update_profile_subscription_with_retry(
         };
         match update_profile_subscription(retry_params).await
         {
-            Ok(needs_rebuild) => return Ok(needs_rebuild),
+            Ok(update_result) => return Ok(update_result),
             Err(err) => {
                 if attempt >= max_attempts {
                     return Err(err);
@@ -5,12 +5,12 @@ mod tests {
     use mihomo_config::{ConfigManager, Profile};
     use chrono::{Utc, Duration as ChronoDuration};
     use std::sync::{Arc, Mutex};
-    use crate::scheduler::subscription::{SubscriptionUpdateSummary, update_all_subscriptions, schedule_next_attempt};
+    use crate::scheduler::subscription::{SubscriptionUpdateSummary, update_all_subscriptions, schedule_next_attempt, run_subscription_tick};
     use infiltrator_core::subscription::mask_subscription_url;
     use infiltrator_core::AppSettings;
-    use std::sync::LazyLock;
 
-    static TEST_MUTEX: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
+    // 测试共享 home 目录覆盖，需要串行；用异步锁以便跨 await 持有
+    static TEST_MUTEX: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
 
     #[derive(Clone)]
     struct MockContext {
@@ -43,6 +43,21 @@ mod tests {
         async fn open_profile_in_editor(&self, _profile_name: &str) -> anyhow::Result<()> { Ok(()) }
         async fn get_app_settings(&self) -> AppSettings { AppSettings::default() }
         async fn save_app_settings(&self, _settings: AppSettings) -> anyhow::Result<()> { Ok(()) }
+        async fn controller_client(&self) -> anyhow::Result<mihomo_api::MihomoClient> {
+            Err(anyhow::anyhow!("core not running"))
+        }
+        async fn usage_ledger(&self) -> anyhow::Result<infiltrator_core::usage::UsageLedger> {
+            infiltrator_core::usage::UsageLedger::open_default().await
+        }
+        fn runtime_generation(&self) -> u64 { 0 }
+        async fn profile_hot_applied(&self, _content: String) {}
+        fn delay_history(&self) -> mihomo_api::DelayHistoryStore {
+            mihomo_api::DelayHistoryStore::default()
+        }
+        fn connection_analytics(&self) -> mihomo_api::ConnectionAnalytics {
+            mihomo_api::ConnectionAnalytics::default()
+        }
+        fn emit_admin_event(&self, _event: crate::admin_api::AdminEvent) {}
     }
 
     #[tokio::test]
@@ -62,7 +77,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_update_all_subscriptions_with_no_profiles() {
-        let _guard = TEST_MUTEX.lock().unwrap();
+        let _guard = TEST_MUTEX.lock().await;
         let temp_dir = tempfile::Builder::new().prefix("sub-test-none-").tempdir().unwrap();
         mihomo_platform::clear_home_dir_override();
         mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
@@ -84,7 +99,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_update_all_subscriptions_parallel_concurrency() {
-        let _guard = TEST_MUTEX.lock().unwrap();
+        let _guard = TEST_MUTEX.lock().await;
         let temp_dir = tempfile::Builder::new().prefix("sub-test-parallel-").tempdir().unwrap();
         mihomo_platform::clear_home_dir_override();
         mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
@@ -100,8 +115,10 @@ mod tests {
         let configs_dir = temp_dir.path().join("configs");
         let _ = std::fs::create_dir_all(&configs_dir);
 
-        for i in 0..10 {
-            let profile_name = format!("test-profile-{}", i);
+        // 订阅链接存在钥匙串里，不随临时目录删除，名字需每次运行唯一
+        let suffix = temp_dir.path().file_name().unwrap().to_string_lossy().into_owned();
+        let names: Vec<String> = (0..10).map(|i| format!("test-profile-{suffix}-{i}")).collect();
+        for (i, profile_name) in names.iter().enumerate() {
             let profile_path = configs_dir.join(format!("{}.yaml", profile_name));
             let _ = std::fs::write(&profile_path, "port: 7890");
             
@@ -114,7 +131,7 @@ mod tests {
             profile.auto_update_enabled = true;
             profile.update_interval_hours = Some(24);
             
-            manager.update_profile_metadata(&profile_name, &profile).await.unwrap();
+            manager.update_profile_metadata(profile_name, &profile).await.unwrap();
         }
 
         let profiles = manager.list_profiles().await.unwrap();
@@ -126,6 +143,9 @@ mod tests {
         let summary = result.unwrap();
         assert!(summary.total >= 10, "Summary total should be >= 10, but was {}", summary.total);
 
+        for profile_name in &names {
+            let _ = manager.delete_profile(profile_name).await;
+        }
         mihomo_platform::clear_home_dir_override();
     }
 
@@ -149,14 +169,17 @@ mod tests {
 
     #[tokio::test]
     async fn test_schedule_next_attempt() {
-        let _guard = TEST_MUTEX.lock().unwrap();
+        let _guard = TEST_MUTEX.lock().await;
         let temp_dir = tempfile::Builder::new().prefix("sub-test-schedule-").tempdir().unwrap();
         mihomo_platform::clear_home_dir_override();
         mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
         
         let manager = ConfigManager::new().unwrap();
 
-        let profile_name = "test-schedule".to_string();
+        let profile_name = format!(
+            "test-schedule-{}",
+            temp_dir.path().file_name().unwrap().to_string_lossy()
+        );
         let configs_dir = temp_dir.path().join("configs");
         let _ = std::fs::create_dir_all(&configs_dir);
         let profile_path = configs_dir.join(format!("{}.yaml", profile_name));
@@ -187,4 +210,108 @@ mod tests {
 
         mihomo_platform::clear_home_dir_override();
     }
+
+    #[tokio::test]
+    async fn test_subscription_tick_records_header_metadata() {
+        let _guard = TEST_MUTEX.lock().await;
+        let mut server = mockito::Server::new_async().await;
+        let _m = server
+            .mock("GET", "/sub")
+            .with_status(200)
+            .with_header(
+                "subscription-userinfo",
+                "upload=100; download=400; total=1000; expire=1767225600",
+            )
+            .with_header("profile-update-interval", "24")
+            .with_body("port: 7890\nmode: rule")
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::Builder::new().prefix("sub-test-headers-").tempdir().unwrap();
+        mihomo_platform::clear_home_dir_override();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+
+        // Subscription URLs live in the OS keyring, which outlives the temp dir.
+        let profile_name = format!(
+            "with-headers-{}",
+            temp_dir.path().file_name().unwrap().to_string_lossy()
+        );
+        let manager = ConfigManager::new().unwrap();
+        manager.save(&profile_name, "port: 7890").await.unwrap();
+        let mut profile = Profile::new(
+            profile_name.clone(),
+            temp_dir.path().join("configs").join(format!("{profile_name}.yaml")),
+            false,
+        );
+        profile.subscription_url = Some(format!("{}/sub", server.url()));
+        profile.auto_update_enabled = true;
+        profile.update_interval_hours = Some(6);
+        manager.update_profile_metadata(&profile_name, &profile).await.unwrap();
+
+        let ctx = MockContext {
+            notifications: Arc::new(Mutex::new(vec![])),
+        };
+        let client = HttpClient::new();
+        let before = Utc::now();
+        run_subscription_tick(&ctx, &client, &client).await.unwrap();
+
+        let updated = manager.get_profile_metadata(&profile_name).await.unwrap();
+        let info = updated.subscription_info.expect("userinfo should be stored");
+        assert_eq!(info.used(), 500);
+        assert_eq!(info.remaining(), Some(500));
+        assert_eq!(updated.update_interval_hours, Some(6));
+        assert_eq!(updated.suggested_update_interval_hours, Some(24));
+        let next_update = updated.next_update.expect("next update should be scheduled");
+        assert!(next_update >= before + ChronoDuration::hours(24));
+
+        manager.delete_profile(&profile_name).await.unwrap();
+        mihomo_platform::clear_home_dir_override();
+    }
+
+    #[tokio::test]
+    async fn test_subscription_tick_reports_unparsed_links() {
+        let _guard = TEST_MUTEX.lock().await;
+        let mut server = mockito::Server::new_async().await;
+        let _m = server
+            .mock("GET", "/links")
+            .with_status(200)
+            .with_body("trojan://pw@a.example.com:443#A\nssr://nope\n")
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::Builder::new().prefix("sub-test-links-").tempdir().unwrap();
+        mihomo_platform::clear_home_dir_override();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+
+        let profile_name = format!(
+            "share-links-{}",
+            temp_dir.path().file_name().unwrap().to_string_lossy()
+        );
+        let manager = ConfigManager::new().unwrap();
+        manager.save(&profile_name, "port: 7890").await.unwrap();
+        let mut profile = Profile::new(
+            profile_name.clone(),
+            temp_dir.path().join("configs").join(format!("{profile_name}.yaml")),
+            false,
+        );
+        profile.subscription_url = Some(format!("{}/links", server.url()));
+        profile.auto_update_enabled = true;
+        profile.update_interval_hours = Some(6);
+        manager.update_profile_metadata(&profile_name, &profile).await.unwrap();
+
+        let ctx = MockContext {
+            notifications: Arc::new(Mutex::new(vec![])),
+        };
+        let client = HttpClient::new();
+        run_subscription_tick(&ctx, &client, &client).await.unwrap();
+
+        let notifications = ctx.notifications.lock().unwrap().clone();
+        assert_eq!(notifications.len(), 1);
+        assert!(notifications[0].1);
+        let note = notifications[0].2.as_deref().expect("skipped links should be reported");
+        assert!(note.contains("第 2 行 (ssr)"), "{note}");
+
+        manager.delete_profile(&profile_name).await.unwrap();
+        mihomo_platform::clear_home_dir_override();
+    }
 }
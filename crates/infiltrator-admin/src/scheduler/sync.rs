@@ -1,13 +1,18 @@
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use log::{info, warn};
+use serde::Serialize;
 
-use dav_client::client::WebDavClient;
-use state_store::StateStore;
-use sync_engine::{SyncPlanner, executor::SyncExecutor};
+use state_store::{StateStore, SyncConflictRow};
+use sync_engine::{
+    SyncAction, SyncPlanner,
+    executor::{ConflictResolution, SyncExecutor},
+};
+use mihomo_config::{ConfigManager, RevisionSource};
 use mihomo_platform::get_home_dir;
+use std::path::{Component, Path, PathBuf};
 
 use crate::admin_api::AdminApiContext;
-use infiltrator_core::settings::WebDavConfig;
+use infiltrator_core::{settings::WebDavConfig, sync_backend};
 
 /// Sync result summary for notification purposes
 #[derive(Debug, Default)]
@@ -15,6 +20,18 @@ pub struct SyncSummary {
     pub success_count: usize,
     pub failed_count: usize,
     pub total_actions: usize,
+    /// Conflicts waiting for manual resolution after this tick
+    pub conflict_count: usize,
+}
+
+/// The three versions behind a recorded conflict, for side-by-side review
+#[derive(Debug, Serialize)]
+pub struct ConflictDetail {
+    pub conflict: SyncConflictRow,
+    /// Content of the last successful sync, when one is known
+    pub base: Option<String>,
+    pub local: Option<String>,
+    pub remote: String,
 }
 
 pub async fn run_sync_tick<C: AdminApiContext>(
@@ -25,40 +42,23 @@ pub async fn run_sync_tick<C: AdminApiContext>(
         return Ok(SyncSummary::default());
     }
     
-    if config.url.is_empty() {
-        return Err(anyhow!("WebDAV URL is empty"));
-    }
+    sync_backend::validate(config)?;
 
     info!("Starting WebDAV sync tick...");
 
-    // 1. 初始化组件 - 带有错误上下文
-    let dav = WebDavClient::new(&config.url, &config.username, &config.password)
-        .context("Failed to create WebDAV client")?;
-    
-    // 定位数据目录
-    let home = get_home_dir().map_err(|e| anyhow!("Failed to get home directory: {}", e))?;
-    let local_root = home.join("configs");
-    
-    // 确保本地目录存在
-    if !local_root.exists() {
-        tokio::fs::create_dir_all(&local_root)
-            .await
-            .context("Failed to create local configs directory")?;
-    }
-    
-    let db_path = home.join("sync_state.db").to_string_lossy().to_string();
-    
-    let store = StateStore::new(&db_path)
-        .await
-        .context("Failed to open sync state database")?;
+    // 1. 初始化组件 - 带有错误上下文，开启加密时密钥不匹配会在这里中止
+    let dav = sync_backend::open_remote(config).await?;
+    let (home, local_root) = sync_dirs().await?;
+    let store = open_store(&home, config).await?;
     
     // 2. 生成计划
     let planner = SyncPlanner::new(
         local_root.clone(),
         "/".to_string(), // 远端根路径
-        &dav,
+        &*dav,
         &store
-    );
+    )
+    .with_conflict_policy(config.conflict_policy.clone());
     
     let actions = match planner.build_plan().await {
         Ok(actions) => actions,
@@ -71,20 +71,36 @@ pub async fn run_sync_tick<C: AdminApiContext>(
     
     if actions.is_empty() {
         info!("No sync actions needed.");
-        return Ok(SyncSummary::default());
+        return Ok(SyncSummary {
+            conflict_count: store.list_conflicts().await?.len(),
+            ..SyncSummary::default()
+        });
     }
 
     let total_actions = actions.len();
     info!("Found {} sync actions to perform.", total_actions);
 
     // 3. 执行动作 - 统计成功/失败
-    let executor = SyncExecutor::new(&dav, &store);
+    let executor = SyncExecutor::new(&*dav, &store);
+    let manager = ConfigManager::with_home(home.clone())
+        .map_err(|e| anyhow!("Failed to init config manager: {}", e))?;
     let mut success_count = 0usize;
     let mut failed_count = 0usize;
     
     for action in actions {
+        let downloaded_profile = touched_profile(&action, &local_root);
+        if let Some(profile) = downloaded_profile.as_deref()
+            && let Err(err) = manager.ensure_history_baseline(profile).await
+        {
+            warn!("Failed to snapshot profile history for {profile}: {err}");
+        }
         match executor.execute(action).await {
-            Ok(()) => success_count = success_count.saturating_add(1),
+            Ok(()) => {
+                success_count = success_count.saturating_add(1);
+                if let Some(profile) = downloaded_profile.as_deref() {
+                    record_download(&manager, profile).await;
+                }
+            }
             Err(err) => {
                 warn!("Failed to execute sync action: {err:#}");
                 failed_count = failed_count.saturating_add(1);
@@ -98,5 +114,127 @@ pub async fn run_sync_tick<C: AdminApiContext>(
         success_count,
         failed_count,
         total_actions,
+        conflict_count: store.list_conflicts().await?.len(),
+    })
+}
+
+/// Conflicts recorded by earlier sync runs
+pub async fn list_conflicts(config: &WebDavConfig) -> Result<Vec<SyncConflictRow>> {
+    let (home, _) = sync_dirs().await?;
+    open_store(&home, config).await?.list_conflicts().await
+}
+
+pub async fn conflict_detail(config: &WebDavConfig, path: &str) -> Result<ConflictDetail> {
+    let (home, local_root) = sync_dirs().await?;
+    let store = open_store(&home, config).await?;
+    let conflict = store
+        .get_conflict(path)
+        .await?
+        .ok_or_else(|| anyhow!("no conflict recorded for {path}"))?;
+    let local = tokio::fs::read(conflict_local_path(&local_root, path)?).await.ok();
+    let dav = sync_backend::open_remote(config).await?;
+    let remote = dav.get(path).await.context("Failed to download remote version")?;
+    let base = store.get_base(path).await?;
+    let text = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned();
+    Ok(ConflictDetail {
+        conflict,
+        base: base.map(text),
+        local: local.map(text),
+        remote: text(remote),
     })
 }
+
+pub async fn resolve_conflict(
+    config: &WebDavConfig,
+    path: &str,
+    resolution: ConflictResolution,
+) -> Result<()> {
+    let (home, local_root) = sync_dirs().await?;
+    let local = conflict_local_path(&local_root, path)?;
+    let store = open_store(&home, config).await?;
+    let dav = sync_backend::open_remote(config).await?;
+    let manager = ConfigManager::with_home(home.clone())
+        .map_err(|e| anyhow!("Failed to init config manager: {}", e))?;
+    let profile = profile_file(&local, &local_root);
+    if let Some(profile) = profile.as_deref()
+        && let Err(err) = manager.ensure_history_baseline(profile).await
+    {
+        warn!("Failed to snapshot profile history for {profile}: {err}");
+    }
+
+    SyncExecutor::new(&*dav, &store)
+        .resolve_conflict(&local, path, resolution)
+        .await?;
+    if let Some(profile) = profile.as_deref() {
+        record_download(&manager, profile).await;
+    }
+    Ok(())
+}
+
+/// Home directory and the synced configs directory, created when missing
+async fn sync_dirs() -> Result<(PathBuf, PathBuf)> {
+    let home = get_home_dir().map_err(|e| anyhow!("Failed to get home directory: {}", e))?;
+    let local_root = home.join("configs");
+    if !local_root.exists() {
+        tokio::fs::create_dir_all(&local_root)
+            .await
+            .context("Failed to create local configs directory")?;
+    }
+    Ok((home, local_root))
+}
+
+async fn open_store(home: &Path, config: &WebDavConfig) -> Result<StateStore> {
+    let db_path = home
+        .join(sync_backend::state_db_name(config))
+        .to_string_lossy()
+        .to_string();
+    StateStore::new(&db_path)
+        .await
+        .context("Failed to open sync state database")
+}
+
+/// Local file of a synced path, refusing paths that leave the configs dir
+fn conflict_local_path(local_root: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!("invalid sync path: {path}");
+    }
+    Ok(local_root.join(relative))
+}
+
+/// Profile whose file a download or merge may rewrite
+fn touched_profile(action: &SyncAction, local_root: &Path) -> Option<String> {
+    match action {
+        SyncAction::Download { local, .. } | SyncAction::Conflict { local, .. } => {
+            profile_file(local, local_root)
+        }
+        _ => None,
+    }
+}
+
+/// Profile name of a file directly in the configs directory
+fn profile_file(local: &Path, local_root: &Path) -> Option<String> {
+    if local.parent() != Some(local_root)
+        || local.extension().and_then(|ext| ext.to_str()) != Some("yaml")
+    {
+        return None;
+    }
+    local.file_stem().and_then(|stem| stem.to_str()).map(str::to_string)
+}
+
+async fn record_download(manager: &ConfigManager, profile: &str) {
+    let result = match manager.load(profile).await {
+        Ok(content) => manager
+            .record_revision(profile, &content, RevisionSource::SyncDownload)
+            .await
+            .map(|_| ()),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        warn!("Failed to record synced revision for {profile}: {err}");
+    }
+}
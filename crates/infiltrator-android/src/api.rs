@@ -35,6 +35,10 @@ where
         self.adapter.is_running().await
     }
 
+    pub async fn core_test_config(&self, config_path: &std::path::Path) -> Result<()> {
+        self.adapter.test_config(config_path).await
+    }
+
     pub async fn credential_get(&self, service: &str, key: &str) -> Result<Option<String>> {
         self.adapter.get(service, key).await
     }
@@ -124,6 +128,10 @@ mod tests {
             Some("http://127.0.0.1:9090".to_string())
         }
 
+        async fn core_test_config(&self, _config_path: &std::path::Path) -> Result<Option<String>> {
+            Ok(None)
+        }
+
         async fn credential_get(&self, _service: &str, _key: &str) -> Result<Option<String>> {
             Ok(None)
         }
@@ -1,9 +1,9 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::sync::Arc;
 
 use jni::errors::Error as JniError;
-use jni::objects::{GlobalRef, JObject, JString, JValue};
+use jni::objects::{GlobalRef, JObject, JString, JValue, JValueOwned};
 use jni::sys::{jint, jstring};
 use jni::{JNIEnv, JavaVM};
 use mihomo_api::{MihomoError, Result};
@@ -18,6 +18,7 @@ const SIG_STR_STR_STR_BOOL: &str = "(Ljava/lang/String;Ljava/lang/String;Ljava/l
 const SIG_STR_STR_STRING: &str =
     "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;";
 const SIG_BOOL_BOOL: &str = "(Z)Z";
+const SIG_STR_STRING: &str = "(Ljava/lang/String;)Ljava/lang/String;";
 
 struct JniBridge {
     vm: JavaVM,
@@ -42,9 +43,7 @@ impl JniBridge {
         args: &[JValue],
     ) -> Result<bool> {
         let mut env = self.env()?;
-        let value = env
-            .call_method(self.host.as_obj(), method, sig, args)
-            .map_err(|err| map_jni_error(method, err))?;
+        let value = call_host(&mut env, &self.host, method, sig, args)?;
         value
             .z()
             .map_err(|err| map_jni_error(method, err))
@@ -57,9 +56,7 @@ impl JniBridge {
         args: &[JValue],
     ) -> Result<Option<String>> {
         let mut env = self.env()?;
-        let value = env
-            .call_method(self.host.as_obj(), method, sig, args)
-            .map_err(|err| map_jni_error(method, err))?;
+        let value = call_host(&mut env, &self.host, method, sig, args)?;
         let obj = value
             .l()
             .map_err(|err| map_jni_error(method, err))?;
@@ -109,9 +106,7 @@ impl JniBridge {
             JValue::Object(arg1.as_ref()),
             JValue::Object(arg2.as_ref()),
         ];
-        let value = env
-            .call_method(self.host.as_obj(), method, SIG_STR_STR_STRING, &args)
-            .map_err(|err| map_jni_error(method, err))?;
+        let value = call_host(&mut env, &self.host, method, SIG_STR_STR_STRING, &args)?;
         let obj = value
             .l()
             .map_err(|err| map_jni_error(method, err))?;
@@ -148,9 +143,7 @@ impl JniBridge {
         if let Some(arg3_value) = arg3_value.as_ref() {
             values.push(JValue::Object(arg3_value.as_ref()));
         }
-        let ok = env
-            .call_method(self.host.as_obj(), method, sig, &values)
-            .map_err(|err| map_jni_error(method, err))?
+        let ok = call_host(&mut env, &self.host, method, sig, &values)?
             .z()
             .map_err(|err| map_jni_error(method, err))?;
         if ok {
@@ -185,6 +178,31 @@ impl AndroidBridge for JniBridge {
             })
     }
 
+    async fn core_test_config(&self, config_path: &Path) -> Result<Option<String>> {
+        let method = "coreTestConfig";
+        let mut env = self.env()?;
+        let path = Self::to_java_string(&mut env, &config_path.to_string_lossy())?;
+        let value = call_host(
+            &mut env,
+            &self.host,
+            method,
+            SIG_STR_STRING,
+            &[JValue::Object(path.as_ref())],
+        )?;
+        let obj = value
+            .l()
+            .map_err(|err| map_jni_error(method, err))?;
+        if obj.is_null() {
+            return Ok(None);
+        }
+        let jstr = JString::from(obj);
+        let text: String = env
+            .get_string(&jstr)
+            .map_err(|err| map_jni_error(method, err))?
+            .into();
+        Ok(Some(text))
+    }
+
     async fn credential_get(&self, service: &str, key: &str) -> Result<Option<String>> {
         self.call_string_with_args("credentialGet", service, key)
     }
@@ -238,9 +256,7 @@ impl AndroidBridge for JniBridge {
     async fn tun_set_enabled(&self, enabled: bool) -> Result<bool> {
         let mut env = self.env()?;
         let args = [JValue::Bool(enabled.into())];
-        let ok = env
-            .call_method(self.host.as_obj(), "tunSetEnabled", SIG_BOOL_BOOL, &args)
-            .map_err(|err| map_jni_error("tunSetEnabled", err))?
+        let ok = call_host(&mut env, &self.host, "tunSetEnabled", SIG_BOOL_BOOL, &args)?
             .z()
             .map_err(|err| map_jni_error("tunSetEnabled", err))?;
         Ok(ok)
@@ -361,6 +377,26 @@ fn read_java_string(
         })
 }
 
+/// Call a host method and clear any Java exception it left pending, so the
+/// attached thread stays usable for the next JNI call
+fn call_host<'local>(
+    env: &mut JNIEnv<'local>,
+    host: &GlobalRef,
+    method: &str,
+    sig: &str,
+    args: &[JValue],
+) -> Result<JValueOwned<'local>> {
+    let result = env.call_method(host.as_obj(), method, sig, args);
+    if env.exception_check().unwrap_or(false) {
+        let _ = env.exception_describe();
+        let _ = env.exception_clear();
+        if result.is_ok() {
+            return Err(MihomoError::Service(format!("jni {method} threw an exception")));
+        }
+    }
+    result.map_err(|err| map_jni_error(method, err))
+}
+
 fn map_jni_error(context: &str, err: JniError) -> MihomoError {
     MihomoError::Service(format!("jni {context} failed: {err}"))
 }
@@ -12,7 +12,7 @@ pub use uniffi_api::{
     DnsSettings, DnsSettingsPatch, DnsSettingsResult, FakeIpSettings,
     FakeIpSettingsPatch, FakeIpSettingsResult, IpCheckResult, IpResult,
     ProfileSummary, ProfilesResult, ProxyGroupSummary, ProxyGroupsResult,
-    RuleEntryRecord, RuleProvidersResult, RulesResult, TrafficResult,
+    RuleEntryRecord, RuleProvidersResult, RulesResult, SyncBackendSettings, TrafficResult,
     TrafficSnapshot, TunStatusResult, VpnTunSettings, VpnTunSettingsPatch,
     VpnTunSettingsResult, WebDavSettings, WebDavSettingsResult, WebDavSyncResult,
 };
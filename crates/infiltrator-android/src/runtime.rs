@@ -1,8 +1,8 @@
 use async_trait::async_trait;
-use mihomo_api::Result;
+use mihomo_api::{MihomoError, Result};
 pub use mihomo_platform::AndroidBridge;
 use mihomo_platform::{CoreController, CredentialStore, DataDirProvider};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone)]
 pub struct AndroidBridgeAdapter<B> {
@@ -70,6 +70,13 @@ where
     async fn pid(&self) -> Option<u32> {
         None
     }
+
+    async fn test_config(&self, config_path: &Path) -> Result<()> {
+        match self.bridge.core_test_config(config_path).await? {
+            Some(message) => Err(MihomoError::Config(message)),
+            None => Ok(()),
+        }
+    }
 }
 
 #[async_trait]
@@ -193,6 +200,11 @@ mod tests {
             self.controller.clone()
         }
 
+        async fn core_test_config(&self, config_path: &Path) -> Result<Option<String>> {
+            let broken = config_path.to_string_lossy().contains("broken");
+            Ok(broken.then(|| "proxy group G: proxy 'missing' not found".to_string()))
+        }
+
         async fn credential_get(&self, service: &str, key: &str) -> Result<Option<String>> {
             Ok(self
                 .store
@@ -254,6 +266,20 @@ mod tests {
         assert!(!adapter.is_running().await);
     }
 
+    #[tokio::test]
+    async fn test_adapter_test_config() {
+        let adapter = AndroidBridgeAdapter::new(TestBridge::new());
+        adapter
+            .test_config(Path::new("configs/good.yaml"))
+            .await
+            .expect("config ok");
+        let err = adapter
+            .test_config(Path::new("configs/broken.yaml"))
+            .await
+            .expect_err("config rejected");
+        assert!(matches!(err, MihomoError::Config(message) if message.contains("missing")));
+    }
+
     #[tokio::test]
     async fn test_adapter_credentials() {
         let adapter = AndroidBridgeAdapter::new(TestBridge::new());
@@ -1,5 +1,4 @@
 use crate::ffi::{FfiBoolResult, FfiErrorCode, FfiStatus};
-use dav_client::client::WebDavClient;
 use dav_client::DavClient;
 use infiltrator_core::app_routing::{
     load_app_routing, save_app_routing, set_routing_mode as core_set_routing_mode,
@@ -15,15 +14,21 @@ use infiltrator_core::fake_ip::{
     FakeIpConfig as CoreFakeIpConfig, FakeIpConfigPatch as CoreFakeIpConfigPatch,
 };
 use infiltrator_core::profiles::{
-    create_profile_from_url, list_profile_infos, select_profile as core_select_profile,
+    create_profile_from_url, list_profile_infos, load_profile_info,
+    select_profile as core_select_profile,
     update_profile as core_update_profile, ProfileInfo,
 };
+use infiltrator_core::proxy_selection;
 use infiltrator_core::rules::{
-    load_rule_providers, load_rules, save_rule_providers, save_rules,
-    RuleEntry as CoreRuleEntry, RuleProviders as CoreRuleProviders,
+    load_rule_providers, load_rules, save_rule_providers, save_rules, validate_rules,
+    Rule as CoreRule, RuleEntry as CoreRuleEntry, RuleProviders as CoreRuleProviders,
+};
+use infiltrator_core::{sync_backend, sync_crypto};
+use infiltrator_core::share::{
+    load_share_links, subscription_body, QrMatrix, ShareLink as CoreShareLink,
 };
 use infiltrator_core::settings::{
-    load_settings, save_settings, settings_path, AppSettings,
+    load_settings, save_settings, settings_path, AppSettings, SyncBackend, SyncEncryption,
     WebDavConfig as CoreWebDavConfig,
 };
 use infiltrator_core::tun::{
@@ -42,7 +47,10 @@ use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant};
 use sync_engine::{executor::SyncExecutor, SyncPlanner};
 use tokio::runtime::Runtime;
-use std::{collections::BTreeMap, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 fn get_runtime() -> &'static Runtime {
     static RUNTIME: OnceLock<Runtime> = OnceLock::new();
@@ -268,6 +276,13 @@ pub struct FakeIpSettingsResult {
 pub struct RuleEntryRecord {
     pub rule: String,
     pub enabled: bool,
+    /// Rule type such as `DOMAIN-SUFFIX` or `AND`, `None` for a rule kept
+    /// verbatim; filled on output only
+    #[uniffi(default = None)]
+    pub rule_type: Option<String>,
+    /// Policy the rule sends traffic to; filled on output only
+    #[uniffi(default = None)]
+    pub target: Option<String>,
 }
 
 #[derive(Debug, Clone, uniffi::Record)]
@@ -282,6 +297,34 @@ pub struct RuleProvidersResult {
     pub json: String,
 }
 
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ShareLinkRecord {
+    pub name: String,
+    pub proxy_type: String,
+    /// Absent when the proxy has no share-link form
+    pub link: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ShareLinksResult {
+    pub status: FfiStatus,
+    pub links: Vec<ShareLinkRecord>,
+    /// Base64 subscription body of every exportable link
+    pub subscription: String,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct QrCodeResult {
+    pub status: FfiStatus,
+    /// Modules per side, without the quiet zone
+    pub size: u32,
+    /// Row-major module grid, `true` for dark
+    pub modules: Vec<bool>,
+    /// Ready-to-share PNG including the quiet zone
+    pub png: Vec<u8>,
+}
+
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct WebDavSettings {
     pub enabled: bool,
@@ -290,6 +333,77 @@ pub struct WebDavSettings {
     pub password: String,
     pub sync_interval_mins: u32,
     pub sync_on_startup: bool,
+    /// End-to-end encrypt synced files; needs `webdav_set_passphrase` first
+    #[uniffi(default = false)]
+    pub encryption_enabled: bool,
+    /// Store encrypted files under opaque names
+    #[uniffi(default = false)]
+    pub encryption_obfuscate_names: bool,
+    /// Where files are synced to; `None` keeps the saved backend
+    #[uniffi(default = None)]
+    pub backend: Option<SyncBackendSettings>,
+}
+
+/// Sync target; for S3 `username` and `password` hold the access key pair
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum SyncBackendSettings {
+    WebDav,
+    Local {
+        path: String,
+    },
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        prefix: String,
+        path_style: bool,
+    },
+}
+
+impl From<SyncBackend> for SyncBackendSettings {
+    fn from(backend: SyncBackend) -> Self {
+        match backend {
+            SyncBackend::WebDav => SyncBackendSettings::WebDav,
+            SyncBackend::Local { path } => SyncBackendSettings::Local { path },
+            SyncBackend::S3 {
+                endpoint,
+                region,
+                bucket,
+                prefix,
+                path_style,
+            } => SyncBackendSettings::S3 {
+                endpoint,
+                region,
+                bucket,
+                prefix,
+                path_style,
+            },
+        }
+    }
+}
+
+impl From<SyncBackendSettings> for SyncBackend {
+    fn from(backend: SyncBackendSettings) -> Self {
+        match backend {
+            SyncBackendSettings::WebDav => SyncBackend::WebDav,
+            SyncBackendSettings::Local { path } => SyncBackend::Local {
+                path: path.trim().to_string(),
+            },
+            SyncBackendSettings::S3 {
+                endpoint,
+                region,
+                bucket,
+                prefix,
+                path_style,
+            } => SyncBackend::S3 {
+                endpoint: endpoint.trim().to_string(),
+                region: region.trim().to_string(),
+                bucket: bucket.trim().to_string(),
+                prefix: prefix.trim().to_string(),
+                path_style,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone, uniffi::Record)]
@@ -402,14 +516,13 @@ fn log_buffer() -> &'static Mutex<LogBuffer> {
     LOG_BUFFER.get_or_init(|| Mutex::new(LogBuffer::new(500)))
 }
 
-fn parse_log_level(level: &str) -> LogLevel {
-    match level.to_lowercase().as_str() {
-        "debug" => LogLevel::Debug,
-        "info" => LogLevel::Info,
-        "warning" | "warn" => LogLevel::Warning,
-        "error" => LogLevel::Error,
-        "silent" => LogLevel::Silent,
-        _ => LogLevel::Info,
+fn map_log_level(level: mihomo_api::LogLevel) -> LogLevel {
+    match level {
+        mihomo_api::LogLevel::Debug => LogLevel::Debug,
+        mihomo_api::LogLevel::Info => LogLevel::Info,
+        mihomo_api::LogLevel::Warning => LogLevel::Warning,
+        mihomo_api::LogLevel::Error => LogLevel::Error,
+        mihomo_api::LogLevel::Silent => LogLevel::Silent,
     }
 }
 
@@ -440,7 +553,7 @@ pub async fn logs_start_streaming() -> FfiStatus {
                 }
             };
 
-            let rx = match client.stream_logs(Some("info")).await {
+            let rx = match client.stream_logs(Some(mihomo_api::LogLevel::Info)).await {
                 Ok(rx) => rx,
                 Err(e) => {
                     let mut buffer = log_buffer().lock().unwrap_or_else(|p| p.into_inner());
@@ -452,28 +565,16 @@ pub async fn logs_start_streaming() -> FfiStatus {
             tokio::spawn(async move {
                 let mut rx = rx;
                 while let Some(line) = rx.recv().await {
-                    if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&line) {
-                        let level_str = parsed
-                            .get("level")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("info");
-                        let msg = parsed
-                            .get("payload")
-                            .or_else(|| parsed.get("message"))
-                            .and_then(|v| v.as_str())
-                            .unwrap_or(&line)
-                            .to_string();
-                        let entry = LogEntry {
-                            level: parse_log_level(level_str),
-                            message: msg,
-                            timestamp: std::time::SystemTime::now()
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .map(|d| d.as_secs())
-                                .unwrap_or(0),
-                        };
-                        let mut buffer = log_buffer().lock().unwrap_or_else(|p| p.into_inner());
-                        buffer.push(entry);
-                    }
+                    let entry = LogEntry {
+                        level: map_log_level(line.level),
+                        message: line.payload,
+                        timestamp: std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map(|d| d.as_secs())
+                            .unwrap_or(0),
+                    };
+                    let mut buffer = log_buffer().lock().unwrap_or_else(|p| p.into_inner());
+                    buffer.push(entry);
                 }
                 let mut buffer = log_buffer().lock().unwrap_or_else(|p| p.into_inner());
                 buffer.is_streaming = false;
@@ -550,10 +651,16 @@ pub async fn profile_create(name: String, url: String) -> FfiStatus {
 pub async fn profile_select(name: String) -> FfiStatus {
     get_runtime()
         .spawn(async move {
+            // Let the core check the candidate first; a rejected profile must
+            // not replace the one that is running.
+            if let Err(status) = profile_test_config_internal(&name).await {
+                return status;
+            }
             match core_select_profile(&name).await {
                 Ok(_) => {
                     // After switching profiles, we should restart the core if it's running
-                    // to apply the new config.
+                    // to apply the new config. The host restores the remembered
+                    // proxy selections once the core is up again.
                     if let Some(bridge) = get_android_bridge()
                         && let Ok(true) = bridge.core_is_running().await {
                             let _ = bridge.core_stop().await;
@@ -570,6 +677,24 @@ pub async fn profile_select(name: String) -> FfiStatus {
         })
 }
 
+async fn profile_test_config_internal(name: &str) -> Result<(), FfiStatus> {
+    let Some(bridge) = get_android_bridge() else {
+        return Ok(());
+    };
+    let profile = load_profile_info(name).await.map_err(map_anyhow_error)?;
+    match bridge
+        .core_test_config(Path::new(&profile.path))
+        .await
+        .map_err(map_mihomo_error)?
+    {
+        Some(message) => Err(FfiStatus::err(
+            FfiErrorCode::InvalidInput,
+            format!("内核配置检查失败: {message}"),
+        )),
+        None => Ok(()),
+    }
+}
+
 #[uniffi::export]
 pub async fn profile_update(name: String) -> FfiStatus {
     get_runtime()
@@ -624,6 +749,24 @@ pub async fn proxy_select(group: String, server: String) -> FfiStatus {
         })
 }
 
+/// Re-apply the selections remembered for the active profile. The host calls
+/// this after every successful core start; it waits for the controller to
+/// answer first.
+#[uniffi::export]
+pub async fn proxy_selections_restore() -> FfiStatus {
+    get_runtime()
+        .spawn(async move {
+            match proxy_selections_restore_internal().await {
+                Ok(_) => FfiStatus::ok(),
+                Err(status) => status,
+            }
+        })
+        .await
+        .unwrap_or_else(|e| {
+            FfiStatus::err(FfiErrorCode::Unknown, format!("runtime join error: {}", e))
+        })
+}
+
 // --- Config/Mode API ---
 
 #[uniffi::export]
@@ -888,8 +1031,15 @@ pub async fn rules_list() -> RulesResult {
 pub async fn rules_save(rules: Vec<RuleEntryRecord>) -> RulesResult {
     get_runtime()
         .spawn(async move {
-            let core_rules: Vec<CoreRuleEntry> =
-                rules.iter().map(record_to_core_rule).collect();
+            let core_rules = match records_to_core_rules(&rules) {
+                Ok(rules) => rules,
+                Err(status) => {
+                    return RulesResult {
+                        status,
+                        rules: Vec::new(),
+                    }
+                }
+            };
             match save_rules(core_rules).await.map_err(map_anyhow_error) {
                 Ok(rules) => RulesResult {
                     status: FfiStatus::ok(),
@@ -961,6 +1111,64 @@ pub async fn rule_providers_save(json: String) -> RuleProvidersResult {
         })
 }
 
+// --- Share API ---
+
+#[uniffi::export]
+pub async fn profile_share_links(name: String) -> ShareLinksResult {
+    get_runtime()
+        .spawn(async move {
+            match load_share_links(&name).await.map_err(map_anyhow_error) {
+                Ok(links) => ShareLinksResult {
+                    status: FfiStatus::ok(),
+                    subscription: subscription_body(&links),
+                    links: links.into_iter().map(core_share_link_to_record).collect(),
+                },
+                Err(status) => ShareLinksResult {
+                    status,
+                    links: Vec::new(),
+                    subscription: String::new(),
+                },
+            }
+        })
+        .await
+        .unwrap_or_else(|e| ShareLinksResult {
+            status: FfiStatus::err(FfiErrorCode::Unknown, format!("runtime join error: {}", e)),
+            links: Vec::new(),
+            subscription: String::new(),
+        })
+}
+
+#[uniffi::export]
+pub fn share_qr_code(text: String, scale: u32) -> QrCodeResult {
+    let rendered = QrMatrix::encode(&text).and_then(|matrix| {
+        let png = matrix.to_png(scale)?;
+        Ok((matrix, png))
+    });
+    match rendered {
+        Ok((matrix, png)) => QrCodeResult {
+            status: FfiStatus::ok(),
+            size: matrix.size as u32,
+            modules: matrix.modules,
+            png,
+        },
+        Err(err) => QrCodeResult {
+            status: FfiStatus::err(FfiErrorCode::InvalidInput, err.to_string()),
+            size: 0,
+            modules: Vec::new(),
+            png: Vec::new(),
+        },
+    }
+}
+
+fn core_share_link_to_record(link: CoreShareLink) -> ShareLinkRecord {
+    ShareLinkRecord {
+        name: link.name,
+        proxy_type: link.kind,
+        link: link.link,
+        error: link.error,
+    }
+}
+
 // --- WebDAV API ---
 
 #[uniffi::export]
@@ -1017,6 +1225,23 @@ pub async fn webdav_test(settings: WebDavSettings) -> FfiStatus {
         })
 }
 
+/// Derive and store the end-to-end sync key; fails when the remote was
+/// encrypted with a different passphrase
+#[uniffi::export]
+pub async fn webdav_set_passphrase(passphrase: String) -> FfiStatus {
+    get_runtime()
+        .spawn(async move {
+            match set_webdav_passphrase(&passphrase).await {
+                Ok(()) => FfiStatus::ok(),
+                Err(status) => status,
+            }
+        })
+        .await
+        .unwrap_or_else(|e| {
+            FfiStatus::err(FfiErrorCode::Unknown, format!("runtime join error: {}", e))
+        })
+}
+
 #[uniffi::export]
 pub async fn webdav_sync_now() -> WebDavSyncResult {
     get_runtime()
@@ -1070,7 +1295,35 @@ async fn proxy_select_internal(group: &str, server: &str) -> Result<(), FfiStatu
     client
         .switch_proxy(group, server)
         .await
-        .map_err(map_mihomo_error)
+        .map_err(map_mihomo_error)?;
+    let manager = ConfigManager::new().map_err(map_mihomo_error)?;
+    let profile = manager.get_current().await.map_err(map_mihomo_error)?;
+    if let Err(err) = proxy_selection::record_selection(&profile, group, server) {
+        log::warn!("failed to remember proxy selection: {err:#}");
+    }
+    Ok(())
+}
+
+const CONTROLLER_READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+async fn proxy_selections_restore_internal() -> Result<(), FfiStatus> {
+    let client = build_controller_client().await?;
+    let deadline = Instant::now() + CONTROLLER_READY_TIMEOUT;
+    while let Err(err) = client.get_version().await {
+        if Instant::now() >= deadline {
+            return Err(FfiStatus::err(
+                FfiErrorCode::NotReady,
+                format!("controller not ready: {err}"),
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    let manager = ConfigManager::new().map_err(map_mihomo_error)?;
+    let profile = manager.get_current().await.map_err(map_mihomo_error)?;
+    proxy_selection::restore_selections(&client, &profile)
+        .await
+        .map(|_| ())
+        .map_err(map_anyhow_error)
 }
 
 async fn config_patch_mode_internal(mode: &str) -> Result<(), FfiStatus> {
@@ -1309,17 +1562,34 @@ fn build_fake_ip_settings_patch(patch: FakeIpSettingsPatch) -> CoreFakeIpConfigP
 }
 
 fn core_rule_to_record(entry: CoreRuleEntry) -> RuleEntryRecord {
+    let rule_type = match &entry.rule {
+        CoreRule::Simple { kind, .. } => Some(kind.as_str()),
+        CoreRule::Logic { op, .. } => Some(op.as_str()),
+        CoreRule::SubRule { .. } => Some("SUB-RULE"),
+        CoreRule::Match { .. } => Some("MATCH"),
+        CoreRule::Raw { .. } => None,
+    };
     RuleEntryRecord {
-        rule: entry.rule,
+        rule_type: rule_type.map(str::to_string),
+        target: entry.rule.target().map(str::to_string),
+        rule: entry.rule.to_string(),
         enabled: entry.enabled,
     }
 }
 
-fn record_to_core_rule(entry: &RuleEntryRecord) -> CoreRuleEntry {
-    CoreRuleEntry {
-        rule: entry.rule.trim().to_string(),
-        enabled: entry.enabled,
-    }
+fn records_to_core_rules(records: &[RuleEntryRecord]) -> Result<Vec<CoreRuleEntry>, FfiStatus> {
+    let invalid = |err: String| FfiStatus::err(FfiErrorCode::InvalidInput, err);
+    let rules = records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let rule = record.rule.trim();
+            CoreRuleEntry::parse_lenient(rule, record.enabled)
+                .map_err(|err| invalid(format!("rules[{index}] `{rule}`: {err}")))
+        })
+        .collect::<Result<Vec<_>, FfiStatus>>()?;
+    validate_rules(&rules).map_err(|err| invalid(err.to_string()))?;
+    Ok(rules)
 }
 
 fn rule_providers_to_json(providers: &CoreRuleProviders) -> String {
@@ -1356,7 +1626,12 @@ async fn load_webdav_settings() -> Result<WebDavSettings, FfiStatus> {
 
 async fn save_webdav_settings(settings: WebDavSettings) -> Result<WebDavSettings, FfiStatus> {
     let (mut app_settings, path) = load_app_settings().await?;
-    app_settings.webdav = webdav_settings_to_core(settings);
+    let conflict_policy = std::mem::take(&mut app_settings.webdav.conflict_policy);
+    let saved_backend = std::mem::take(&mut app_settings.webdav.backend);
+    app_settings.webdav = CoreWebDavConfig {
+        conflict_policy,
+        ..webdav_settings_to_core(settings, saved_backend)
+    };
     save_settings(&path, &app_settings)
         .await
         .map_err(map_anyhow_error)?;
@@ -1365,16 +1640,21 @@ async fn save_webdav_settings(settings: WebDavSettings) -> Result<WebDavSettings
 
 async fn test_webdav_settings(settings: WebDavSettings) -> FfiStatus {
     crate::tls::ensure_rustls_provider();
-    let config = webdav_settings_to_core(settings);
-    if let Err(status) = validate_webdav_config(&config) {
-        return status;
-    }
-    let dav = match WebDavClient::new(&config.url, &config.username, &config.password) {
+    let saved_backend = if settings.backend.is_some() {
+        SyncBackend::default()
+    } else {
+        match load_app_settings().await {
+            Ok((app_settings, _)) => app_settings.webdav.backend,
+            Err(status) => return status,
+        }
+    };
+    let config = webdav_settings_to_core(settings, saved_backend);
+    let dav = match sync_backend::connect(&config) {
         Ok(client) => client,
         Err(err) => {
             return FfiStatus::err(
                 FfiErrorCode::InvalidInput,
-                format!("invalid WebDAV config: {err}"),
+                format!("invalid sync config: {err}"),
             )
         }
     };
@@ -1403,15 +1683,29 @@ async fn sync_webdav_now() -> Result<WebDavSyncSummary, FfiStatus> {
     run_webdav_sync(&settings.webdav).await
 }
 
+async fn set_webdav_passphrase(passphrase: &str) -> Result<(), FfiStatus> {
+    crate::tls::ensure_rustls_provider();
+    if passphrase.is_empty() {
+        return Err(FfiStatus::err(FfiErrorCode::InvalidInput, "passphrase is empty"));
+    }
+    let (settings, _) = load_app_settings().await?;
+    validate_webdav_config(&settings.webdav)?;
+    let dav = sync_backend::connect(&settings.webdav).map_err(|err| {
+        FfiStatus::err(
+            FfiErrorCode::InvalidInput,
+            format!("invalid sync config: {err}"),
+        )
+    })?;
+    sync_crypto::set_passphrase(&*dav, passphrase)
+        .await
+        .map_err(map_anyhow_error)
+}
+
 async fn run_webdav_sync(config: &CoreWebDavConfig) -> Result<WebDavSyncSummary, FfiStatus> {
     validate_webdav_config(config)?;
-    let dav = WebDavClient::new(&config.url, &config.username, &config.password)
-        .map_err(|err| {
-            FfiStatus::err(
-                FfiErrorCode::InvalidInput,
-                format!("invalid WebDAV config: {err}"),
-            )
-        })?;
+    let dav = sync_backend::open_remote(config)
+        .await
+        .map_err(map_anyhow_error)?;
 
     let home = get_home_dir().map_err(map_mihomo_error)?;
     let local_root = home.join("configs");
@@ -1420,12 +1714,16 @@ async fn run_webdav_sync(config: &CoreWebDavConfig) -> Result<WebDavSyncSummary,
             .await
             .map_err(|e| FfiStatus::err(FfiErrorCode::Io, e.to_string()))?;
     }
-    let db_path = home.join("sync_state.db").to_string_lossy().to_string();
+    let db_path = home
+        .join(sync_backend::state_db_name(config))
+        .to_string_lossy()
+        .to_string();
     let store = StateStore::new(&db_path)
         .await
         .map_err(map_anyhow_error)?;
 
-    let planner = SyncPlanner::new(local_root, "/".to_string(), &dav, &store);
+    let planner = SyncPlanner::new(local_root, "/".to_string(), &*dav, &store)
+        .with_conflict_policy(config.conflict_policy.clone());
     let actions = planner
         .build_plan()
         .await
@@ -1435,7 +1733,7 @@ async fn run_webdav_sync(config: &CoreWebDavConfig) -> Result<WebDavSyncSummary,
         return Ok(WebDavSyncSummary::default());
     }
 
-    let executor = SyncExecutor::new(&dav, &store);
+    let executor = SyncExecutor::new(&*dav, &store);
     let total_actions = actions.len();
     let mut success_count = 0usize;
     let mut failed_count = 0usize;
@@ -1469,10 +1767,14 @@ fn webdav_settings_from_core(config: &CoreWebDavConfig) -> WebDavSettings {
         password: config.password.clone(),
         sync_interval_mins: config.sync_interval_mins,
         sync_on_startup: config.sync_on_startup,
+        encryption_enabled: config.encryption.enabled,
+        encryption_obfuscate_names: config.encryption.obfuscate_names,
+        backend: Some(config.backend.clone().into()),
     }
 }
 
-fn webdav_settings_to_core(settings: WebDavSettings) -> CoreWebDavConfig {
+/// `saved_backend` is used when the caller left `backend` unset
+fn webdav_settings_to_core(settings: WebDavSettings, saved_backend: SyncBackend) -> CoreWebDavConfig {
     CoreWebDavConfig {
         enabled: settings.enabled,
         url: settings.url.trim().to_string(),
@@ -1480,17 +1782,18 @@ fn webdav_settings_to_core(settings: WebDavSettings) -> CoreWebDavConfig {
         password: settings.password,
         sync_interval_mins: settings.sync_interval_mins,
         sync_on_startup: settings.sync_on_startup,
+        encryption: SyncEncryption {
+            enabled: settings.encryption_enabled,
+            obfuscate_names: settings.encryption_obfuscate_names,
+        },
+        backend: settings.backend.map_or(saved_backend, SyncBackend::from),
+        ..CoreWebDavConfig::default()
     }
 }
 
 fn validate_webdav_config(config: &CoreWebDavConfig) -> Result<(), FfiStatus> {
-    if config.url.trim().is_empty() {
-        return Err(FfiStatus::err(
-            FfiErrorCode::InvalidInput,
-            "WebDAV URL is empty",
-        ));
-    }
-    Ok(())
+    sync_backend::validate(config)
+        .map_err(|err| FfiStatus::err(FfiErrorCode::InvalidInput, err.to_string()))
 }
 
 fn normalize_optional_string(value: Option<String>) -> Option<String> {
@@ -1757,3 +2060,77 @@ fn map_reqwest_error(context: &str, err: reqwest::Error) -> FfiStatus {
     let message = format!("{context}: {err}");
     FfiStatus::err(FfiErrorCode::Network, message)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webdav_settings_backend_round_trip() {
+        let saved = SyncBackend::Local { path: "/sdcard/sync".to_string() };
+        let mut settings = webdav_settings_from_core(&CoreWebDavConfig {
+            backend: saved.clone(),
+            ..CoreWebDavConfig::default()
+        });
+        assert_eq!(
+            settings.backend,
+            Some(SyncBackendSettings::Local { path: "/sdcard/sync".to_string() })
+        );
+
+        // Hosts that predate the field leave it unset and keep the saved backend
+        settings.backend = None;
+        let config = webdav_settings_to_core(settings.clone(), saved.clone());
+        assert_eq!(config.backend, saved);
+
+        settings.backend = Some(SyncBackendSettings::S3 {
+            endpoint: " http://127.0.0.1:9000 ".to_string(),
+            region: String::new(),
+            bucket: "sync".to_string(),
+            prefix: String::new(),
+            path_style: true,
+        });
+        let config = webdav_settings_to_core(settings, saved);
+        assert!(matches!(
+            &config.backend,
+            SyncBackend::S3 { endpoint, path_style: true, .. } if endpoint == "http://127.0.0.1:9000"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_selections_restore_after_core_start() {
+        let mut server = mockito::Server::new_async().await;
+        let version = server
+            .mock("GET", "/version")
+            .with_body(r#"{"version":"v1.19.0"}"#)
+            .create_async()
+            .await;
+        let proxies = server
+            .mock("GET", "/proxies")
+            .with_body(
+                r#"{"proxies":{"Proxy":{"type":"Selector","now":"HK","all":["HK","JP"]}}}"#,
+            )
+            .create_async()
+            .await;
+        let switch = server
+            .mock("PUT", "/proxies/Proxy")
+            .match_body(mockito::Matcher::Json(serde_json::json!({ "name": "JP" })))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        mihomo_platform::set_home_dir_override(temp_dir.path().to_path_buf());
+        let manager = ConfigManager::new().unwrap();
+        let content = format!("external-controller: {}\n", server.url());
+        manager.save("restore", &content).await.unwrap();
+        manager.set_current("restore").await.unwrap();
+        proxy_selection::record_selection("restore", "Proxy", "JP").unwrap();
+
+        let status = proxy_selections_restore().await;
+        assert_eq!(status.code, FfiErrorCode::Ok, "{:?}", status.message);
+        version.assert_async().await;
+        proxies.assert_async().await;
+        switch.assert_async().await;
+        mihomo_platform::clear_home_dir_override();
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::anyhow;
+use mihomo_config::validate::{self, Diagnostic};
 use yaml_rust2::YamlLoader;
 
 pub fn validate_yaml(content: &str) -> anyhow::Result<()> {
@@ -10,6 +11,12 @@ pub fn validate_yaml(content: &str) -> anyhow::Result<()> {
         .map_err(|_| anyhow!("配置内容不是有效的 YAML"))
 }
 
+/// Parse the profile and run the semantic checks on it
+pub fn check_profile(content: &str) -> anyhow::Result<Vec<Diagnostic>> {
+    validate_yaml(content)?;
+    validate::validate_profile(content).map_err(|_| anyhow!("配置内容不是有效的 YAML"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -35,6 +42,15 @@ mod tests {
         assert!(validate_yaml(tab_indent).is_err());
     }
 
+    #[test]
+    fn test_check_profile_reports_paths() {
+        let content = "proxy-groups:\n  - { name: G, type: select, proxies: [nope] }\n";
+        let diagnostics = check_profile(content).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "proxy-groups[0].proxies[0]");
+        assert!(check_profile("").is_err());
+    }
+
     #[test]
     fn test_validate_yaml_empty() {
         assert!(validate_yaml("").is_err());
@@ -0,0 +1,881 @@
+//! Share-link subscription converter
+//!
+//! Turns base64 or plain lists of `ss://`, `vmess://`, `vless://`,
+//! `trojan://`, `hysteria2://` and `tuic://` links into a mihomo profile.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use log::warn;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+/// Name of the generated select group
+pub const SELECT_GROUP: &str = "Proxy";
+/// Name of the generated url-test group
+pub const URL_TEST_GROUP: &str = "Auto";
+
+const URL_TEST_URL: &str = "https://www.gstatic.com/generate_204";
+const URL_TEST_INTERVAL_SECS: u64 = 300;
+const URL_TEST_TOLERANCE_MS: u64 = 50;
+
+const LENIENT_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true),
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubscriptionFormat {
+    /// Already a mihomo/Clash YAML document
+    ClashYaml,
+    /// Share links, optionally wrapped in base64
+    ShareLinks,
+}
+
+/// A link that was skipped during conversion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnparsedLink {
+    /// 1-based line in the (decoded) subscription body
+    pub line: usize,
+    pub scheme: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConvertedSubscription {
+    pub format: SubscriptionFormat,
+    pub content: String,
+    pub proxy_count: usize,
+    pub unparsed: Vec<UnparsedLink>,
+}
+
+/// Detect the format of a subscription body
+pub fn detect_format(content: &str) -> Option<SubscriptionFormat> {
+    if parse_yaml_mapping(content).is_some() {
+        Some(SubscriptionFormat::ClashYaml)
+    } else if extract_links(content).is_some() {
+        Some(SubscriptionFormat::ShareLinks)
+    } else {
+        None
+    }
+}
+
+/// Make a fetched subscription body usable as a profile: mihomo YAML is
+/// kept as-is, share-link lists are converted into a complete profile.
+pub fn normalize_subscription(content: &str) -> Result<ConvertedSubscription> {
+    if let Some(doc) = parse_yaml_mapping(content) {
+        let proxy_count = doc
+            .get("proxies")
+            .and_then(Value::as_sequence)
+            .map_or(0, Vec::len);
+        return Ok(ConvertedSubscription {
+            format: SubscriptionFormat::ClashYaml,
+            content: content.to_string(),
+            proxy_count,
+            unparsed: Vec::new(),
+        });
+    }
+    let converted = convert_share_links(content)?;
+    for link in &converted.unparsed {
+        warn!(
+            "skipped share link at line {} ({}): {}",
+            link.line, link.scheme, link.reason
+        );
+    }
+    Ok(converted)
+}
+
+/// Short note on the links a conversion skipped, for API responses and
+/// notifications. `None` when every link was converted.
+pub fn describe_unparsed(unparsed: &[UnparsedLink]) -> Option<String> {
+    const SHOWN: usize = 3;
+    if unparsed.is_empty() {
+        return None;
+    }
+    let details: Vec<String> = unparsed
+        .iter()
+        .take(SHOWN)
+        .map(|link| format!("第 {} 行 ({}): {}", link.line, link.scheme, link.reason))
+        .collect();
+    let more = if unparsed.len() > SHOWN { "；…" } else { "" };
+    Some(format!(
+        "跳过 {} 条无法解析的分享链接：{}{more}",
+        unparsed.len(),
+        details.join("；")
+    ))
+}
+
+/// Convert a share-link subscription into a profile with default groups
+pub fn convert_share_links(content: &str) -> Result<ConvertedSubscription> {
+    let links = extract_links(content)
+        .ok_or_else(|| anyhow!("订阅内容不是有效的 YAML，也不是分享链接列表"))?;
+
+    let mut proxies = Vec::new();
+    let mut unparsed = Vec::new();
+    let mut names = HashSet::new();
+    for (line, link) in links {
+        match parse_share_link(&link) {
+            Ok(mut proxy) => {
+                let name = proxy
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                proxy.insert("name".into(), unique_name(&mut names, name).into());
+                proxies.push(proxy);
+            }
+            Err(err) => unparsed.push(UnparsedLink {
+                line,
+                scheme: link_scheme(&link),
+                reason: err.to_string(),
+            }),
+        }
+    }
+    if proxies.is_empty() {
+        return Err(anyhow!(
+            "没有可解析的分享链接（{} 条解析失败）",
+            unparsed.len()
+        ));
+    }
+
+    let proxy_count = proxies.len();
+    Ok(ConvertedSubscription {
+        format: SubscriptionFormat::ShareLinks,
+        content: build_profile(proxies)?,
+        proxy_count,
+        unparsed,
+    })
+}
+
+/// Parse one share link into a mihomo proxy mapping
+pub fn parse_share_link(link: &str) -> Result<Mapping> {
+    let link = link.trim();
+    match link_scheme(link).as_str() {
+        "ss" => parse_shadowsocks(link),
+        "vmess" => parse_vmess(link),
+        "vless" => parse_vless(link),
+        "trojan" => parse_trojan(link),
+        "hysteria2" | "hy2" => parse_hysteria2(link),
+        "tuic" => parse_tuic(link),
+        "" => bail!("不是分享链接"),
+        other => bail!("不支持的协议: {other}"),
+    }
+}
+
+/// Extract share-link lines, decoding a base64 body first when needed
+fn extract_links(content: &str) -> Option<Vec<(usize, String)>> {
+    let trimmed = content.trim();
+    if let Some(links) = link_lines(trimmed) {
+        return Some(links);
+    }
+    let decoded = String::from_utf8(decode_base64(trimmed)?).ok()?;
+    link_lines(&decoded)
+}
+
+fn link_lines(text: &str) -> Option<Vec<(usize, String)>> {
+    let links: Vec<(usize, String)> = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| line.contains("://"))
+        .map(|(line, link)| (line, link.to_string()))
+        .collect();
+    (!links.is_empty()).then_some(links)
+}
+
+fn parse_yaml_mapping(content: &str) -> Option<Mapping> {
+    match serde_yaml::from_str::<Value>(content) {
+        Ok(Value::Mapping(doc)) => Some(doc),
+        _ => None,
+    }
+}
+
+fn build_profile(proxies: Vec<Mapping>) -> Result<String> {
+    let names: Vec<Value> = proxies
+        .iter()
+        .filter_map(|proxy| proxy.get("name").cloned())
+        .collect();
+
+    let mut select_members = vec![Value::from(URL_TEST_GROUP)];
+    select_members.extend(names.iter().cloned());
+    select_members.push(Value::from("DIRECT"));
+    let mut select = Mapping::new();
+    put(&mut select, "name", SELECT_GROUP);
+    put(&mut select, "type", "select");
+    put(&mut select, "proxies", select_members);
+
+    let mut url_test = Mapping::new();
+    put(&mut url_test, "name", URL_TEST_GROUP);
+    put(&mut url_test, "type", "url-test");
+    put(&mut url_test, "url", URL_TEST_URL);
+    put(&mut url_test, "interval", URL_TEST_INTERVAL_SECS);
+    put(&mut url_test, "tolerance", URL_TEST_TOLERANCE_MS);
+    put(&mut url_test, "proxies", names);
+
+    let mut doc = Mapping::new();
+    put(&mut doc, "mixed-port", 7890u16);
+    put(&mut doc, "allow-lan", false);
+    put(&mut doc, "mode", "rule");
+    put(&mut doc, "log-level", "info");
+    put(
+        &mut doc,
+        "proxies",
+        proxies.into_iter().map(Value::Mapping).collect::<Vec<_>>(),
+    );
+    put(
+        &mut doc,
+        "proxy-groups",
+        vec![Value::Mapping(select), Value::Mapping(url_test)],
+    );
+    put(&mut doc, "rules", vec![format!("MATCH,{SELECT_GROUP}")]);
+    serde_yaml::to_string(&Value::Mapping(doc)).context("serialize converted profile")
+}
+
+fn unique_name(taken: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut suffix = 2;
+    while !taken.insert(candidate.clone()) {
+        candidate = format!("{name} {suffix}");
+        suffix += 1;
+    }
+    candidate
+}
+
+fn parse_shadowsocks(link: &str) -> Result<Mapping> {
+    let body = &link["ss://".len()..];
+    let (body, fragment) = split_fragment(body);
+    let (main, query) = match body.split_once('?') {
+        Some((main, query)) => (main.trim_end_matches('/'), Some(query)),
+        None => (body.trim_end_matches('/'), None),
+    };
+
+    // SIP002 keeps the server in clear; the legacy format encodes everything.
+    let main = if main.contains('@') {
+        main.to_string()
+    } else {
+        decode_base64_text(main).context("ss 链接不是有效的 base64")?
+    };
+    let (userinfo, server) = main
+        .rsplit_once('@')
+        .ok_or_else(|| anyhow!("缺少服务器地址"))?;
+    let userinfo = decode_component(userinfo);
+    let userinfo = if userinfo.contains(':') {
+        userinfo
+    } else {
+        decode_base64_text(&userinfo).context("ss 用户信息不是有效的 base64")?
+    };
+    let (cipher, password) = userinfo
+        .split_once(':')
+        .ok_or_else(|| anyhow!("缺少加密方式或密码"))?;
+    let (server, port) = split_host_port(server)?;
+
+    let mut proxy = Mapping::new();
+    put(&mut proxy, "name", link_name(fragment, &server, port));
+    put(&mut proxy, "type", "ss");
+    put(&mut proxy, "server", server);
+    put(&mut proxy, "port", port);
+    put(&mut proxy, "cipher", cipher);
+    put(&mut proxy, "password", password);
+    put(&mut proxy, "udp", true);
+
+    let params: HashMap<String, String> = query
+        .map(|query| url::form_urlencoded::parse(query.as_bytes()).into_owned().collect())
+        .unwrap_or_default();
+    if let Some(plugin) = params.get("plugin").filter(|plugin| !plugin.is_empty()) {
+        apply_ss_plugin(&mut proxy, plugin)?;
+    }
+    Ok(proxy)
+}
+
+fn apply_ss_plugin(proxy: &mut Mapping, plugin: &str) -> Result<()> {
+    let mut parts = plugin.split(';');
+    let name = parts.next().unwrap_or_default();
+    let options: Vec<(&str, &str)> = parts
+        .map(|part| part.split_once('=').unwrap_or((part, "")))
+        .collect();
+    let option = |key: &str| {
+        options
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| *value)
+    };
+
+    let mut opts = Mapping::new();
+    match name {
+        "obfs-local" | "simple-obfs" | "obfs" => {
+            put(proxy, "plugin", "obfs");
+            put(&mut opts, "mode", option("obfs").unwrap_or("http"));
+            if let Some(host) = option("obfs-host") {
+                put(&mut opts, "host", host);
+            }
+        }
+        "v2ray-plugin" => {
+            put(proxy, "plugin", "v2ray-plugin");
+            put(&mut opts, "mode", option("mode").unwrap_or("websocket"));
+            put(&mut opts, "tls", option("tls").is_some());
+            if let Some(host) = option("host") {
+                put(&mut opts, "host", host);
+            }
+            if let Some(path) = option("path") {
+                put(&mut opts, "path", path);
+            }
+        }
+        other => bail!("不支持的 ss 插件: {other}"),
+    }
+    put(proxy, "plugin-opts", opts);
+    Ok(())
+}
+
+fn parse_vmess(link: &str) -> Result<Mapping> {
+    let body = &link["vmess://".len()..];
+    let json = decode_base64_text(body).context("vmess 链接不是有效的 base64 JSON")?;
+    let json: serde_json::Value =
+        serde_json::from_str(&json).context("vmess 链接不是有效的 base64 JSON")?;
+    let field = |key: &str| match json.get(key) {
+        Some(serde_json::Value::String(value)) if !value.trim().is_empty() => {
+            Some(value.trim().to_string())
+        }
+        Some(serde_json::Value::Number(value)) => Some(value.to_string()),
+        _ => None,
+    };
+
+    let server = field("add").ok_or_else(|| anyhow!("缺少服务器地址"))?;
+    let port = field("port")
+        .and_then(|port| port.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("缺少端口"))?;
+    let uuid = field("id").ok_or_else(|| anyhow!("缺少 UUID"))?;
+    let alter_id = field("aid")
+        .and_then(|aid| aid.parse::<u32>().ok())
+        .unwrap_or(0);
+
+    let mut proxy = Mapping::new();
+    put(
+        &mut proxy,
+        "name",
+        field("ps").unwrap_or_else(|| format!("{server}:{port}")),
+    );
+    put(&mut proxy, "type", "vmess");
+    put(&mut proxy, "server", server);
+    put(&mut proxy, "port", port);
+    put(&mut proxy, "uuid", uuid);
+    put(&mut proxy, "alterId", alter_id);
+    put(
+        &mut proxy,
+        "cipher",
+        field("scy").unwrap_or_else(|| "auto".to_string()),
+    );
+    put(&mut proxy, "udp", true);
+    if field("tls").as_deref() == Some("tls") {
+        put(&mut proxy, "tls", true);
+        if let Some(sni) = field("sni") {
+            put(&mut proxy, "servername", sni);
+        }
+        if let Some(alpn) = field("alpn") {
+            put(&mut proxy, "alpn", split_list(&alpn));
+        }
+        if let Some(fingerprint) = field("fp") {
+            put(&mut proxy, "client-fingerprint", fingerprint);
+        }
+    }
+    let network = field("net").unwrap_or_default();
+    let path = field("path");
+    apply_transport(
+        &mut proxy,
+        &network,
+        field("host").as_deref(),
+        path.as_deref(),
+        path.as_deref(),
+    )?;
+    Ok(proxy)
+}
+
+fn parse_vless(link: &str) -> Result<Mapping> {
+    let url = Url::parse(link).context("vless 链接格式无效")?;
+    let params = query_params(&url);
+    let (server, port) = url_host_port(&url, None)?;
+    let uuid = decode_component(url.username());
+    if uuid.is_empty() {
+        bail!("缺少 UUID");
+    }
+    if let Some(encryption) = params.get("encryption")
+        && !encryption.is_empty()
+        && encryption != "none"
+    {
+        bail!("不支持的 vless 加密方式: {encryption}");
+    }
+
+    let mut proxy = Mapping::new();
+    put(&mut proxy, "name", link_name(url.fragment(), &server, port));
+    put(&mut proxy, "type", "vless");
+    put(&mut proxy, "server", server);
+    put(&mut proxy, "port", port);
+    put(&mut proxy, "uuid", uuid);
+    put(&mut proxy, "udp", true);
+    if let Some(flow) = params.get("flow").filter(|flow| !flow.is_empty()) {
+        put(&mut proxy, "flow", flow.as_str());
+    }
+    match params.get("security").map(String::as_str) {
+        Some("tls") => apply_tls(&mut proxy, &params, "servername"),
+        Some("reality") => {
+            apply_tls(&mut proxy, &params, "servername");
+            let public_key = params
+                .get("pbk")
+                .filter(|key| !key.is_empty())
+                .ok_or_else(|| anyhow!("reality 缺少公钥 (pbk)"))?;
+            let mut reality = Mapping::new();
+            put(&mut reality, "public-key", public_key.as_str());
+            if let Some(short_id) = params.get("sid").filter(|sid| !sid.is_empty()) {
+                put(&mut reality, "short-id", short_id.as_str());
+            }
+            put(&mut proxy, "reality-opts", reality);
+            if !proxy.contains_key("client-fingerprint") {
+                put(&mut proxy, "client-fingerprint", "chrome");
+            }
+        }
+        Some("none") | Some("") | None => {}
+        Some(other) => bail!("不支持的 vless 安全类型: {other}"),
+    }
+    apply_query_transport(&mut proxy, &params)?;
+    Ok(proxy)
+}
+
+fn parse_trojan(link: &str) -> Result<Mapping> {
+    let url = Url::parse(link).context("trojan 链接格式无效")?;
+    let params = query_params(&url);
+    let (server, port) = url_host_port(&url, Some(443))?;
+    let password = decode_component(url.username());
+    if password.is_empty() {
+        bail!("缺少密码");
+    }
+
+    let mut proxy = Mapping::new();
+    put(&mut proxy, "name", link_name(url.fragment(), &server, port));
+    put(&mut proxy, "type", "trojan");
+    put(&mut proxy, "server", server);
+    put(&mut proxy, "port", port);
+    put(&mut proxy, "password", password);
+    put(&mut proxy, "udp", true);
+    apply_tls(&mut proxy, &params, "sni");
+    apply_query_transport(&mut proxy, &params)?;
+    Ok(proxy)
+}
+
+fn parse_hysteria2(link: &str) -> Result<Mapping> {
+    let url = Url::parse(link).context("hysteria2 链接格式无效")?;
+    let params = query_params(&url);
+    let (server, port) = url_host_port(&url, Some(443))?;
+    let mut password = decode_component(url.username());
+    if let Some(secret) = url.password() {
+        password = format!("{password}:{}", decode_component(secret));
+    }
+    if password.is_empty() {
+        bail!("缺少认证密码");
+    }
+
+    let mut proxy = Mapping::new();
+    put(&mut proxy, "name", link_name(url.fragment(), &server, port));
+    put(&mut proxy, "type", "hysteria2");
+    put(&mut proxy, "server", server);
+    put(&mut proxy, "port", port);
+    put(&mut proxy, "password", password);
+    if let Some(ports) = params.get("mport").filter(|ports| !ports.is_empty()) {
+        put(&mut proxy, "ports", ports.as_str());
+    }
+    if let Some(obfs) = params.get("obfs").filter(|obfs| !obfs.is_empty() && *obfs != "none") {
+        put(&mut proxy, "obfs", obfs.as_str());
+        if let Some(obfs_password) = params.get("obfs-password") {
+            put(&mut proxy, "obfs-password", obfs_password.as_str());
+        }
+    }
+    apply_tls_options(&mut proxy, &params, "sni");
+    Ok(proxy)
+}
+
+fn parse_tuic(link: &str) -> Result<Mapping> {
+    let url = Url::parse(link).context("tuic 链接格式无效")?;
+    let params = query_params(&url);
+    let (server, port) = url_host_port(&url, None)?;
+    let uuid = decode_component(url.username());
+    let password = url.password().map(decode_component).unwrap_or_default();
+    if uuid.is_empty() || password.is_empty() {
+        bail!("缺少 UUID 或密码");
+    }
+
+    let mut proxy = Mapping::new();
+    put(&mut proxy, "name", link_name(url.fragment(), &server, port));
+    put(&mut proxy, "type", "tuic");
+    put(&mut proxy, "server", server);
+    put(&mut proxy, "port", port);
+    put(&mut proxy, "uuid", uuid);
+    put(&mut proxy, "password", password);
+    if let Some(congestion) = params.get("congestion_control").filter(|c| !c.is_empty()) {
+        put(&mut proxy, "congestion-controller", congestion.as_str());
+    }
+    if let Some(mode) = params.get("udp_relay_mode").filter(|mode| !mode.is_empty()) {
+        put(&mut proxy, "udp-relay-mode", mode.as_str());
+    }
+    if is_truthy(params.get("disable_sni")) {
+        put(&mut proxy, "disable-sni", true);
+    }
+    apply_tls_options(&mut proxy, &params, "sni");
+    Ok(proxy)
+}
+
+/// TLS switch plus options for protocols where TLS is optional
+fn apply_tls(proxy: &mut Mapping, params: &HashMap<String, String>, sni_key: &str) {
+    put(proxy, "tls", true);
+    apply_tls_options(proxy, params, sni_key);
+}
+
+fn apply_tls_options(proxy: &mut Mapping, params: &HashMap<String, String>, sni_key: &str) {
+    if let Some(sni) = params
+        .get("sni")
+        .or_else(|| params.get("peer"))
+        .filter(|sni| !sni.is_empty())
+    {
+        put(proxy, sni_key, sni.as_str());
+    }
+    if let Some(alpn) = params.get("alpn").filter(|alpn| !alpn.is_empty()) {
+        put(proxy, "alpn", split_list(alpn));
+    }
+    if let Some(fingerprint) = params.get("fp").filter(|fp| !fp.is_empty()) {
+        put(proxy, "client-fingerprint", fingerprint.as_str());
+    }
+    if is_truthy(params.get("allowInsecure"))
+        || is_truthy(params.get("insecure"))
+        || is_truthy(params.get("allow_insecure"))
+    {
+        put(proxy, "skip-cert-verify", true);
+    }
+}
+
+fn apply_query_transport(proxy: &mut Mapping, params: &HashMap<String, String>) -> Result<()> {
+    let network = params.get("type").map(String::as_str).unwrap_or_default();
+    apply_transport(
+        proxy,
+        network,
+        params.get("host").map(String::as_str),
+        params.get("path").map(String::as_str),
+        params.get("serviceName").map(String::as_str),
+    )
+}
+
+fn apply_transport(
+    proxy: &mut Mapping,
+    network: &str,
+    host: Option<&str>,
+    path: Option<&str>,
+    service_name: Option<&str>,
+) -> Result<()> {
+    let host = host.filter(|host| !host.is_empty());
+    let path = path.filter(|path| !path.is_empty());
+    match network {
+        "" | "tcp" => {}
+        "ws" | "httpupgrade" => {
+            let mut opts = Mapping::new();
+            put(&mut opts, "path", path.unwrap_or("/"));
+            if let Some(host) = host {
+                let mut headers = Mapping::new();
+                put(&mut headers, "Host", host);
+                put(&mut opts, "headers", headers);
+            }
+            if network == "httpupgrade" {
+                put(&mut opts, "v2ray-http-upgrade", true);
+            }
+            put(proxy, "network", "ws");
+            put(proxy, "ws-opts", opts);
+        }
+        "grpc" => {
+            let mut opts = Mapping::new();
+            if let Some(service_name) = service_name.filter(|name| !name.is_empty()) {
+                put(&mut opts, "grpc-service-name", service_name);
+            }
+            put(proxy, "network", "grpc");
+            put(proxy, "grpc-opts", opts);
+        }
+        "h2" => {
+            let mut opts = Mapping::new();
+            if let Some(host) = host {
+                put(&mut opts, "host", split_list(host));
+            }
+            put(&mut opts, "path", path.unwrap_or("/"));
+            put(proxy, "network", "h2");
+            put(proxy, "h2-opts", opts);
+        }
+        "http" => {
+            let mut opts = Mapping::new();
+            put(&mut opts, "path", vec![path.unwrap_or("/")]);
+            if let Some(host) = host {
+                let mut headers = Mapping::new();
+                put(&mut headers, "Host", split_list(host));
+                put(&mut opts, "headers", headers);
+            }
+            put(proxy, "network", "http");
+            put(proxy, "http-opts", opts);
+        }
+        other => bail!("不支持的传输方式: {other}"),
+    }
+    Ok(())
+}
+
+fn put(map: &mut Mapping, key: &str, value: impl Into<Value>) {
+    map.insert(Value::from(key), value.into());
+}
+
+fn link_scheme(link: &str) -> String {
+    link.split_once("://")
+        .map(|(scheme, _)| scheme.trim().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn link_name(fragment: Option<&str>, server: &str, port: u16) -> String {
+    fragment
+        .map(decode_component)
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("{server}:{port}"))
+}
+
+fn split_fragment(body: &str) -> (&str, Option<&str>) {
+    match body.split_once('#') {
+        Some((body, fragment)) => (body, Some(fragment)),
+        None => (body, None),
+    }
+}
+
+fn split_host_port(value: &str) -> Result<(String, u16)> {
+    let (host, port) = value
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("缺少端口"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        bail!("缺少服务器地址");
+    }
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| anyhow!("无效的端口: {port}"))?;
+    Ok((host.to_string(), port))
+}
+
+fn url_host_port(url: &Url, default_port: Option<u16>) -> Result<(String, u16)> {
+    let host = url
+        .host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| anyhow!("缺少服务器地址"))?;
+    let port = url
+        .port()
+        .or(default_port)
+        .ok_or_else(|| anyhow!("缺少端口"))?;
+    Ok((decode_component(host), port))
+}
+
+fn query_params(url: &Url) -> HashMap<String, String> {
+    url.query_pairs().into_owned().collect()
+}
+
+fn decode_component(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    let normalized: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            other => other,
+        })
+        .collect();
+    if normalized.is_empty() {
+        return None;
+    }
+    LENIENT_BASE64.decode(normalized).ok()
+}
+
+fn decode_base64_text(value: &str) -> Result<String> {
+    let bytes = decode_base64(value).ok_or_else(|| anyhow!("base64 解码失败"))?;
+    String::from_utf8(bytes).map_err(|_| anyhow!("base64 内容不是 UTF-8 文本"))
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_truthy(value: Option<&String>) -> bool {
+    matches!(
+        value.map(|value| value.trim().to_ascii_lowercase()).as_deref(),
+        Some("1" | "true")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get<'a>(proxy: &'a Mapping, key: &str) -> &'a Value {
+        proxy.get(key).unwrap_or_else(|| panic!("missing {key}"))
+    }
+
+    fn encode(value: &str) -> String {
+        base64::engine::general_purpose::STANDARD.encode(value)
+    }
+
+    #[test]
+    fn test_parse_shadowsocks_sip002_and_legacy() {
+        let userinfo = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("aes-256-gcm:pa:ss");
+        let sip002 = format!(
+            "ss://{userinfo}@1.2.3.4:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dbing.com#HK%2001"
+        );
+        let proxy = parse_share_link(&sip002).unwrap();
+        assert_eq!(get(&proxy, "name"), "HK 01");
+        assert_eq!(get(&proxy, "cipher"), "aes-256-gcm");
+        assert_eq!(get(&proxy, "password"), "pa:ss");
+        assert_eq!(get(&proxy, "port"), 8388);
+        assert_eq!(get(&proxy, "plugin"), "obfs");
+        assert_eq!(get(get(&proxy, "plugin-opts").as_mapping().unwrap(), "host"), "bing.com");
+
+        let legacy = format!("ss://{}#JP", encode("chacha20-ietf-poly1305:secret@[2001:db8::1]:443"));
+        let proxy = parse_share_link(&legacy).unwrap();
+        assert_eq!(get(&proxy, "server"), "2001:db8::1");
+        assert_eq!(get(&proxy, "password"), "secret");
+
+        let plain = "ss://2022-blake3-aes-128-gcm:abc%3D%3D@example.com:443#SG";
+        let proxy = parse_share_link(plain).unwrap();
+        assert_eq!(get(&proxy, "cipher"), "2022-blake3-aes-128-gcm");
+        assert_eq!(get(&proxy, "password"), "abc==");
+    }
+
+    #[test]
+    fn test_parse_vmess() {
+        let json = r#"{"v":"2","ps":"US ws","add":"us.example.com","port":"443","id":"b831381d-6324-4d53-ad4f-8cda48b30811","aid":0,"net":"ws","host":"cdn.example.com","path":"/ray","tls":"tls","sni":"us.example.com"}"#;
+        let proxy = parse_share_link(&format!("vmess://{}", encode(json))).unwrap();
+        assert_eq!(get(&proxy, "name"), "US ws");
+        assert_eq!(get(&proxy, "port"), 443);
+        assert_eq!(get(&proxy, "cipher"), "auto");
+        assert_eq!(get(&proxy, "tls").as_bool(), Some(true));
+        assert_eq!(get(&proxy, "network"), "ws");
+        let ws = get(&proxy, "ws-opts").as_mapping().unwrap();
+        assert_eq!(get(ws, "path"), "/ray");
+        assert_eq!(get(get(ws, "headers").as_mapping().unwrap(), "Host"), "cdn.example.com");
+    }
+
+    #[test]
+    fn test_parse_vless_reality() {
+        let link = "vless://b831381d-6324-4d53-ad4f-8cda48b30811@5.6.7.8:443?encryption=none&security=reality&sni=www.apple.com&pbk=PUBKEY&sid=6ba8&flow=xtls-rprx-vision&type=grpc&serviceName=grpc#Reality";
+        let proxy = parse_share_link(link).unwrap();
+        assert_eq!(get(&proxy, "servername"), "www.apple.com");
+        assert_eq!(get(&proxy, "flow"), "xtls-rprx-vision");
+        assert_eq!(get(&proxy, "client-fingerprint"), "chrome");
+        let reality = get(&proxy, "reality-opts").as_mapping().unwrap();
+        assert_eq!(get(reality, "public-key"), "PUBKEY");
+        assert_eq!(get(reality, "short-id"), "6ba8");
+        let grpc = get(&proxy, "grpc-opts").as_mapping().unwrap();
+        assert_eq!(get(grpc, "grpc-service-name"), "grpc");
+
+        let missing_key = "vless://id@5.6.7.8:443?security=reality#x";
+        assert!(parse_share_link(missing_key).is_err());
+    }
+
+    #[test]
+    fn test_parse_trojan_hysteria2_tuic() {
+        let trojan = parse_share_link(
+            "trojan://p%40ss@t.example.com:8443?sni=t.example.com&allowInsecure=1&type=ws&path=%2Fws#Trojan",
+        )
+        .unwrap();
+        assert_eq!(get(&trojan, "password"), "p@ss");
+        assert_eq!(get(&trojan, "sni"), "t.example.com");
+        assert_eq!(get(&trojan, "skip-cert-verify").as_bool(), Some(true));
+        assert_eq!(get(&trojan, "network"), "ws");
+
+        let hy2 = parse_share_link(
+            "hy2://secret@h.example.com?sni=h.example.com&obfs=salamander&obfs-password=x&mport=20000-30000#Hy2",
+        )
+        .unwrap();
+        assert_eq!(get(&hy2, "type"), "hysteria2");
+        assert_eq!(get(&hy2, "port"), 443);
+        assert_eq!(get(&hy2, "obfs"), "salamander");
+        assert_eq!(get(&hy2, "ports"), "20000-30000");
+
+        let tuic = parse_share_link(
+            "tuic://b831381d-6324-4d53-ad4f-8cda48b30811:pw@u.example.com:443?congestion_control=bbr&alpn=h3,spdy/3.1&udp_relay_mode=native#TUIC",
+        )
+        .unwrap();
+        assert_eq!(get(&tuic, "password"), "pw");
+        assert_eq!(get(&tuic, "congestion-controller"), "bbr");
+        assert_eq!(get(&tuic, "alpn").as_sequence().unwrap().len(), 2);
+        assert!(parse_share_link("tuic://only-uuid@u.example.com:443").is_err());
+    }
+
+    #[test]
+    fn test_convert_base64_list_reports_failures() {
+        let list = [
+            "trojan://pw@a.example.com:443#Node",
+            "trojan://pw@b.example.com:443#Node",
+            "ssr://unsupported",
+            "vmess://not-json",
+            "",
+            "hysteria2://pw@c.example.com:443#Hy",
+        ]
+        .join("\n");
+        let converted = convert_share_links(&encode(&list)).unwrap();
+        assert_eq!(converted.format, SubscriptionFormat::ShareLinks);
+        assert_eq!(converted.proxy_count, 3);
+        let failed: Vec<(usize, &str)> = converted
+            .unparsed
+            .iter()
+            .map(|link| (link.line, link.scheme.as_str()))
+            .collect();
+        assert_eq!(failed, vec![(3, "ssr"), (4, "vmess")]);
+        let note = describe_unparsed(&converted.unparsed).unwrap();
+        assert!(note.starts_with("跳过 2 条无法解析的分享链接：第 3 行 (ssr)"), "{note}");
+        assert!(describe_unparsed(&[]).is_none());
+
+        let doc: Value = serde_yaml::from_str(&converted.content).unwrap();
+        let names: Vec<&str> = doc["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|proxy| proxy["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["Node", "Node 2", "Hy"]);
+        let groups = doc["proxy-groups"].as_sequence().unwrap();
+        assert_eq!(groups[0]["name"], SELECT_GROUP);
+        assert_eq!(groups[0]["proxies"][0], URL_TEST_GROUP);
+        assert_eq!(groups[1]["type"], "url-test");
+        assert_eq!(groups[1]["proxies"].as_sequence().unwrap().len(), 3);
+        assert_eq!(doc["rules"][0], "MATCH,Proxy");
+    }
+
+    #[test]
+    fn test_normalize_subscription() {
+        let yaml = "port: 7890\nproxies:\n  - {name: a, type: ss, server: 1.1.1.1, port: 1, cipher: none, password: x}\n";
+        let normalized = normalize_subscription(yaml).unwrap();
+        assert_eq!(normalized.format, SubscriptionFormat::ClashYaml);
+        assert_eq!(normalized.content, yaml);
+        assert_eq!(normalized.proxy_count, 1);
+
+        let links = "trojan://pw@a.example.com:443#A\r\ntrojan://pw@b.example.com:443#B\r\n";
+        assert_eq!(detect_format(links), Some(SubscriptionFormat::ShareLinks));
+        assert_eq!(normalize_subscription(links).unwrap().proxy_count, 2);
+
+        assert_eq!(detect_format("just some text"), None);
+        assert!(normalize_subscription("just some text").is_err());
+        assert!(normalize_subscription("ssr://a\nssr://b").is_err());
+    }
+}
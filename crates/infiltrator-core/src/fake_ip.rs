@@ -1,9 +1,11 @@
 use anyhow::{anyhow, Context, Result};
-use mihomo_config::ConfigManager;
+use mihomo_config::{ConfigManager, RevisionSource};
 use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
 use tokio::fs;
 
+use crate::overlay;
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct FakeIpConfig {
@@ -54,10 +56,7 @@ pub async fn save_fake_ip_config(patch: FakeIpConfigPatch) -> Result<FakeIpConfi
     apply_fake_ip_config(&mut doc, &config)?;
 
     let updated = serde_yaml::to_string(&doc).context("serialize profile yaml")?;
-    manager
-        .save(&profile, &updated)
-        .await
-        .context("save profile config")?;
+    overlay::save_profile_edit(&manager, &profile, &updated, RevisionSource::SettingsEdit).await?;
     Ok(config)
 }
 
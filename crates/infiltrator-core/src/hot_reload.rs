@@ -0,0 +1,238 @@
+//! Decide how a profile change reaches the running core.
+//!
+//! Restarting the core drops every connection, so a change is applied with
+//! the lightest controller call that covers it: `PATCH /configs` for the few
+//! runtime switches the core accepts in place, `PUT /configs` to reload the
+//! file for everything else, and a full rebuild only when listeners or the
+//! controller itself move.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+/// Keys the core can change through `PATCH /configs`
+const PATCH_KEYS: &[&str] = &[
+    "mode",
+    "log-level",
+    "allow-lan",
+    "bind-address",
+    "ipv6",
+    "sniffing",
+    "tcp-concurrent",
+    "interface-name",
+];
+
+/// Keys whose change needs a new process: listeners and the controller
+const REBUILD_KEYS: &[&str] = &[
+    "port",
+    "socks-port",
+    "mixed-port",
+    "redir-port",
+    "tproxy-port",
+    "external-controller",
+    "external-controller-tls",
+    "external-controller-unix",
+    "external-controller-pipe",
+    "external-ui",
+    "secret",
+];
+
+/// TUN settings that recreate the device
+const TUN_REBUILD_KEYS: &[&str] = &["stack", "device", "mtu"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApplyMethod {
+    Unchanged,
+    Patch,
+    Reload,
+    Rebuild,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApplyPlan {
+    pub method: ApplyMethod,
+    /// Body for `PATCH /configs` when `method` is `Patch`
+    pub patch: Option<serde_json::Value>,
+    /// Changed keys that decided the method
+    pub reasons: Vec<String>,
+}
+
+impl ApplyPlan {
+    /// Plan a restart without comparing documents, e.g. when nothing is known
+    /// about the running profile
+    pub fn rebuild(reason: impl Into<String>) -> Self {
+        Self::new(ApplyMethod::Rebuild, vec![reason.into()])
+    }
+
+    fn new(method: ApplyMethod, reasons: Vec<String>) -> Self {
+        Self {
+            method,
+            patch: None,
+            reasons,
+        }
+    }
+}
+
+/// Compare the profile the core is running with the saved one
+pub fn classify(applied: &str, updated: &str) -> Result<ApplyPlan> {
+    let applied = parse_mapping(applied).context("parse applied profile")?;
+    let updated = parse_mapping(updated).context("parse updated profile")?;
+
+    let mut changed: Vec<String> = vec![];
+    for (key, value) in &updated {
+        if applied.get(key) != Some(value) {
+            changed.push(key_name(key));
+        }
+    }
+    for key in applied.keys() {
+        if !updated.contains_key(key) {
+            changed.push(key_name(key));
+        }
+    }
+    if changed.is_empty() {
+        return Ok(ApplyPlan::new(ApplyMethod::Unchanged, vec![]));
+    }
+
+    let mut rebuild: Vec<String> = changed
+        .iter()
+        .filter(|key| REBUILD_KEYS.contains(&key.as_str()))
+        .cloned()
+        .collect();
+    let tun_changes = tun_changes(&applied, &updated);
+    rebuild.extend(
+        tun_changes
+            .iter()
+            .filter(|key| TUN_REBUILD_KEYS.contains(&key.as_str()))
+            .map(|key| format!("tun.{key}")),
+    );
+    if !rebuild.is_empty() {
+        return Ok(ApplyPlan::new(ApplyMethod::Rebuild, rebuild));
+    }
+
+    let tun_patchable = tun_changes.iter().all(|key| key == "enable");
+    let patchable = changed.iter().all(|key| {
+        let present = updated.contains_key(Value::String(key.clone()));
+        present && (PATCH_KEYS.contains(&key.as_str()) || (key == "tun" && tun_patchable))
+    });
+    if !patchable {
+        return Ok(ApplyPlan::new(ApplyMethod::Reload, changed));
+    }
+
+    let mut patch = serde_json::Map::new();
+    for key in &changed {
+        let value = if key == "tun" {
+            serde_json::json!({ "enable": updated["tun"]["enable"].as_bool().unwrap_or(false) })
+        } else {
+            serde_json::to_value(&updated[key.as_str()]).context("convert patch value")?
+        };
+        patch.insert(key.clone(), value);
+    }
+    Ok(ApplyPlan {
+        method: ApplyMethod::Patch,
+        patch: Some(serde_json::Value::Object(patch)),
+        reasons: changed,
+    })
+}
+
+fn parse_mapping(content: &str) -> Result<Mapping> {
+    match serde_yaml::from_str::<Value>(content)? {
+        Value::Mapping(mapping) => Ok(mapping),
+        Value::Null => Ok(Mapping::new()),
+        _ => Err(anyhow::anyhow!("配置根节点必须是映射")),
+    }
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|text| text.trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Keys under `tun` that differ between the two documents
+fn tun_changes(applied: &Mapping, updated: &Mapping) -> Vec<String> {
+    let empty = Mapping::new();
+    let before = applied
+        .get("tun")
+        .and_then(Value::as_mapping)
+        .unwrap_or(&empty);
+    let after = updated
+        .get("tun")
+        .and_then(Value::as_mapping)
+        .unwrap_or(&empty);
+    let mut keys: Vec<String> = vec![];
+    for (key, value) in after {
+        if before.get(key) != Some(value) {
+            keys.push(key_name(key));
+        }
+    }
+    for key in before.keys() {
+        if !after.contains_key(key) {
+            keys.push(key_name(key));
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+mixed-port: 7890
+mode: rule
+log-level: info
+tun:
+  enable: false
+  stack: system
+dns:
+  enable: true
+rules:
+  - MATCH,DIRECT
+"#;
+
+    #[test]
+    fn test_classify_unchanged() {
+        let plan = classify(BASE, BASE).unwrap();
+        assert_eq!(plan.method, ApplyMethod::Unchanged);
+    }
+
+    #[test]
+    fn test_classify_patch() {
+        let updated = BASE
+            .replace("mode: rule", "mode: global")
+            .replace("enable: false", "enable: true");
+        let plan = classify(BASE, &updated).unwrap();
+        assert_eq!(plan.method, ApplyMethod::Patch);
+        assert_eq!(
+            plan.patch,
+            Some(serde_json::json!({ "mode": "global", "tun": { "enable": true } }))
+        );
+    }
+
+    #[test]
+    fn test_classify_reload() {
+        let updated = BASE.replace("MATCH,DIRECT", "MATCH,REJECT");
+        let plan = classify(BASE, &updated).unwrap();
+        assert_eq!(plan.method, ApplyMethod::Reload);
+        assert_eq!(plan.reasons, vec!["rules"]);
+
+        let removed = BASE.replace("log-level: info\n", "");
+        let plan = classify(BASE, &removed).unwrap();
+        assert_eq!(plan.method, ApplyMethod::Reload);
+    }
+
+    #[test]
+    fn test_classify_rebuild() {
+        let updated = BASE
+            .replace("mixed-port: 7890", "mixed-port: 7891")
+            .replace("stack: system", "stack: gvisor")
+            .replace("MATCH,DIRECT", "MATCH,REJECT");
+        let plan = classify(BASE, &updated).unwrap();
+        assert_eq!(plan.method, ApplyMethod::Rebuild);
+        assert_eq!(plan.reasons, vec!["mixed-port", "tun.stack"]);
+    }
+}
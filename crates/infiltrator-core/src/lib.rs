@@ -1,13 +1,25 @@
 pub mod app_routing;
 pub mod config;
+pub mod converter;
 pub mod dns;
 pub mod fake_ip;
+pub mod hot_reload;
+pub mod overlay;
+pub mod rule_providers;
 pub mod rules;
+pub mod rule_test;
 pub mod tun;
 pub mod profiles;
+pub mod proxies;
+pub mod proxy_selection;
 pub mod settings;
+pub mod share;
 pub mod subscription;
+pub mod sync_backend;
+pub mod sync_crypto;
+pub mod usage;
 
 pub use app_routing::{AppRoutingConfig, AppRoutingMode};
 pub use profiles::{ProfileDetail, ProfileInfo};
+pub use proxy_selection::ProxySelections;
 pub use settings::AppSettings;
@@ -0,0 +1,857 @@
+//! Per-profile overlays
+//!
+//! A subscription refresh replaces the whole profile document, so local edits
+//! made through the DNS, TUN, rule and rule-provider editors used to vanish on
+//! the next update. Each profile now keeps two documents next to its YAML:
+//!
+//! - `base.yaml`: the content last fetched (or, for local profiles, the
+//!   content before the first edit)
+//! - `overlay.yaml`: the local customizations, stored as a structured diff
+//! - `fetched/<revision>.yaml`: the fetched content behind each subscription
+//!   revision, so restoring one swaps the base and keeps the overlay
+//!
+//! The profile YAML the core loads is always `base` with the overlay merged
+//! on top, so a refresh only has to swap the base and render again. Edits go
+//! the other way: the edited document is diffed against the base and the
+//! difference becomes the new overlay.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use mihomo_config::{ConfigManager, RevisionSource};
+use mihomo_platform::get_home_dir;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use tokio::fs;
+
+const OVERLAY_FILE: &str = "overlay.yaml";
+const BASE_FILE: &str = "base.yaml";
+const FETCHED_DIR: &str = "fetched";
+
+const PROXIES: &str = "proxies";
+const PROXY_GROUPS: &str = "proxy-groups";
+const RULE_PROVIDERS: &str = "rule-providers";
+const RULES: &str = "rules";
+const DNS: &str = "dns";
+const TUN: &str = "tun";
+
+/// Top-level keys with a structured overlay section
+const STRUCTURED_KEYS: [&str; 6] = [PROXIES, PROXY_GROUPS, RULE_PROVIDERS, RULES, DNS, TUN];
+
+/// Serialises read-modify-write cycles on overlay and base files
+static OVERLAY_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Local customizations merged onto a profile's fetched content.
+///
+/// Sections are applied in field order; `overrides` comes last and replaces
+/// whole top-level keys (a `null` value removes the key).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct ProfileOverlay {
+    /// Proxies added or replaced by name
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub proxies: Vec<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_proxies: Vec<String>,
+    /// Proxy groups added or replaced by name
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub proxy_groups: Vec<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_proxy_groups: Vec<String>,
+    /// Rule providers added or replaced by name
+    #[serde(skip_serializing_if = "Mapping::is_empty")]
+    pub rule_providers: Mapping,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_rule_providers: Vec<String>,
+    /// Rules placed before the fetched rules
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub prepend_rules: Vec<String>,
+    /// Rules placed after the fetched rules
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub append_rules: Vec<String>,
+    /// Fetched rules to comment out
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disabled_rules: Vec<String>,
+    /// Keys merged into the `dns` section
+    #[serde(skip_serializing_if = "Mapping::is_empty")]
+    pub dns: Mapping,
+    /// Keys merged into the `tun` section
+    #[serde(skip_serializing_if = "Mapping::is_empty")]
+    pub tun: Mapping,
+    /// Whole top-level keys to replace
+    #[serde(skip_serializing_if = "Mapping::is_empty")]
+    pub overrides: Mapping,
+}
+
+impl ProfileOverlay {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merge the overlay onto `doc`. The result only depends on `doc` and the
+    /// overlay, so rendering the same inputs twice gives the same document.
+    pub fn apply(&self, doc: &mut Value) -> Result<()> {
+        let map = doc
+            .as_mapping_mut()
+            .ok_or_else(|| anyhow!("profile config is not a mapping"))?;
+
+        let proxies = apply_named_list(map.get(PROXIES), &self.proxies, &self.removed_proxies);
+        set_section(map, PROXIES, proxies);
+        let groups = apply_named_list(
+            map.get(PROXY_GROUPS),
+            &self.proxy_groups,
+            &self.removed_proxy_groups,
+        );
+        set_section(map, PROXY_GROUPS, groups);
+        let providers = apply_named_mapping(
+            map.get(RULE_PROVIDERS),
+            &self.rule_providers,
+            &self.removed_rule_providers,
+        );
+        set_section(map, RULE_PROVIDERS, providers);
+        let rules = apply_rules(
+            map.get(RULES),
+            &self.prepend_rules,
+            &self.append_rules,
+            &self.disabled_rules,
+        );
+        set_section(map, RULES, rules);
+        let dns = apply_mapping_patch(map.get(DNS), &self.dns);
+        set_section(map, DNS, dns);
+        let tun = apply_mapping_patch(map.get(TUN), &self.tun);
+        set_section(map, TUN, tun);
+
+        for (key, value) in &self.overrides {
+            if value.is_null() {
+                map.remove(key);
+            } else {
+                map.insert(key.clone(), value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Derive the overlay that turns `base` into `updated`.
+    ///
+    /// Each section is first expressed structurally (so later changes to the
+    /// fetched content still come through); when that cannot reproduce the
+    /// edit exactly, the whole key falls back to an override.
+    pub fn diff(base: &Value, updated: &Value) -> Result<Self> {
+        let base = base
+            .as_mapping()
+            .ok_or_else(|| anyhow!("base profile config is not a mapping"))?;
+        let updated = updated
+            .as_mapping()
+            .ok_or_else(|| anyhow!("profile config is not a mapping"))?;
+        let mut overlay = Self::default();
+
+        for key in STRUCTURED_KEYS {
+            let before = base.get(key);
+            let after = updated.get(key);
+            if before == after {
+                continue;
+            }
+            let mut section = Self::default();
+            let captured = match key {
+                PROXIES => diff_named_list(before, after).map(|(items, removed)| {
+                    section.proxies = items;
+                    section.removed_proxies = removed;
+                }),
+                PROXY_GROUPS => diff_named_list(before, after).map(|(items, removed)| {
+                    section.proxy_groups = items;
+                    section.removed_proxy_groups = removed;
+                }),
+                RULE_PROVIDERS => diff_named_mapping(before, after).map(|(items, removed)| {
+                    section.rule_providers = items;
+                    section.removed_rule_providers = removed;
+                }),
+                RULES => diff_rules(before, after).map(|(prepend, append, disabled)| {
+                    section.prepend_rules = prepend;
+                    section.append_rules = append;
+                    section.disabled_rules = disabled;
+                }),
+                DNS => diff_mapping_patch(before, after).map(|patch| section.dns = patch),
+                _ => diff_mapping_patch(before, after).map(|patch| section.tun = patch),
+            };
+            let reproduced = captured.is_some() && {
+                let mut probe = Value::Mapping(base.clone());
+                section.apply(&mut probe)?;
+                probe.get(key) == after
+            };
+            if reproduced {
+                overlay.merge_section(section);
+            } else {
+                overlay
+                    .overrides
+                    .insert(key_value(key), after.cloned().unwrap_or(Value::Null));
+            }
+        }
+
+        for (key, value) in updated {
+            if is_structured(key) {
+                continue;
+            }
+            if base.get(key) != Some(value) {
+                overlay.overrides.insert(key.clone(), value.clone());
+            }
+        }
+        for key in base.keys() {
+            if !is_structured(key) && !updated.contains_key(key) {
+                overlay.overrides.insert(key.clone(), Value::Null);
+            }
+        }
+        Ok(overlay)
+    }
+
+    fn merge_section(&mut self, section: Self) {
+        self.proxies.extend(section.proxies);
+        self.removed_proxies.extend(section.removed_proxies);
+        self.proxy_groups.extend(section.proxy_groups);
+        self.removed_proxy_groups.extend(section.removed_proxy_groups);
+        self.rule_providers.extend(section.rule_providers);
+        self.removed_rule_providers.extend(section.removed_rule_providers);
+        self.prepend_rules.extend(section.prepend_rules);
+        self.append_rules.extend(section.append_rules);
+        self.disabled_rules.extend(section.disabled_rules);
+        self.dns.extend(section.dns);
+        self.tun.extend(section.tun);
+    }
+}
+
+/// Merge `overlay` onto the YAML `base` content
+pub fn render(base: &str, overlay: &ProfileOverlay) -> Result<String> {
+    if overlay.is_empty() {
+        return Ok(base.to_string());
+    }
+    let mut doc: Value = serde_yaml::from_str(base).context("parse profile yaml")?;
+    if doc.is_null() {
+        doc = Value::Mapping(Mapping::new());
+    }
+    overlay.apply(&mut doc)?;
+    serde_yaml::to_string(&doc).context("serialize profile yaml")
+}
+
+/// Load the overlay of a profile, empty when none was saved
+pub async fn load_overlay(profile: &str) -> Result<ProfileOverlay> {
+    let path = overlay_dir(profile)?.join(OVERLAY_FILE);
+    if !fs::try_exists(&path).await.context("check profile overlay")? {
+        return Ok(ProfileOverlay::default());
+    }
+    let content = fs::read_to_string(&path)
+        .await
+        .context("read profile overlay")?;
+    serde_yaml::from_str(&content).context("parse profile overlay")
+}
+
+/// Replace the overlay of a profile and render the profile again
+pub async fn save_overlay(
+    manager: &ConfigManager,
+    profile: &str,
+    overlay: &ProfileOverlay,
+) -> Result<()> {
+    let _guard = OVERLAY_LOCK.lock().await;
+    let base = load_or_snapshot_base(manager, profile, None).await?;
+    let rendered = render(&base, overlay)?;
+    manager
+        .save_with_source(profile, &rendered, RevisionSource::AdminSave)
+        .await
+        .context("save profile config")?;
+    write_overlay(profile, overlay).await
+}
+
+/// Store freshly fetched content as the base of a profile and render it
+/// with the profile's overlay. Subscription updates go through here.
+pub async fn save_profile_base(
+    manager: &ConfigManager,
+    profile: &str,
+    content: &str,
+    source: RevisionSource,
+) -> Result<()> {
+    let _guard = OVERLAY_LOCK.lock().await;
+    let overlay = load_overlay(profile).await?;
+    let rendered = render(content, &overlay)?;
+    let revision = manager
+        .save_with_source(profile, &rendered, source)
+        .await
+        .context("save profile config")?;
+    write_base(profile, content).await?;
+    if source == RevisionSource::SubscriptionUpdate
+        && let Some(revision) = revision
+    {
+        write_fetched_base(manager, profile, &revision.id, content).await?;
+    }
+    Ok(())
+}
+
+/// Save an edited profile document, keeping the edit as overlay so the next
+/// subscription update does not undo it.
+pub async fn save_profile_edit(
+    manager: &ConfigManager,
+    profile: &str,
+    content: &str,
+    source: RevisionSource,
+) -> Result<()> {
+    let _guard = OVERLAY_LOCK.lock().await;
+    let base = load_or_snapshot_base(manager, profile, Some(content)).await?;
+    let base_doc = parse_doc(&base).context("parse base profile yaml")?;
+    let updated_doc = parse_doc(content).context("parse profile yaml")?;
+    let overlay = ProfileOverlay::diff(&base_doc, &updated_doc)?;
+    manager
+        .save_with_source(profile, content, source)
+        .await
+        .context("save profile config")?;
+    write_overlay(profile, &overlay).await
+}
+
+/// Restore a stored revision. Other revisions are treated like an edit, so
+/// the overlay follows the restored content; a subscription revision puts
+/// back the content fetched at that time as base and keeps the overlay.
+/// Returns the profile content after the restore.
+pub async fn restore_profile_revision(
+    manager: &ConfigManager,
+    profile: &str,
+    revision: &str,
+) -> Result<String> {
+    let content = manager
+        .load_revision(profile, revision)
+        .await
+        .context("read profile revision")?;
+    let source = manager
+        .list_revisions(profile)
+        .await
+        .context("list profile revisions")?
+        .into_iter()
+        .find(|entry| entry.id == revision)
+        .map(|entry| entry.source);
+    if source != Some(RevisionSource::SubscriptionUpdate) {
+        save_profile_edit(manager, profile, &content, RevisionSource::Restore).await?;
+        return Ok(content);
+    }
+
+    let _guard = OVERLAY_LOCK.lock().await;
+    match read_fetched_base(profile, revision).await? {
+        Some(base) => {
+            let overlay = load_overlay(profile).await?;
+            let rendered = render(&base, &overlay)?;
+            manager
+                .save_with_source(profile, &rendered, RevisionSource::Restore)
+                .await
+                .context("save profile config")?;
+            write_base(profile, &base).await?;
+            Ok(rendered)
+        }
+        None => {
+            // Revisions recorded before fetched content was kept: the edits
+            // of their time cannot be told apart from the subscription, so
+            // the revision becomes the base as-is. Diffing it against the
+            // current base would pin the old subscription into the overlay.
+            manager
+                .save_with_source(profile, &content, RevisionSource::Restore)
+                .await
+                .context("save profile config")?;
+            write_base(profile, &content).await?;
+            write_overlay(profile, &ProfileOverlay::default()).await?;
+            Ok(content)
+        }
+    }
+}
+
+/// Drop the overlay and base of a deleted profile
+pub async fn remove_profile_overlay(profile: &str) -> Result<()> {
+    let _guard = OVERLAY_LOCK.lock().await;
+    let dir = overlay_dir(profile)?;
+    if fs::try_exists(&dir).await.context("check profile overlay")? {
+        fs::remove_dir_all(&dir)
+            .await
+            .context("remove profile overlay")?;
+    }
+    Ok(())
+}
+
+/// Drop every stored overlay and base
+pub async fn remove_all_overlays() -> Result<()> {
+    let _guard = OVERLAY_LOCK.lock().await;
+    let dir = overlays_root()?;
+    if fs::try_exists(&dir).await.context("check overlays directory")? {
+        fs::remove_dir_all(&dir)
+            .await
+            .context("remove overlays directory")?;
+    }
+    Ok(())
+}
+
+fn overlays_root() -> Result<PathBuf> {
+    Ok(get_home_dir()?.join("overlays"))
+}
+
+fn overlay_dir(profile: &str) -> Result<PathBuf> {
+    Ok(overlays_root()?.join(profile))
+}
+
+/// The stored base, or the profile's current content (or `fallback` for a
+/// profile that does not exist yet) recorded as base on first use.
+async fn load_or_snapshot_base(
+    manager: &ConfigManager,
+    profile: &str,
+    fallback: Option<&str>,
+) -> Result<String> {
+    let path = overlay_dir(profile)?.join(BASE_FILE);
+    if fs::try_exists(&path).await.context("check profile base")? {
+        return fs::read_to_string(&path)
+            .await
+            .context("read profile base");
+    }
+    let content = match manager.load(profile).await {
+        Ok(content) => content,
+        Err(err) => match fallback {
+            Some(content) => content.to_string(),
+            None => return Err(anyhow!(err.to_string())).context("read profile config"),
+        },
+    };
+    write_base(profile, &content).await?;
+    Ok(content)
+}
+
+async fn write_base(profile: &str, content: &str) -> Result<()> {
+    let dir = overlay_dir(profile)?;
+    fs::create_dir_all(&dir)
+        .await
+        .context("create overlay directory")?;
+    fs::write(dir.join(BASE_FILE), content)
+        .await
+        .context("write profile base")
+}
+
+/// Keep the fetched content of a subscription revision and drop the ones
+/// whose revision was pruned from history
+async fn write_fetched_base(
+    manager: &ConfigManager,
+    profile: &str,
+    revision: &str,
+    content: &str,
+) -> Result<()> {
+    let dir = overlay_dir(profile)?.join(FETCHED_DIR);
+    fs::create_dir_all(&dir)
+        .await
+        .context("create fetched base directory")?;
+    fs::write(dir.join(format!("{revision}.yaml")), content)
+        .await
+        .context("write fetched base")?;
+
+    let kept: HashSet<String> = manager
+        .list_revisions(profile)
+        .await
+        .context("list profile revisions")?
+        .into_iter()
+        .map(|entry| entry.id)
+        .collect();
+    let mut entries = fs::read_dir(&dir).await.context("read fetched bases")?;
+    while let Some(entry) = entries.next_entry().await.context("read fetched bases")? {
+        let path = entry.path();
+        let stale = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|id| !kept.contains(id));
+        if stale {
+            fs::remove_file(&path)
+                .await
+                .context("remove fetched base")?;
+        }
+    }
+    Ok(())
+}
+
+async fn read_fetched_base(profile: &str, revision: &str) -> Result<Option<String>> {
+    let path = overlay_dir(profile)?
+        .join(FETCHED_DIR)
+        .join(format!("{revision}.yaml"));
+    if !fs::try_exists(&path).await.context("check fetched base")? {
+        return Ok(None);
+    }
+    fs::read_to_string(&path)
+        .await
+        .map(Some)
+        .context("read fetched base")
+}
+
+async fn write_overlay(profile: &str, overlay: &ProfileOverlay) -> Result<()> {
+    let path = overlay_dir(profile)?.join(OVERLAY_FILE);
+    if overlay.is_empty() {
+        if fs::try_exists(&path).await.context("check profile overlay")? {
+            fs::remove_file(&path)
+                .await
+                .context("remove profile overlay")?;
+        }
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .await
+            .context("create overlay directory")?;
+    }
+    let content = serde_yaml::to_string(overlay).context("serialize profile overlay")?;
+    fs::write(&path, content)
+        .await
+        .context("write profile overlay")
+}
+
+fn parse_doc(content: &str) -> Result<Value> {
+    let doc: Value = serde_yaml::from_str(content)?;
+    Ok(if doc.is_null() {
+        Value::Mapping(Mapping::new())
+    } else {
+        doc
+    })
+}
+
+fn key_value(key: &str) -> Value {
+    Value::String(key.to_string())
+}
+
+fn is_structured(key: &Value) -> bool {
+    key.as_str()
+        .is_some_and(|key| STRUCTURED_KEYS.contains(&key))
+}
+
+fn set_section(map: &mut Mapping, key: &str, value: Option<Value>) {
+    match value {
+        Some(value) => {
+            map.insert(key_value(key), value);
+        }
+        None => {
+            map.remove(key);
+        }
+    }
+}
+
+fn item_name(item: &Value) -> Option<&str> {
+    item.get("name").and_then(Value::as_str)
+}
+
+fn apply_named_list(base: Option<&Value>, items: &[Value], removed: &[String]) -> Option<Value> {
+    if items.is_empty() && removed.is_empty() {
+        return base.cloned();
+    }
+    let mut list: Vec<Value> = base
+        .and_then(Value::as_sequence)
+        .cloned()
+        .unwrap_or_default();
+    list.retain(|item| !item_name(item).is_some_and(|name| removed.iter().any(|r| r == name)));
+    for item in items {
+        let existing = item_name(item)
+            .and_then(|name| list.iter().position(|entry| item_name(entry) == Some(name)));
+        match existing {
+            Some(index) => list[index] = item.clone(),
+            None => list.push(item.clone()),
+        }
+    }
+    Some(Value::Sequence(list))
+}
+
+fn diff_named_list(
+    base: Option<&Value>,
+    updated: Option<&Value>,
+) -> Option<(Vec<Value>, Vec<String>)> {
+    let updated = updated?.as_sequence()?;
+    let empty = Vec::new();
+    let base = match base {
+        Some(value) => value.as_sequence()?,
+        None => &empty,
+    };
+    let removed = base
+        .iter()
+        .filter_map(item_name)
+        .filter(|name| !updated.iter().any(|item| item_name(item) == Some(*name)))
+        .map(str::to_string)
+        .collect();
+    let items = updated
+        .iter()
+        .filter(|item| !base.contains(item))
+        .cloned()
+        .collect();
+    Some((items, removed))
+}
+
+fn apply_named_mapping(base: Option<&Value>, items: &Mapping, removed: &[String]) -> Option<Value> {
+    if items.is_empty() && removed.is_empty() {
+        return base.cloned();
+    }
+    let mut map = base
+        .and_then(Value::as_mapping)
+        .cloned()
+        .unwrap_or_default();
+    for name in removed {
+        map.remove(name.as_str());
+    }
+    for (key, value) in items {
+        map.insert(key.clone(), value.clone());
+    }
+    Some(Value::Mapping(map))
+}
+
+fn diff_named_mapping(
+    base: Option<&Value>,
+    updated: Option<&Value>,
+) -> Option<(Mapping, Vec<String>)> {
+    let updated = updated?.as_mapping()?;
+    let empty = Mapping::new();
+    let base = match base {
+        Some(value) => value.as_mapping()?,
+        None => &empty,
+    };
+    let mut removed = Vec::new();
+    for key in base.keys() {
+        if !updated.contains_key(key) {
+            removed.push(key.as_str()?.to_string());
+        }
+    }
+    let items = updated
+        .iter()
+        .filter(|(key, value)| base.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    Some((items, removed))
+}
+
+fn apply_mapping_patch(base: Option<&Value>, patch: &Mapping) -> Option<Value> {
+    if patch.is_empty() {
+        return base.cloned();
+    }
+    let mut map = base
+        .and_then(Value::as_mapping)
+        .cloned()
+        .unwrap_or_default();
+    for (key, value) in patch {
+        map.insert(key.clone(), value.clone());
+    }
+    Some(Value::Mapping(map))
+}
+
+fn diff_mapping_patch(base: Option<&Value>, updated: Option<&Value>) -> Option<Mapping> {
+    let updated = updated?.as_mapping()?;
+    let empty = Mapping::new();
+    let base = match base {
+        Some(value) => value.as_mapping()?,
+        None => &empty,
+    };
+    if base.keys().any(|key| !updated.contains_key(key)) {
+        return None;
+    }
+    Some(
+        updated
+            .iter()
+            .filter(|(key, value)| base.get(*key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+    )
+}
+
+/// Rule text without the `#` marker used for disabled rules
+fn rule_text(rule: &str) -> &str {
+    let trimmed = rule.trim();
+    trimmed
+        .strip_prefix('#')
+        .map(str::trim_start)
+        .unwrap_or(trimmed)
+}
+
+fn is_disabled_rule(rule: &str) -> bool {
+    rule.trim_start().starts_with('#')
+}
+
+fn rule_strings(value: Option<&Value>) -> Option<Vec<String>> {
+    match value {
+        None => Some(Vec::new()),
+        Some(value) => value
+            .as_sequence()?
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect(),
+    }
+}
+
+fn apply_rules(
+    base: Option<&Value>,
+    prepend: &[String],
+    append: &[String],
+    disabled: &[String],
+) -> Option<Value> {
+    if prepend.is_empty() && append.is_empty() && disabled.is_empty() {
+        return base.cloned();
+    }
+    let mut rules: Vec<Value> = prepend.iter().cloned().map(Value::String).collect();
+    if let Some(base_rules) = base.and_then(Value::as_sequence) {
+        for rule in base_rules {
+            match rule.as_str() {
+                Some(text)
+                    if !is_disabled_rule(text)
+                        && disabled.iter().any(|entry| entry == rule_text(text)) =>
+                {
+                    rules.push(Value::String(format!("# {}", rule_text(text))));
+                }
+                _ => rules.push(rule.clone()),
+            }
+        }
+    }
+    rules.extend(append.iter().cloned().map(Value::String));
+    Some(Value::Sequence(rules))
+}
+
+/// Split `updated` into rules before and after the fetched block, plus the
+/// fetched rules that were commented out. `None` when the fetched rules are
+/// no longer present in order.
+fn diff_rules(
+    base: Option<&Value>,
+    updated: Option<&Value>,
+) -> Option<(Vec<String>, Vec<String>, Vec<String>)> {
+    let base = rule_strings(base)?;
+    let updated = rule_strings(Some(updated?))?;
+    if updated.len() < base.len() {
+        return None;
+    }
+    let start = (0..=updated.len() - base.len()).find(|&start| {
+        updated[start..start + base.len()]
+            .iter()
+            .zip(&base)
+            .all(|(after, before)| rule_text(after) == rule_text(before))
+    })?;
+    let mut disabled = Vec::new();
+    for (after, before) in updated[start..start + base.len()].iter().zip(&base) {
+        if after == before {
+            continue;
+        }
+        if is_disabled_rule(after) && !is_disabled_rule(before) {
+            disabled.push(rule_text(before).to_string());
+        } else {
+            return None;
+        }
+    }
+    Some((
+        updated[..start].to_vec(),
+        updated[start + base.len()..].to_vec(),
+        disabled,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+mixed-port: 7890
+mode: rule
+proxies:
+  - { name: a, type: ss, server: a.example.com, port: 1 }
+  - { name: b, type: ss, server: b.example.com, port: 2 }
+proxy-groups:
+  - { name: Proxy, type: select, proxies: [a, b] }
+rule-providers:
+  ads: { type: http, behavior: domain, url: "https://example.com/ads.yaml" }
+rules:
+  - RULE-SET,ads,REJECT
+  - MATCH,Proxy
+dns:
+  enable: true
+  nameserver: [1.1.1.1]
+"#;
+
+    fn doc(content: &str) -> Value {
+        serde_yaml::from_str(content).expect("yaml")
+    }
+
+    fn round_trip(base: &Value, updated: &Value) -> ProfileOverlay {
+        let overlay = ProfileOverlay::diff(base, updated).expect("diff");
+        let mut rendered = base.clone();
+        overlay.apply(&mut rendered).expect("apply");
+        assert_eq!(&rendered, updated);
+        overlay
+    }
+
+    #[test]
+    fn test_rule_edits_become_prepend_append_and_disabled() {
+        let base = doc(BASE);
+        let mut updated = base.clone();
+        updated["rules"] = doc(
+            "['DOMAIN,local.lan,DIRECT', '# RULE-SET,ads,REJECT', 'MATCH,Proxy', 'DOMAIN,late,DIRECT']",
+        );
+        let overlay = round_trip(&base, &updated);
+        assert_eq!(overlay.prepend_rules, vec!["DOMAIN,local.lan,DIRECT"]);
+        assert_eq!(overlay.append_rules, vec!["DOMAIN,late,DIRECT"]);
+        assert_eq!(overlay.disabled_rules, vec!["RULE-SET,ads,REJECT"]);
+        assert!(overlay.overrides.is_empty());
+
+        // A refreshed subscription keeps the local rules around its own.
+        let mut refreshed = doc(BASE);
+        refreshed["rules"] = doc("['RULE-SET,ads,REJECT', 'GEOIP,CN,DIRECT', 'MATCH,Proxy']");
+        overlay.apply(&mut refreshed).expect("apply");
+        let rules: Vec<&str> = refreshed["rules"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                "DOMAIN,local.lan,DIRECT",
+                "# RULE-SET,ads,REJECT",
+                "GEOIP,CN,DIRECT",
+                "MATCH,Proxy",
+                "DOMAIN,late,DIRECT",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reordered_rules_fall_back_to_override() {
+        let base = doc(BASE);
+        let mut updated = base.clone();
+        updated["rules"] = doc("['MATCH,Proxy', 'RULE-SET,ads,REJECT']");
+        let overlay = round_trip(&base, &updated);
+        assert!(overlay.prepend_rules.is_empty());
+        assert!(overlay.overrides.contains_key("rules"));
+    }
+
+    #[test]
+    fn test_named_sections_and_overrides() {
+        let base = doc(BASE);
+        let mut updated = base.clone();
+        updated["proxies"] = doc(
+            "[{ name: a, type: ss, server: a.example.com, port: 10 }, { name: mine, type: socks5, server: 127.0.0.1, port: 1080 }]",
+        );
+        updated["rule-providers"]["local"] = doc("{ type: file, behavior: domain, path: ./local.yaml }");
+        updated["dns"]["enhanced-mode"] = Value::String("fake-ip".to_string());
+        updated["tun"] = doc("{ enable: true, stack: gvisor }");
+        updated["mode"] = Value::String("global".to_string());
+        updated
+            .as_mapping_mut()
+            .unwrap()
+            .remove("mixed-port");
+
+        let overlay = round_trip(&base, &updated);
+        assert_eq!(overlay.proxies.len(), 2);
+        assert_eq!(overlay.removed_proxies, vec!["b"]);
+        assert_eq!(overlay.rule_providers.len(), 1);
+        assert_eq!(overlay.dns.len(), 1);
+        assert_eq!(overlay.tun.len(), 2);
+        assert_eq!(overlay.overrides.get("mode"), Some(&Value::String("global".to_string())));
+        assert_eq!(overlay.overrides.get("mixed-port"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let overlay = ProfileOverlay {
+            prepend_rules: vec!["DOMAIN,a.lan,DIRECT".to_string()],
+            dns: doc("{ ipv6: false }").as_mapping().unwrap().clone(),
+            ..ProfileOverlay::default()
+        };
+        let first = render(BASE, &overlay).expect("render");
+        let second = render(BASE, &overlay).expect("render");
+        assert_eq!(first, second);
+        assert_eq!(render(BASE, &ProfileOverlay::default()).unwrap(), BASE);
+
+        let serialized = serde_yaml::to_string(&overlay).unwrap();
+        let parsed: ProfileOverlay = serde_yaml::from_str(&serialized).unwrap();
+        assert_eq!(parsed, overlay);
+    }
+}
@@ -1,10 +1,13 @@
 use anyhow::anyhow;
 use chrono::{DateTime, Utc};
-use mihomo_config::{port::find_available_port, ConfigManager, Profile as MihomoProfile};
+use mihomo_config::{
+    port::find_available_port, ConfigManager, Profile as MihomoProfile, RevisionSource,
+    SubscriptionUserInfo,
+};
 use mihomo_platform::get_home_dir;
 use serde::Serialize;
 use tokio::fs;
-use crate::{config as core_config, subscription as core_subscription};
+use crate::{converter, overlay, subscription as core_subscription};
 use infiltrator_http::{build_http_client, build_raw_http_client};
 
 #[derive(Debug, Clone, Serialize)]
@@ -19,6 +22,8 @@ pub struct ProfileInfo {
     pub update_interval_hours: Option<u32>,
     pub last_updated: Option<DateTime<Utc>>,
     pub next_update: Option<DateTime<Utc>>,
+    pub subscription_info: Option<SubscriptionUserInfo>,
+    pub suggested_update_interval_hours: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -32,6 +37,7 @@ pub struct ProfileDetail {
     pub update_interval_hours: Option<u32>,
     pub last_updated: Option<DateTime<Utc>>,
     pub next_update: Option<DateTime<Utc>>,
+    pub subscription_info: Option<SubscriptionUserInfo>,
 }
 
 pub fn profile_to_info(profile: MihomoProfile) -> ProfileInfo {
@@ -46,6 +52,8 @@ pub fn profile_to_info(profile: MihomoProfile) -> ProfileInfo {
         update_interval_hours: profile.update_interval_hours,
         last_updated: profile.last_updated,
         next_update: profile.next_update,
+        subscription_info: profile.subscription_info,
+        suggested_update_interval_hours: profile.suggested_update_interval_hours,
     }
 }
 
@@ -74,27 +82,25 @@ pub async fn create_profile_from_url(name: &str, url: &str) -> anyhow::Result<Pr
 
     let client = build_http_client();
     let raw_client = build_raw_http_client(&client);
-    let content =
+    let fetched =
         core_subscription::fetch_subscription_text(&client, &raw_client, source_url).await?;
-    let content = core_subscription::strip_utf8_bom(&content);
-    if core_config::validate_yaml(&content).is_err() {
-        return Err(anyhow!("订阅内容不是有效的 YAML"));
-    }
+    let content =
+        converter::normalize_subscription(&core_subscription::strip_utf8_bom(&fetched.content))?
+            .content;
 
     let manager = ConfigManager::new()?;
-    manager.save(&profile_name, &content).await?;
+    overlay::save_profile_base(
+        &manager,
+        &profile_name,
+        &content,
+        RevisionSource::SubscriptionUpdate,
+    )
+    .await?;
 
-    let now = Utc::now();
     let mut metadata = manager.get_profile_metadata(&profile_name).await?;
     metadata.subscription_url = Some(source_url.to_string());
-    metadata.last_updated = Some(now);
-    metadata.next_update = if metadata.auto_update_enabled {
-        metadata
-            .update_interval_hours
-            .map(|hours| now + chrono::Duration::hours(hours as i64))
-    } else {
-        None
-    };
+    metadata.apply_subscription_metadata(&fetched.metadata);
+    metadata.mark_updated(Utc::now());
     manager
         .update_profile_metadata(&profile_name, &metadata)
         .await?;
@@ -120,22 +126,20 @@ pub async fn update_profile(name: &str) -> anyhow::Result<ProfileInfo> {
 
     let client = build_http_client();
     let raw_client = build_raw_http_client(&client);
-    let content = core_subscription::fetch_subscription_text(&client, &raw_client, url).await?;
-    let content = core_subscription::strip_utf8_bom(&content);
-    if core_config::validate_yaml(&content).is_err() {
-        return Err(anyhow!("订阅内容不是有效的 YAML"));
-    }
-    manager.save(&profile_name, &content).await?;
-
-    let now = Utc::now();
-    metadata.last_updated = Some(now);
-    metadata.next_update = if metadata.auto_update_enabled {
-        metadata
-            .update_interval_hours
-            .map(|hours| now + chrono::Duration::hours(hours as i64))
-    } else {
-        None
-    };
+    let fetched = core_subscription::fetch_subscription_text(&client, &raw_client, url).await?;
+    let content =
+        converter::normalize_subscription(&core_subscription::strip_utf8_bom(&fetched.content))?
+            .content;
+    overlay::save_profile_base(
+        &manager,
+        &profile_name,
+        &content,
+        RevisionSource::SubscriptionUpdate,
+    )
+    .await?;
+
+    metadata.apply_subscription_metadata(&fetched.metadata);
+    metadata.mark_updated(Utc::now());
     manager
         .update_profile_metadata(&profile_name, &metadata)
         .await?;
@@ -157,6 +161,7 @@ pub async fn load_profile_detail(name: &str) -> anyhow::Result<ProfileDetail> {
         update_interval_hours: profile.update_interval_hours,
         last_updated: profile.last_updated,
         next_update: profile.next_update,
+        subscription_info: profile.subscription_info,
     })
 }
 
@@ -182,6 +187,7 @@ pub async fn reset_profiles_to_default() -> anyhow::Result<ProfileInfo> {
     if config_dir.exists() {
         fs::remove_dir_all(&config_dir).await?;
     }
+    overlay::remove_all_overlays().await?;
 
     let manager = ConfigManager::with_home(home)?;
     let default_config = build_default_config()?;
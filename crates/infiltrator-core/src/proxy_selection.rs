@@ -0,0 +1,245 @@
+//! Remembered proxy group selections
+//!
+//! Keeps the node the user last picked for each selector group, keyed by
+//! profile, so other components can honour or restore manual choices.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use mihomo_api::{MihomoClient, ProxyInfo};
+use mihomo_platform::get_home_dir;
+
+/// Selections per profile: profile -> group -> node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxySelections {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl ProxySelections {
+    /// Node last selected for a group in a profile
+    pub fn get(&self, profile: &str, group: &str) -> Option<&str> {
+        self.profiles
+            .get(profile)
+            .and_then(|groups| groups.get(group))
+            .map(String::as_str)
+    }
+
+    /// All remembered selections of a profile
+    pub fn for_profile(&self, profile: &str) -> BTreeMap<String, String> {
+        self.profiles.get(profile).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, profile: &str, group: &str, node: &str) {
+        self.profiles
+            .entry(profile.to_string())
+            .or_default()
+            .insert(group.to_string(), node.to_string());
+    }
+
+    pub fn remove(&mut self, profile: &str, group: &str) -> Option<String> {
+        let groups = self.profiles.get_mut(profile)?;
+        let removed = groups.remove(group);
+        if groups.is_empty() {
+            self.profiles.remove(profile);
+        }
+        removed
+    }
+}
+
+/// Serialises read-modify-write cycles on the selections file
+static SELECTIONS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Get the path to the proxy selections file
+fn selections_path() -> anyhow::Result<PathBuf> {
+    let home = get_home_dir()?;
+    Ok(home.join("proxy_selections.toml"))
+}
+
+/// Load remembered proxy selections
+pub fn load_proxy_selections() -> anyhow::Result<ProxySelections> {
+    let path = selections_path()?;
+    if !path.exists() {
+        return Ok(ProxySelections::default());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    let selections: ProxySelections = toml::from_str(&content)?;
+    Ok(selections)
+}
+
+/// Save remembered proxy selections
+pub fn save_proxy_selections(selections: &ProxySelections) -> anyhow::Result<()> {
+    let path = selections_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let content = toml::to_string_pretty(selections)?;
+    std::fs::write(&path, content)?;
+    Ok(())
+}
+
+/// Remember `node` as the selection of `group` in `profile`
+pub fn record_selection(profile: &str, group: &str, node: &str) -> anyhow::Result<()> {
+    let _guard = SELECTIONS_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut selections = load_proxy_selections()?;
+    if selections.get(profile, group) == Some(node) {
+        return Ok(());
+    }
+    selections.set(profile, group, node);
+    save_proxy_selections(&selections)
+}
+
+/// Forget the remembered selection of `group` in `profile`
+pub fn forget_selection(profile: &str, group: &str) -> anyhow::Result<()> {
+    let _guard = SELECTIONS_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut selections = load_proxy_selections()?;
+    if selections.remove(profile, group).is_some() {
+        save_proxy_selections(&selections)?;
+    }
+    Ok(())
+}
+
+/// A `(group, node)` pair
+pub type Selection = (String, String);
+
+/// Outcome of re-applying remembered selections to a freshly started core
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Selections switched back
+    pub restored: Vec<Selection>,
+    /// Selections whose group or node no longer exists
+    pub missing: Vec<Selection>,
+    /// Selections the controller refused to apply
+    pub failed: Vec<Selection>,
+}
+
+/// Split remembered selections into those that still apply to the running
+/// config and those whose group or node is gone. Selections that already
+/// match the group's current node are dropped.
+fn plan_restore(
+    saved: &BTreeMap<String, String>,
+    proxies: &HashMap<String, ProxyInfo>,
+) -> (Vec<Selection>, Vec<Selection>) {
+    let mut apply = Vec::new();
+    let mut missing = Vec::new();
+    for (group, node) in saved {
+        let Some(info) = proxies.get(group) else {
+            missing.push((group.clone(), node.clone()));
+            continue;
+        };
+        let exists = info.proxy_type == "Selector"
+            && info
+                .all
+                .as_ref()
+                .is_some_and(|members| members.iter().any(|m| m == node));
+        if !exists {
+            missing.push((group.clone(), node.clone()));
+        } else if info.now.as_deref() != Some(node.as_str()) {
+            apply.push((group.clone(), node.clone()));
+        }
+    }
+    (apply, missing)
+}
+
+/// Re-apply the selections remembered for `profile` through the controller
+pub async fn restore_selections(
+    client: &MihomoClient,
+    profile: &str,
+) -> anyhow::Result<RestoreReport> {
+    let saved = load_proxy_selections()?.for_profile(profile);
+    if saved.is_empty() {
+        return Ok(RestoreReport::default());
+    }
+    let proxies = client.get_proxies().await?;
+    let (apply, missing) = plan_restore(&saved, &proxies);
+
+    let mut report = RestoreReport {
+        missing,
+        ..RestoreReport::default()
+    };
+    for (group, node) in &report.missing {
+        log::warn!("remembered selection {group} -> {node} no longer exists in profile {profile}");
+    }
+    for (group, node) in apply {
+        match client.switch_proxy(&group, &node).await {
+            Ok(()) => report.restored.push((group, node)),
+            Err(err) => {
+                log::warn!("failed to restore selection {group} -> {node}: {err}");
+                report.failed.push((group, node));
+            }
+        }
+    }
+    if !report.restored.is_empty() {
+        log::info!(
+            "restored {} proxy selection(s) for profile {profile}",
+            report.restored.len()
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selections_set_get_remove() {
+        let mut selections = ProxySelections::default();
+        selections.set("default", "Proxy", "HK-01");
+        selections.set("default", "Proxy", "JP-02");
+        selections.set("work", "Proxy", "US-01");
+
+        assert_eq!(selections.get("default", "Proxy"), Some("JP-02"));
+        assert_eq!(selections.get("work", "Proxy"), Some("US-01"));
+        assert_eq!(selections.get("default", "Other"), None);
+        assert_eq!(selections.for_profile("default").len(), 1);
+
+        assert_eq!(selections.remove("work", "Proxy").as_deref(), Some("US-01"));
+        assert!(!selections.profiles.contains_key("work"));
+    }
+
+    fn group(proxy_type: &str, now: &str, all: &[&str]) -> ProxyInfo {
+        ProxyInfo {
+            proxy_type: proxy_type.to_string(),
+            now: Some(now.to_string()),
+            all: Some(all.iter().map(|s| s.to_string()).collect()),
+            history: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_plan_restore() {
+        let proxies = HashMap::from([
+            ("Proxy".to_string(), group("Selector", "HK", &["HK", "JP", "US"])),
+            ("Media".to_string(), group("Selector", "JP", &["HK", "JP"])),
+            ("Auto".to_string(), group("URLTest", "HK", &["HK", "JP"])),
+        ]);
+        let saved = BTreeMap::from([
+            ("Proxy".to_string(), "US".to_string()),
+            ("Media".to_string(), "JP".to_string()),
+            ("Auto".to_string(), "JP".to_string()),
+            ("Gone".to_string(), "HK".to_string()),
+            ("Proxy2".to_string(), "SG".to_string()),
+        ]);
+
+        let (apply, missing) = plan_restore(&saved, &proxies);
+        assert_eq!(apply, vec![("Proxy".to_string(), "US".to_string())]);
+        let missing_groups: Vec<&str> = missing.iter().map(|(g, _)| g.as_str()).collect();
+        assert_eq!(missing_groups, vec!["Auto", "Gone", "Proxy2"]);
+    }
+
+    #[test]
+    fn test_selections_toml_roundtrip() {
+        let mut selections = ProxySelections::default();
+        selections.set("my profile", "🚀 节点选择", "香港 01");
+        let content = toml::to_string_pretty(&selections).unwrap();
+        let parsed: ProxySelections = toml::from_str(&content).unwrap();
+        assert_eq!(parsed, selections);
+    }
+}
@@ -0,0 +1,896 @@
+//! Local cache of rule providers.
+//!
+//! HTTP providers are downloaded on demand into `rule-providers/` next to the
+//! profiles. That directory is the core's home directory, so a provider can
+//! be switched to its cached copy (`type: file`) without tripping the core's
+//! safe-path check, and keeps working when the source is unreachable.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use infiltrator_http::HttpClient;
+use ipnet::{IpNet, Ipv4Subnets, Ipv6Subnets};
+use mihomo_config::{ConfigManager, RevisionSource};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use tokio::fs;
+
+use crate::overlay;
+
+const CACHE_DIR: &str = "rule-providers";
+const INDEX_FILE: &str = "index.json";
+const MRS_MAGIC: [u8; 4] = *b"MRS\x01";
+
+static CACHE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderBehavior {
+    Domain,
+    Ipcidr,
+    Classical,
+}
+
+impl ProviderBehavior {
+    fn parse(value: Option<&str>) -> Result<Self> {
+        match value.map(str::to_ascii_lowercase).as_deref() {
+            Some("domain") => Ok(Self::Domain),
+            Some("ipcidr") => Ok(Self::Ipcidr),
+            Some("classical") | None => Ok(Self::Classical),
+            Some(other) => bail!("unknown rule provider behavior `{other}`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderFormat {
+    Yaml,
+    Text,
+    Mrs,
+}
+
+impl ProviderFormat {
+    fn parse(value: Option<&str>) -> Result<Self> {
+        match value.map(str::to_ascii_lowercase).as_deref() {
+            Some("yaml") | None => Ok(Self::Yaml),
+            Some("text") => Ok(Self::Text),
+            Some("mrs") => Ok(Self::Mrs),
+            Some(other) => bail!("unknown rule provider format `{other}`"),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Yaml => "yaml",
+            Self::Text => "txt",
+            Self::Mrs => "mrs",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleProviderStatus {
+    pub name: String,
+    /// `http`, `file` or `inline`, as written in the profile
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub behavior: ProviderBehavior,
+    pub format: ProviderFormat,
+    pub url: Option<String>,
+    pub path: Option<String>,
+    /// The profile reads this provider from the local cache
+    pub offline: bool,
+    /// Entry count of the local copy; `None` when there is none
+    pub entries: Option<usize>,
+    pub size: Option<u64>,
+    /// RFC 3339 time of the last download or file change
+    pub updated_at: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleProviderSearch {
+    pub name: String,
+    /// Number of entries matching the query
+    pub total: usize,
+    /// The first matches, up to the requested limit
+    pub matches: Vec<String>,
+}
+
+/// Cached downloads keyed by source URL
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    #[serde(default)]
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// File name inside the cache directory
+    file: String,
+    entries: usize,
+    size: u64,
+    updated_at: String,
+    /// `path` of the HTTP provider before it was switched to the cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_path: Option<String>,
+}
+
+/// The fields of a `rule-providers` entry this module cares about
+struct ProviderDef {
+    kind: String,
+    behavior: ProviderBehavior,
+    format: ProviderFormat,
+    url: Option<String>,
+    path: Option<String>,
+}
+
+impl ProviderDef {
+    fn from_value(name: &str, value: &Value) -> Result<Self> {
+        let mapping = value
+            .as_mapping()
+            .ok_or_else(|| anyhow!("rule provider {name} is not a mapping"))?;
+        let field = |key: &str| mapping.get(key).and_then(Value::as_str);
+        Ok(Self {
+            kind: field("type").unwrap_or("http").to_ascii_lowercase(),
+            behavior: ProviderBehavior::parse(field("behavior"))?,
+            format: ProviderFormat::parse(field("format"))?,
+            url: field("url").map(str::to_string),
+            path: field("path").map(str::to_string),
+        })
+    }
+
+    fn reads_cache(&self) -> bool {
+        self.kind == "file" && self.path.as_deref().is_some_and(is_cache_path)
+    }
+}
+
+struct CurrentProfile {
+    manager: ConfigManager,
+    name: String,
+    base_dir: PathBuf,
+    doc: Value,
+}
+
+impl CurrentProfile {
+    async fn load() -> Result<Self> {
+        let manager = ConfigManager::new().context("init config manager")?;
+        let name = manager.get_current().await.context("load current profile")?;
+        let path = manager
+            .get_current_path()
+            .await
+            .context("load current profile")?;
+        let content = manager.load(&name).await.context("read profile config")?;
+        let doc = serde_yaml::from_str(&content).context("parse profile yaml")?;
+        Ok(Self {
+            manager,
+            name,
+            base_dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            doc,
+        })
+    }
+
+    fn providers(&self) -> Vec<(String, &Value)> {
+        self.doc
+            .get("rule-providers")
+            .and_then(Value::as_mapping)
+            .map(|providers| {
+                providers
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.as_str()?.to_string(), value)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn provider(&self, name: &str) -> Result<&Value> {
+        self.doc
+            .get("rule-providers")
+            .and_then(|providers| providers.get(name))
+            .ok_or_else(|| anyhow!("rule provider {name} not found"))
+    }
+
+    fn provider_mut(&mut self, name: &str) -> Result<&mut Mapping> {
+        self.doc
+            .get_mut("rule-providers")
+            .and_then(|providers| providers.get_mut(name))
+            .and_then(Value::as_mapping_mut)
+            .ok_or_else(|| anyhow!("rule provider {name} not found"))
+    }
+
+    async fn save(&self) -> Result<()> {
+        let updated = serde_yaml::to_string(&self.doc).context("serialize profile yaml")?;
+        overlay::save_profile_edit(&self.manager, &self.name, &updated, RevisionSource::RulesEdit)
+            .await
+    }
+}
+
+/// Status of every provider in the current profile
+pub async fn list_rule_provider_cache() -> Result<Vec<RuleProviderStatus>> {
+    let current = CurrentProfile::load().await?;
+    let index = read_index(&current.base_dir).await?;
+    let mut statuses = vec![];
+    for (name, value) in current.providers() {
+        statuses.push(provider_status(&current.base_dir, &index, &name, value).await);
+    }
+    Ok(statuses)
+}
+
+/// Download one provider into the cache
+pub async fn refresh_rule_provider(client: &HttpClient, name: &str) -> Result<RuleProviderStatus> {
+    let current = CurrentProfile::load().await?;
+    let value = current.provider(name)?;
+    download(client, &current.base_dir, name, value).await?;
+    let index = read_index(&current.base_dir).await?;
+    Ok(provider_status(&current.base_dir, &index, name, value).await)
+}
+
+/// Download every provider that has a URL; failures are reported per provider
+pub async fn refresh_rule_providers(client: &HttpClient) -> Result<Vec<RuleProviderStatus>> {
+    let current = CurrentProfile::load().await?;
+    let mut failures = BTreeMap::new();
+    for (name, value) in current.providers() {
+        let has_url = value.get("url").and_then(Value::as_str).is_some();
+        if has_url && let Err(err) = download(client, &current.base_dir, &name, value).await {
+            log::warn!("failed to download rule provider {name}: {err:#}");
+            failures.insert(name, format!("{err:#}"));
+        }
+    }
+    let index = read_index(&current.base_dir).await?;
+    let mut statuses = vec![];
+    for (name, value) in current.providers() {
+        let mut status = provider_status(&current.base_dir, &index, &name, value).await;
+        if let Some(error) = failures.remove(&name) {
+            status.error = Some(error);
+        }
+        statuses.push(status);
+    }
+    Ok(statuses)
+}
+
+/// Entries of a provider containing `query`, ignoring case
+pub async fn search_rule_provider(
+    name: &str,
+    query: &str,
+    limit: usize,
+) -> Result<RuleProviderSearch> {
+    let current = CurrentProfile::load().await?;
+    let value = current.provider(name)?;
+    let def = ProviderDef::from_value(name, value)?;
+    let entries = if def.kind == "inline" {
+        string_list(value.get("payload"))
+    } else {
+        let path = local_file(value, &current.base_dir)
+            .ok_or_else(|| anyhow!("rule provider {name} has no local copy"))?;
+        let bytes = fs::read(&path)
+            .await
+            .with_context(|| format!("read {}", path.display()))?;
+        parse_provider_content(&bytes, def.behavior, def.format)?
+    };
+    let needle = query.trim().to_ascii_lowercase();
+    let mut matches: Vec<String> = entries
+        .into_iter()
+        .filter(|entry| entry.to_ascii_lowercase().contains(&needle))
+        .collect();
+    let total = matches.len();
+    matches.truncate(limit);
+    Ok(RuleProviderSearch {
+        name: name.to_string(),
+        total,
+        matches,
+    })
+}
+
+/// Point an HTTP provider at its cached copy, or back at its URL
+pub async fn set_rule_provider_offline(name: &str, offline: bool) -> Result<RuleProviderStatus> {
+    let _guard = CACHE_LOCK.lock().await;
+    let mut current = CurrentProfile::load().await?;
+    let mut index = read_index(&current.base_dir).await?;
+    let def = ProviderDef::from_value(name, current.provider(name)?)?;
+    if offline == def.reads_cache() {
+        return Ok(provider_status(&current.base_dir, &index, name, current.provider(name)?).await);
+    }
+    let url = def
+        .url
+        .clone()
+        .ok_or_else(|| anyhow!("rule provider {name} has no url"))?;
+    let entry = index.entries.get_mut(&url);
+
+    let provider = current.provider_mut(name)?;
+    if offline {
+        if def.kind != "http" {
+            bail!("rule provider {name} is not an http provider");
+        }
+        let entry = entry.ok_or_else(|| anyhow!("rule provider {name} has not been downloaded"))?;
+        entry.original_path = def.path.clone();
+        provider.insert("type".into(), "file".into());
+        provider.insert("path".into(), cache_path(&entry.file).into());
+    } else {
+        provider.insert("type".into(), "http".into());
+        match entry.and_then(|entry| entry.original_path.take()) {
+            Some(path) => provider.insert("path".into(), path.into()),
+            None => provider.remove("path"),
+        };
+    }
+
+    write_index(&current.base_dir, &index).await?;
+    current.save().await?;
+    Ok(provider_status(&current.base_dir, &index, name, current.provider(name)?).await)
+}
+
+/// Parse provider content into its entries: domains, CIDRs or rule lines
+/// depending on `behavior`
+pub fn parse_provider_content(
+    bytes: &[u8],
+    behavior: ProviderBehavior,
+    format: ProviderFormat,
+) -> Result<Vec<String>> {
+    match format {
+        ProviderFormat::Text => Ok(String::from_utf8_lossy(bytes)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()),
+        ProviderFormat::Yaml => {
+            let doc: Value = serde_yaml::from_slice(bytes).context("parse rule provider yaml")?;
+            Ok(string_list(doc.get("payload")))
+        }
+        ProviderFormat::Mrs => {
+            let (found, entries) = mrs::decode(bytes)?;
+            if found != behavior {
+                bail!("mrs file holds {found:?} rules, provider expects {behavior:?}");
+            }
+            Ok(entries)
+        }
+    }
+}
+
+/// Local file the core would read for `provider`: its own `path` when that
+/// exists, otherwise the cached download of its URL
+pub(crate) fn local_file(provider: &Value, base_dir: &Path) -> Option<PathBuf> {
+    let field = |key: &str| provider.get(key).and_then(Value::as_str);
+    if let Some(path) = field("path").map(|path| base_dir.join(path))
+        && path.is_file()
+    {
+        return Some(path);
+    }
+    let index: CacheIndex = std::fs::read(cache_dir(base_dir).join(INDEX_FILE))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())?;
+    let entry = index.entries.get(field("url")?)?;
+    Some(cache_dir(base_dir).join(&entry.file)).filter(|path| path.is_file())
+}
+
+pub(crate) fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_sequence)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|item| item.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn download(client: &HttpClient, base_dir: &Path, name: &str, value: &Value) -> Result<()> {
+    let def = ProviderDef::from_value(name, value)?;
+    let url = def
+        .url
+        .as_deref()
+        .ok_or_else(|| anyhow!("rule provider {name} has no url"))?;
+    let bytes = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("download rule provider {name}"))?
+        .bytes()
+        .await
+        .with_context(|| format!("download rule provider {name}"))?;
+    let entries = parse_provider_content(&bytes, def.behavior, def.format)
+        .with_context(|| format!("parse rule provider {name}"))?;
+
+    let _guard = CACHE_LOCK.lock().await;
+    let mut index = read_index(base_dir).await?;
+    let file = match index.entries.get(url) {
+        Some(entry) => entry.file.clone(),
+        None => allocate_file_name(&index, name, def.format),
+    };
+    let dir = cache_dir(base_dir);
+    fs::create_dir_all(&dir)
+        .await
+        .context("create rule provider cache directory")?;
+    let temp = dir.join(format!("{file}.tmp"));
+    fs::write(&temp, &bytes)
+        .await
+        .context("write rule provider cache")?;
+    fs::rename(&temp, dir.join(&file))
+        .await
+        .context("write rule provider cache")?;
+
+    let original_path = index
+        .entries
+        .get(url)
+        .and_then(|entry| entry.original_path.clone());
+    index.entries.insert(
+        url.to_string(),
+        CacheEntry {
+            file,
+            entries: entries.len(),
+            size: bytes.len() as u64,
+            updated_at: Utc::now().to_rfc3339(),
+            original_path,
+        },
+    );
+    write_index(base_dir, &index).await
+}
+
+async fn provider_status(
+    base_dir: &Path,
+    index: &CacheIndex,
+    name: &str,
+    value: &Value,
+) -> RuleProviderStatus {
+    let def = match ProviderDef::from_value(name, value) {
+        Ok(def) => def,
+        Err(err) => {
+            return RuleProviderStatus {
+                name: name.to_string(),
+                kind: String::new(),
+                behavior: ProviderBehavior::Classical,
+                format: ProviderFormat::Yaml,
+                url: None,
+                path: None,
+                offline: false,
+                entries: None,
+                size: None,
+                updated_at: None,
+                error: Some(format!("{err:#}")),
+            }
+        }
+    };
+    let mut status = RuleProviderStatus {
+        name: name.to_string(),
+        kind: def.kind.clone(),
+        behavior: def.behavior,
+        format: def.format,
+        url: def.url.clone(),
+        path: def.path.clone(),
+        offline: def.reads_cache(),
+        entries: None,
+        size: None,
+        updated_at: None,
+        error: None,
+    };
+
+    if def.kind == "inline" {
+        status.entries = Some(string_list(value.get("payload")).len());
+        return status;
+    }
+    if let Some(entry) = def.url.as_ref().and_then(|url| index.entries.get(url)) {
+        status.entries = Some(entry.entries);
+        status.size = Some(entry.size);
+        status.updated_at = Some(entry.updated_at.clone());
+        return status;
+    }
+    if let Some(path) = def.path.as_ref().map(|path| base_dir.join(path)) {
+        match read_file_info(&path, def.behavior, def.format).await {
+            Ok(Some((entries, size, updated_at))) => {
+                status.entries = Some(entries);
+                status.size = Some(size);
+                status.updated_at = updated_at;
+            }
+            Ok(None) if def.kind == "file" => {
+                status.error = Some(format!("{} does not exist", path.display()));
+            }
+            Ok(None) => {}
+            Err(err) => status.error = Some(format!("{err:#}")),
+        }
+    }
+    status
+}
+
+/// Entry count, size and modification time of a provider file, if present
+async fn read_file_info(
+    path: &Path,
+    behavior: ProviderBehavior,
+    format: ProviderFormat,
+) -> Result<Option<(usize, u64, Option<String>)>> {
+    if !fs::try_exists(path).await.unwrap_or(false) {
+        return Ok(None);
+    }
+    let metadata = fs::metadata(path)
+        .await
+        .with_context(|| format!("read {}", path.display()))?;
+    let bytes = fs::read(path)
+        .await
+        .with_context(|| format!("read {}", path.display()))?;
+    let entries = parse_provider_content(&bytes, behavior, format)?;
+    let updated_at = metadata
+        .modified()
+        .ok()
+        .map(|time| DateTime::<Utc>::from(time).to_rfc3339());
+    Ok(Some((entries.len(), metadata.len(), updated_at)))
+}
+
+fn cache_dir(base_dir: &Path) -> PathBuf {
+    base_dir.join(CACHE_DIR)
+}
+
+/// `path` value that makes the core read a cached file
+fn cache_path(file: &str) -> String {
+    format!("./{CACHE_DIR}/{file}")
+}
+
+fn is_cache_path(path: &str) -> bool {
+    let path = path.trim_start_matches("./");
+    path.strip_prefix(CACHE_DIR)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+async fn read_index(base_dir: &Path) -> Result<CacheIndex> {
+    let path = cache_dir(base_dir).join(INDEX_FILE);
+    if !fs::try_exists(&path)
+        .await
+        .context("check rule provider cache index")?
+    {
+        return Ok(CacheIndex::default());
+    }
+    let bytes = fs::read(&path)
+        .await
+        .context("read rule provider cache index")?;
+    serde_json::from_slice(&bytes).context("parse rule provider cache index")
+}
+
+async fn write_index(base_dir: &Path, index: &CacheIndex) -> Result<()> {
+    let dir = cache_dir(base_dir);
+    fs::create_dir_all(&dir)
+        .await
+        .context("create rule provider cache directory")?;
+    let content = serde_json::to_vec_pretty(index).context("serialize rule provider cache index")?;
+    fs::write(dir.join(INDEX_FILE), content)
+        .await
+        .context("write rule provider cache index")
+}
+
+/// A file name derived from the provider name that no other URL uses
+fn allocate_file_name(index: &CacheIndex, name: &str, format: ProviderFormat) -> String {
+    let stem: String = name
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let stem = if stem.is_empty() { "provider".to_string() } else { stem };
+    let taken = |file: &str| index.entries.values().any(|entry| entry.file == file);
+    let mut file = format!("{stem}.{}", format.extension());
+    let mut suffix = 2;
+    while taken(&file) {
+        file = format!("{stem}-{suffix}.{}", format.extension());
+        suffix += 1;
+    }
+    file
+}
+
+/// Reader for the core's binary `mrs` rule-set format: a zstd stream holding
+/// a header and either a succinct domain trie or a list of IP ranges
+mod mrs {
+    use super::*;
+
+    struct Reader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+            let end = self
+                .pos
+                .checked_add(len)
+                .filter(|end| *end <= self.data.len())
+                .ok_or_else(|| anyhow!("truncated mrs file"))?;
+            let bytes = &self.data[self.pos..end];
+            self.pos = end;
+            Ok(bytes)
+        }
+
+        fn u8(&mut self) -> Result<u8> {
+            Ok(self.take(1)?[0])
+        }
+
+        fn u64(&mut self) -> Result<u64> {
+            let bytes: [u8; 8] = self.take(8)?.try_into()?;
+            Ok(u64::from_be_bytes(bytes))
+        }
+
+        fn len(&mut self) -> Result<usize> {
+            let len = self.u64()?;
+            usize::try_from(len)
+                .ok()
+                .filter(|len| *len <= self.data.len())
+                .ok_or_else(|| anyhow!("invalid length {len} in mrs file"))
+        }
+
+        fn u64_list(&mut self) -> Result<Vec<u64>> {
+            let len = self.len()?;
+            (0..len).map(|_| self.u64()).collect()
+        }
+    }
+
+    pub(super) fn decode(bytes: &[u8]) -> Result<(ProviderBehavior, Vec<String>)> {
+        let data = zstd::decode_all(bytes).context("decompress mrs file")?;
+        let mut reader = Reader { data: &data, pos: 0 };
+        if reader.take(4)? != MRS_MAGIC {
+            bail!("not an mrs file");
+        }
+        let behavior = match reader.u8()? {
+            0 => ProviderBehavior::Domain,
+            1 => ProviderBehavior::Ipcidr,
+            other => bail!("unsupported mrs behavior {other}"),
+        };
+        let _count = reader.u64()?;
+        let extra = reader.len()?;
+        reader.take(extra)?;
+        let version = reader.u8()?;
+        if version != 1 {
+            bail!("unsupported mrs payload version {version}");
+        }
+        let entries = match behavior {
+            ProviderBehavior::Domain => decode_domains(&mut reader)?,
+            _ => decode_ranges(&mut reader)?,
+        };
+        Ok((behavior, entries))
+    }
+
+    /// Walk the LOUDS-encoded trie: nodes are numbered in breadth-first
+    /// order, each lists its child labels as `0` bits closed by a `1` bit,
+    /// and label `k` leads to node `k + 1`. Keys are stored reversed.
+    fn decode_domains(reader: &mut Reader<'_>) -> Result<Vec<String>> {
+        let leaves = reader.u64_list()?;
+        let bitmap = reader.u64_list()?;
+        let label_len = reader.len()?;
+        let labels = reader.take(label_len)?;
+        let bit = |words: &[u64], index: usize| {
+            words
+                .get(index / 64)
+                .is_some_and(|word| (word >> (index % 64)) & 1 == 1)
+        };
+
+        let mut parents = Vec::with_capacity(labels.len() + 1);
+        parents.push(0usize);
+        let mut node = 0;
+        let mut index = 0;
+        while node <= labels.len() {
+            if index >= bitmap.len() * 64 {
+                bail!("corrupt mrs domain set");
+            }
+            if bit(&bitmap, index) {
+                node += 1;
+            } else {
+                if parents.len() > labels.len() {
+                    bail!("corrupt mrs domain set");
+                }
+                parents.push(node);
+            }
+            index += 1;
+        }
+
+        let mut domains = vec![];
+        for leaf in 1..parents.len() {
+            if !bit(&leaves, leaf) {
+                continue;
+            }
+            let mut domain = vec![];
+            let mut current = leaf;
+            while current != 0 {
+                domain.push(labels[current - 1]);
+                current = parents[current];
+            }
+            domains.push(String::from_utf8_lossy(&domain).into_owned());
+        }
+        Ok(domains)
+    }
+
+    fn decode_ranges(reader: &mut Reader<'_>) -> Result<Vec<String>> {
+        let count = reader.len()?;
+        let mut cidrs = vec![];
+        for _ in 0..count {
+            let from = read_addr(reader)?;
+            let to = read_addr(reader)?;
+            let nets: Vec<IpNet> = match (from, to) {
+                (IpAddr::V4(from), IpAddr::V4(to)) => {
+                    Ipv4Subnets::new(from, to, 0).map(IpNet::V4).collect()
+                }
+                (IpAddr::V6(from), IpAddr::V6(to)) => {
+                    Ipv6Subnets::new(from, to, 0).map(IpNet::V6).collect()
+                }
+                _ => bail!("mrs range mixes IPv4 and IPv6"),
+            };
+            cidrs.extend(nets.iter().map(IpNet::to_string));
+        }
+        Ok(cidrs)
+    }
+
+    fn read_addr(reader: &mut Reader<'_>) -> Result<IpAddr> {
+        let bytes: [u8; 16] = reader.take(16)?.try_into()?;
+        let addr = Ipv6Addr::from(bytes);
+        Ok(match addr.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(addr),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build an mrs file the way the core's converter does
+    fn encode_mrs(behavior: u8, payload: &[u8], count: usize) -> Vec<u8> {
+        let mut raw = MRS_MAGIC.to_vec();
+        raw.push(behavior);
+        raw.extend((count as u64).to_be_bytes());
+        raw.extend(0u64.to_be_bytes());
+        raw.push(1);
+        raw.extend(payload);
+        zstd::encode_all(raw.as_slice(), 0).unwrap()
+    }
+
+    fn encode_domain_set(domains: &[&str]) -> Vec<u8> {
+        let mut keys: Vec<Vec<u8>> = domains
+            .iter()
+            .map(|domain| domain.bytes().rev().collect())
+            .collect();
+        keys.sort();
+        let set_bit = |words: &mut Vec<u64>, index: usize| {
+            if words.len() <= index / 64 {
+                words.resize(index / 64 + 1, 0);
+            }
+            words[index / 64] |= 1 << (index % 64);
+        };
+        let (mut leaves, mut bitmap, mut labels) = (vec![], vec![], vec![]);
+        let mut queue = vec![(0usize, keys.len(), 0usize)];
+        let mut label_index = 0;
+        let mut i = 0;
+        while i < queue.len() {
+            let (mut start, end, col) = queue[i];
+            if col == keys[start].len() {
+                start += 1;
+                set_bit(&mut leaves, i);
+            }
+            let mut j = start;
+            while j < end {
+                let from = j;
+                while j < end && keys[j][col] == keys[from][col] {
+                    j += 1;
+                }
+                queue.push((from, j, col + 1));
+                labels.push(keys[from][col]);
+                label_index += 1;
+            }
+            set_bit(&mut bitmap, label_index);
+            label_index += 1;
+            i += 1;
+        }
+
+        let mut out = vec![];
+        for words in [&leaves, &bitmap] {
+            out.extend((words.len() as u64).to_be_bytes());
+            for word in words {
+                out.extend(word.to_be_bytes());
+            }
+        }
+        out.extend((labels.len() as u64).to_be_bytes());
+        out.extend(labels);
+        out
+    }
+
+    #[test]
+    fn test_parse_text_and_yaml() {
+        let text = b"# comment\n\nexample.com\n  +.example.org \n";
+        let entries =
+            parse_provider_content(text, ProviderBehavior::Domain, ProviderFormat::Text).unwrap();
+        assert_eq!(entries, vec!["example.com", "+.example.org"]);
+
+        let yaml = b"payload:\n  - DOMAIN,a.com\n  - IP-CIDR,10.0.0.0/8\n";
+        let entries =
+            parse_provider_content(yaml, ProviderBehavior::Classical, ProviderFormat::Yaml)
+                .unwrap();
+        assert_eq!(entries, vec!["DOMAIN,a.com", "IP-CIDR,10.0.0.0/8"]);
+    }
+
+    #[test]
+    fn test_parse_mrs_domain_set() {
+        let domains = ["+.ads.example", "ads.example.net", "*.track.example", "a.example"];
+        let bytes = encode_mrs(0, &encode_domain_set(&domains), domains.len());
+        let mut entries =
+            parse_provider_content(&bytes, ProviderBehavior::Domain, ProviderFormat::Mrs).unwrap();
+        entries.sort();
+        let mut expected = domains.to_vec();
+        expected.sort();
+        assert_eq!(entries, expected);
+
+        let err = parse_provider_content(&bytes, ProviderBehavior::Ipcidr, ProviderFormat::Mrs)
+            .unwrap_err();
+        assert!(err.to_string().contains("holds Domain rules"));
+    }
+
+    #[test]
+    fn test_parse_mrs_ip_ranges() {
+        let mut payload = 2u64.to_be_bytes().to_vec();
+        let v4 = |addr: [u8; 4]| std::net::Ipv4Addr::from(addr).to_ipv6_mapped().octets();
+        payload.extend(v4([10, 0, 0, 0]));
+        payload.extend(v4([10, 255, 255, 255]));
+        payload.extend("2001:db8::".parse::<Ipv6Addr>().unwrap().octets());
+        payload.extend("2001:db8::3".parse::<Ipv6Addr>().unwrap().octets());
+        let bytes = encode_mrs(1, &payload, 2);
+        let entries =
+            parse_provider_content(&bytes, ProviderBehavior::Ipcidr, ProviderFormat::Mrs).unwrap();
+        assert_eq!(entries, vec!["10.0.0.0/8", "2001:db8::/126"]);
+
+        assert!(parse_provider_content(b"garbage", ProviderBehavior::Ipcidr, ProviderFormat::Mrs)
+            .is_err());
+    }
+
+    #[test]
+    fn test_local_file_falls_back_to_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache_dir(dir.path());
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("ads.yaml"), "payload: []\n").unwrap();
+        std::fs::write(
+            cache.join(INDEX_FILE),
+            r#"{"entries":{"https://a.example/ads":{"file":"ads.yaml","entries":0,"size":12,"updated_at":"2026-01-01T00:00:00Z"}}}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("own.yaml"), "payload: []\n").unwrap();
+
+        let provider = |yaml: &str| serde_yaml::from_str::<Value>(yaml).unwrap();
+        assert_eq!(
+            local_file(&provider("{ url: 'https://a.example/ads' }"), dir.path()),
+            Some(cache.join("ads.yaml"))
+        );
+        assert_eq!(
+            local_file(&provider("{ url: 'https://a.example/ads', path: ./own.yaml }"), dir.path()),
+            Some(dir.path().join("./own.yaml"))
+        );
+        assert_eq!(local_file(&provider("{ url: 'https://b.example' }"), dir.path()), None);
+    }
+
+    #[test]
+    fn test_cache_paths() {
+        assert!(is_cache_path("./rule-providers/ads.yaml"));
+        assert!(is_cache_path("rule-providers/ads.yaml"));
+        assert!(!is_cache_path("./rule-providers-old/ads.yaml"));
+        assert!(!is_cache_path("./ruleset/ads.yaml"));
+
+        let mut index = CacheIndex::default();
+        let name = allocate_file_name(&index, "ads/cn", ProviderFormat::Text);
+        assert_eq!(name, "ads_cn.txt");
+        index.entries.insert(
+            "https://a.example/ads".into(),
+            CacheEntry {
+                file: name,
+                entries: 0,
+                size: 0,
+                updated_at: String::new(),
+                original_path: None,
+            },
+        );
+        assert_eq!(
+            allocate_file_name(&index, "ads/cn", ProviderFormat::Text),
+            "ads_cn-2.txt"
+        );
+    }
+}
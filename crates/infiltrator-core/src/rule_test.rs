@@ -0,0 +1,794 @@
+//! Offline rule evaluation: find which entry of the current profile's rules
+//! a destination would hit, without asking the running core.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use mihomo_config::ConfigManager;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::rule_providers::{self, ProviderBehavior, ProviderFormat};
+use crate::rules::{self, Condition, LogicOp, Rule, RuleOptions, RuleType};
+
+const GEOIP_FILES: &[&str] = &["geoip.metadb", "Country.mmdb", "country.mmdb"];
+const GEOSITE_FILES: &[&str] = &["GeoSite.dat", "geosite.dat"];
+/// Mihomo's fallback when no rule matches
+const DEFAULT_POLICY: &str = "DIRECT";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleTestQuery {
+    pub host: Option<String>,
+    pub ip: Option<IpAddr>,
+    pub src_ip: Option<IpAddr>,
+    pub port: Option<u16>,
+    /// `tcp` or `udp`
+    pub network: Option<String>,
+    /// Process name or full path
+    pub process: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedRule {
+    pub index: usize,
+    pub rule: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTestResult {
+    /// Index into the list returned by `rules::load_rules`
+    pub index: Option<usize>,
+    pub rule: Option<String>,
+    pub policy: String,
+    /// Rules before the match that could not be evaluated offline
+    pub skipped: Vec<SkippedRule>,
+}
+
+/// Evaluate `query` against the rules of the current profile
+pub async fn test_rules(query: RuleTestQuery) -> Result<RuleTestResult> {
+    let manager = ConfigManager::new().context("init config manager")?;
+    let path = manager.get_current_path().await.context("load current profile")?;
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .context("read profile config")?;
+    let doc: Value = serde_yaml::from_str(&content).context("parse profile yaml")?;
+    let base_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    tokio::task::spawn_blocking(move || evaluate(&doc, &base_dir, &query))
+        .await
+        .context("rule test task failed")?
+}
+
+/// Evaluate `query` against the rules of `doc`; geo databases and rule-set
+/// files are looked up relative to `base_dir` like the core does
+pub fn evaluate(doc: &Value, base_dir: &Path, query: &RuleTestQuery) -> Result<RuleTestResult> {
+    let entries = rules::extract_rules(doc)?;
+    let empty = Mapping::new();
+    let providers = doc
+        .get("rule-providers")
+        .and_then(Value::as_mapping)
+        .unwrap_or(&empty);
+    let mut evaluator = Evaluator::new(query, base_dir, providers);
+    let mut skipped = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        if !entry.enabled {
+            continue;
+        }
+        match evaluator.match_top(&entry.rule) {
+            Ok(Some(policy)) => {
+                return Ok(RuleTestResult {
+                    index: Some(index),
+                    rule: Some(entry.rule.to_string()),
+                    policy,
+                    skipped,
+                });
+            }
+            Ok(None) => {}
+            Err(reason) => skipped.push(SkippedRule {
+                index,
+                rule: entry.rule.to_string(),
+                reason,
+            }),
+        }
+    }
+    Ok(RuleTestResult {
+        index: None,
+        rule: None,
+        policy: DEFAULT_POLICY.to_string(),
+        skipped,
+    })
+}
+
+/// Outcome of a single matcher; `Err` means the rule cannot be decided offline
+type MatchResult = std::result::Result<bool, String>;
+
+struct Evaluator<'a> {
+    host: Option<String>,
+    ip: Option<IpAddr>,
+    query: &'a RuleTestQuery,
+    base_dir: &'a Path,
+    providers: &'a Mapping,
+    geoip: Option<Option<maxminddb::Reader<Vec<u8>>>>,
+    geosite: Option<Option<Vec<u8>>>,
+    geosite_codes: HashMap<String, Vec<SiteDomain>>,
+    rule_sets: HashMap<String, std::result::Result<RuleSet, String>>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(query: &'a RuleTestQuery, base_dir: &'a Path, providers: &'a Mapping) -> Self {
+        let host = query
+            .host
+            .as_deref()
+            .map(|host| host.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|host| !host.is_empty());
+        let literal = host.as_deref().and_then(|host| {
+            IpAddr::from_str(host.trim_start_matches('[').trim_end_matches(']')).ok()
+        });
+        let ip = query.ip.or(literal);
+        Self {
+            host: host.filter(|_| literal.is_none()),
+            ip,
+            query,
+            base_dir,
+            providers,
+            geoip: None,
+            geosite: None,
+            geosite_codes: HashMap::new(),
+            rule_sets: HashMap::new(),
+        }
+    }
+
+    /// Policy of `rule` when it matches
+    fn match_top(&mut self, rule: &Rule) -> std::result::Result<Option<String>, String> {
+        let matched = match rule {
+            Rule::Simple {
+                kind,
+                payload,
+                options,
+                ..
+            } => self.match_rule(*kind, payload, options)?,
+            Rule::Logic { op, conditions, .. } => self.match_logic(*op, conditions)?,
+            Rule::SubRule { .. } => return Err("不支持离线测试 SUB-RULE 规则".to_string()),
+            Rule::Raw { reason, .. } => return Err(format!("无法识别的规则: {reason}")),
+            Rule::Match { .. } => true,
+        };
+        Ok(matched.then(|| rule.target().unwrap_or_default().to_string()))
+    }
+
+    fn match_rule(&mut self, kind: RuleType, payload: &str, options: &RuleOptions) -> MatchResult {
+        match kind {
+            RuleType::Domain => Ok(self.host.as_deref() == Some(&payload.to_ascii_lowercase())),
+            RuleType::DomainSuffix => Ok(self
+                .host
+                .as_deref()
+                .is_some_and(|host| matches_suffix(host, &payload.to_ascii_lowercase()))),
+            RuleType::DomainKeyword => Ok(self
+                .host
+                .as_deref()
+                .is_some_and(|host| host.contains(&payload.to_ascii_lowercase()))),
+            RuleType::DomainRegex => {
+                let regex = Regex::new(payload).map_err(|e| format!("正则无效: {e}"))?;
+                Ok(self.host.as_deref().is_some_and(|host| regex.is_match(host)))
+            }
+            RuleType::IpCidr | RuleType::IpCidr6 => {
+                let net = parse_cidr(payload)?;
+                Ok(self.destination_ip(options)?.is_some_and(|ip| net.contains(&ip)))
+            }
+            RuleType::SrcIpCidr => {
+                let net = parse_cidr(payload)?;
+                Ok(self.query.src_ip.is_some_and(|ip| net.contains(&ip)))
+            }
+            RuleType::DstPort => {
+                let port = self.query.port;
+                Ok(port.is_some_and(|port| matches_port(payload, port)))
+            }
+            RuleType::Network => Ok(self
+                .query
+                .network
+                .as_deref()
+                .is_some_and(|network| network.eq_ignore_ascii_case(payload))),
+            RuleType::ProcessName => Ok(self.query.process.as_deref().is_some_and(|process| {
+                let name = Path::new(process)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(process);
+                name == payload
+            })),
+            RuleType::ProcessPath => Ok(self.query.process.as_deref() == Some(payload)),
+            RuleType::GeoIp => self.match_geoip(payload, options),
+            RuleType::GeoSite => self.match_geosite(payload),
+            RuleType::RuleSet => self.match_rule_set(payload, options),
+            _ => Err(format!("不支持离线测试 {kind} 规则")),
+        }
+    }
+
+    /// Evaluate a rule written without a policy, as inside logic rules and
+    /// classical rule sets
+    fn match_condition(&mut self, condition: &Condition) -> MatchResult {
+        match condition {
+            Condition::Simple {
+                kind,
+                payload,
+                options,
+            } => self.match_rule(*kind, payload, options),
+            Condition::Logic { op, conditions } => self.match_logic(*op, conditions),
+        }
+    }
+
+    fn match_logic(&mut self, op: LogicOp, conditions: &[Condition]) -> MatchResult {
+        let mut results = vec![];
+        for condition in conditions {
+            results.push(self.match_condition(condition)?);
+        }
+        match op {
+            LogicOp::And => Ok(!results.is_empty() && results.iter().all(|matched| *matched)),
+            LogicOp::Or => Ok(results.iter().any(|matched| *matched)),
+            LogicOp::Not => match results.as_slice() {
+                [matched] => Ok(!matched),
+                _ => Err("NOT 规则只能包含一个条件".to_string()),
+            },
+        }
+    }
+
+    /// Destination IP for IP-based rules; the core would resolve a bare host
+    /// name first, which cannot be done offline
+    fn destination_ip(&self, options: &RuleOptions) -> std::result::Result<Option<IpAddr>, String> {
+        match (self.ip, &self.host) {
+            (Some(ip), _) => Ok(Some(ip)),
+            (None, Some(_)) if !options.no_resolve => {
+                Err("需要解析域名才能判断，请填写目标 IP".to_string())
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn match_geoip(&mut self, code: &str, options: &RuleOptions) -> MatchResult {
+        let Some(ip) = self.destination_ip(options)? else {
+            return Ok(false);
+        };
+        if code.eq_ignore_ascii_case("LAN") {
+            return Ok(is_lan(ip));
+        }
+        if self.geoip.is_none() {
+            let reader = find_file(self.base_dir, GEOIP_FILES)
+                .and_then(|path| maxminddb::Reader::open_readfile(path).ok());
+            self.geoip = Some(reader);
+        }
+        let reader = self
+            .geoip
+            .as_ref()
+            .and_then(Option::as_ref)
+            .ok_or("未找到 GeoIP 数据库")?;
+        let codes = match reader.lookup::<GeoIpRecord>(ip) {
+            Ok(record) => record.codes(),
+            Err(maxminddb::MaxMindDBError::AddressNotFoundError(_)) => vec![],
+            Err(err) => return Err(format!("读取 GeoIP 数据库失败: {err}")),
+        };
+        Ok(codes.iter().any(|found| found.eq_ignore_ascii_case(code)))
+    }
+
+    fn match_geosite(&mut self, code: &str) -> MatchResult {
+        let Some(host) = self.host.clone() else {
+            return Ok(false);
+        };
+        let key = code.to_ascii_lowercase();
+        if !self.geosite_codes.contains_key(&key) {
+            if self.geosite.is_none() {
+                let data =
+                    find_file(self.base_dir, GEOSITE_FILES).and_then(|path| std::fs::read(path).ok());
+                self.geosite = Some(data);
+            }
+            let data = self
+                .geosite
+                .as_ref()
+                .and_then(Option::as_deref)
+                .ok_or("未找到 GeoSite 数据库")?;
+            let (name, attribute) = match key.split_once('@') {
+                Some((name, attribute)) => (name, Some(attribute)),
+                None => (key.as_str(), None),
+            };
+            let domains = geosite::lookup(data, name, attribute)
+                .ok_or_else(|| format!("GeoSite 中没有 {code}"))?;
+            self.geosite_codes.insert(key.clone(), domains);
+        }
+        Ok(self.geosite_codes[&key]
+            .iter()
+            .any(|domain| domain.matches(&host)))
+    }
+
+    fn match_rule_set(&mut self, name: &str, options: &RuleOptions) -> MatchResult {
+        if !self.rule_sets.contains_key(name) {
+            let loaded = load_rule_set(self.providers, self.base_dir, name);
+            self.rule_sets.insert(name.to_string(), loaded);
+        }
+        let rule_set = self.rule_sets[name].clone()?;
+        match rule_set.behavior {
+            ProviderBehavior::Domain => Ok(self.host.as_deref().is_some_and(|host| {
+                rule_set
+                    .entries
+                    .iter()
+                    .any(|pattern| matches_domain_pattern(host, pattern))
+            })),
+            ProviderBehavior::Ipcidr => {
+                let Some(ip) = self.destination_ip(options)? else {
+                    return Ok(false);
+                };
+                for entry in &rule_set.entries {
+                    if parse_cidr(entry)?.contains(&ip) {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            ProviderBehavior::Classical => {
+                for entry in &rule_set.entries {
+                    let condition: Condition = entry
+                        .parse()
+                        .map_err(|err| format!("规则集条目 {entry} 无效: {err}"))?;
+                    if self.match_condition(&condition)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RuleSet {
+    behavior: ProviderBehavior,
+    entries: Vec<String>,
+}
+
+fn load_rule_set(
+    providers: &Mapping,
+    base_dir: &Path,
+    name: &str,
+) -> std::result::Result<RuleSet, String> {
+    let provider = providers
+        .get(name)
+        .filter(|provider| provider.is_mapping())
+        .ok_or_else(|| format!("未定义规则集 {name}"))?;
+    let field = |key: &str| provider.get(key).and_then(Value::as_str);
+    let behavior = match field("behavior").map(str::to_ascii_lowercase).as_deref() {
+        Some("domain") => ProviderBehavior::Domain,
+        Some("ipcidr") => ProviderBehavior::Ipcidr,
+        _ => ProviderBehavior::Classical,
+    };
+
+    let entries = if field("type") == Some("inline") {
+        rule_providers::string_list(provider.get("payload"))
+    } else {
+        let format = match field("format").map(str::to_ascii_lowercase).as_deref() {
+            Some("text") => ProviderFormat::Text,
+            Some("mrs") => ProviderFormat::Mrs,
+            _ => ProviderFormat::Yaml,
+        };
+        let bytes = rule_providers::local_file(provider, base_dir)
+            .and_then(|path| std::fs::read(path).ok())
+            .ok_or_else(|| format!("规则集 {name} 未缓存到本地"))?;
+        rule_providers::parse_provider_content(&bytes, behavior, format)
+            .map_err(|e| format!("解析规则集 {name} 失败: {e}"))?
+    };
+    Ok(RuleSet { behavior, entries })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GeoIpRecord {
+    Code(String),
+    Codes(Vec<String>),
+    Country { country: Option<IsoCode> },
+}
+
+#[derive(Deserialize)]
+struct IsoCode {
+    iso_code: Option<String>,
+}
+
+impl GeoIpRecord {
+    fn codes(self) -> Vec<String> {
+        match self {
+            GeoIpRecord::Code(code) => vec![code],
+            GeoIpRecord::Codes(codes) => codes,
+            GeoIpRecord::Country { country } => {
+                country.and_then(|country| country.iso_code).into_iter().collect()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SiteDomain {
+    Keyword(String),
+    Regex(String),
+    Suffix(String),
+    Full(String),
+}
+
+impl SiteDomain {
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            SiteDomain::Keyword(keyword) => host.contains(keyword.as_str()),
+            SiteDomain::Regex(pattern) => Regex::new(pattern).is_ok_and(|re| re.is_match(host)),
+            SiteDomain::Suffix(suffix) => matches_suffix(host, suffix),
+            SiteDomain::Full(domain) => host == domain,
+        }
+    }
+}
+
+/// Just enough protobuf to read v2ray's `GeoSiteList`
+mod geosite {
+    use super::SiteDomain;
+
+    enum Field<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+        Fixed,
+    }
+
+    fn read_varint(buf: &[u8], pos: &mut usize) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *buf.get(*pos)?;
+            *pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn fields(buf: &[u8]) -> impl Iterator<Item = (u64, Field<'_>)> {
+        let mut pos = 0;
+        std::iter::from_fn(move || {
+            if pos >= buf.len() {
+                return None;
+            }
+            let key = read_varint(buf, &mut pos)?;
+            let field = match key & 7 {
+                0 => Field::Varint(read_varint(buf, &mut pos)?),
+                1 => {
+                    pos += 8;
+                    Field::Fixed
+                }
+                2 => {
+                    let len = read_varint(buf, &mut pos)? as usize;
+                    let bytes = buf.get(pos..pos.checked_add(len)?)?;
+                    pos += len;
+                    Field::Bytes(bytes)
+                }
+                5 => {
+                    pos += 4;
+                    Field::Fixed
+                }
+                _ => return None,
+            };
+            Some((key >> 3, field))
+        })
+    }
+
+    fn string(bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+
+    /// Domains listed under `code`, optionally only those tagged `@attribute`
+    pub(super) fn lookup(data: &[u8], code: &str, attribute: Option<&str>) -> Option<Vec<SiteDomain>> {
+        for (number, field) in fields(data) {
+            let (1, Field::Bytes(site)) = (number, field) else {
+                continue;
+            };
+            let matches_code = fields(site).any(|(number, field)| {
+                matches!(field, Field::Bytes(name) if number == 1 && string(name).eq_ignore_ascii_case(code))
+            });
+            if !matches_code {
+                continue;
+            }
+            let domains = fields(site)
+                .filter_map(|(number, field)| match field {
+                    Field::Bytes(domain) if number == 2 => parse_domain(domain, attribute),
+                    _ => None,
+                })
+                .collect();
+            return Some(domains);
+        }
+        None
+    }
+
+    fn parse_domain(buf: &[u8], attribute: Option<&str>) -> Option<SiteDomain> {
+        let mut kind = 0;
+        let mut value = String::new();
+        let mut attributes = vec![];
+        for (number, field) in fields(buf) {
+            match (number, field) {
+                (1, Field::Varint(found)) => kind = found,
+                (2, Field::Bytes(bytes)) => value = string(bytes),
+                (3, Field::Bytes(attr)) => attributes.extend(fields(attr).filter_map(
+                    |(number, field)| match field {
+                        Field::Bytes(key) if number == 1 => Some(string(key)),
+                        _ => None,
+                    },
+                )),
+                _ => {}
+            }
+        }
+        if let Some(attribute) = attribute
+            && !attributes.iter().any(|found| found.eq_ignore_ascii_case(attribute))
+        {
+            return None;
+        }
+        // Regex entries are case sensitive; everything else matches the
+        // lowercased host
+        Some(match kind {
+            0 => SiteDomain::Keyword(value.to_ascii_lowercase()),
+            1 => SiteDomain::Regex(value),
+            2 => SiteDomain::Suffix(value.to_ascii_lowercase()),
+            _ => SiteDomain::Full(value.to_ascii_lowercase()),
+        })
+    }
+}
+
+fn matches_suffix(host: &str, suffix: &str) -> bool {
+    host == suffix
+        || host
+            .strip_suffix(suffix)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// Domain entries of a `behavior: domain` rule set
+fn matches_domain_pattern(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    if let Some(suffix) = pattern.strip_prefix("+.") {
+        matches_suffix(host, suffix)
+    } else if let Some(suffix) = pattern.strip_prefix('.') {
+        host.len() > suffix.len() && matches_suffix(host, suffix)
+    } else if let Some(suffix) = pattern.strip_prefix("*.") {
+        host.strip_suffix(suffix)
+            .and_then(|rest| rest.strip_suffix('.'))
+            .is_some_and(|label| !label.is_empty() && !label.contains('.'))
+    } else {
+        host == pattern
+    }
+}
+
+fn matches_port(payload: &str, port: u16) -> bool {
+    payload.split('/').any(|item| match item.split_once('-') {
+        Some((start, end)) => match (start.trim().parse::<u16>(), end.trim().parse::<u16>()) {
+            (Ok(start), Ok(end)) => (start..=end).contains(&port),
+            _ => false,
+        },
+        None => item.trim().parse::<u16>() == Ok(port),
+    })
+}
+
+fn parse_cidr(value: &str) -> std::result::Result<IpNet, String> {
+    let value = value.trim();
+    IpNet::from_str(value)
+        .or_else(|_| IpAddr::from_str(value).map(IpNet::from))
+        .map_err(|_| format!("无效的 CIDR: {value}"))
+}
+
+fn is_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || (ip.segments()[0] & 0xfe00) == 0xfc00
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+fn find_file(base_dir: &Path, names: &[&str]) -> Option<PathBuf> {
+    names
+        .iter()
+        .map(|name| base_dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(profile: &str, base_dir: &Path, query: RuleTestQuery) -> RuleTestResult {
+        let doc: Value = serde_yaml::from_str(profile).unwrap();
+        evaluate(&doc, base_dir, &query).unwrap()
+    }
+
+    fn host(host: &str) -> RuleTestQuery {
+        RuleTestQuery {
+            host: Some(host.to_string()),
+            ..RuleTestQuery::default()
+        }
+    }
+
+    const PROFILE: &str = r##"
+rules:
+  - DOMAIN,exact.example.com,A
+  - DOMAIN-SUFFIX,example.com,B
+  - DOMAIN-KEYWORD,tracker,REJECT
+  - DOMAIN-REGEX,^cdn[0-9]+\.,C
+  - "# DOMAIN,disabled.test,D"
+  - IP-CIDR,10.0.0.0/8,LAN,no-resolve
+  - AND,((DST-PORT,22),(NETWORK,tcp)),SSH
+  - NOT,((DST-PORT,80/443/8000-9000)),ODD
+  - PROCESS-NAME,curl,E
+  - GEOIP,CN,DIRECT
+  - MATCH,PROXY
+"##;
+
+    #[test]
+    fn test_domain_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = run(PROFILE, dir.path(), host("exact.example.com"));
+        assert_eq!((result.index, result.policy.as_str()), (Some(0), "A"));
+        let result = run(PROFILE, dir.path(), host("www.Example.com."));
+        assert_eq!((result.index, result.policy.as_str()), (Some(1), "B"));
+        let result = run(PROFILE, dir.path(), host("notexample.com"));
+        assert_ne!(result.index, Some(1));
+        let result = run(PROFILE, dir.path(), host("ad-tracker.net"));
+        assert_eq!(result.policy, "REJECT");
+        let result = run(PROFILE, dir.path(), host("cdn12.host.net"));
+        assert_eq!(result.policy, "C");
+    }
+
+    #[test]
+    fn test_ip_port_and_logic_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = run(PROFILE, dir.path(), host("10.1.2.3"));
+        assert_eq!((result.index, result.policy.as_str()), (Some(5), "LAN"));
+
+        let query = RuleTestQuery {
+            host: Some("git.host.net".into()),
+            port: Some(22),
+            network: Some("TCP".into()),
+            ..RuleTestQuery::default()
+        };
+        assert_eq!(run(PROFILE, dir.path(), query).policy, "SSH");
+
+        let query = RuleTestQuery {
+            port: Some(8443),
+            ..RuleTestQuery::default()
+        };
+        assert_eq!(run(PROFILE, dir.path(), query).policy, "PROXY");
+        let query = RuleTestQuery {
+            port: Some(21),
+            ..RuleTestQuery::default()
+        };
+        assert_eq!(run(PROFILE, dir.path(), query).policy, "ODD");
+
+        let query = RuleTestQuery {
+            port: Some(443),
+            process: Some("/usr/bin/curl".into()),
+            ..RuleTestQuery::default()
+        };
+        assert_eq!(run(PROFILE, dir.path(), query).policy, "E");
+    }
+
+    #[test]
+    fn test_missing_geoip_database_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let query = RuleTestQuery {
+            ip: Some("1.2.3.4".parse().unwrap()),
+            port: Some(443),
+            ..RuleTestQuery::default()
+        };
+        let result = run(PROFILE, dir.path(), query);
+        assert_eq!((result.index, result.policy.as_str()), (Some(10), "PROXY"));
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(result.skipped[0].index, 9);
+        assert_eq!(result.skipped[0].reason, "未找到 GeoIP 数据库");
+
+        let profile = "rules:\n  - GEOIP,LAN,DIRECT\n";
+        let result = run(profile, dir.path(), host("192.168.1.1"));
+        assert_eq!(result.index, Some(0));
+        let result = run(profile, dir.path(), host("8.8.8.8"));
+        assert_eq!((result.index, result.policy.as_str()), (None, DEFAULT_POLICY));
+    }
+
+    #[test]
+    fn test_rule_set_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("ruleset")).unwrap();
+        std::fs::write(
+            dir.path().join("ruleset/ads.yaml"),
+            "payload:\n  - '+.ads.example'\n  - '*.track.example'\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("ruleset/lan.txt"),
+            "# private\n192.168.0.0/16\n",
+        )
+        .unwrap();
+        let profile = r#"
+rule-providers:
+  ads: { type: http, behavior: domain, path: ./ruleset/ads.yaml, url: "https://example.com/ads.yaml" }
+  lan: { type: file, behavior: ipcidr, format: text, path: ./ruleset/lan.txt }
+  missing: { type: http, behavior: domain, path: ./ruleset/missing.yaml, url: "https://example.com/m.yaml" }
+  local: { type: inline, behavior: classical, payload: ["DOMAIN-SUFFIX,corp.example", "DST-PORT,3389"] }
+rules:
+  - RULE-SET,missing,X
+  - RULE-SET,ads,REJECT
+  - RULE-SET,lan,DIRECT
+  - RULE-SET,local,CORP
+  - MATCH,PROXY
+"#;
+        let result = run(profile, dir.path(), host("ads.example"));
+        assert_eq!(result.policy, "REJECT");
+        assert_eq!(result.skipped[0].reason, "规则集 missing 未缓存到本地");
+        let result = run(profile, dir.path(), host("a.track.example"));
+        assert_eq!(result.policy, "REJECT");
+        let result = run(profile, dir.path(), host("a.b.track.example"));
+        assert_eq!(result.policy, "PROXY");
+        let result = run(profile, dir.path(), host("192.168.3.4"));
+        assert_eq!(result.policy, "DIRECT");
+        let result = run(profile, dir.path(), host("mail.corp.example"));
+        assert_eq!((result.index, result.policy.as_str()), (Some(3), "CORP"));
+    }
+
+    fn encode_field(number: u64, bytes: &[u8]) -> Vec<u8> {
+        let mut out = vec![((number << 3) | 2) as u8, bytes.len() as u8];
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    #[test]
+    fn test_geosite_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let domain = |kind: u8, value: &str, attr: Option<&str>| {
+            let mut out = vec![0x08, kind];
+            out.extend(encode_field(2, value.as_bytes()));
+            if let Some(attr) = attr {
+                out.extend(encode_field(3, &encode_field(1, attr.as_bytes())));
+            }
+            encode_field(2, &out)
+        };
+        let mut site = encode_field(1, b"GOOGLE");
+        site.extend(domain(2, "google.com", None));
+        site.extend(domain(3, "ads.google.cn", Some("ads")));
+        site.extend(domain(1, r"^\D+\.google\.io$", None));
+        let data = encode_field(1, &site);
+        std::fs::write(dir.path().join("GeoSite.dat"), data).unwrap();
+
+        let profile = "rules:\n  - GEOSITE,google@ads,REJECT\n  - GEOSITE,google,PROXY\n  - GEOSITE,nope,X\n";
+        let result = run(profile, dir.path(), host("ads.google.cn"));
+        assert_eq!(result.policy, "REJECT");
+        let result = run(profile, dir.path(), host("mail.google.com"));
+        assert_eq!((result.index, result.policy.as_str()), (Some(1), "PROXY"));
+        let result = run(profile, dir.path(), host("example.org"));
+        assert_eq!(result.index, None);
+        assert_eq!(result.skipped[0].reason, "GeoSite 中没有 nope");
+        let result = run(profile, dir.path(), host("mail.google.io"));
+        assert_eq!((result.index, result.policy.as_str()), (Some(1), "PROXY"));
+    }
+
+    #[test]
+    fn test_ip_rules_need_an_ip_for_host_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = "rules:\n  - IP-CIDR,10.0.0.0/8,LAN,no-resolve\n  - IP-CIDR,1.0.0.0/8,A\n  - GEOIP,CN,DIRECT\n  - MATCH,PROXY\n";
+        let result = run(profile, dir.path(), host("www.example.com"));
+        assert_eq!((result.index, result.policy.as_str()), (Some(3), "PROXY"));
+        let skipped: Vec<usize> = result.skipped.iter().map(|rule| rule.index).collect();
+        assert_eq!(skipped, vec![1, 2]);
+        assert_eq!(result.skipped[0].reason, "需要解析域名才能判断，请填写目标 IP");
+
+        let query = RuleTestQuery {
+            host: Some("www.example.com".into()),
+            ip: Some("1.2.3.4".parse().unwrap()),
+            ..RuleTestQuery::default()
+        };
+        let result = run(profile, dir.path(), query);
+        assert_eq!((result.index, result.policy.as_str()), (Some(1), "A"));
+    }
+}
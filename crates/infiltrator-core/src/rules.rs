@@ -1,10 +1,16 @@
 use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 
-use anyhow::{anyhow, Context, Result};
-use mihomo_config::ConfigManager;
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail, Context, Result};
+use ipnet::IpNet;
+use mihomo_config::{ConfigManager, RevisionSource};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml::{Mapping, Value};
 
+use crate::overlay;
+
 pub type RuleProviders = BTreeMap<String, serde_json::Value>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,15 +18,68 @@ pub struct RuleProvidersPayload {
     pub providers: RuleProviders,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RuleEntry {
-    pub rule: String,
+    pub rule: Rule,
     pub enabled: bool,
 }
 
+impl RuleEntry {
+    /// Parse an entry sent by a client like [`Rule::parse_lenient`]; a
+    /// disabled entry that does not parse is kept verbatim, as it may be a
+    /// note rather than a rule
+    pub fn parse_lenient(rule: &str, enabled: bool) -> Result<Self> {
+        let rule = match Rule::parse_lenient(rule) {
+            Err(err) if !enabled => Rule::raw(rule, &err),
+            parsed => parsed?,
+        };
+        Ok(Self { rule, enabled })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawRulesPayload")]
 pub struct RulesPayload {
     pub rules: Vec<RuleEntry>,
+    /// Rules kept verbatim because they could not be parsed, with the reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unrecognized: Vec<String>,
+}
+
+impl RulesPayload {
+    pub fn new(rules: Vec<RuleEntry>) -> Self {
+        let unrecognized = unrecognized_rules(&rules);
+        Self { rules, unrecognized }
+    }
+}
+
+/// Wire form of `RulesPayload`, parsed here so errors can name the index
+#[derive(Deserialize)]
+struct RawRulesPayload {
+    rules: Vec<RawRuleEntry>,
+}
+
+#[derive(Deserialize)]
+struct RawRuleEntry {
+    rule: String,
+    enabled: bool,
+}
+
+impl TryFrom<RawRulesPayload> for RulesPayload {
+    type Error = String;
+
+    fn try_from(raw: RawRulesPayload) -> std::result::Result<Self, Self::Error> {
+        let rules = raw
+            .rules
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                RuleEntry::parse_lenient(&entry.rule, entry.enabled)
+                    .map_err(|err| format!("rules[{index}] `{}`: {err}", entry.rule.trim()))
+            })
+            .collect::<std::result::Result<_, String>>()?;
+        Ok(Self::new(rules))
+    }
 }
 
 pub async fn load_rule_providers() -> Result<RuleProviders> {
@@ -37,10 +96,7 @@ pub async fn save_rule_providers(providers: RuleProviders) -> Result<RuleProvide
     apply_rule_providers(&mut doc, &providers)?;
 
     let updated = serde_yaml::to_string(&doc).context("serialize profile yaml")?;
-    manager
-        .save(&profile, &updated)
-        .await
-        .context("save profile config")?;
+    overlay::save_profile_edit(&manager, &profile, &updated, RevisionSource::RulesEdit).await?;
     Ok(providers)
 }
 
@@ -59,10 +115,7 @@ pub async fn save_rules(rules: Vec<RuleEntry>) -> Result<Vec<RuleEntry>> {
     apply_rules(&mut doc, &rules)?;
 
     let updated = serde_yaml::to_string(&doc).context("serialize profile yaml")?;
-    manager
-        .save(&profile, &updated)
-        .await
-        .context("save profile config")?;
+    overlay::save_profile_edit(&manager, &profile, &updated, RevisionSource::RulesEdit).await?;
     Ok(rules)
 }
 
@@ -113,7 +166,573 @@ fn apply_rule_providers(doc: &mut Value, providers: &RuleProviders) -> Result<()
     Ok(())
 }
 
-fn extract_rules(doc: &Value) -> Result<Vec<RuleEntry>> {
+/// Payload-carrying rule types. Structural rules (`AND`/`OR`/`NOT`,
+/// `SUB-RULE`, `MATCH`) are variants of [`Rule`] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleType {
+    Domain,
+    DomainSuffix,
+    DomainKeyword,
+    DomainWildcard,
+    DomainRegex,
+    GeoSite,
+    IpCidr,
+    IpCidr6,
+    IpSuffix,
+    IpAsn,
+    GeoIp,
+    SrcGeoIp,
+    SrcIpAsn,
+    SrcIpCidr,
+    SrcIpSuffix,
+    DstPort,
+    SrcPort,
+    InPort,
+    InType,
+    InUser,
+    InName,
+    ProcessPath,
+    ProcessPathRegex,
+    ProcessName,
+    ProcessNameRegex,
+    Uid,
+    Network,
+    Dscp,
+    RuleSet,
+}
+
+impl RuleType {
+    pub const ALL: [RuleType; 29] = [
+        RuleType::Domain,
+        RuleType::DomainSuffix,
+        RuleType::DomainKeyword,
+        RuleType::DomainWildcard,
+        RuleType::DomainRegex,
+        RuleType::GeoSite,
+        RuleType::IpCidr,
+        RuleType::IpCidr6,
+        RuleType::IpSuffix,
+        RuleType::IpAsn,
+        RuleType::GeoIp,
+        RuleType::SrcGeoIp,
+        RuleType::SrcIpAsn,
+        RuleType::SrcIpCidr,
+        RuleType::SrcIpSuffix,
+        RuleType::DstPort,
+        RuleType::SrcPort,
+        RuleType::InPort,
+        RuleType::InType,
+        RuleType::InUser,
+        RuleType::InName,
+        RuleType::ProcessPath,
+        RuleType::ProcessPathRegex,
+        RuleType::ProcessName,
+        RuleType::ProcessNameRegex,
+        RuleType::Uid,
+        RuleType::Network,
+        RuleType::Dscp,
+        RuleType::RuleSet,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RuleType::Domain => "DOMAIN",
+            RuleType::DomainSuffix => "DOMAIN-SUFFIX",
+            RuleType::DomainKeyword => "DOMAIN-KEYWORD",
+            RuleType::DomainWildcard => "DOMAIN-WILDCARD",
+            RuleType::DomainRegex => "DOMAIN-REGEX",
+            RuleType::GeoSite => "GEOSITE",
+            RuleType::IpCidr => "IP-CIDR",
+            RuleType::IpCidr6 => "IP-CIDR6",
+            RuleType::IpSuffix => "IP-SUFFIX",
+            RuleType::IpAsn => "IP-ASN",
+            RuleType::GeoIp => "GEOIP",
+            RuleType::SrcGeoIp => "SRC-GEOIP",
+            RuleType::SrcIpAsn => "SRC-IP-ASN",
+            RuleType::SrcIpCidr => "SRC-IP-CIDR",
+            RuleType::SrcIpSuffix => "SRC-IP-SUFFIX",
+            RuleType::DstPort => "DST-PORT",
+            RuleType::SrcPort => "SRC-PORT",
+            RuleType::InPort => "IN-PORT",
+            RuleType::InType => "IN-TYPE",
+            RuleType::InUser => "IN-USER",
+            RuleType::InName => "IN-NAME",
+            RuleType::ProcessPath => "PROCESS-PATH",
+            RuleType::ProcessPathRegex => "PROCESS-PATH-REGEX",
+            RuleType::ProcessName => "PROCESS-NAME",
+            RuleType::ProcessNameRegex => "PROCESS-NAME-REGEX",
+            RuleType::Uid => "UID",
+            RuleType::Network => "NETWORK",
+            RuleType::Dscp => "DSCP",
+            RuleType::RuleSet => "RULE-SET",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str().eq_ignore_ascii_case(value))
+    }
+
+    /// Types that take the `no-resolve` and `src` options
+    fn takes_ip_options(self) -> bool {
+        matches!(
+            self,
+            RuleType::IpCidr
+                | RuleType::IpCidr6
+                | RuleType::IpSuffix
+                | RuleType::IpAsn
+                | RuleType::GeoIp
+                | RuleType::RuleSet
+        )
+    }
+
+    fn validate_payload(self, payload: &str) -> Result<()> {
+        match self {
+            RuleType::IpCidr
+            | RuleType::IpCidr6
+            | RuleType::IpSuffix
+            | RuleType::SrcIpCidr
+            | RuleType::SrcIpSuffix => {
+                IpNet::from_str(payload).map_err(|_| anyhow!("invalid CIDR `{payload}`"))?;
+            }
+            RuleType::DomainRegex | RuleType::ProcessPathRegex | RuleType::ProcessNameRegex => {
+                Regex::new(payload).map_err(|err| anyhow!("invalid regex: {err}"))?;
+            }
+            RuleType::DstPort | RuleType::SrcPort | RuleType::InPort => {
+                validate_ranges(payload, u64::from(u16::MAX), "port")?;
+            }
+            RuleType::Uid => validate_ranges(payload, u64::from(u32::MAX), "uid")?,
+            RuleType::Dscp => validate_ranges(payload, 63, "DSCP value")?,
+            RuleType::IpAsn | RuleType::SrcIpAsn => {
+                payload
+                    .parse::<u32>()
+                    .map_err(|_| anyhow!("invalid ASN `{payload}`"))?;
+            }
+            RuleType::Network
+                if !payload.eq_ignore_ascii_case("tcp") && !payload.eq_ignore_ascii_case("udp") =>
+            {
+                bail!("network must be tcp or udp");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RuleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `a/b-c` lists used by port, UID and DSCP rules
+fn validate_ranges(payload: &str, max: u64, what: &str) -> Result<()> {
+    for item in payload.split('/') {
+        let (start, end) = item.split_once('-').unwrap_or((item, item));
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|value| *value <= max)
+                .ok_or_else(|| anyhow!("invalid {what} `{}`", value.trim()))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start > end {
+            bail!("invalid {what} range `{item}`");
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicOp {
+    And,
+    Or,
+    Not,
+}
+
+impl LogicOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogicOp::And => "AND",
+            LogicOp::Or => "OR",
+            LogicOp::Not => "NOT",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "AND" => Some(LogicOp::And),
+            "OR" => Some(LogicOp::Or),
+            "NOT" => Some(LogicOp::Not),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleOptions {
+    pub no_resolve: bool,
+    pub src: bool,
+}
+
+impl RuleOptions {
+    fn parse(params: &[&str]) -> Result<Self> {
+        let mut options = Self::default();
+        for param in params {
+            match param.to_ascii_lowercase().as_str() {
+                "no-resolve" => options.no_resolve = true,
+                "src" => options.src = true,
+                other => bail!("unknown rule option `{other}`"),
+            }
+        }
+        Ok(options)
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.no_resolve && !self.src
+    }
+}
+
+impl fmt::Display for RuleOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.no_resolve {
+            f.write_str(",no-resolve")?;
+        }
+        if self.src {
+            f.write_str(",src")?;
+        }
+        Ok(())
+    }
+}
+
+/// A rule without a target, as nested in logic rules and `SUB-RULE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Simple {
+        kind: RuleType,
+        payload: String,
+        options: RuleOptions,
+    },
+    Logic {
+        op: LogicOp,
+        conditions: Vec<Condition>,
+    },
+}
+
+impl Condition {
+    fn validate(&self) -> Result<()> {
+        match self {
+            Condition::Simple {
+                kind,
+                payload,
+                options,
+            } => validate_simple(*kind, payload, options),
+            Condition::Logic { op, conditions } => validate_logic(*op, conditions),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let parts = split_top(value);
+        if let Some(op) = LogicOp::parse(parts[0]) {
+            let [_, payload] = parts.as_slice() else {
+                bail!("{} condition takes exactly one payload", op.as_str());
+            };
+            return Ok(Condition::Logic {
+                op,
+                conditions: parse_conditions(payload)?,
+            });
+        }
+        let kind = parse_rule_type(parts[0])?;
+        let payload = parts
+            .get(1)
+            .filter(|payload| !payload.is_empty())
+            .ok_or_else(|| anyhow!("{kind} condition is missing its payload"))?;
+        Ok(Condition::Simple {
+            kind,
+            payload: payload.to_string(),
+            options: RuleOptions::parse(&parts[2..])?,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Simple {
+                kind,
+                payload,
+                options,
+            } => write!(f, "{kind},{payload}{options}"),
+            Condition::Logic { op, conditions } => {
+                write!(f, "{},{}", op.as_str(), format_conditions(conditions))
+            }
+        }
+    }
+}
+
+/// One entry of a profile's `rules` list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    Simple {
+        kind: RuleType,
+        payload: String,
+        target: String,
+        options: RuleOptions,
+    },
+    Logic {
+        op: LogicOp,
+        conditions: Vec<Condition>,
+        target: String,
+    },
+    /// Hands matching connections to the named `sub-rules` list
+    SubRule { condition: Condition, name: String },
+    Match { target: String },
+    /// A rule this parser does not understand, e.g. a type added by a newer
+    /// core; written back verbatim
+    Raw { text: String, reason: String },
+}
+
+impl Rule {
+    /// Proxy, group or built-in policy the rule sends traffic to; `None`
+    /// for `SUB-RULE`
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Rule::Simple { target, .. } | Rule::Logic { target, .. } | Rule::Match { target } => {
+                Some(target)
+            }
+            Rule::SubRule { .. } | Rule::Raw { .. } => None,
+        }
+    }
+
+    fn target_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Rule::Simple { target, .. } | Rule::Logic { target, .. } | Rule::Match { target } => {
+                Some(target)
+            }
+            Rule::SubRule { .. } | Rule::Raw { .. } => None,
+        }
+    }
+
+    /// Parse `value`, keeping a rule of an unknown type as [`Rule::Raw`]
+    /// instead of failing
+    pub fn parse_lenient(value: &str) -> Result<Self> {
+        match value.parse::<Rule>() {
+            Err(err) if err.is::<UnknownRuleType>() => Ok(Rule::raw(value, &err)),
+            parsed => parsed,
+        }
+    }
+
+    fn raw(value: &str, err: &anyhow::Error) -> Self {
+        Rule::Raw {
+            text: value.trim().to_string(),
+            reason: err.to_string(),
+        }
+    }
+
+    /// Check the payload of every condition beyond what parsing enforces
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Rule::Simple {
+                kind,
+                payload,
+                options,
+                ..
+            } => validate_simple(*kind, payload, options),
+            Rule::Logic { op, conditions, .. } => validate_logic(*op, conditions),
+            Rule::SubRule { condition, .. } => condition.validate(),
+            Rule::Match { .. } | Rule::Raw { .. } => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            bail!("rule is empty");
+        }
+        let parts = split_top(value);
+        let head = parts[0].to_ascii_uppercase();
+        let target = |index: usize| -> Result<String> {
+            parts
+                .get(index)
+                .filter(|target| !target.is_empty())
+                .map(|target| target.to_string())
+                .ok_or_else(|| anyhow!("{head} rule is missing its target"))
+        };
+
+        if head == "MATCH" || head == "FINAL" {
+            if parts.len() > 2 {
+                bail!("{head} rule takes only a target");
+            }
+            return Ok(Rule::Match { target: target(1)? });
+        }
+        if head == "SUB-RULE" {
+            if parts.len() != 3 {
+                bail!("SUB-RULE must be `SUB-RULE,(condition),name`");
+            }
+            let condition = strip_parens(parts[1])
+                .ok_or_else(|| anyhow!("SUB-RULE condition must be wrapped in parentheses"))?;
+            return Ok(Rule::SubRule {
+                condition: condition.parse()?,
+                name: target(2)?,
+            });
+        }
+        if let Some(op) = LogicOp::parse(&head) {
+            if parts.len() != 3 {
+                bail!("{head} rule must be `{head},((condition),...),target`");
+            }
+            return Ok(Rule::Logic {
+                op,
+                conditions: parse_conditions(parts[1])?,
+                target: target(2)?,
+            });
+        }
+
+        let kind = parse_rule_type(parts[0])?;
+        if parts.get(1).is_none_or(|payload| payload.is_empty()) {
+            bail!("{kind} rule is missing its payload");
+        }
+        Ok(Rule::Simple {
+            kind,
+            payload: parts[1].to_string(),
+            target: target(2)?,
+            options: RuleOptions::parse(&parts[3..])?,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Simple {
+                kind,
+                payload,
+                target,
+                options,
+            } => write!(f, "{kind},{payload},{target}{options}"),
+            Rule::Logic {
+                op,
+                conditions,
+                target,
+            } => write!(f, "{},{},{target}", op.as_str(), format_conditions(conditions)),
+            Rule::SubRule { condition, name } => write!(f, "SUB-RULE,({condition}),{name}"),
+            Rule::Match { target } => write!(f, "MATCH,{target}"),
+            Rule::Raw { text, .. } => f.write_str(text),
+        }
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parse error for a rule type missing from [`RuleType`]
+#[derive(Debug)]
+struct UnknownRuleType(String);
+
+impl fmt::Display for UnknownRuleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown rule type `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownRuleType {}
+
+fn parse_rule_type(value: &str) -> Result<RuleType> {
+    RuleType::parse(value).ok_or_else(|| UnknownRuleType(value.trim().to_string()).into())
+}
+
+/// `((A,x),(B,y))` into its conditions
+fn parse_conditions(payload: &str) -> Result<Vec<Condition>> {
+    let inner = strip_parens(payload)
+        .ok_or_else(|| anyhow!("logic payload must be wrapped in parentheses"))?;
+    if inner.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    split_top(inner)
+        .into_iter()
+        .map(|item| {
+            strip_parens(item)
+                .ok_or_else(|| anyhow!("condition `{item}` must be wrapped in parentheses"))?
+                .parse()
+        })
+        .collect()
+}
+
+fn format_conditions(conditions: &[Condition]) -> String {
+    let items: Vec<String> = conditions
+        .iter()
+        .map(|condition| format!("({condition})"))
+        .collect();
+    format!("({})", items.join(","))
+}
+
+fn validate_simple(kind: RuleType, payload: &str, options: &RuleOptions) -> Result<()> {
+    if !options.is_empty() && !kind.takes_ip_options() {
+        bail!("{kind} does not take no-resolve or src");
+    }
+    kind.validate_payload(payload.trim())
+}
+
+fn validate_logic(op: LogicOp, conditions: &[Condition]) -> Result<()> {
+    match (op, conditions.len()) {
+        (_, 0) => bail!("{} rule has no conditions", op.as_str()),
+        (LogicOp::Not, count) if count > 1 => bail!("NOT rule takes exactly one condition"),
+        _ => {}
+    }
+    for condition in conditions {
+        condition
+            .validate()
+            .with_context(|| format!("condition `{condition}`"))?;
+    }
+    Ok(())
+}
+
+/// Split on commas outside parentheses
+fn split_top(rule: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (index, ch) in rule.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(rule[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(rule[start..].trim());
+    parts
+}
+
+fn strip_parens(value: &str) -> Option<&str> {
+    value.trim().strip_prefix('(')?.strip_suffix(')')
+}
+
+pub(crate) fn extract_rules(doc: &Value) -> Result<Vec<RuleEntry>> {
     let value = match doc.get("rules") {
         Some(v) => v.clone(),
         None => return Ok(Vec::new()),
@@ -122,15 +741,75 @@ fn extract_rules(doc: &Value) -> Result<Vec<RuleEntry>> {
         .as_sequence()
         .ok_or_else(|| anyhow!("rules is not a list"))?;
     let mut rules = Vec::with_capacity(seq.len());
-    for item in seq {
-        if let Some(rule) = item.as_str() {
-            rules.push(parse_rule_entry(rule));
+    for (index, item) in seq.iter().enumerate() {
+        let Some(rule) = item.as_str() else {
+            continue;
+        };
+        let (entry, err) = read_rule_entry(rule);
+        if let Some(err) = err {
+            log::warn!("keep unrecognized rule at rules[{index}] verbatim: {rule}: {err}");
         }
+        rules.push(entry);
     }
     Ok(rules)
 }
 
+/// Parse an entry of `rules`, keeping one that does not parse verbatim so
+/// saving the list writes it back; a commented-out line may just be a note
+fn read_rule_entry(value: &str) -> (RuleEntry, Option<anyhow::Error>) {
+    match parse_rule_entry(value) {
+        Ok(entry) => (entry, None),
+        Err(err) => {
+            let entry = match value.trim_start().strip_prefix('#') {
+                Some(rest) => RuleEntry {
+                    rule: Rule::raw(rest, &err),
+                    enabled: false,
+                },
+                None => RuleEntry {
+                    rule: Rule::raw(value, &err),
+                    enabled: true,
+                },
+            };
+            (entry, Some(err))
+        }
+    }
+}
+
+/// Point every rule that targets `from` at `to`, e.g. after a proxy or
+/// group is renamed. Entries that do not parse are left alone.
+pub(crate) fn rename_rule_targets(doc: &mut Value, from: &str, to: &str) {
+    let rename = |rules: &mut Value| {
+        for item in rules.as_sequence_mut().into_iter().flatten() {
+            let Some(Ok(mut entry)) = item.as_str().map(parse_rule_entry) else {
+                continue;
+            };
+            if let Some(target) = entry.rule.target_mut()
+                && target == from
+            {
+                *target = to.to_string();
+                *item = Value::String(format_rule_entry(&entry));
+            }
+        }
+    };
+    if let Some(rules) = doc.get_mut("rules") {
+        rename(rules);
+    }
+    if let Some(sub_rules) = doc.get_mut("sub-rules").and_then(Value::as_mapping_mut) {
+        sub_rules.values_mut().for_each(rename);
+    }
+}
+
 fn apply_rules(doc: &mut Value, rules: &[RuleEntry]) -> Result<()> {
+    // Rules that were not edited keep their original text, so the overlay
+    // diff still lines them up with the fetched rules
+    let mut originals: Vec<(RuleEntry, String)> = doc
+        .get("rules")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|text| (read_rule_entry(text).0, text.to_string()))
+        .collect();
     let map = doc
         .as_mapping_mut()
         .ok_or_else(|| anyhow!("profile config is not a mapping"))?;
@@ -140,7 +819,13 @@ fn apply_rules(doc: &mut Value, rules: &[RuleEntry]) -> Result<()> {
     }
     let entries: Vec<Value> = rules
         .iter()
-        .map(|entry| Value::String(format_rule_entry(entry)))
+        .map(|entry| {
+            let text = match originals.iter().position(|(original, _)| original == entry) {
+                Some(position) => originals.swap_remove(position).1,
+                None => format_rule_entry(entry),
+            };
+            Value::String(text)
+        })
         .collect();
     map.insert(
         Value::String("rules".to_string()),
@@ -149,35 +834,50 @@ fn apply_rules(doc: &mut Value, rules: &[RuleEntry]) -> Result<()> {
     Ok(())
 }
 
-fn parse_rule_entry(value: &str) -> RuleEntry {
+fn parse_rule_entry(value: &str) -> Result<RuleEntry> {
     let trimmed = value.trim_start();
-    if let Some(rest) = trimmed.strip_prefix('#') {
-        RuleEntry {
-            rule: rest.trim_start().to_string(),
+    match trimmed.strip_prefix('#') {
+        Some(rest) => Ok(RuleEntry {
+            rule: rest.parse()?,
             enabled: false,
-        }
-    } else {
-        RuleEntry {
-            rule: trimmed.to_string(),
+        }),
+        None => Ok(RuleEntry {
+            rule: trimmed.parse()?,
             enabled: true,
-        }
+        }),
     }
 }
 
 fn format_rule_entry(entry: &RuleEntry) -> String {
-    let rule = entry.rule.trim();
     if entry.enabled {
-        rule.to_string()
+        entry.rule.to_string()
     } else {
-        format!("# {rule}")
+        format!("# {}", entry.rule)
     }
 }
 
-fn validate_rules(rules: &[RuleEntry]) -> Result<()> {
-    for entry in rules {
-        if entry.rule.trim().is_empty() {
-            return Err(anyhow!("rule entry is empty"));
-        }
+/// Describe the rules kept verbatim, by index
+pub fn unrecognized_rules(rules: &[RuleEntry]) -> Vec<String> {
+    rules
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| match &entry.rule {
+            // Disabled lines that are not rules are notes, nothing to warn about
+            Rule::Raw { text, reason } if entry.enabled => {
+                Some(format!("rules[{index}] `{text}`: {reason}"))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Validate every rule, naming the first bad one by its index
+pub fn validate_rules(rules: &[RuleEntry]) -> Result<()> {
+    for (index, entry) in rules.iter().enumerate() {
+        entry
+            .rule
+            .validate()
+            .map_err(|err| anyhow!("rules[{index}] `{}`: {err:#}", entry.rule))?;
     }
     Ok(())
 }
@@ -188,20 +888,169 @@ mod tests {
 
     #[test]
     fn test_parse_rule_entry() {
-        let entry = parse_rule_entry("DOMAIN,example.com");
+        let entry = parse_rule_entry("DOMAIN,example.com,DIRECT").unwrap();
         assert!(entry.enabled);
-        let entry = parse_rule_entry("# DIRECT");
+        let entry = parse_rule_entry("# MATCH,DIRECT").unwrap();
         assert!(!entry.enabled);
-        assert_eq!(entry.rule, "DIRECT");
+        assert_eq!(entry.rule, Rule::Match { target: "DIRECT".to_string() });
+        assert!(parse_rule_entry("DOMAIN,example.com").is_err());
     }
 
     #[test]
     fn test_format_rule_entry() {
         let entry = RuleEntry {
-            rule: "DIRECT".to_string(),
+            rule: "MATCH,DIRECT".parse().unwrap(),
             enabled: false,
         };
-        assert_eq!(format_rule_entry(&entry), "# DIRECT");
+        assert_eq!(format_rule_entry(&entry), "# MATCH,DIRECT");
+    }
+
+    #[test]
+    fn test_rule_round_trip() {
+        for rule in [
+            "DOMAIN-SUFFIX,example.com,PROXY",
+            "IP-CIDR,10.0.0.0/8,DIRECT,no-resolve",
+            "GEOIP,CN,DIRECT,no-resolve,src",
+            "RULE-SET,ads,REJECT",
+            "DST-PORT,80/443/8000-9000,PROXY",
+            "AND,((NETWORK,udp),(DST-PORT,443)),REJECT",
+            "OR,((DOMAIN,a.com),(NOT,((DOMAIN-KEYWORD,b)))),PROXY",
+            "SUB-RULE,(AND,((NETWORK,tcp),(IP-CIDR,1.0.0.0/8,no-resolve))),sub",
+            "MATCH,PROXY",
+        ] {
+            let parsed: Rule = rule.parse().unwrap();
+            assert_eq!(parsed.to_string(), rule);
+            parsed.validate().unwrap();
+        }
+        let parsed: Rule = " domain-suffix , example.com , PROXY , NO-RESOLVE ".parse().unwrap();
+        assert_eq!(parsed.to_string(), "DOMAIN-SUFFIX,example.com,PROXY,no-resolve");
+        assert_eq!("FINAL,DIRECT".parse::<Rule>().unwrap().to_string(), "MATCH,DIRECT");
+    }
+
+    #[test]
+    fn test_rule_parse_errors() {
+        for (rule, message) in [
+            ("", "rule is empty"),
+            ("DOMAIN,example.com", "DOMAIN rule is missing its target"),
+            ("HOST,example.com,DIRECT", "unknown rule type `HOST`"),
+            ("DOMAIN,example.com,DIRECT,fast", "unknown rule option `fast`"),
+            ("AND,(NETWORK,udp),DIRECT", "condition `NETWORK` must be wrapped in parentheses"),
+            ("AND,((MATCH,x)),DIRECT", "unknown rule type `MATCH`"),
+        ] {
+            let err = rule.parse::<Rule>().unwrap_err();
+            assert_eq!(err.to_string(), message, "{rule}");
+        }
+    }
+
+    #[test]
+    fn test_validate_rules_reports_index() {
+        let entries = |rules: &[&str]| -> Vec<RuleEntry> {
+            rules
+                .iter()
+                .map(|rule| RuleEntry {
+                    rule: rule.parse().unwrap(),
+                    enabled: true,
+                })
+                .collect()
+        };
+        for (rule, message) in [
+            ("IP-CIDR,10.0.0.0/33,DIRECT", "invalid CIDR `10.0.0.0/33`"),
+            ("DST-PORT,443-80,DIRECT", "invalid port range `443-80`"),
+            ("SRC-PORT,70000,DIRECT", "invalid port `70000`"),
+            ("DOMAIN-REGEX,[a-,DIRECT", "invalid regex"),
+            ("DOMAIN,a.com,DIRECT,no-resolve", "DOMAIN does not take no-resolve or src"),
+            ("NOT,((DOMAIN,a),(DOMAIN,b)),DIRECT", "NOT rule takes exactly one condition"),
+            ("AND,((NETWORK,icmp)),DIRECT", "condition `NETWORK,icmp`: network must be tcp or udp"),
+        ] {
+            let err = validate_rules(&entries(&["MATCH,DIRECT", rule])).unwrap_err();
+            let expected = format!("rules[1] `{rule}`: {message}");
+            assert!(err.to_string().starts_with(&expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_rules_payload_reports_index() {
+        let json = r#"{"rules":[{"rule":"MATCH,DIRECT","enabled":true},{"rule":"DOMAIN,a.com","enabled":true}]}"#;
+        let err = serde_json::from_str::<RulesPayload>(json).unwrap_err();
+        assert!(
+            err.to_string().starts_with("rules[1] `DOMAIN,a.com`: DOMAIN rule is missing its target"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_disabled_notes_round_trip() {
+        let content = "rules:\n  - '#keep ads blocked'\n  - '# DOMAIN,a.com'\n  - '# GEOIP,CN,DIRECT'\n  - MATCH,PROXY\n";
+        let mut doc: Value = serde_yaml::from_str(content).unwrap();
+        let rules = extract_rules(&doc).unwrap();
+        assert_eq!(rules.len(), 4);
+        assert!(rules[..3].iter().all(|entry| !entry.enabled));
+        assert_eq!(rules[0].rule.to_string(), "keep ads blocked");
+        assert!(unrecognized_rules(&rules).is_empty());
+
+        // Saving the list as loaded keeps the notes as written
+        apply_rules(&mut doc, &rules).unwrap();
+        assert_eq!(doc, serde_yaml::from_str::<Value>(content).unwrap());
+
+        // So does a client sending them back
+        let json = serde_json::to_string(&RulesPayload::new(rules.clone())).unwrap();
+        let payload: RulesPayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(payload.rules, rules);
+        validate_rules(&payload.rules).unwrap();
+        apply_rules(&mut doc, &payload.rules).unwrap();
+        assert_eq!(doc, serde_yaml::from_str::<Value>(content).unwrap());
+
+        let json = r#"{"rules":[{"rule":"DOMAIN,a.com","enabled":true}]}"#;
+        assert!(serde_json::from_str::<RulesPayload>(json).is_err());
+    }
+
+    #[test]
+    fn test_unrecognized_rules_pass_through() {
+        let content = "rules:\n  - MATCH,PROXY\n  - BOGUS\n  - NEW-TYPE,x,DIRECT\n";
+        let mut doc: Value = serde_yaml::from_str(content).unwrap();
+        let rules = extract_rules(&doc).unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(
+            unrecognized_rules(&rules),
+            vec![
+                "rules[1] `BOGUS`: unknown rule type `BOGUS`",
+                "rules[2] `NEW-TYPE,x,DIRECT`: unknown rule type `NEW-TYPE`",
+            ]
+        );
+        validate_rules(&rules).unwrap();
+
+        apply_rules(&mut doc, &rules).unwrap();
+        assert_eq!(doc, serde_yaml::from_str::<Value>(content).unwrap());
+
+        let json = r#"{"rules":[{"rule":"AND,((NEW-TYPE,x)),DIRECT","enabled":true}]}"#;
+        let payload: RulesPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.rules[0].rule.to_string(), "AND,((NEW-TYPE,x)),DIRECT");
+        assert_eq!(payload.unrecognized.len(), 1);
+    }
+
+    #[test]
+    fn test_apply_rules_keeps_original_text() {
+        let mut doc: Value =
+            serde_yaml::from_str("rules:\n  - 'domain-suffix, a.com ,PROXY'\n  - FINAL,DIRECT\n").unwrap();
+        let mut rules = extract_rules(&doc).unwrap();
+        rules.insert(0, parse_rule_entry("DOMAIN,b.com,DIRECT").unwrap());
+        apply_rules(&mut doc, &rules).unwrap();
+        assert_eq!(doc["rules"][0], "DOMAIN,b.com,DIRECT");
+        assert_eq!(doc["rules"][1], "domain-suffix, a.com ,PROXY");
+        assert_eq!(doc["rules"][2], "FINAL,DIRECT");
+    }
+
+    #[test]
+    fn test_rename_rule_targets() {
+        let mut doc: Value = serde_yaml::from_str(
+            "rules:\n  - DOMAIN,a.com,hk\n  - '# MATCH,hk'\n  - DOMAIN,hk,DIRECT\nsub-rules:\n  s:\n    - AND,((NETWORK,udp)),hk\n",
+        )
+        .unwrap();
+        rename_rule_targets(&mut doc, "hk", "hk-2");
+        assert_eq!(doc["rules"][0], "DOMAIN,a.com,hk-2");
+        assert_eq!(doc["rules"][1], "# MATCH,hk-2");
+        assert_eq!(doc["rules"][2], "DOMAIN,hk,DIRECT");
+        assert_eq!(doc["sub-rules"]["s"][0], "AND,((NETWORK,udp)),hk-2");
     }
 
     #[test]
@@ -217,7 +1066,7 @@ mod tests {
 
     #[test]
     fn test_apply_rules_empty_removes() {
-        let mut doc: Value = serde_yaml::from_str("rules:\n  - DIRECT\n").expect("yaml");
+        let mut doc: Value = serde_yaml::from_str("rules:\n  - MATCH,DIRECT\n").expect("yaml");
         apply_rules(&mut doc, &[]).expect("apply rules");
         let map = doc.as_mapping().expect("mapping");
         assert!(map.get(Value::String("rules".to_string())).is_none());
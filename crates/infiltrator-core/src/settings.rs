@@ -1,6 +1,7 @@
 use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use sync_engine::policy::ConflictPolicy;
 
 #[derive(Clone, Deserialize, Serialize)]
 #[serde(default)]
@@ -11,6 +12,43 @@ pub struct WebDavConfig {
     pub password: String,
     pub sync_interval_mins: u32,
     pub sync_on_startup: bool,
+    /// How files changed on both sides are settled
+    pub conflict_policy: ConflictPolicy,
+    pub encryption: SyncEncryption,
+    /// Where files are synced to; `url` only applies to WebDAV
+    pub backend: SyncBackend,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum SyncBackend {
+    #[default]
+    #[serde(rename = "webdav")]
+    WebDav,
+    /// A local or mounted directory, e.g. a NAS share or a Syncthing folder
+    Local { path: String },
+    /// S3-compatible object storage; `username` and `password` hold the
+    /// access key pair
+    S3 {
+        endpoint: String,
+        #[serde(default)]
+        region: String,
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+        #[serde(default)]
+        path_style: bool,
+    },
+}
+
+/// Client-side encryption of synced files; the key itself lives in the
+/// credential store, see `sync_crypto`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncEncryption {
+    pub enabled: bool,
+    /// Store files under opaque names instead of `<path>.mfenc`
+    pub obfuscate_names: bool,
 }
 
 impl Default for WebDavConfig {
@@ -22,6 +60,62 @@ impl Default for WebDavConfig {
             password: "".to_string(),
             sync_interval_mins: 60,
             sync_on_startup: false,
+            conflict_policy: ConflictPolicy::default(),
+            encryption: SyncEncryption::default(),
+            backend: SyncBackend::default(),
+        }
+    }
+}
+
+/// How the auto-select engine picks a node inside a selector group.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum SelectionStrategy {
+    /// Always move to the fastest reachable node.
+    #[default]
+    LowestLatency,
+    /// Stay on the current node unless another one is faster by more than
+    /// `tolerance_ms`.
+    Hysteresis { tolerance_ms: u32 },
+    /// Use the first reachable node of `order`; unlisted nodes are only used
+    /// (fastest first) when every listed one is down.
+    Failover { order: Vec<String> },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GroupPolicy {
+    pub group: String,
+    #[serde(default)]
+    pub strategy: SelectionStrategy,
+    /// Regex matched against node names; matching nodes are never selected.
+    #[serde(default)]
+    pub exclude: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AutoSelectConfig {
+    pub enabled: bool,
+    pub interval_mins: u32,
+    pub test_url: String,
+    pub timeout_ms: u32,
+    pub expected_status: Option<String>,
+    /// A manual pick is kept while the policy's choice is at most this much
+    /// faster; beyond that the pick is dropped and the policy takes over.
+    pub manual_margin_ms: u32,
+    pub groups: Vec<GroupPolicy>,
+}
+
+impl Default for AutoSelectConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_mins: 10,
+            test_url: "https://www.gstatic.com/generate_204".to_string(),
+            timeout_ms: 5000,
+            expected_status: None,
+            manual_margin_ms: 200,
+            groups: Vec::new(),
         }
     }
 }
@@ -35,6 +129,7 @@ pub struct AppSettings {
     pub language: String,
     pub theme: String,
     pub webdav: WebDavConfig,
+    pub auto_select: AutoSelectConfig,
 }
 
 impl Default for AppSettings {
@@ -46,6 +141,7 @@ impl Default for AppSettings {
             language: "zh-CN".to_string(),
             theme: "system".to_string(),
             webdav: WebDavConfig::default(),
+            auto_select: AutoSelectConfig::default(),
         }
     }
 }
@@ -115,8 +211,10 @@ mod tests {
         let temp_dir = tempfile::tempdir().unwrap();
         let settings_file = temp_dir.path().join("settings.toml");
         
-        let mut settings = AppSettings::default();
-        settings.language = "en-US".to_string();
+        let mut settings = AppSettings {
+            language: "en-US".to_string(),
+            ..AppSettings::default()
+        };
         settings.webdav.enabled = true;
         
         save_settings(&settings_file, &settings).await.unwrap();
@@ -125,4 +223,37 @@ mod tests {
         assert_eq!(loaded.language, "en-US");
         assert!(loaded.webdav.enabled);
     }
+
+    #[tokio::test]
+    async fn test_auto_select_settings_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let settings_file = temp_dir.path().join("settings.toml");
+
+        let mut settings = AppSettings::default();
+        settings.auto_select.enabled = true;
+        settings.auto_select.groups = vec![
+            GroupPolicy {
+                group: "Proxy".to_string(),
+                strategy: SelectionStrategy::Hysteresis { tolerance_ms: 50 },
+                exclude: Some("(?i)expire|traffic".to_string()),
+            },
+            GroupPolicy {
+                group: "Streaming".to_string(),
+                strategy: SelectionStrategy::Failover {
+                    order: vec!["HK-01".to_string(), "JP-01".to_string()],
+                },
+                exclude: None,
+            },
+        ];
+
+        save_settings(&settings_file, &settings).await.unwrap();
+        let loaded = load_settings(&settings_file).await.unwrap();
+        assert!(loaded.auto_select.enabled);
+        assert_eq!(loaded.auto_select.groups, settings.auto_select.groups);
+
+        let legacy: AppSettings = toml::from_str("language = \"en-US\"").unwrap();
+        assert!(!legacy.auto_select.enabled);
+        assert_eq!(legacy.auto_select.interval_mins, 10);
+        assert_eq!(legacy.auto_select.manual_margin_ms, 200);
+    }
 }
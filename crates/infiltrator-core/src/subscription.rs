@@ -2,13 +2,24 @@ use anyhow::anyhow;
 use log::{info, warn};
 use infiltrator_http::reqwest::header::{ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
 use infiltrator_http::HttpClient;
+use mihomo_config::SubscriptionMetadata;
 use std::io::Read;
 
+const SUBSCRIPTION_USERINFO: &str = "subscription-userinfo";
+const PROFILE_UPDATE_INTERVAL: &str = "profile-update-interval";
+
+/// Decoded subscription body together with the metadata from its headers
+#[derive(Debug, Clone)]
+pub struct SubscriptionText {
+    pub content: String,
+    pub metadata: SubscriptionMetadata,
+}
+
 pub async fn fetch_subscription_text(
     default_client: &HttpClient,
     raw_client: &HttpClient,
     url: &str,
-) -> anyhow::Result<String> {
+) -> anyhow::Result<SubscriptionText> {
     let primary = fetch_subscription_bytes(default_client, url, false).await;
     let response = match primary {
         Ok(response) => response,
@@ -29,7 +40,10 @@ pub async fn fetch_subscription_text(
     };
 
     let content = decode_utf8_text(&bytes)?;
-    Ok(strip_utf8_bom(&content))
+    Ok(SubscriptionText {
+        content: strip_utf8_bom(&content),
+        metadata: response.metadata,
+    })
 }
 
 pub fn strip_utf8_bom(content: &str) -> String {
@@ -56,6 +70,7 @@ pub fn mask_subscription_url(url: &str) -> String {
 struct SubscriptionResponse {
     bytes: Vec<u8>,
     encoding: Option<String>,
+    metadata: SubscriptionMetadata,
     used_raw_client: bool,
 }
 
@@ -81,6 +96,17 @@ async fn fetch_subscription_bytes(
         .get(CONTENT_ENCODING)
         .and_then(|value| value.to_str().ok())
         .map(|value| value.to_string());
+    let header_text = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    };
+    let metadata = SubscriptionMetadata::from_headers(
+        header_text(SUBSCRIPTION_USERINFO).as_deref(),
+        header_text(PROFILE_UPDATE_INTERVAL).as_deref(),
+    );
     let bytes = response.bytes().await?;
     let size = bytes.len();
     info!(
@@ -99,6 +125,7 @@ async fn fetch_subscription_bytes(
     Ok(SubscriptionResponse {
         bytes: bytes.to_vec(),
         encoding,
+        metadata,
         used_raw_client: force_identity,
     })
 }
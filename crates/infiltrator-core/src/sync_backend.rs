@@ -0,0 +1,129 @@
+use anyhow::{bail, Context, Result};
+use dav_client::client::WebDavClient;
+use dav_client::local::LocalDirClient;
+use dav_client::s3::{S3Client, S3Config};
+use dav_client::DavClient;
+
+use crate::settings::{SyncBackend, WebDavConfig};
+use crate::sync_crypto;
+
+pub fn validate(config: &WebDavConfig) -> Result<()> {
+    match &config.backend {
+        SyncBackend::WebDav if config.url.trim().is_empty() => bail!("WebDAV URL is empty"),
+        SyncBackend::Local { path } if path.trim().is_empty() => bail!("sync directory is empty"),
+        SyncBackend::S3 { endpoint, .. } if endpoint.trim().is_empty() => bail!("S3 endpoint is empty"),
+        SyncBackend::S3 { bucket, .. } if bucket.trim().is_empty() => bail!("S3 bucket is empty"),
+        _ => Ok(()),
+    }
+}
+
+/// Client of the configured backend, without encryption
+pub fn connect(config: &WebDavConfig) -> Result<Box<dyn DavClient>> {
+    validate(config)?;
+    Ok(match &config.backend {
+        SyncBackend::WebDav => Box::new(
+            WebDavClient::new(&config.url, &config.username, &config.password)
+                .context("Failed to create WebDAV client")?,
+        ),
+        SyncBackend::Local { path } => Box::new(LocalDirClient::new(path.trim())?),
+        SyncBackend::S3 { endpoint, region, bucket, prefix, path_style } => {
+            Box::new(S3Client::new(S3Config {
+                endpoint: endpoint.trim().to_string(),
+                region: if region.is_empty() { "us-east-1".to_string() } else { region.clone() },
+                bucket: bucket.trim().to_string(),
+                prefix: prefix.clone(),
+                access_key: config.username.clone(),
+                secret_key: config.password.clone(),
+                path_style: *path_style,
+            })?)
+        }
+    })
+}
+
+/// Client of the configured backend, wrapped for end-to-end encryption when
+/// enabled; a missing or mismatched key fails here, before any transfer
+pub async fn open_remote(config: &WebDavConfig) -> Result<Box<dyn DavClient>> {
+    let dav = connect(config)?;
+    if !config.encryption.enabled {
+        // 明文模式下同步加密远端会把密文拉到本地、把明文传到密文旁边
+        if sync_engine::crypto::is_encrypted(&*dav).await? {
+            bail!("remote is end-to-end encrypted; enable sync encryption and set the passphrase");
+        }
+        return Ok(dav);
+    }
+    let encrypted = sync_crypto::open_encrypted(dav, &config.encryption)
+        .await
+        .context("Failed to unlock encrypted sync")?;
+    Ok(Box::new(encrypted))
+}
+
+/// Sync state database for the configured target. Each backend and
+/// encryption mode sees a different set of remote names, so sharing one
+/// state would read the files of another as deleted remotely.
+pub fn state_db_name(config: &WebDavConfig) -> String {
+    let backend = match config.backend {
+        SyncBackend::WebDav => "",
+        SyncBackend::Local { .. } => "_local",
+        SyncBackend::S3 { .. } => "_s3",
+    };
+    let encryption = match (config.encryption.enabled, config.encryption.obfuscate_names) {
+        (false, _) => "",
+        (true, false) => "_e2e",
+        (true, true) => "_e2e_names",
+    };
+    format!("sync_state{backend}{encryption}.db")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::SyncEncryption;
+
+    #[test]
+    fn test_each_target_has_its_own_state() {
+        let mut config = WebDavConfig::default();
+        assert_eq!(state_db_name(&config), "sync_state.db");
+        config.encryption = SyncEncryption { enabled: false, obfuscate_names: true };
+        assert_eq!(state_db_name(&config), "sync_state.db");
+        config.encryption.enabled = true;
+        assert_eq!(state_db_name(&config), "sync_state_e2e_names.db");
+        config.backend = SyncBackend::Local { path: "/mnt/nas".to_string() };
+        config.encryption.obfuscate_names = false;
+        assert_eq!(state_db_name(&config), "sync_state_local_e2e.db");
+    }
+
+    #[test]
+    fn test_backend_settings_and_validation() {
+        let config: WebDavConfig = serde_json::from_str(
+            r#"{"backend":{"type":"s3","endpoint":"http://127.0.0.1:9000","bucket":"sync","path_style":true}}"#,
+        )
+        .unwrap();
+        assert!(matches!(&config.backend, SyncBackend::S3 { bucket, path_style: true, .. } if bucket == "sync"));
+        assert!(connect(&config).is_ok());
+
+        let legacy: WebDavConfig = serde_json::from_str(r#"{"url":"https://dav.example"}"#).unwrap();
+        assert_eq!(legacy.backend, SyncBackend::WebDav);
+        assert_eq!(serde_json::to_value(&legacy.backend).unwrap(), serde_json::json!({"type":"webdav"}));
+
+        let mut local = WebDavConfig { backend: SyncBackend::Local { path: " ".to_string() }, ..WebDavConfig::default() };
+        assert!(validate(&local).is_err());
+        local.backend = SyncBackend::Local { path: "/mnt/nas".to_string() };
+        // The WebDAV URL is not needed for other backends
+        assert!(validate(&local).is_ok());
+        assert!(validate(&WebDavConfig::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_sync_refuses_encrypted_remote() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WebDavConfig {
+            backend: SyncBackend::Local { path: dir.path().to_string_lossy().into_owned() },
+            ..WebDavConfig::default()
+        };
+        assert!(open_remote(&config).await.is_ok());
+
+        std::fs::write(dir.path().join(sync_engine::crypto::KEY_CHECK_FILE), "{}").unwrap();
+        let err = open_remote(&config).await.err().unwrap();
+        assert!(err.to_string().contains("end-to-end encrypted"), "{err}");
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::{anyhow, Result};
+use dav_client::DavClient;
+use mihomo_platform::{CredentialStore, DefaultCredentialStore};
+use sync_engine::crypto::{self, EncryptedDav, SyncKey};
+
+use crate::settings::SyncEncryption;
+
+const SYNC_KEY_SERVICE: &str = "MusicFrog-Despicable-Infiltrator";
+const SYNC_KEY_NAME: &str = "webdav-sync-key";
+
+/// Derive the sync key from `passphrase`, verify it against the remote and
+/// keep it in the credential store
+pub async fn set_passphrase(dav: &dyn DavClient, passphrase: &str) -> Result<()> {
+    let key = crypto::unlock(dav, passphrase).await?;
+    DefaultCredentialStore::default()
+        .set(SYNC_KEY_SERVICE, SYNC_KEY_NAME, &key.export())
+        .await?;
+    Ok(())
+}
+
+pub async fn clear_passphrase() -> Result<()> {
+    DefaultCredentialStore::default()
+        .delete(SYNC_KEY_SERVICE, SYNC_KEY_NAME)
+        .await?;
+    Ok(())
+}
+
+pub async fn has_passphrase() -> bool {
+    matches!(
+        DefaultCredentialStore::default().get(SYNC_KEY_SERVICE, SYNC_KEY_NAME).await,
+        Ok(Some(_))
+    )
+}
+
+/// Wrap `dav` with the stored sync key, failing before any transfer when the
+/// key is missing or no longer matches the remote
+pub async fn open_encrypted<D: DavClient>(dav: D, encryption: &SyncEncryption) -> Result<EncryptedDav<D>> {
+    let encoded = DefaultCredentialStore::default()
+        .get(SYNC_KEY_SERVICE, SYNC_KEY_NAME)
+        .await?
+        .ok_or_else(|| anyhow!("sync passphrase is not set on this device"))?;
+    let key = SyncKey::import(&encoded)?;
+    EncryptedDav::open(dav, key, encryption.obfuscate_names).await
+}
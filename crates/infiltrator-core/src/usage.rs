@@ -0,0 +1,541 @@
+//! Traffic usage ledger
+//!
+//! Stores per-profile, per-day upload/download totals in SQLite and checks
+//! them against optional monthly quotas.
+
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteConnectOptions, SqlitePool};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use mihomo_platform::get_home_dir;
+
+const DEFAULT_WARN_PERCENT: u8 = 80;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, sqlx::FromRow)]
+pub struct DailyUsage {
+    pub day: String,
+    pub upload: i64,
+    pub download: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct UsageTotals {
+    pub upload: u64,
+    pub download: u64,
+}
+
+impl UsageTotals {
+    pub fn total(&self) -> u64 {
+        self.upload + self.download
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UsageQuota {
+    /// Allowed upload + download per calendar month, in bytes.
+    pub monthly_bytes: u64,
+    /// Usage percentage that triggers an early warning; 100% always alerts.
+    #[serde(default = "default_warn_percent")]
+    pub warn_percent: u8,
+}
+
+fn default_warn_percent() -> u8 {
+    DEFAULT_WARN_PERCENT
+}
+
+impl UsageQuota {
+    fn thresholds(&self) -> Vec<u8> {
+        let mut thresholds = vec![self.warn_percent.clamp(1, 100), 100];
+        thresholds.dedup();
+        thresholds
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileUsage {
+    pub profile: String,
+    /// `YYYY-MM`
+    pub month: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    pub quota: Option<UsageQuota>,
+    pub used_percent: Option<f64>,
+}
+
+/// A quota threshold crossed for the first time this month.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct QuotaAlert {
+    pub profile: String,
+    pub month: String,
+    pub threshold: u8,
+    pub used: u64,
+    pub quota: u64,
+}
+
+#[derive(Clone)]
+pub struct UsageLedger {
+    pool: SqlitePool,
+}
+
+impl UsageLedger {
+    pub async fn open(db_path: &Path) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let options =
+            SqliteConnectOptions::from_str(&format!("sqlite:{}", db_path.to_string_lossy()))?
+                .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await?;
+        Self::init(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    /// Open the ledger in the app data directory
+    pub async fn open_default() -> Result<Self> {
+        Self::open(&ledger_path()?).await
+    }
+
+    #[cfg(test)]
+    pub async fn new_in_memory() -> Result<Self> {
+        // Every connection to `:memory:` is a separate database.
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")?;
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        Self::init(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    async fn init(pool: &SqlitePool) -> Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS usage_daily (
+                profile TEXT NOT NULL,
+                day TEXT NOT NULL,
+                upload INTEGER NOT NULL DEFAULT 0,
+                download INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (profile, day)
+            )",
+        )
+        .execute(pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS usage_quota (
+                profile TEXT PRIMARY KEY,
+                monthly_bytes INTEGER NOT NULL,
+                warn_percent INTEGER NOT NULL
+            )",
+        )
+        .execute(pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS usage_alerts (
+                profile TEXT NOT NULL,
+                month TEXT NOT NULL,
+                threshold INTEGER NOT NULL,
+                alerted_at DATETIME NOT NULL,
+                PRIMARY KEY (profile, month, threshold)
+            )",
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn add_usage(
+        &self,
+        profile: &str,
+        day: NaiveDate,
+        upload: u64,
+        download: u64,
+    ) -> Result<()> {
+        if upload == 0 && download == 0 {
+            return Ok(());
+        }
+        sqlx::query(
+            "INSERT INTO usage_daily (profile, day, upload, download)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(profile, day) DO UPDATE SET
+                upload = upload + excluded.upload,
+                download = download + excluded.download",
+        )
+        .bind(profile)
+        .bind(day_key(day))
+        .bind(to_i64(upload))
+        .bind(to_i64(download))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn daily_usage(
+        &self,
+        profile: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DailyUsage>> {
+        let rows = sqlx::query_as::<_, DailyUsage>(
+            "SELECT day, upload, download FROM usage_daily
+             WHERE profile = ? AND day >= ? AND day <= ?
+             ORDER BY day",
+        )
+        .bind(profile)
+        .bind(day_key(from))
+        .bind(day_key(to))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Totals of the calendar month containing `day`
+    pub async fn month_usage(&self, profile: &str, day: NaiveDate) -> Result<UsageTotals> {
+        let (first, last) = month_bounds(day);
+        let (upload, download): (i64, i64) = sqlx::query_as(
+            "SELECT COALESCE(SUM(upload), 0), COALESCE(SUM(download), 0) FROM usage_daily
+             WHERE profile = ? AND day >= ? AND day <= ?",
+        )
+        .bind(profile)
+        .bind(day_key(first))
+        .bind(day_key(last))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(UsageTotals {
+            upload: upload.max(0) as u64,
+            download: download.max(0) as u64,
+        })
+    }
+
+    /// Usage of every profile that has traffic or a quota in the month of `day`
+    pub async fn month_summary(&self, day: NaiveDate) -> Result<Vec<ProfileUsage>> {
+        let (first, last) = month_bounds(day);
+        let profiles: Vec<(String,)> = sqlx::query_as(
+            "SELECT profile FROM usage_daily WHERE day >= ? AND day <= ?
+             UNION SELECT profile FROM usage_quota
+             ORDER BY profile",
+        )
+        .bind(day_key(first))
+        .bind(day_key(last))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut summary = Vec::with_capacity(profiles.len());
+        for (profile,) in profiles {
+            summary.push(self.profile_usage(&profile, day).await?);
+        }
+        Ok(summary)
+    }
+
+    pub async fn profile_usage(&self, profile: &str, day: NaiveDate) -> Result<ProfileUsage> {
+        let totals = self.month_usage(profile, day).await?;
+        let quota = self.get_quota(profile).await?;
+        let used_percent = quota
+            .filter(|quota| quota.monthly_bytes > 0)
+            .map(|quota| totals.total() as f64 * 100.0 / quota.monthly_bytes as f64);
+        Ok(ProfileUsage {
+            profile: profile.to_string(),
+            month: month_key(day),
+            totals,
+            quota,
+            used_percent,
+        })
+    }
+
+    pub async fn get_quota(&self, profile: &str) -> Result<Option<UsageQuota>> {
+        let row: Option<(i64, i64)> = sqlx::query_as(
+            "SELECT monthly_bytes, warn_percent FROM usage_quota WHERE profile = ?",
+        )
+        .bind(profile)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|(monthly_bytes, warn_percent)| UsageQuota {
+            monthly_bytes: monthly_bytes.max(0) as u64,
+            warn_percent: warn_percent.clamp(0, 100) as u8,
+        }))
+    }
+
+    /// Set or clear (`None`) the monthly quota of a profile
+    pub async fn set_quota(&self, profile: &str, quota: Option<UsageQuota>) -> Result<()> {
+        match quota {
+            Some(quota) => {
+                if quota.monthly_bytes == 0 {
+                    return Err(anyhow!("流量配额必须大于 0"));
+                }
+                if quota.warn_percent == 0 || quota.warn_percent > 100 {
+                    return Err(anyhow!("提醒阈值必须在 1-100 之间"));
+                }
+                sqlx::query(
+                    "INSERT INTO usage_quota (profile, monthly_bytes, warn_percent)
+                     VALUES (?, ?, ?)
+                     ON CONFLICT(profile) DO UPDATE SET
+                        monthly_bytes = excluded.monthly_bytes,
+                        warn_percent = excluded.warn_percent",
+                )
+                .bind(profile)
+                .bind(to_i64(quota.monthly_bytes))
+                .bind(quota.warn_percent as i64)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM usage_quota WHERE profile = ?")
+                    .bind(profile)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        // A changed quota re-arms the alerts of the current period.
+        sqlx::query("DELETE FROM usage_alerts WHERE profile = ?")
+            .bind(profile)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Thresholds of `profile`'s quota crossed for the first time this
+    /// month. Each threshold is reported once per month.
+    pub async fn check_quota(&self, profile: &str, day: NaiveDate) -> Result<Vec<QuotaAlert>> {
+        let Some(quota) = self.get_quota(profile).await? else {
+            return Ok(Vec::new());
+        };
+        let used = self.month_usage(profile, day).await?.total();
+        let month = month_key(day);
+
+        let mut alerts = Vec::new();
+        for threshold in quota.thresholds() {
+            let limit = quota.monthly_bytes as u128 * threshold as u128 / 100;
+            if (used as u128) < limit {
+                continue;
+            }
+            let inserted = sqlx::query(
+                "INSERT OR IGNORE INTO usage_alerts (profile, month, threshold, alerted_at)
+                 VALUES (?, ?, ?, ?)",
+            )
+            .bind(profile)
+            .bind(&month)
+            .bind(threshold as i64)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+            if inserted > 0 {
+                alerts.push(QuotaAlert {
+                    profile: profile.to_string(),
+                    month: month.clone(),
+                    threshold,
+                    used,
+                    quota: quota.monthly_bytes,
+                });
+            }
+        }
+        Ok(alerts)
+    }
+}
+
+/// Bytes counted since the last flush into the ledger
+#[derive(Clone, Default)]
+pub struct UsageAccumulator {
+    pending: Arc<Mutex<UsageTotals>>,
+    last_seen: Arc<Mutex<Option<UsageTotals>>>,
+}
+
+impl UsageAccumulator {
+    pub fn add(&self, upload: u64, download: u64) {
+        let mut guard = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard.upload += upload;
+        guard.download += download;
+    }
+
+    /// Count the growth of the core's cumulative `uploadTotal`/`downloadTotal`
+    /// since the previous snapshot. The first snapshot only sets the baseline;
+    /// a counter that goes backwards means the core restarted from zero.
+    pub fn record_totals(&self, upload_total: u64, download_total: u64) {
+        let current = UsageTotals {
+            upload: upload_total,
+            download: download_total,
+        };
+        let previous = self
+            .last_seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .replace(current);
+        let Some(previous) = previous else {
+            return;
+        };
+        let delta = |now: u64, before: u64| now.checked_sub(before).unwrap_or(now);
+        self.add(
+            delta(current.upload, previous.upload),
+            delta(current.download, previous.download),
+        );
+    }
+
+    pub fn take(&self) -> UsageTotals {
+        let mut guard = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::mem::take(&mut *guard)
+    }
+}
+
+/// Get the path to the usage ledger database
+pub fn ledger_path() -> Result<PathBuf> {
+    let home = get_home_dir()?;
+    Ok(home.join("usage.db"))
+}
+
+/// Parse a `YYYY-MM` month into its first day
+pub fn parse_month(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", value.trim()), "%Y-%m-%d")
+        .map_err(|_| anyhow!("无效的月份: {value}，应为 YYYY-MM"))
+}
+
+pub fn month_key(day: NaiveDate) -> String {
+    day.format("%Y-%m").to_string()
+}
+
+fn day_key(day: NaiveDate) -> String {
+    day.format("%Y-%m-%d").to_string()
+}
+
+fn month_bounds(day: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first = day.with_day(1).unwrap_or(day);
+    let next = if first.month() == 12 {
+        NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+    };
+    let last = next.and_then(|next| next.pred_opt()).unwrap_or(first);
+    (first, last)
+}
+
+fn to_i64(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_add_usage_accumulates_per_day() {
+        let ledger = UsageLedger::new_in_memory().await.unwrap();
+        ledger.add_usage("work", day(2026, 10, 1), 100, 200).await.unwrap();
+        ledger.add_usage("work", day(2026, 10, 1), 1, 2).await.unwrap();
+        ledger.add_usage("work", day(2026, 10, 2), 10, 20).await.unwrap();
+        ledger.add_usage("work", day(2026, 11, 1), 5, 5).await.unwrap();
+        ledger.add_usage("home", day(2026, 10, 1), 7, 7).await.unwrap();
+
+        let daily = ledger
+            .daily_usage("work", day(2026, 10, 1), day(2026, 10, 31))
+            .await
+            .unwrap();
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily[0].upload, 101);
+        assert_eq!(daily[0].download, 202);
+
+        let month = ledger.month_usage("work", day(2026, 10, 15)).await.unwrap();
+        assert_eq!(month, UsageTotals { upload: 111, download: 222 });
+
+        let summary = ledger.month_summary(day(2026, 10, 1)).await.unwrap();
+        let names: Vec<&str> = summary.iter().map(|p| p.profile.as_str()).collect();
+        assert_eq!(names, vec!["home", "work"]);
+    }
+
+    #[tokio::test]
+    async fn test_quota_alerts_fire_once_per_threshold() {
+        let ledger = UsageLedger::new_in_memory().await.unwrap();
+        let today = day(2026, 10, 17);
+        ledger
+            .set_quota("work", Some(UsageQuota { monthly_bytes: 1000, warn_percent: 80 }))
+            .await
+            .unwrap();
+
+        ledger.add_usage("work", today, 400, 350).await.unwrap();
+        assert!(ledger.check_quota("work", today).await.unwrap().is_empty());
+
+        ledger.add_usage("work", today, 50, 0).await.unwrap();
+        let alerts = ledger.check_quota("work", today).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].threshold, 80);
+        assert!(ledger.check_quota("work", today).await.unwrap().is_empty());
+
+        ledger.add_usage("work", today, 0, 300).await.unwrap();
+        let alerts = ledger.check_quota("work", today).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].threshold, 100);
+        assert_eq!(alerts[0].used, 1100);
+
+        // Next month starts over.
+        ledger.add_usage("work", day(2026, 11, 1), 900, 0).await.unwrap();
+        let alerts = ledger.check_quota("work", day(2026, 11, 1)).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].month, "2026-11");
+
+        let usage = ledger.profile_usage("work", today).await.unwrap();
+        assert_eq!(usage.used_percent.map(|p| p.round() as u32), Some(110));
+    }
+
+    #[tokio::test]
+    async fn test_set_quota_validation_and_clear() {
+        let ledger = UsageLedger::new_in_memory().await.unwrap();
+        assert!(ledger
+            .set_quota("p", Some(UsageQuota { monthly_bytes: 0, warn_percent: 80 }))
+            .await
+            .is_err());
+        assert!(ledger
+            .set_quota("p", Some(UsageQuota { monthly_bytes: 1, warn_percent: 0 }))
+            .await
+            .is_err());
+        ledger
+            .set_quota("p", Some(UsageQuota { monthly_bytes: 10, warn_percent: 50 }))
+            .await
+            .unwrap();
+        assert_eq!(ledger.get_quota("p").await.unwrap().unwrap().monthly_bytes, 10);
+        ledger.set_quota("p", None).await.unwrap();
+        assert!(ledger.get_quota("p").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_month_helpers() {
+        assert_eq!(month_bounds(day(2026, 12, 5)), (day(2026, 12, 1), day(2026, 12, 31)));
+        assert_eq!(month_bounds(day(2028, 2, 10)).1, day(2028, 2, 29));
+        assert_eq!(parse_month("2026-10").unwrap(), day(2026, 10, 1));
+        assert!(parse_month("2026-13").is_err());
+        assert_eq!(month_key(day(2026, 1, 31)), "2026-01");
+    }
+
+    #[test]
+    fn test_accumulator_take_resets() {
+        let acc = UsageAccumulator::default();
+        acc.add(1, 2);
+        acc.add(3, 4);
+        assert_eq!(acc.take(), UsageTotals { upload: 4, download: 6 });
+        assert_eq!(acc.take(), UsageTotals::default());
+    }
+
+    #[test]
+    fn test_accumulator_records_total_deltas() {
+        let acc = UsageAccumulator::default();
+        acc.record_totals(500, 1000);
+        assert_eq!(acc.take(), UsageTotals::default());
+
+        acc.record_totals(600, 1500);
+        acc.record_totals(650, 1500);
+        assert_eq!(acc.take(), UsageTotals { upload: 150, download: 500 });
+
+        // The core restarted and its counters began again from zero.
+        acc.record_totals(20, 30);
+        assert_eq!(acc.take(), UsageTotals { upload: 20, download: 30 });
+    }
+}
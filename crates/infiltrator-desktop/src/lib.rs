@@ -6,4 +6,5 @@ pub mod version;
 
 pub use proxy::SystemProxyState;
 pub use runtime::{MihomoRuntime, MihomoSummary};
+pub use mihomo_platform::SupervisorEvent;
 pub use service::{ServiceManager, ServiceStatus};
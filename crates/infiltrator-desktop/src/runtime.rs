@@ -1,11 +1,15 @@
 use anyhow::anyhow;
-use mihomo_api::{MihomoClient, ProxyGroup, ProxyManager};
-use mihomo_config::ConfigManager;
+use infiltrator_core::{overlay, proxy_selection};
+use mihomo_api::{MihomoClient, MihomoError, ProxyGroup, ProxyManager};
+use mihomo_config::{ConfigManager, RevisionSource};
+use mihomo_platform::SupervisorEvent;
 use mihomo_version::VersionManager;
 use reqwest::{header::ACCEPT_ENCODING, Client};
 use serde::Serialize;
 use serde_json::json;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::broadcast;
 use yaml_rust2::{Yaml, YamlLoader};
 
 use crate::service::{ServiceManager, ServiceStatus};
@@ -17,6 +21,8 @@ pub struct MihomoRuntime {
     pub controller_url: String,
     client: MihomoClient,
     service_manager: ServiceManager,
+    /// 内核当前运行的配置内容（启动或热更新后），预检失败时据此还原
+    loaded_config: Mutex<Option<String>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -48,11 +54,16 @@ impl MihomoRuntime {
         let service_manager = ServiceManager::new(binary, config_path.clone());
 
         if !service_manager.is_running().await {
+            service_manager
+                .test_config(&config_path)
+                .await
+                .map_err(map_config_test_error)?;
             log::info!("Starting mihomo service");
             service_manager.start().await?;
         }
 
         let client = MihomoClient::new(&controller_url, None)?;
+        let loaded_config = Mutex::new(tokio::fs::read_to_string(&config_path).await.ok());
 
         Ok(Self {
             config_manager: cm,
@@ -60,13 +71,65 @@ impl MihomoRuntime {
             controller_url,
             client,
             service_manager,
+            loaded_config,
         })
     }
 
+    /// Record content the core has picked up without a restart, e.g. by a
+    /// config patch or reload
+    pub fn set_loaded_config(&self, content: String) {
+        if let Ok(mut loaded) = self.loaded_config.lock() {
+            *loaded = Some(content);
+        }
+    }
+
+    /// Save back the config the running core uses, undoing a candidate that
+    /// failed preflight. It goes through the overlay so the rejected edit is
+    /// not rendered into the next subscription update either.
+    pub async fn restore_loaded_config(&self) -> anyhow::Result<()> {
+        let Some(loaded) = self.loaded_config.lock().ok().and_then(|loaded| loaded.clone()) else {
+            return Ok(());
+        };
+        let Some(profile) = self.config_path.file_stem().and_then(|stem| stem.to_str()) else {
+            return Ok(());
+        };
+        if tokio::fs::read_to_string(&self.config_path).await.ok().as_deref() != Some(loaded.as_str()) {
+            overlay::save_profile_edit(&self.config_manager, profile, &loaded, RevisionSource::Restore)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Check the current profile with the binary that would run it, so a
+    /// rebuild can be abandoned before the running core is stopped.
+    pub async fn preflight(
+        vm: &VersionManager,
+        use_bundled: bool,
+        bundled_candidates: &[PathBuf],
+        data_dir: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        let cm = ConfigManager::new()?;
+        let config_path = cm.get_current_path().await?;
+        let binary =
+            version::resolve_binary(vm, use_bundled, bundled_candidates, data_dir).await?;
+        let geoip_candidates = collect_geoip_candidates(&binary, bundled_candidates);
+        ensure_geoip_database(&config_path, &geoip_candidates).await?;
+        ServiceManager::new(binary, config_path.clone())
+            .test_config(&config_path)
+            .await
+            .map_err(map_config_test_error)?;
+        Ok(config_path)
+    }
+
     pub fn client(&self) -> MihomoClient {
         self.client.clone()
     }
 
+    /// Crash and restart notifications for the core this runtime started
+    pub fn subscribe_core_events(&self) -> broadcast::Receiver<SupervisorEvent> {
+        self.service_manager.subscribe()
+    }
+
     pub async fn summary(&self) -> anyhow::Result<MihomoSummary> {
         let profile = self.config_manager.get_current().await?;
         let mode = self.read_mode(&profile).await?;
@@ -107,7 +170,20 @@ impl MihomoRuntime {
         self.client
             .switch_proxy(group, proxy)
             .await
-            .map_err(|err| anyhow!(err.to_string()))
+            .map_err(|err| anyhow!(err.to_string()))?;
+        let profile = self.config_manager.get_current().await?;
+        if let Err(err) = proxy_selection::record_selection(&profile, group, proxy) {
+            log::warn!("failed to remember proxy selection: {err:#}");
+        }
+        Ok(())
+    }
+
+    /// Re-apply the selections remembered for the current profile. Call once
+    /// the controller answers; a fresh core starts every selector on its
+    /// first member.
+    pub async fn restore_proxy_selections(&self) -> anyhow::Result<proxy_selection::RestoreReport> {
+        let profile = self.config_manager.get_current().await?;
+        proxy_selection::restore_selections(&self.client, &profile).await
     }
 
     pub async fn set_tun_enabled(&self, enabled: bool) -> anyhow::Result<()> {
@@ -144,6 +220,13 @@ impl MihomoRuntime {
     }
 }
 
+fn map_config_test_error(err: MihomoError) -> anyhow::Error {
+    match err {
+        MihomoError::Config(message) => anyhow!("内核配置检查失败: {message}"),
+        other => anyhow!(other.to_string()),
+    }
+}
+
 fn normalize_mode(mode: &str) -> String {
     let trimmed = mode.trim();
     if trimmed.is_empty() {
@@ -1,11 +1,16 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use mihomo_api::Result;
-use mihomo_platform::{CoreController, ProcessCoreController};
+use mihomo_platform::{CoreController, CoreState, ProcessCoreController, SupervisorEvent};
+use tokio::sync::broadcast;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ServiceStatus {
     Running(u32),
+    /// Waiting out the backoff before the given restart attempt
+    Restarting(u32),
+    /// Crash loop limit reached; carries the last exit reason
+    Crashed(String),
     Stopped,
 }
 
@@ -48,7 +53,22 @@ impl ServiceManager {
         self.start().await
     }
 
+    /// Let the core binary check `config_path` without starting it
+    pub async fn test_config(&self, config_path: &Path) -> Result<()> {
+        self.controller.test_config(config_path).await
+    }
+
+    /// Core lifecycle events from the process supervisor
+    pub fn subscribe(&self) -> broadcast::Receiver<SupervisorEvent> {
+        self.controller.subscribe()
+    }
+
     pub async fn status(&self) -> Result<ServiceStatus> {
+        match self.controller.core_state().await {
+            Some(CoreState::Restarting { attempt }) => return Ok(ServiceStatus::Restarting(attempt)),
+            Some(CoreState::Crashed(reason)) => return Ok(ServiceStatus::Crashed(reason)),
+            _ => {}
+        }
         if self.is_running().await {
             let pid = self.controller.pid().await.unwrap_or(0);
             Ok(ServiceStatus::Running(pid))
@@ -73,10 +93,13 @@ mod tests {
         let status2 = ServiceStatus::Running(1234);
         let status3 = ServiceStatus::Running(5678);
         let status4 = ServiceStatus::Stopped;
+        let status5 = ServiceStatus::Crashed("exit status: 1".to_string());
 
         assert_eq!(status1, status2);
         assert_ne!(status1, status3);
         assert_ne!(status1, status4);
+        assert_ne!(status4, status5);
+        assert_ne!(ServiceStatus::Restarting(1), ServiceStatus::Restarting(2));
     }
 
     #[test]
@@ -1,11 +1,10 @@
-use crate::error::Result;
+use crate::error::{MihomoError, Result};
 use crate::types::*;
-use futures_util::StreamExt;
+use crate::stream::{parse_json, spawn_stream, StreamOptions, StreamReceiver, StreamTarget};
 use reqwest::Client;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::time::Duration;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
 use url::Url;
 
 #[derive(Clone)]
@@ -45,6 +44,32 @@ impl MihomoClient {
         Ok(url)
     }
 
+    fn build_url_segments(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| MihomoError::Config(format!("invalid base url: {}", self.base_url)))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response> {
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        let body = resp.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|v| v.get("message").and_then(|m| m.as_str()).map(str::to_string))
+            .unwrap_or(body);
+        if status == reqwest::StatusCode::NOT_FOUND {
+            Err(MihomoError::NotFound(message))
+        } else {
+            Err(MihomoError::Service(format!("HTTP {}: {}", status.as_u16(), message)))
+        }
+    }
+
     fn add_auth(&self, mut req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
         if let Some(secret) = &self.secret {
             req = req.bearer_auth(secret);
@@ -117,6 +142,37 @@ impl MihomoClient {
         Ok(data.delay)
     }
 
+    /// Like [`Self::test_delay`], but passes the core an `expected` status
+    /// range (e.g. `204` or `200-299`) and reports timeouts as errors instead
+    /// of a JSON decode failure.
+    pub async fn test_delay_expected(
+        &self,
+        proxy: &str,
+        test_url: &str,
+        timeout: u32,
+        expected: Option<&str>,
+    ) -> Result<u32> {
+        let mut url = self.build_url_segments(&["proxies", proxy, "delay"])?;
+        {
+            let mut pairs = url.query_pairs_mut();
+            pairs
+                .append_pair("timeout", &timeout.to_string())
+                .append_pair("url", test_url);
+            if let Some(expected) = expected {
+                pairs.append_pair("expected", expected);
+            }
+        }
+        // The core waits up to `timeout` itself; leave headroom over the client default.
+        let req = self
+            .client
+            .get(url)
+            .timeout(Duration::from_millis(timeout as u64 + 2000));
+        let req = self.add_auth(req);
+        let resp = Self::check_status(req.send().await?).await?;
+        let data: DelayTestResponse = resp.json().await?;
+        Ok(data.delay)
+    }
+
     pub async fn reload_config(&self, path: Option<&str>) -> Result<()> {
         let url = self.build_url_with_query("/configs", &[("force", "true".to_string())])?;
         let mut req = self.client.put(url);
@@ -124,7 +180,7 @@ impl MihomoClient {
             req = req.json(&json!({ "path": path }));
         }
         let req = self.add_auth(req);
-        req.send().await?;
+        Self::check_status(req.send().await?).await?;
         Ok(())
     }
 
@@ -132,87 +188,151 @@ impl MihomoClient {
         let url = self.build_url("/configs")?;
         let req = self.client.patch(url).json(&updates);
         let req = self.add_auth(req);
-        req.send().await?;
+        Self::check_status(req.send().await?).await?;
+        Ok(())
+    }
+
+    pub async fn get_proxy_providers(&self) -> Result<HashMap<String, ProxyProvider>> {
+        let url = self.build_url("/providers/proxies")?;
+        let req = self.add_auth(self.client.get(url));
+        let resp = Self::check_status(req.send().await?).await?;
+        let data: ProvidersResponse<ProxyProvider> = resp.json().await?;
+        log::debug!("Received {} proxy providers", data.providers.len());
+        Ok(data.providers)
+    }
+
+    pub async fn get_proxy_provider(&self, name: &str) -> Result<ProxyProvider> {
+        let url = self.build_url_segments(&["providers", "proxies", name])?;
+        let req = self.add_auth(self.client.get(url));
+        let resp = Self::check_status(req.send().await?).await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn update_proxy_provider(&self, name: &str) -> Result<()> {
+        let url = self.build_url_segments(&["providers", "proxies", name])?;
+        log::debug!("Updating proxy provider '{}'", name);
+        let req = self.add_auth(self.client.put(url));
+        Self::check_status(req.send().await?).await?;
         Ok(())
     }
 
-    pub async fn stream_logs(
+    pub async fn healthcheck_proxy_provider(&self, name: &str) -> Result<()> {
+        let url = self.build_url_segments(&["providers", "proxies", name, "healthcheck"])?;
+        log::debug!("Health checking proxy provider '{}'", name);
+        let req = self.add_auth(self.client.get(url));
+        Self::check_status(req.send().await?).await?;
+        Ok(())
+    }
+
+    pub async fn get_rule_providers(&self) -> Result<HashMap<String, RuleProvider>> {
+        let url = self.build_url("/providers/rules")?;
+        let req = self.add_auth(self.client.get(url));
+        let resp = Self::check_status(req.send().await?).await?;
+        let data: ProvidersResponse<RuleProvider> = resp.json().await?;
+        log::debug!("Received {} rule providers", data.providers.len());
+        Ok(data.providers)
+    }
+
+    pub async fn update_rule_provider(&self, name: &str) -> Result<()> {
+        let url = self.build_url_segments(&["providers", "rules", name])?;
+        log::debug!("Updating rule provider '{}'", name);
+        let req = self.add_auth(self.client.put(url));
+        Self::check_status(req.send().await?).await?;
+        Ok(())
+    }
+
+    /// Tests every member of `group` on the core side and returns the reachable ones.
+    pub async fn test_group_delay(
         &self,
-        level: Option<&str>,
-    ) -> Result<tokio::sync::mpsc::UnboundedReceiver<String>> {
-        let mut ws_url = self.base_url.clone();
-        ws_url
-            .set_scheme(if ws_url.scheme() == "https" {
-                "wss"
-            } else {
-                "ws"
-            })
-            .ok();
-        ws_url.set_path("/logs");
-        if let Some(level) = level {
-            ws_url.set_query(Some(&format!("level={}", level)));
+        group: &str,
+        test_url: &str,
+        timeout: u32,
+    ) -> Result<HashMap<String, u32>> {
+        let mut url = self.build_url_segments(&["group", group, "delay"])?;
+        url.query_pairs_mut()
+            .append_pair("url", test_url)
+            .append_pair("timeout", &timeout.to_string());
+        let req = self.add_auth(self.client.get(url));
+        let resp = Self::check_status(req.send().await?).await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn get_rules(&self) -> Result<Vec<RuleInfo>> {
+        let url = self.build_url("/rules")?;
+        let req = self.add_auth(self.client.get(url));
+        let resp = Self::check_status(req.send().await?).await?;
+        let data: RulesResponse = resp.json().await?;
+        Ok(data.rules)
+    }
+
+    pub async fn dns_query(&self, name: &str, record_type: Option<&str>) -> Result<DnsQueryResponse> {
+        let mut params = vec![("name", name.to_string())];
+        if let Some(record_type) = record_type {
+            params.push(("type", record_type.to_string()));
         }
+        let url = self.build_url_with_query("/dns/query", &params)?;
+        let req = self.add_auth(self.client.get(url));
+        let resp = Self::check_status(req.send().await?).await?;
+        Ok(resp.json().await?)
+    }
 
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        let ws_url_str = ws_url.to_string();
+    pub async fn flush_fakeip_cache(&self) -> Result<()> {
+        let url = self.build_url("/cache/fakeip/flush")?;
+        let req = self.add_auth(self.client.post(url));
+        Self::check_status(req.send().await?).await?;
+        Ok(())
+    }
 
-        tokio::spawn(async move {
-            if let Ok((ws_stream, _)) = connect_async(&ws_url_str).await {
-                let (_, mut read) = ws_stream.split();
-                while let Some(msg) = read.next().await {
-                    match msg {
-                        Ok(Message::Text(text)) => {
-                            if tx.send(text.to_string()).is_err() {
-                                break;
-                            }
-                        }
-                        Ok(Message::Close(_)) => break,
-                        Err(_) => break,
-                        _ => {}
-                    }
-                }
-            }
-        });
+    pub async fn restart(&self) -> Result<()> {
+        let url = self.build_url("/restart")?;
+        let req = self.add_auth(self.client.post(url).json(&json!({ "path": "" })));
+        Self::check_status(req.send().await?).await?;
+        Ok(())
+    }
 
-        Ok(rx)
+    pub async fn upgrade_geo(&self) -> Result<()> {
+        let url = self.build_url("/upgrade/geo")?;
+        let req = self.add_auth(self.client.post(url));
+        Self::check_status(req.send().await?).await?;
+        Ok(())
     }
 
-    pub async fn stream_traffic(
-        &self,
-    ) -> Result<tokio::sync::mpsc::UnboundedReceiver<TrafficData>> {
-        let mut ws_url = self.base_url.clone();
-        ws_url
-            .set_scheme(if ws_url.scheme() == "https" {
-                "wss"
-            } else {
-                "ws"
-            })
+    fn stream_target(&self, path: &str, query: Option<String>) -> StreamTarget {
+        let mut url = self.base_url.clone();
+        url.set_scheme(if url.scheme() == "https" { "wss" } else { "ws" })
             .ok();
-        ws_url.set_path("/traffic");
+        url.set_path(path);
+        url.set_query(query.as_deref());
+        StreamTarget {
+            url,
+            secret: self.secret.clone(),
+        }
+    }
 
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        let ws_url_str = ws_url.to_string();
+    pub async fn stream_logs(&self, level: Option<LogLevel>) -> Result<StreamReceiver<LogEntry>> {
+        self.stream_logs_with(level, StreamOptions::default()).await
+    }
 
-        tokio::spawn(async move {
-            if let Ok((ws_stream, _)) = connect_async(&ws_url_str).await {
-                let (_, mut read) = ws_stream.split();
-                while let Some(msg) = read.next().await {
-                    match msg {
-                        Ok(Message::Text(text)) => {
-                            if let Ok(traffic) = serde_json::from_str::<TrafficData>(text.as_ref())
-                                && tx.send(traffic).is_err() {
-                                    break;
-                                }
-                        }
-                        Ok(Message::Close(_)) => break,
-                        Err(_) => break,
-                        _ => {}
-                    }
-                }
-            }
-        });
+    pub async fn stream_logs_with(
+        &self,
+        level: Option<LogLevel>,
+        options: StreamOptions,
+    ) -> Result<StreamReceiver<LogEntry>> {
+        let query = level.map(|level| format!("level={}", level.as_str()));
+        let target = self.stream_target("/logs", query);
+        spawn_stream(target, options, |text| Some(LogEntry::parse(text)))
+    }
 
-        Ok(rx)
+    pub async fn stream_traffic(&self) -> Result<StreamReceiver<TrafficData>> {
+        self.stream_traffic_with(StreamOptions::default()).await
+    }
+
+    pub async fn stream_traffic_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<StreamReceiver<TrafficData>> {
+        let target = self.stream_target("/traffic", None);
+        spawn_stream(target, options, parse_json::<TrafficData>)
     }
 
     pub async fn get_memory(&self) -> Result<MemoryData> {
@@ -254,43 +374,16 @@ impl MihomoClient {
         Ok(())
     }
 
-    pub async fn stream_connections(
-        &self,
-    ) -> Result<tokio::sync::mpsc::UnboundedReceiver<ConnectionSnapshot>> {
-        let mut ws_url = self.base_url.clone();
-        ws_url
-            .set_scheme(if ws_url.scheme() == "https" {
-                "wss"
-            } else {
-                "ws"
-            })
-            .ok();
-        ws_url.set_path("/connections");
-
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        let ws_url_str = ws_url.to_string();
-
-        tokio::spawn(async move {
-            if let Ok((ws_stream, _)) = connect_async(&ws_url_str).await {
-                let (_, mut read) = ws_stream.split();
-                while let Some(msg) = read.next().await {
-                    match msg {
-                        Ok(Message::Text(text)) => {
-                            if let Ok(snapshot) =
-                                serde_json::from_str::<ConnectionSnapshot>(text.as_ref())
-                                && tx.send(snapshot).is_err() {
-                                    break;
-                                }
-                        }
-                        Ok(Message::Close(_)) => break,
-                        Err(_) => break,
-                        _ => {}
-                    }
-                }
-            }
-        });
+    pub async fn stream_connections(&self) -> Result<StreamReceiver<ConnectionSnapshot>> {
+        self.stream_connections_with(StreamOptions::default()).await
+    }
 
-        Ok(rx)
+    pub async fn stream_connections_with(
+        &self,
+        options: StreamOptions,
+    ) -> Result<StreamReceiver<ConnectionSnapshot>> {
+        let target = self.stream_target("/connections", None);
+        spawn_stream(target, options, parse_json::<ConnectionSnapshot>)
     }
 }
 
@@ -298,6 +391,7 @@ impl MihomoClient {
 mod tests {
     use super::*;
     use mockito::{Matcher, Server};
+    use futures_util::StreamExt;
     use tokio::net::TcpListener;
     use tokio_tungstenite::{accept_async, tungstenite::Message as WsMessage};
 
@@ -445,6 +539,26 @@ mod tests {
         assert_eq!(result.unwrap(), 123);
     }
 
+    #[tokio::test]
+    async fn test_test_delay_expected_timeout() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/proxies/proxy1/delay")
+            .match_query(Matcher::UrlEncoded("expected".into(), "204".into()))
+            .with_status(408)
+            .with_body(r#"{"message":"Timeout"}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let result = client
+            .test_delay_expected("proxy1", "http://www.gstatic.com/generate_204", 1000, Some("204"))
+            .await;
+
+        mock.assert_async().await;
+        assert_eq!(result.unwrap_err().to_string(), "Service error: HTTP 408: Timeout");
+    }
+
     #[tokio::test]
     async fn test_reload_config_with_path() {
         let mut server = Server::new_async().await;
@@ -482,6 +596,27 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_reload_config_reports_core_error() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("PUT", "/configs")
+            .match_query(Matcher::UrlEncoded("force".into(), "true".into()))
+            .with_status(400)
+            .with_body(r#"{"message":"proxy [Ghost] not found"}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let result = client.reload_config(Some("/path/to/config.yaml")).await;
+
+        mock.assert_async().await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Service error: HTTP 400: proxy [Ghost] not found"
+        );
+    }
+
     #[tokio::test]
     async fn test_get_memory() {
         let mut server = Server::new_async().await;
@@ -576,6 +711,170 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_get_proxy_providers() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/providers/proxies")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"providers":{"airport":{"name":"airport","type":"Proxy","vehicleType":"HTTP","proxies":[]}}}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let providers = client.get_proxy_providers().await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(providers["airport"].vehicle_type, "HTTP");
+    }
+
+    #[tokio::test]
+    async fn test_update_proxy_provider_encodes_name() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("PUT", "/providers/proxies/my%20airport")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let result = client.update_proxy_provider("my airport").await;
+
+        mock.assert_async().await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_rule_provider_error_message() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("PUT", "/providers/rules/reject")
+            .with_status(503)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"message":"download failed"}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let err = client.update_rule_provider("reject").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert_eq!(err.to_string(), "Service error: HTTP 503: download failed");
+    }
+
+    #[tokio::test]
+    async fn test_healthcheck_missing_provider() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/providers/proxies/missing/healthcheck")
+            .with_status(404)
+            .with_body(r#"{"message":"Resource not found"}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let err = client.healthcheck_proxy_provider("missing").await.unwrap_err();
+
+        mock.assert_async().await;
+        assert!(matches!(err, MihomoError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_test_group_delay() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/group/Auto/delay")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("timeout".into(), "3000".into()),
+                Matcher::UrlEncoded("url".into(), "http://www.gstatic.com/generate_204".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"HK-01":120,"JP-01":80}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let delays = client
+            .test_group_delay("Auto", "http://www.gstatic.com/generate_204", 3000)
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(delays["JP-01"], 80);
+    }
+
+    #[tokio::test]
+    async fn test_get_rules() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/rules")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"rules":[{"type":"DomainSuffix","payload":"google.com","proxy":"Proxy","size":-1}]}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let rules = client.get_rules().await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].proxy, "Proxy");
+    }
+
+    #[tokio::test]
+    async fn test_dns_query() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/dns/query")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("name".into(), "example.com".into()),
+                Matcher::UrlEncoded("type".into(), "AAAA".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"Status":0,"Answer":null}"#)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let response = client.dns_query("example.com", Some("AAAA")).await.unwrap();
+
+        mock.assert_async().await;
+        assert!(response.answer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flush_restart_and_upgrade_geo() {
+        let mut server = Server::new_async().await;
+        let flush = server
+            .mock("POST", "/cache/fakeip/flush")
+            .with_status(204)
+            .create_async()
+            .await;
+        let restart = server
+            .mock("POST", "/restart")
+            .with_status(200)
+            .create_async()
+            .await;
+        let geo = server
+            .mock("POST", "/upgrade/geo")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        assert!(client.flush_fakeip_cache().await.is_ok());
+        assert!(client.restart().await.is_ok());
+        assert!(client.upgrade_geo().await.is_ok());
+
+        flush.assert_async().await;
+        restart.assert_async().await;
+        geo.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_stream_logs_message_handling() {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
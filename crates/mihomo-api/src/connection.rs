@@ -1,3 +1,5 @@
+pub mod analytics;
 pub mod manager;
 
+pub use analytics::{AggregateEntry, BucketRollup, ConnectionAnalytics, Dimension, TrafficTotals};
 pub use manager::ConnectionManager;
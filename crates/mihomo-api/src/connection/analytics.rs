@@ -0,0 +1,478 @@
+use crate::{Connection, ConnectionSnapshot};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_BUCKET_WIDTH: Duration = Duration::from_secs(300);
+const DEFAULT_BUCKET_RETENTION: usize = 288;
+const UNKNOWN_KEY: &str = "unknown";
+
+/// What connection traffic is grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dimension {
+    Host,
+    Process,
+    Rule,
+    Chain,
+    Outbound,
+}
+
+impl Dimension {
+    pub const ALL: [Dimension; 5] = [
+        Dimension::Host,
+        Dimension::Process,
+        Dimension::Rule,
+        Dimension::Chain,
+        Dimension::Outbound,
+    ];
+
+    fn key_of(self, conn: &Connection) -> String {
+        let key = match self {
+            Dimension::Host => {
+                if conn.metadata.host.is_empty() {
+                    conn.metadata.destination_ip.clone()
+                } else {
+                    conn.metadata.host.clone()
+                }
+            }
+            Dimension::Process => Path::new(&conn.metadata.process_path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Dimension::Rule => {
+                if conn.rule_payload.is_empty() {
+                    conn.rule.clone()
+                } else {
+                    format!("{}({})", conn.rule, conn.rule_payload)
+                }
+            }
+            // mihomo lists the chain from the outbound node up to the
+            // matched group; show it in routing order instead.
+            Dimension::Chain => conn
+                .chains
+                .iter()
+                .rev()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" -> "),
+            Dimension::Outbound => conn.chains.first().cloned().unwrap_or_default(),
+        };
+        if key.is_empty() {
+            UNKNOWN_KEY.to_string()
+        } else {
+            key
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TrafficTotals {
+    pub upload: u64,
+    pub download: u64,
+    /// Connections that contributed, counted once each.
+    pub connections: u64,
+}
+
+impl TrafficTotals {
+    pub fn total(&self) -> u64 {
+        self.upload + self.download
+    }
+
+    fn add(&mut self, upload: u64, download: u64, new_connection: bool) {
+        self.upload += upload;
+        self.download += download;
+        if new_connection {
+            self.connections += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregateEntry {
+    pub key: String,
+    #[serde(flatten)]
+    pub totals: TrafficTotals,
+}
+
+/// Traffic seen during one rollup bucket.
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketRollup {
+    /// Unix timestamp in milliseconds of the bucket start.
+    pub start: i64,
+    #[serde(flatten)]
+    pub totals: TrafficTotals,
+}
+
+#[derive(Debug, Clone)]
+struct LiveConnection {
+    upload: u64,
+    download: u64,
+    keys: [String; 5],
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    start: i64,
+    totals: TrafficTotals,
+    by: HashMap<Dimension, HashMap<String, TrafficTotals>>,
+}
+
+#[derive(Debug, Default)]
+struct AnalyticsState {
+    live: HashMap<String, LiveConnection>,
+    totals: TrafficTotals,
+    by: HashMap<Dimension, HashMap<String, TrafficTotals>>,
+    buckets: VecDeque<Bucket>,
+    closed: u64,
+}
+
+/// Cumulative per-host/process/rule/chain/outbound traffic built from
+/// `/connections` snapshots.
+///
+/// Byte counters of connections are diffed between snapshots, so traffic of
+/// connections that have since closed stays in the totals.
+#[derive(Debug, Clone)]
+pub struct ConnectionAnalytics {
+    inner: Arc<RwLock<AnalyticsState>>,
+    bucket_width_ms: i64,
+    retention: usize,
+}
+
+impl Default for ConnectionAnalytics {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUCKET_WIDTH, DEFAULT_BUCKET_RETENTION)
+    }
+}
+
+impl ConnectionAnalytics {
+    pub fn new(bucket_width: Duration, retention: usize) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(AnalyticsState::default())),
+            bucket_width_ms: (bucket_width.as_millis() as i64).max(1000),
+            retention: retention.max(1),
+        }
+    }
+
+    pub fn bucket_width(&self) -> Duration {
+        Duration::from_millis(self.bucket_width_ms as u64)
+    }
+
+    pub fn ingest(&self, snapshot: &ConnectionSnapshot) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        self.ingest_at(snapshot, now);
+    }
+
+    pub fn ingest_at(&self, snapshot: &ConnectionSnapshot, timestamp: i64) {
+        let mut guard = self
+            .inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let state = &mut *guard;
+
+        let bucket_start = timestamp - timestamp.rem_euclid(self.bucket_width_ms);
+        if state.buckets.back().is_none_or(|b| b.start < bucket_start) {
+            if state.buckets.len() >= self.retention {
+                state.buckets.pop_front();
+            }
+            state.buckets.push_back(Bucket {
+                start: bucket_start,
+                ..Bucket::default()
+            });
+        }
+        let Some(bucket) = state.buckets.back_mut() else {
+            return;
+        };
+
+        let mut seen = HashMap::with_capacity(snapshot.connections.len());
+        for conn in &snapshot.connections {
+            let (upload, download, is_new, keys) = match state.live.remove(&conn.id) {
+                // Counters only grow; a drop means the id was reused.
+                Some(prev) if conn.upload >= prev.upload && conn.download >= prev.download => (
+                    conn.upload - prev.upload,
+                    conn.download - prev.download,
+                    false,
+                    prev.keys,
+                ),
+                _ => (
+                    conn.upload,
+                    conn.download,
+                    true,
+                    Dimension::ALL.map(|dimension| dimension.key_of(conn)),
+                ),
+            };
+
+            state.totals.add(upload, download, is_new);
+            bucket.totals.add(upload, download, is_new);
+            for (dimension, key) in Dimension::ALL.iter().zip(keys.iter()) {
+                state
+                    .by
+                    .entry(*dimension)
+                    .or_default()
+                    .entry(key.clone())
+                    .or_default()
+                    .add(upload, download, is_new);
+                bucket
+                    .by
+                    .entry(*dimension)
+                    .or_default()
+                    .entry(key.clone())
+                    .or_default()
+                    .add(upload, download, is_new);
+            }
+
+            seen.insert(
+                conn.id.clone(),
+                LiveConnection {
+                    upload: conn.upload,
+                    download: conn.download,
+                    keys,
+                },
+            );
+        }
+
+        state.closed += state.live.len() as u64;
+        state.live = seen;
+    }
+
+    /// Traffic of every connection seen so far.
+    pub fn totals(&self) -> TrafficTotals {
+        self.read().totals
+    }
+
+    /// Connections that were seen and have since closed.
+    pub fn closed_connections(&self) -> u64 {
+        self.read().closed
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.read().live.len()
+    }
+
+    /// Cumulative traffic per key, largest first. `limit` of 0 returns all.
+    pub fn aggregate(&self, dimension: Dimension, limit: usize) -> Vec<AggregateEntry> {
+        let guard = self.read();
+        sorted_entries(guard.by.get(&dimension), limit)
+    }
+
+    /// Per-bucket traffic starting at `since` (ms), optionally for one key.
+    pub fn timeline(
+        &self,
+        since: Option<i64>,
+        filter: Option<(Dimension, &str)>,
+    ) -> Vec<BucketRollup> {
+        let guard = self.read();
+        guard
+            .buckets
+            .iter()
+            .filter(|bucket| since.is_none_or(|since| bucket.start + self.bucket_width_ms > since))
+            .map(|bucket| {
+                let totals = match filter {
+                    Some((dimension, key)) => bucket
+                        .by
+                        .get(&dimension)
+                        .and_then(|entries| entries.get(key))
+                        .copied()
+                        .unwrap_or_default(),
+                    None => bucket.totals,
+                };
+                BucketRollup {
+                    start: bucket.start,
+                    totals,
+                }
+            })
+            .collect()
+    }
+
+    /// Top keys over the buckets starting at `since` (ms).
+    pub fn aggregate_since(
+        &self,
+        dimension: Dimension,
+        since: i64,
+        limit: usize,
+    ) -> Vec<AggregateEntry> {
+        let guard = self.read();
+        let mut merged: HashMap<String, TrafficTotals> = HashMap::new();
+        for bucket in guard
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.start + self.bucket_width_ms > since)
+        {
+            for (key, totals) in bucket.by.get(&dimension).into_iter().flatten() {
+                let entry = merged.entry(key.clone()).or_default();
+                entry.upload += totals.upload;
+                entry.download += totals.download;
+                entry.connections += totals.connections;
+            }
+        }
+        sorted_entries(Some(&merged), limit)
+    }
+
+    pub fn reset(&self) {
+        let mut guard = self
+            .inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Keep the live counters so the next snapshot only adds new bytes.
+        let live = std::mem::take(&mut guard.live);
+        *guard = AnalyticsState {
+            live,
+            ..AnalyticsState::default()
+        };
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, AnalyticsState> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn sorted_entries(
+    entries: Option<&HashMap<String, TrafficTotals>>,
+    limit: usize,
+) -> Vec<AggregateEntry> {
+    let mut entries: Vec<AggregateEntry> = entries
+        .into_iter()
+        .flatten()
+        .map(|(key, totals)| AggregateEntry {
+            key: key.clone(),
+            totals: *totals,
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.totals
+            .total()
+            .cmp(&a.totals.total())
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    if limit > 0 {
+        entries.truncate(limit);
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConnectionMetadata;
+
+    fn conn(id: &str, host: &str, process: &str, upload: u64, download: u64) -> Connection {
+        Connection {
+            id: id.to_string(),
+            metadata: ConnectionMetadata {
+                host: host.to_string(),
+                destination_ip: "1.1.1.1".to_string(),
+                process_path: process.to_string(),
+                ..ConnectionMetadata::default()
+            },
+            upload,
+            download,
+            start: String::new(),
+            chains: vec!["HK-01".to_string(), "Proxy".to_string()],
+            rule: "DomainSuffix".to_string(),
+            rule_payload: "example.com".to_string(),
+        }
+    }
+
+    fn snapshot(connections: Vec<Connection>) -> ConnectionSnapshot {
+        ConnectionSnapshot {
+            download_total: 0,
+            upload_total: 0,
+            connections,
+        }
+    }
+
+    #[test]
+    fn test_counts_deltas_and_keeps_closed_connections() {
+        let analytics = ConnectionAnalytics::new(Duration::from_secs(60), 10);
+        analytics.ingest_at(
+            &snapshot(vec![
+                conn("1", "a.com", "/usr/bin/curl", 100, 1000),
+                conn("2", "", "/usr/bin/chrome", 10, 20),
+            ]),
+            1_000,
+        );
+        analytics.ingest_at(
+            &snapshot(vec![conn("1", "a.com", "/usr/bin/curl", 150, 3000)]),
+            2_000,
+        );
+        // Connection 1 closed; its bytes must stay counted.
+        analytics.ingest_at(&snapshot(vec![]), 3_000);
+
+        let totals = analytics.totals();
+        assert_eq!(totals.upload, 160);
+        assert_eq!(totals.download, 3020);
+        assert_eq!(totals.connections, 2);
+        assert_eq!(analytics.closed_connections(), 2);
+        assert_eq!(analytics.active_connections(), 0);
+
+        let hosts = analytics.aggregate(Dimension::Host, 0);
+        assert_eq!(hosts[0].key, "a.com");
+        assert_eq!(hosts[0].totals.download, 3000);
+        assert_eq!(hosts[1].key, "1.1.1.1");
+
+        let processes = analytics.aggregate(Dimension::Process, 1);
+        assert_eq!(processes.len(), 1);
+        assert_eq!(processes[0].key, "curl");
+    }
+
+    #[test]
+    fn test_rule_chain_and_outbound_keys() {
+        let analytics = ConnectionAnalytics::default();
+        let mut direct = conn("2", "b.com", "", 5, 5);
+        direct.chains = vec![];
+        direct.rule = "Match".to_string();
+        direct.rule_payload = String::new();
+        analytics.ingest_at(&snapshot(vec![conn("1", "a.com", "", 1, 1), direct]), 0);
+
+        let rules: Vec<String> = analytics
+            .aggregate(Dimension::Rule, 0)
+            .into_iter()
+            .map(|e| e.key)
+            .collect();
+        assert_eq!(rules, vec!["Match", "DomainSuffix(example.com)"]);
+        assert_eq!(analytics.aggregate(Dimension::Chain, 0)[1].key, "Proxy -> HK-01");
+        assert_eq!(analytics.aggregate(Dimension::Outbound, 0)[0].key, UNKNOWN_KEY);
+        assert_eq!(analytics.aggregate(Dimension::Process, 0)[0].key, UNKNOWN_KEY);
+    }
+
+    #[test]
+    fn test_timeline_buckets_and_retention() {
+        let analytics = ConnectionAnalytics::new(Duration::from_secs(60), 2);
+        analytics.ingest_at(&snapshot(vec![conn("1", "a.com", "", 10, 10)]), 0);
+        analytics.ingest_at(&snapshot(vec![conn("1", "a.com", "", 30, 10)]), 60_000);
+        analytics.ingest_at(&snapshot(vec![conn("2", "b.com", "", 1, 1)]), 125_000);
+
+        let timeline = analytics.timeline(None, None);
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[0].start, 60_000);
+        assert_eq!(timeline[0].totals.upload, 20);
+        assert_eq!(timeline[1].start, 120_000);
+
+        let a_only = analytics.timeline(None, Some((Dimension::Host, "a.com")));
+        assert_eq!(a_only[0].totals.upload, 20);
+        assert_eq!(a_only[1].totals.upload, 0);
+
+        let recent = analytics.aggregate_since(Dimension::Host, 120_000, 0);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].key, "b.com");
+        // Lifetime totals are not affected by bucket retention.
+        assert_eq!(analytics.totals().upload, 31);
+    }
+
+    #[test]
+    fn test_reset_keeps_live_baseline() {
+        let analytics = ConnectionAnalytics::default();
+        analytics.ingest_at(&snapshot(vec![conn("1", "a.com", "", 100, 100)]), 0);
+        analytics.reset();
+        analytics.ingest_at(&snapshot(vec![conn("1", "a.com", "", 110, 100)]), 1_000);
+        assert_eq!(analytics.totals().upload, 10);
+        assert_eq!(analytics.totals().connections, 0);
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{Connection, ConnectionSnapshot, ConnectionsResponse, MihomoClient, Result};
+use crate::{
+    Connection, ConnectionSnapshot, ConnectionsResponse, MihomoClient, Result, StreamReceiver,
+};
 
 pub struct ConnectionManager {
     client: MihomoClient,
@@ -78,7 +80,7 @@ impl ConnectionManager {
         ))
     }
 
-    pub async fn stream(&self) -> Result<tokio::sync::mpsc::UnboundedReceiver<ConnectionSnapshot>> {
+    pub async fn stream(&self) -> Result<StreamReceiver<ConnectionSnapshot>> {
         self.client.stream_connections().await
     }
 
@@ -2,10 +2,17 @@ pub mod client;
 pub mod connection;
 pub mod error;
 pub mod proxy;
+pub mod stream;
 pub mod types;
 
 pub use client::MihomoClient;
-pub use connection::ConnectionManager;
+pub use connection::{
+    AggregateEntry, BucketRollup, ConnectionAnalytics, ConnectionManager, Dimension, TrafficTotals,
+};
 pub use error::{MihomoError, Result};
-pub use proxy::ProxyManager;
+pub use proxy::{
+    DelayHistoryStore, DelayResult, DelaySample, DelayStats, DelayTestOptions, DelayTrend,
+    ProxyManager,
+};
+pub use stream::{OverflowPolicy, StreamOptions, StreamReceiver};
 pub use types::*;
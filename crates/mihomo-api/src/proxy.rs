@@ -1,5 +1,7 @@
+pub mod history;
 pub mod manager;
 pub mod test;
 
 pub use manager::ProxyManager;
-pub use test::{test_all_delays, test_delay};
+pub use history::{DelayHistoryStore, DelaySample, DelayStats, DelayTrend};
+pub use test::{test_all_delays, test_delay, DelayResult, DelayTestOptions};
@@ -0,0 +1,224 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_HISTORY_CAPACITY: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DelaySample {
+    /// Unix timestamp in milliseconds.
+    pub timestamp: i64,
+    /// `None` when the test timed out or failed.
+    pub delay: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DelayTrend {
+    Improving,
+    Stable,
+    Degrading,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DelayStats {
+    pub latest: Option<u32>,
+    pub average: Option<u32>,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub samples: usize,
+    pub failures: usize,
+    pub trend: DelayTrend,
+}
+
+/// Rolling per-node delay history shared between delay testers and UIs.
+#[derive(Debug, Clone)]
+pub struct DelayHistoryStore {
+    inner: Arc<RwLock<HashMap<String, VecDeque<DelaySample>>>>,
+    capacity: usize,
+}
+
+impl Default for DelayHistoryStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl DelayHistoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(HashMap::new())),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn record(&self, node: &str, delay: Option<u32>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        self.record_at(node, DelaySample { timestamp, delay });
+    }
+
+    pub fn record_at(&self, node: &str, sample: DelaySample) {
+        let mut guard = self
+            .inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let samples = guard.entry(node.to_string()).or_default();
+        if samples.len() >= self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    pub fn samples(&self, node: &str) -> Vec<DelaySample> {
+        let guard = self
+            .inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard
+            .get(node)
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn latest(&self, node: &str) -> Option<DelaySample> {
+        let guard = self
+            .inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard.get(node).and_then(|samples| samples.back().copied())
+    }
+
+    pub fn stats(&self, node: &str) -> Option<DelayStats> {
+        let samples = self.samples(node);
+        if samples.is_empty() {
+            return None;
+        }
+        Some(compute_stats(&samples))
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, Vec<DelaySample>> {
+        let guard = self
+            .inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard
+            .iter()
+            .map(|(name, samples)| (name.clone(), samples.iter().copied().collect()))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        let mut guard = self
+            .inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        guard.clear();
+    }
+}
+
+fn compute_stats(samples: &[DelaySample]) -> DelayStats {
+    let ok: Vec<u32> = samples.iter().filter_map(|s| s.delay).collect();
+    let average = if ok.is_empty() {
+        None
+    } else {
+        Some((ok.iter().map(|d| *d as u64).sum::<u64>() / ok.len() as u64) as u32)
+    };
+    DelayStats {
+        latest: samples.last().and_then(|s| s.delay),
+        average,
+        min: ok.iter().copied().min(),
+        max: ok.iter().copied().max(),
+        samples: samples.len(),
+        failures: samples.len() - ok.len(),
+        trend: compute_trend(samples),
+    }
+}
+
+/// Compares the latest sample against the mean of the earlier ones; a swing of
+/// more than 20% counts as a trend, and a fresh failure always degrades.
+fn compute_trend(samples: &[DelaySample]) -> DelayTrend {
+    let Some((last, earlier)) = samples.split_last() else {
+        return DelayTrend::Stable;
+    };
+    let earlier_ok: Vec<u32> = earlier.iter().filter_map(|s| s.delay).collect();
+    match last.delay {
+        None if !earlier_ok.is_empty() => DelayTrend::Degrading,
+        None => DelayTrend::Stable,
+        Some(_) if earlier_ok.is_empty() && !earlier.is_empty() => DelayTrend::Improving,
+        Some(_) if earlier_ok.is_empty() => DelayTrend::Stable,
+        Some(latest) => {
+            let mean = earlier_ok.iter().map(|d| *d as f64).sum::<f64>() / earlier_ok.len() as f64;
+            let latest = latest as f64;
+            if latest < mean * 0.8 {
+                DelayTrend::Improving
+            } else if latest > mean * 1.2 {
+                DelayTrend::Degrading
+            } else {
+                DelayTrend::Stable
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: i64, delay: Option<u32>) -> DelaySample {
+        DelaySample { timestamp, delay }
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let store = DelayHistoryStore::new(3);
+        for i in 0..5 {
+            store.record_at("node", sample(i, Some(100 + i as u32)));
+        }
+        let samples = store.samples("node");
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].timestamp, 2);
+        assert_eq!(store.latest("node").unwrap().delay, Some(104));
+    }
+
+    #[test]
+    fn test_stats_and_trend() {
+        let store = DelayHistoryStore::default();
+        store.record_at("node", sample(1, Some(100)));
+        store.record_at("node", sample(2, None));
+        store.record_at("node", sample(3, Some(200)));
+
+        let stats = store.stats("node").unwrap();
+        assert_eq!(stats.latest, Some(200));
+        assert_eq!(stats.average, Some(150));
+        assert_eq!(stats.min, Some(100));
+        assert_eq!(stats.max, Some(200));
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.trend, DelayTrend::Degrading);
+        assert!(store.stats("missing").is_none());
+    }
+
+    #[test]
+    fn test_trend_classification() {
+        assert_eq!(
+            compute_trend(&[sample(1, Some(200)), sample(2, Some(100))]),
+            DelayTrend::Improving
+        );
+        assert_eq!(
+            compute_trend(&[sample(1, Some(100)), sample(2, Some(110))]),
+            DelayTrend::Stable
+        );
+        assert_eq!(
+            compute_trend(&[sample(1, Some(100)), sample(2, None)]),
+            DelayTrend::Degrading
+        );
+        assert_eq!(
+            compute_trend(&[sample(1, None), sample(2, Some(100))]),
+            DelayTrend::Improving
+        );
+        assert_eq!(compute_trend(&[sample(1, Some(100))]), DelayTrend::Stable);
+    }
+}
@@ -1,13 +1,26 @@
-use crate::{MihomoClient, ProxyGroup, ProxyInfo, ProxyNode, Result};
-use std::collections::HashMap;
+use crate::proxy::history::DelayHistoryStore;
+use crate::proxy::test::{DelayResult, DelayTestOptions};
+use crate::{MihomoClient, MihomoError, ProxyGroup, ProxyInfo, ProxyNode, ProxyProvider, Result};
+use futures_util::stream::{self, StreamExt};
+use std::collections::{BTreeSet, HashMap};
 
 pub struct ProxyManager {
     client: MihomoClient,
+    history: DelayHistoryStore,
 }
 
 impl ProxyManager {
     pub fn new(client: MihomoClient) -> Self {
-        Self { client }
+        Self::with_history(client, DelayHistoryStore::default())
+    }
+
+    /// Builds a manager that records delay results into a shared history.
+    pub fn with_history(client: MihomoClient, history: DelayHistoryStore) -> Self {
+        Self { client, history }
+    }
+
+    pub fn history(&self) -> &DelayHistoryStore {
+        &self.history
     }
 
     pub async fn list_proxies(&self) -> Result<Vec<ProxyNode>> {
@@ -81,6 +94,133 @@ impl ProxyManager {
     pub async fn get_all_proxies(&self) -> Result<HashMap<String, ProxyInfo>> {
         self.client.get_proxies().await
     }
+
+    /// Lists proxy providers declared by the profile, skipping the core's
+    /// built-in `Compatible` provider that wraps inline proxies.
+    pub async fn list_providers(&self) -> Result<Vec<ProxyProvider>> {
+        let providers = self.client.get_proxy_providers().await?;
+        let mut list: Vec<ProxyProvider> = providers
+            .into_values()
+            .filter(|p| p.vehicle_type != "Compatible")
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(list)
+    }
+
+    pub async fn update_provider(&self, name: &str) -> Result<()> {
+        self.client.update_proxy_provider(name).await
+    }
+
+    pub async fn healthcheck_provider(&self, name: &str) -> Result<()> {
+        self.client.healthcheck_proxy_provider(name).await
+    }
+
+    /// Tests every member of `group` and records the results in the history.
+    pub async fn test_group(
+        &self,
+        group: &str,
+        options: &DelayTestOptions,
+    ) -> Result<Vec<DelayResult>> {
+        let proxies = self.client.get_proxies().await?;
+        let info = proxies
+            .get(group)
+            .ok_or_else(|| MihomoError::NotFound(format!("proxy group '{}'", group)))?;
+        let members = testable_members(&proxies, info.all.as_deref().unwrap_or_default());
+        let mut results = self.test_nodes(members.into_iter().collect(), options).await;
+        results.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(results)
+    }
+
+    /// Tests the members of every group, probing each node only once even when
+    /// it belongs to several groups.
+    pub async fn test_all_groups(
+        &self,
+        options: &DelayTestOptions,
+    ) -> Result<HashMap<String, Vec<DelayResult>>> {
+        let proxies = self.client.get_proxies().await?;
+        let groups: Vec<(&String, Vec<String>)> = proxies
+            .iter()
+            .filter(|(_, info)| is_group_type(&info.proxy_type))
+            .map(|(name, info)| {
+                let members = info.all.as_deref().unwrap_or_default();
+                (name, testable_members(&proxies, members).into_iter().collect())
+            })
+            .collect();
+
+        let unique: BTreeSet<String> = groups
+            .iter()
+            .flat_map(|(_, members)| members.iter().cloned())
+            .collect();
+        let tested: HashMap<String, DelayResult> = self
+            .test_nodes(unique.into_iter().collect(), options)
+            .await
+            .into_iter()
+            .map(|result| (result.name.clone(), result))
+            .collect();
+
+        Ok(groups
+            .into_iter()
+            .map(|(name, members)| {
+                let results = members
+                    .iter()
+                    .filter_map(|member| tested.get(member).cloned())
+                    .collect();
+                (name.clone(), results)
+            })
+            .collect())
+    }
+
+    async fn test_nodes(&self, nodes: Vec<String>, options: &DelayTestOptions) -> Vec<DelayResult> {
+        let concurrency = options.concurrency.max(1);
+        log::debug!(
+            "Testing delay of {} nodes with concurrency {}",
+            nodes.len(),
+            concurrency
+        );
+        stream::iter(nodes)
+            .map(|name| async move {
+                let outcome = self
+                    .client
+                    .test_delay_expected(
+                        &name,
+                        &options.url,
+                        options.timeout_ms,
+                        options.expected_status.as_deref(),
+                    )
+                    .await;
+                let (delay, error) = match outcome {
+                    Ok(delay) if delay > 0 => (Some(delay), None),
+                    Ok(_) => (None, Some("timeout".to_string())),
+                    Err(err) => (None, Some(err.to_string())),
+                };
+                self.history.record(&name, delay);
+                DelayResult { name, delay, error }
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await
+    }
+}
+
+fn is_group_type(proxy_type: &str) -> bool {
+    matches!(
+        proxy_type,
+        "Selector" | "URLTest" | "Fallback" | "LoadBalance" | "Relay"
+    )
+}
+
+/// Drops built-in outbounds that have no meaningful latency.
+fn testable_members(proxies: &HashMap<String, ProxyInfo>, members: &[String]) -> BTreeSet<String> {
+    members
+        .iter()
+        .filter(|name| {
+            !matches!(
+                proxies.get(*name).map(|info| info.proxy_type.as_str()),
+                Some("Direct" | "Reject" | "RejectDrop" | "Pass" | "Compatible")
+            )
+        })
+        .cloned()
+        .collect()
 }
 
 #[cfg(test)]
@@ -158,4 +298,105 @@ mod tests {
         assert_eq!(groups[0].now, "Proxy-A");
         assert_eq!(groups[0].all.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_list_providers_skips_compatible() {
+        let mut server = Server::new_async().await;
+        let body = serde_json::json!({
+            "providers": {
+                "default": {"name": "default", "type": "Proxy", "vehicleType": "Compatible", "proxies": []},
+                "b-sub": {"name": "b-sub", "type": "Proxy", "vehicleType": "HTTP", "proxies": []},
+                "a-sub": {"name": "a-sub", "type": "Proxy", "vehicleType": "File", "proxies": []}
+            }
+        });
+
+        let mock = server.mock("GET", "/providers/proxies")
+            .with_status(200)
+            .with_body(serde_json::to_string(&body).unwrap())
+            .create_async().await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let manager = ProxyManager::new(client);
+        let providers = manager.list_providers().await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(providers.len(), 2);
+        assert_eq!(providers[0].name, "a-sub");
+        assert_eq!(providers[1].name, "b-sub");
+    }
+
+    fn group_fixture() -> serde_json::Value {
+        serde_json::json!({
+            "proxies": {
+                "DIRECT": {"type": "Direct", "history": []},
+                "HK": {"type": "Shadowsocks", "history": []},
+                "JP": {"type": "Vmess", "history": []},
+                "Proxy": {"type": "Selector", "now": "HK", "all": ["HK", "JP", "DIRECT"], "history": []},
+                "Auto": {"type": "URLTest", "now": "JP", "all": ["JP"], "history": []}
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_test_group_records_history() {
+        let mut server = Server::new_async().await;
+        let proxies = server.mock("GET", "/proxies")
+            .with_status(200)
+            .with_body(group_fixture().to_string())
+            .create_async().await;
+        let hk = server.mock("GET", "/proxies/HK/delay")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body(r#"{"delay":120}"#)
+            .create_async().await;
+        let jp = server.mock("GET", "/proxies/JP/delay")
+            .match_query(mockito::Matcher::Any)
+            .with_status(504)
+            .with_body(r#"{"message":"Timeout"}"#)
+            .create_async().await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let manager = ProxyManager::new(client);
+        let options = DelayTestOptions { concurrency: 1, ..DelayTestOptions::default() };
+        let results = manager.test_group("Proxy", &options).await.unwrap();
+
+        proxies.assert_async().await;
+        hk.assert_async().await;
+        jp.assert_async().await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].name, "HK");
+        assert_eq!(results[0].delay, Some(120));
+        assert!(results[1].error.is_some());
+        assert_eq!(manager.history().latest("HK").unwrap().delay, Some(120));
+        assert_eq!(manager.history().latest("JP").unwrap().delay, None);
+    }
+
+    #[tokio::test]
+    async fn test_test_all_groups_dedupes_nodes() {
+        let mut server = Server::new_async().await;
+        server.mock("GET", "/proxies")
+            .with_status(200)
+            .with_body(group_fixture().to_string())
+            .create_async().await;
+        server.mock("GET", "/proxies/HK/delay")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body(r#"{"delay":120}"#)
+            .create_async().await;
+        let jp = server.mock("GET", "/proxies/JP/delay")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body(r#"{"delay":80}"#)
+            .expect(1)
+            .create_async().await;
+
+        let client = MihomoClient::new(&server.url(), None).unwrap();
+        let manager = ProxyManager::new(client);
+        let results = manager.test_all_groups(&DelayTestOptions::default()).await.unwrap();
+
+        jp.assert_async().await;
+        assert_eq!(results["Proxy"].len(), 2);
+        assert_eq!(results["Auto"].len(), 1);
+        assert_eq!(results["Auto"][0].delay, Some(80));
+    }
 }
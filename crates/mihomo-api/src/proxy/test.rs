@@ -1,6 +1,39 @@
 use crate::{MihomoClient, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub const DEFAULT_TEST_URL: &str = "http://www.gstatic.com/generate_204";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DelayTestOptions {
+    pub url: String,
+    /// Status code or range the core should treat as success, e.g. `204` or `200-299`.
+    pub expected_status: Option<String>,
+    /// Per-node timeout in milliseconds.
+    pub timeout_ms: u32,
+    /// Maximum number of nodes tested at once.
+    pub concurrency: usize,
+}
+
+impl Default for DelayTestOptions {
+    fn default() -> Self {
+        Self {
+            url: DEFAULT_TEST_URL.to_string(),
+            expected_status: None,
+            timeout_ms: 5000,
+            concurrency: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DelayResult {
+    pub name: String,
+    pub delay: Option<u32>,
+    pub error: Option<String>,
+}
+
 pub async fn test_delay(
     client: &MihomoClient,
     proxy: &str,
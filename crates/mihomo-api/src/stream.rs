@@ -0,0 +1,311 @@
+use crate::error::Result;
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, header::AUTHORIZATION};
+use url::Url;
+
+/// What the stream task does when the consumer falls behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait for the consumer; the socket read stalls and the stream lags behind.
+    Block,
+    /// Discard incoming messages while the channel is full.
+    #[default]
+    DropNewest,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failed connection attempts before the stream gives up.
+    /// `None` keeps retrying for as long as the receiver is alive.
+    pub max_retries: Option<u32>,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 256,
+            overflow: OverflowPolicy::default(),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_retries: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct StreamStats {
+    dropped: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+/// Receiving half of a controller WebSocket stream.
+///
+/// Dropping it stops the background task.
+pub struct StreamReceiver<T> {
+    rx: mpsc::Receiver<T>,
+    stats: Arc<StreamStats>,
+}
+
+impl<T> StreamReceiver<T> {
+    pub async fn recv(&mut self) -> Option<T> {
+        self.rx.recv().await
+    }
+
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Messages discarded by [`OverflowPolicy::DropNewest`].
+    pub fn dropped(&self) -> u64 {
+        self.stats.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Successful connections after the first one.
+    pub fn reconnects(&self) -> u64 {
+        self.stats.reconnects.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) struct StreamTarget {
+    pub url: Url,
+    pub secret: Option<String>,
+}
+
+pub(crate) fn spawn_stream<T, F>(
+    target: StreamTarget,
+    options: StreamOptions,
+    parse: F,
+) -> Result<StreamReceiver<T>>
+where
+    T: Send + 'static,
+    F: Fn(&str) -> Option<T> + Send + 'static,
+{
+    let auth = match &target.secret {
+        Some(secret) => Some(
+            HeaderValue::from_str(&format!("Bearer {}", secret))
+                .map_err(|e| crate::MihomoError::Config(format!("invalid secret: {}", e)))?,
+        ),
+        None => None,
+    };
+    let (tx, rx) = mpsc::channel(options.capacity.max(1));
+    let stats = Arc::new(StreamStats::default());
+    let task_stats = stats.clone();
+    let url = target.url.to_string();
+
+    tokio::spawn(async move {
+        let mut backoff = options.initial_backoff;
+        let mut failures: u32 = 0;
+        let mut connected_once = false;
+
+        loop {
+            let mut request = match url.as_str().into_client_request() {
+                Ok(request) => request,
+                Err(err) => {
+                    log::warn!("invalid stream url {}: {}", url, err);
+                    return;
+                }
+            };
+            if let Some(auth) = &auth {
+                request.headers_mut().insert(AUTHORIZATION, auth.clone());
+            }
+
+            match connect_async(request).await {
+                Ok((ws_stream, _)) => {
+                    if connected_once {
+                        task_stats.reconnects.fetch_add(1, Ordering::Relaxed);
+                        log::debug!("stream {} reconnected", url);
+                    }
+                    connected_once = true;
+                    failures = 0;
+                    backoff = options.initial_backoff;
+
+                    let (_, mut read) = ws_stream.split();
+                    while let Some(msg) = read.next().await {
+                        let text = match msg {
+                            Ok(Message::Text(text)) => text,
+                            Ok(Message::Close(_)) => break,
+                            Err(err) => {
+                                log::debug!("stream {} read error: {}", url, err);
+                                break;
+                            }
+                            _ => continue,
+                        };
+                        let Some(item) = parse(text.as_ref()) else {
+                            continue;
+                        };
+                        let delivered = match options.overflow {
+                            OverflowPolicy::Block => tx.send(item).await.is_ok(),
+                            OverflowPolicy::DropNewest => match tx.try_send(item) {
+                                Ok(()) => true,
+                                Err(mpsc::error::TrySendError::Full(_)) => {
+                                    task_stats.dropped.fetch_add(1, Ordering::Relaxed);
+                                    true
+                                }
+                                Err(mpsc::error::TrySendError::Closed(_)) => false,
+                            },
+                        };
+                        if !delivered {
+                            return;
+                        }
+                    }
+                }
+                Err(err) => {
+                    failures += 1;
+                    log::debug!("stream {} connect failed ({}): {}", url, failures, err);
+                    if options.max_retries.is_some_and(|max| failures > max) {
+                        log::warn!("stream {} gave up after {} attempts", url, failures);
+                        return;
+                    }
+                }
+            }
+
+            tokio::select! {
+                _ = tx.closed() => return,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(options.max_backoff);
+        }
+    });
+
+    Ok(StreamReceiver { rx, stats })
+}
+
+pub(crate) fn parse_json<T: DeserializeOwned>(text: &str) -> Option<T> {
+    serde_json::from_str(text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::SinkExt;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_hdr_async;
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+    fn fast_options() -> StreamOptions {
+        StreamOptions {
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(50),
+            ..StreamOptions::default()
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    async fn test_stream_reconnects_after_close_and_sends_auth() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (auth_tx, mut auth_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            for i in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let auth_tx = auth_tx.clone();
+                let ws = accept_hdr_async(stream, move |req: &Request, resp: Response| {
+                    let header = req
+                        .headers()
+                        .get(AUTHORIZATION)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    auth_tx.send(header).ok();
+                    Ok(resp)
+                })
+                .await
+                .unwrap();
+                let (mut tx, _) = ws.split();
+                tx.send(Message::Text(format!("msg-{}", i).into()))
+                    .await
+                    .ok();
+                tx.send(Message::Close(None)).await.ok();
+            }
+        });
+
+        let target = StreamTarget {
+            url: Url::parse(&format!("ws://{}/logs", addr)).unwrap(),
+            secret: Some("s3cret".to_string()),
+        };
+        let mut rx = spawn_stream(target, fast_options(), |t| Some(t.to_string())).unwrap();
+
+        let first = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap();
+        let second = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(first.as_deref(), Some("msg-0"));
+        assert_eq!(second.as_deref(), Some("msg-1"));
+        assert_eq!(rx.reconnects(), 1);
+        assert_eq!(
+            auth_rx.recv().await.unwrap().as_deref(),
+            Some("Bearer s3cret")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_drop_newest_counts_overflow() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let (mut tx, _) = ws.split();
+            for i in 0..5 {
+                tx.send(Message::Text(i.to_string().into())).await.ok();
+            }
+            // Give the client time to drain the socket before signalling.
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            done_tx.send(()).ok();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let target = StreamTarget {
+            url: Url::parse(&format!("ws://{}/traffic", addr)).unwrap(),
+            secret: None,
+        };
+        let options = StreamOptions {
+            capacity: 2,
+            ..fast_options()
+        };
+        let mut rx = spawn_stream(target, options, |t| t.parse::<u32>().ok()).unwrap();
+        done_rx.await.unwrap();
+
+        assert_eq!(rx.recv().await, Some(0));
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.dropped(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stream_gives_up_after_max_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let target = StreamTarget {
+            url: Url::parse(&format!("ws://{}/logs", addr)).unwrap(),
+            secret: None,
+        };
+        let options = StreamOptions {
+            max_retries: Some(1),
+            ..fast_options()
+        };
+        let mut rx = spawn_stream(target, options, |t| Some(t.to_string())).unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap();
+        assert!(result.is_none());
+    }
+}
@@ -91,6 +91,47 @@ pub struct TrafficData {
     pub down: u64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    #[serde(alias = "warn")]
+    Warning,
+    Error,
+    Silent,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+            LogLevel::Silent => "silent",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    #[serde(rename = "type")]
+    pub level: LogLevel,
+    #[serde(default)]
+    pub payload: String,
+}
+
+impl LogEntry {
+    /// Parses a `/logs` frame, keeping unrecognised frames as info-level text.
+    pub fn parse(text: &str) -> Self {
+        serde_json::from_str(text).unwrap_or_else(|_| Self {
+            level: LogLevel::Info,
+            payload: text.to_string(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryData {
     #[serde(rename = "inuse")]
@@ -175,6 +216,133 @@ pub struct ConnectionSnapshot {
     pub connections: Vec<Connection>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubscriptionInfo {
+    #[serde(rename = "Upload", default)]
+    pub upload: u64,
+    #[serde(rename = "Download", default)]
+    pub download: u64,
+    #[serde(rename = "Total", default)]
+    pub total: u64,
+    #[serde(rename = "Expire", default)]
+    pub expire: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderProxy {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub proxy_type: String,
+    #[serde(default)]
+    pub alive: bool,
+    #[serde(default)]
+    pub udp: bool,
+    #[serde(default, deserialize_with = "deserialize_null_as_empty_vec")]
+    pub history: Vec<DelayHistory>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyProvider {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    #[serde(rename = "vehicleType")]
+    #[serde(default)]
+    pub vehicle_type: String,
+    #[serde(default, deserialize_with = "deserialize_null_as_empty_vec")]
+    pub proxies: Vec<ProviderProxy>,
+    #[serde(rename = "testUrl")]
+    #[serde(default)]
+    pub test_url: Option<String>,
+    #[serde(rename = "updatedAt")]
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(rename = "subscriptionInfo")]
+    #[serde(default)]
+    pub subscription_info: Option<SubscriptionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleProvider {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    #[serde(rename = "vehicleType")]
+    #[serde(default)]
+    pub vehicle_type: String,
+    #[serde(default)]
+    pub behavior: String,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(rename = "ruleCount")]
+    #[serde(default)]
+    pub rule_count: u64,
+    #[serde(rename = "updatedAt")]
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProvidersResponse<T> {
+    #[serde(default = "HashMap::new")]
+    pub providers: HashMap<String, T>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleInfo {
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    #[serde(default)]
+    pub payload: String,
+    #[serde(default)]
+    pub proxy: String,
+    #[serde(default)]
+    pub size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RulesResponse {
+    #[serde(default, deserialize_with = "deserialize_null_as_empty_vec")]
+    pub rules: Vec<RuleInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsQuestion {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Qtype")]
+    #[serde(default)]
+    pub qtype: u16,
+    #[serde(rename = "Qclass")]
+    #[serde(default)]
+    pub qclass: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsAnswer {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: u16,
+    #[serde(rename = "TTL")]
+    #[serde(default)]
+    pub ttl: u32,
+    #[serde(default)]
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsQueryResponse {
+    #[serde(rename = "Status")]
+    #[serde(default)]
+    pub status: i32,
+    #[serde(rename = "Question")]
+    #[serde(default, deserialize_with = "deserialize_null_as_empty_vec")]
+    pub question: Vec<DnsQuestion>,
+    #[serde(rename = "Answer")]
+    #[serde(default, deserialize_with = "deserialize_null_as_empty_vec")]
+    pub answer: Vec<DnsAnswer>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -266,6 +434,17 @@ mod tests {
         assert_eq!(deserialized.down, 2048);
     }
 
+    #[test]
+    fn test_log_entry_parse() {
+        let entry = LogEntry::parse(r#"{"type":"warning","payload":"dial failed"}"#);
+        assert_eq!(entry.level, LogLevel::Warning);
+        assert_eq!(entry.payload, "dial failed");
+
+        let entry = LogEntry::parse("plain text");
+        assert_eq!(entry.level, LogLevel::Info);
+        assert_eq!(entry.payload, "plain text");
+    }
+
     #[test]
     fn test_memory_data_serialization() {
         let json = r#"{"inuse":1048576,"oslimit":4194304}"#;
@@ -503,4 +682,57 @@ mod tests {
         assert_eq!(snapshot.upload_total, 200);
         assert_eq!(snapshot.connections.len(), 0);
     }
+
+    #[test]
+    fn test_proxy_provider_deserialization() {
+        let json = r#"{
+            "name": "airport",
+            "type": "Proxy",
+            "vehicleType": "HTTP",
+            "proxies": [{"name": "HK-01", "type": "Shadowsocks", "alive": true, "history": null}],
+            "updatedAt": "2024-01-01T00:00:00Z",
+            "subscriptionInfo": {"Upload": 1, "Download": 2, "Total": 10, "Expire": 1700000000}
+        }"#;
+        let provider: ProxyProvider = serde_json::from_str(json).unwrap();
+
+        assert_eq!(provider.vehicle_type, "HTTP");
+        assert_eq!(provider.proxies.len(), 1);
+        assert!(provider.proxies[0].history.is_empty());
+        let info = provider.subscription_info.unwrap();
+        assert_eq!(info.total, 10);
+        assert_eq!(info.expire, 1700000000);
+    }
+
+    #[test]
+    fn test_rule_provider_deserialization() {
+        let json = r#"{
+            "name": "reject",
+            "type": "Rule",
+            "vehicleType": "HTTP",
+            "behavior": "Domain",
+            "format": "YamlRule",
+            "ruleCount": 42
+        }"#;
+        let provider: RuleProvider = serde_json::from_str(json).unwrap();
+
+        assert_eq!(provider.behavior, "Domain");
+        assert_eq!(provider.rule_count, 42);
+        assert!(provider.updated_at.is_none());
+    }
+
+    #[test]
+    fn test_dns_query_response_deserialization() {
+        let json = r#"{
+            "Status": 0,
+            "Question": [{"Name": "example.com.", "Qtype": 1, "Qclass": 1}],
+            "Answer": [{"name": "example.com.", "type": 1, "TTL": 300, "data": "93.184.216.34"}]
+        }"#;
+        let response: DnsQueryResponse = serde_json::from_str(json).unwrap();
+
+        assert_eq!(response.status, 0);
+        assert_eq!(response.question[0].name, "example.com.");
+        assert_eq!(response.question[0].qtype, 1);
+        assert_eq!(response.answer[0].data, "93.184.216.34");
+        assert_eq!(response.answer[0].ttl, 300);
+    }
 }
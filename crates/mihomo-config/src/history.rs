@@ -0,0 +1,507 @@
+use crate::yaml;
+use chrono::{DateTime, NaiveDateTime, SubsecRound, Utc};
+use mihomo_api::{MihomoError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use yaml_rust2::Yaml;
+
+/// Number of revisions kept per profile; older ones are pruned
+pub const MAX_REVISIONS: usize = 30;
+
+/// Pseudo revision id that refers to the profile file on disk
+pub const CURRENT_REVISION: &str = "current";
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+/// Largest LCS table built for one list; bigger edits are reported as a
+/// replacement of the changed range
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// What produced a profile revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RevisionSource {
+    /// Content that existed before history tracking started
+    Baseline,
+    SubscriptionUpdate,
+    AdminSave,
+    RulesEdit,
+    SettingsEdit,
+    SyncDownload,
+    Restore,
+}
+
+impl RevisionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Baseline => "baseline",
+            Self::SubscriptionUpdate => "subscription-update",
+            Self::AdminSave => "admin-save",
+            Self::RulesEdit => "rules-edit",
+            Self::SettingsEdit => "settings-edit",
+            Self::SyncDownload => "sync-download",
+            Self::Restore => "restore",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        [
+            Self::Baseline,
+            Self::SubscriptionUpdate,
+            Self::AdminSave,
+            Self::RulesEdit,
+            Self::SettingsEdit,
+            Self::SyncDownload,
+            Self::Restore,
+        ]
+        .into_iter()
+        .find(|source| source.as_str() == value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileRevision {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub source: RevisionSource,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A single difference between two YAML documents.
+///
+/// `path` uses dotted keys and bracketed indices (`dns.nameserver[0]`);
+/// list entries that carry a unique `name` are addressed by it
+/// (`proxies[hk-01].port`) so that reordering does not show up as churn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct YamlChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Record `content` as a new revision in `dir`.
+///
+/// Returns `None` when the content matches the latest revision.
+pub(crate) async fn record(
+    dir: &Path,
+    content: &str,
+    source: RevisionSource,
+) -> Result<Option<ProfileRevision>> {
+    let existing = list(dir).await?;
+    if let Some(latest) = existing.first() {
+        let previous = fs::read_to_string(revision_path(dir, &latest.id)).await?;
+        if previous == content {
+            return Ok(None);
+        }
+    }
+
+    fs::create_dir_all(dir).await?;
+    let mut created_at = Utc::now().trunc_subsecs(3);
+    // Keep ids unique and ordered even when two saves land in the same millisecond
+    if let Some(latest) = existing.first()
+        && created_at <= latest.created_at
+    {
+        created_at = latest.created_at + chrono::Duration::milliseconds(1);
+    }
+    let id = format!("{}-{}", created_at.format(TIMESTAMP_FORMAT), source.as_str());
+    fs::write(revision_path(dir, &id), content).await?;
+
+    for stale in existing.iter().skip(MAX_REVISIONS - 1) {
+        if let Err(err) = fs::remove_file(revision_path(dir, &stale.id)).await {
+            log::warn!("failed to prune revision {}: {err}", stale.id);
+        }
+    }
+
+    Ok(Some(ProfileRevision {
+        id,
+        created_at,
+        source,
+        size: content.len() as u64,
+    }))
+}
+
+/// List revisions stored in `dir`, newest first
+pub(crate) async fn list(dir: &Path) -> Result<Vec<ProfileRevision>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut revisions = vec![];
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("yaml") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let Some((created_at, source)) = parse_revision_id(id) else {
+            continue;
+        };
+        let size = entry.metadata().await.map(|meta| meta.len()).unwrap_or(0);
+        revisions.push(ProfileRevision {
+            id: id.to_string(),
+            created_at,
+            source,
+            size,
+        });
+    }
+    revisions.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(revisions)
+}
+
+pub(crate) async fn load(dir: &Path, id: &str) -> Result<String> {
+    if parse_revision_id(id).is_none() {
+        return Err(MihomoError::NotFound(format!("Revision '{}' not found", id)));
+    }
+    let path = revision_path(dir, id);
+    if !path.exists() {
+        return Err(MihomoError::NotFound(format!("Revision '{}' not found", id)));
+    }
+    Ok(fs::read_to_string(path).await?)
+}
+
+fn revision_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{id}.yaml"))
+}
+
+fn parse_revision_id(id: &str) -> Option<(DateTime<Utc>, RevisionSource)> {
+    let (timestamp, source) = id.split_once('-')?;
+    let created_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()?
+        .and_utc();
+    Some((created_at, RevisionSource::parse(source)?))
+}
+
+/// Structured diff between two YAML documents
+pub fn diff_yaml(before: &str, after: &str) -> Result<Vec<YamlChange>> {
+    let before = yaml::load_yaml(before)?;
+    let after = yaml::load_yaml(after)?;
+    let mut changes = vec![];
+    diff_node("", &before, &after, &mut changes);
+    Ok(changes)
+}
+
+fn diff_node(path: &str, before: &Yaml, after: &Yaml, out: &mut Vec<YamlChange>) {
+    if before == after {
+        return;
+    }
+    match (before, after) {
+        (Yaml::Hash(old), Yaml::Hash(new)) => {
+            for (key, old_value) in old {
+                let child = key_path(path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_node(&child, old_value, new_value, out),
+                    None => out.push(removed(child, old_value)),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    out.push(added(key_path(path, key), new_value));
+                }
+            }
+        }
+        (Yaml::Array(old), Yaml::Array(new)) => {
+            match (named_entries(old), named_entries(new)) {
+                (Some(old_named), Some(new_named)) => {
+                    diff_named(path, &old_named, &new_named, out)
+                }
+                _ => diff_sequence(path, old, new, out),
+            }
+        }
+        _ => out.push(YamlChange {
+            path: display_path(path),
+            kind: ChangeKind::Modified,
+            before: Some(render(before)),
+            after: Some(render(after)),
+        }),
+    }
+}
+
+fn diff_named(
+    path: &str,
+    old: &[(&str, &Yaml)],
+    new: &[(&str, &Yaml)],
+    out: &mut Vec<YamlChange>,
+) {
+    for (name, old_value) in old {
+        let child = format!("{path}[{name}]");
+        match new.iter().find(|(candidate, _)| candidate == name) {
+            Some((_, new_value)) => diff_node(&child, old_value, new_value, out),
+            None => out.push(removed(child, old_value)),
+        }
+    }
+    for (name, new_value) in new {
+        if !old.iter().any(|(candidate, _)| candidate == name) {
+            out.push(added(format!("{path}[{name}]"), new_value));
+        }
+    }
+}
+
+/// Diff plain lists by longest common subsequence. Unmatched entries that sit
+/// in the same gap are reported as modifications, the rest as additions or
+/// removals. The common prefix and suffix are skipped; when the rest is too
+/// large for an LCS table it is compared as one gap.
+fn diff_sequence(path: &str, old: &[Yaml], new: &[Yaml], out: &mut Vec<YamlChange>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (n, m) = (old.len() - prefix - suffix, new.len() - prefix - suffix);
+    let mut gap_old = vec![];
+    let mut gap_new = vec![];
+    if (n + 1) * (m + 1) > MAX_LCS_CELLS {
+        gap_old.extend(prefix..prefix + n);
+        gap_new.extend(prefix..prefix + m);
+        flush_gap(path, old, new, &mut gap_old, &mut gap_new, out);
+        return;
+    }
+
+    let (old_mid, new_mid) = (&old[prefix..prefix + n], &new[prefix..prefix + m]);
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[at(i, j)] = if old_mid[i] == new_mid[j] {
+                lcs[at(i + 1, j + 1)] + 1
+            } else {
+                lcs[at(i + 1, j)].max(lcs[at(i, j + 1)])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_mid[i] == new_mid[j] {
+            flush_gap(path, old, new, &mut gap_old, &mut gap_new, out);
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[at(i, j + 1)] >= lcs[at(i + 1, j)]) {
+            gap_new.push(prefix + j);
+            j += 1;
+        } else {
+            gap_old.push(prefix + i);
+            i += 1;
+        }
+    }
+    flush_gap(path, old, new, &mut gap_old, &mut gap_new, out);
+}
+
+fn flush_gap(
+    path: &str,
+    old: &[Yaml],
+    new: &[Yaml],
+    gap_old: &mut Vec<usize>,
+    gap_new: &mut Vec<usize>,
+    out: &mut Vec<YamlChange>,
+) {
+    let paired = gap_old.len().min(gap_new.len());
+    for (&i, &j) in gap_old.iter().zip(gap_new.iter()) {
+        diff_node(&format!("{path}[{j}]"), &old[i], &new[j], out);
+    }
+    for &i in &gap_old[paired..] {
+        out.push(removed(format!("{path}[{i}]"), &old[i]));
+    }
+    for &j in &gap_new[paired..] {
+        out.push(added(format!("{path}[{j}]"), &new[j]));
+    }
+    gap_old.clear();
+    gap_new.clear();
+}
+
+/// Entries keyed by their `name` field, if every entry has a unique one
+fn named_entries(items: &[Yaml]) -> Option<Vec<(&str, &Yaml)>> {
+    let mut named = Vec::with_capacity(items.len());
+    for item in items {
+        let name = item["name"].as_str()?;
+        if named.iter().any(|(existing, _)| *existing == name) {
+            return None;
+        }
+        named.push((name, item));
+    }
+    Some(named)
+}
+
+fn key_path(parent: &str, key: &Yaml) -> String {
+    let key = match key {
+        Yaml::String(value) => value.clone(),
+        other => render(other),
+    };
+    if parent.is_empty() {
+        key
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+fn display_path(path: &str) -> String {
+    if path.is_empty() {
+        "$".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+fn added(path: String, value: &Yaml) -> YamlChange {
+    YamlChange {
+        path: display_path(&path),
+        kind: ChangeKind::Added,
+        before: None,
+        after: Some(render(value)),
+    }
+}
+
+fn removed(path: String, value: &Yaml) -> YamlChange {
+    YamlChange {
+        path: display_path(&path),
+        kind: ChangeKind::Removed,
+        before: Some(render(value)),
+        after: None,
+    }
+}
+
+fn render(value: &Yaml) -> String {
+    match value {
+        Yaml::String(value) | Yaml::Real(value) => value.clone(),
+        Yaml::Integer(value) => value.to_string(),
+        Yaml::Boolean(value) => value.to_string(),
+        Yaml::Null | Yaml::BadValue => "null".to_string(),
+        other => yaml::to_string(other)
+            .map(|text| text.trim_start_matches("---").trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_diff_yaml_reports_paths() {
+        let before = r#"
+mode: rule
+dns:
+  nameserver:
+    - 1.1.1.1
+    - 8.8.8.8
+proxies:
+  - name: a
+    port: 1
+  - name: b
+    port: 2
+"#;
+        let after = r#"
+mode: global
+dns:
+  nameserver:
+    - 1.1.1.1
+    - 9.9.9.9
+  ipv6: true
+proxies:
+  - name: b
+    port: 3
+  - name: c
+    port: 4
+"#;
+        let changes = diff_yaml(before, after).unwrap();
+        let paths: Vec<_> = changes
+            .iter()
+            .map(|change| (change.path.as_str(), change.kind))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("mode", ChangeKind::Modified),
+                ("dns.nameserver[1]", ChangeKind::Modified),
+                ("dns.ipv6", ChangeKind::Added),
+                ("proxies[a]", ChangeKind::Removed),
+                ("proxies[b].port", ChangeKind::Modified),
+                ("proxies[c]", ChangeKind::Added),
+            ]
+        );
+        assert_eq!(changes[1].before.as_deref(), Some("8.8.8.8"));
+        assert_eq!(changes[1].after.as_deref(), Some("9.9.9.9"));
+    }
+
+    #[test]
+    fn test_diff_yaml_sequence_insertions() {
+        let changes = diff_yaml(
+            "rules:\n  - 'MATCH,DIRECT'\n",
+            "rules:\n  - 'DOMAIN,a.com,PROXY'\n  - 'MATCH,DIRECT'\n",
+        )
+        .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "rules[0]");
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+    }
+
+    #[test]
+    fn test_diff_sequence_large_lists() {
+        let rules = |prefix: &str, count: usize| -> Vec<Yaml> {
+            (0..count)
+                .map(|i| Yaml::String(format!("DOMAIN,{prefix}{i}.com,DIRECT")))
+                .collect()
+        };
+
+        // The shared prefix and suffix are skipped, so no full table is built
+        let old = rules("a", 100_000);
+        let mut new = old.clone();
+        new.insert(50_000, Yaml::String("MATCH,PROXY".to_string()));
+        let mut changes = vec![];
+        diff_sequence("rules", &old, &new, &mut changes);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "rules[50000]");
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+
+        // Past the cap the changed range is reported as a replacement
+        let old = rules("a", 3_000);
+        let new = rules("b", 3_001);
+        let mut changes = vec![];
+        diff_sequence("rules", &old, &new, &mut changes);
+        assert_eq!(changes.len(), 3_001);
+        assert!(changes[..3_000]
+            .iter()
+            .all(|change| change.kind == ChangeKind::Modified));
+        assert_eq!(changes[3_000].kind, ChangeKind::Added);
+    }
+
+    #[tokio::test]
+    async fn test_record_dedups_and_prunes() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("history");
+
+        let first = record(&dir, "mode: rule\n", RevisionSource::AdminSave)
+            .await
+            .unwrap();
+        assert!(first.is_some());
+        let duplicate = record(&dir, "mode: rule\n", RevisionSource::AdminSave)
+            .await
+            .unwrap();
+        assert!(duplicate.is_none());
+
+        for index in 0..MAX_REVISIONS + 5 {
+            record(&dir, &format!("port: {index}\n"), RevisionSource::RulesEdit)
+                .await
+                .unwrap();
+        }
+        let revisions = list(&dir).await.unwrap();
+        assert_eq!(revisions.len(), MAX_REVISIONS);
+        let latest = load(&dir, &revisions[0].id).await.unwrap();
+        assert_eq!(latest, format!("port: {}\n", MAX_REVISIONS + 4));
+        assert_eq!(revisions[0].source, RevisionSource::RulesEdit);
+        assert!(load(&dir, "../secret").await.is_err());
+    }
+}
@@ -1,8 +1,13 @@
+pub mod history;
 pub mod port;
 pub mod manager;
 pub mod profile;
 pub mod subscription;
+pub mod validate;
 pub mod yaml;
 
+pub use history::{ChangeKind, ProfileRevision, RevisionSource, YamlChange};
 pub use manager::ConfigManager;
 pub use profile::Profile;
+pub use validate::{Diagnostic, Severity};
+pub use subscription::{SubscriptionMetadata, SubscriptionUserInfo};
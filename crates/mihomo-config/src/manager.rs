@@ -1,4 +1,9 @@
-use super::{profile::Profile, yaml};
+use super::{
+    history::{self, ProfileRevision, RevisionSource, YamlChange, CURRENT_REVISION},
+    profile::Profile,
+    subscription::SubscriptionUserInfo,
+    validate, yaml,
+};
 use crate::port::{find_available_port, is_port_available, parse_port_from_addr};
 use mihomo_api::{MihomoError, Result};
 use mihomo_platform::{get_home_dir, CredentialStore, DefaultCredentialStore};
@@ -9,6 +14,7 @@ use tokio::fs;
 pub struct ConfigManager<S: CredentialStore = DefaultCredentialStore> {
     config_dir: PathBuf,
     settings_file: PathBuf,
+    history_dir: PathBuf,
     credential_store: S,
 }
 
@@ -21,10 +27,12 @@ impl<S: CredentialStore> ConfigManager<S> {
     pub fn with_home_and_store(home: PathBuf, credential_store: S) -> Result<Self> {
         let config_dir = home.join("configs");
         let settings_file = home.join("config.toml");
+        let history_dir = home.join("history");
 
         Ok(Self {
             config_dir,
             settings_file,
+            history_dir,
             credential_store,
         })
     }
@@ -52,6 +60,81 @@ impl<S: CredentialStore> ConfigManager<S> {
         Ok(())
     }
 
+    /// Save a profile and record the new content in its revision history.
+    ///
+    /// The first tracked save also records the previous content as a
+    /// baseline so it can be restored. Returns the recorded revision, `None`
+    /// when the content matches the latest one.
+    pub async fn save_with_source(
+        &self,
+        profile: &str,
+        content: &str,
+        source: RevisionSource,
+    ) -> Result<Option<ProfileRevision>> {
+        validate::ensure_valid(content)?;
+        self.ensure_history_baseline(profile).await?;
+        self.save(profile, content).await?;
+        self.record_revision(profile, content, source).await
+    }
+
+    /// Record the profile's current content as a baseline if it has no
+    /// history yet. Call before replacing the file by other means.
+    pub async fn ensure_history_baseline(&self, profile: &str) -> Result<()> {
+        let dir = self.profile_history_dir(profile);
+        if history::list(&dir).await?.is_empty()
+            && let Ok(previous) = self.load(profile).await
+        {
+            history::record(&dir, &previous, RevisionSource::Baseline).await?;
+        }
+        Ok(())
+    }
+
+    /// Record content that was written outside of `save_with_source`
+    pub async fn record_revision(
+        &self,
+        profile: &str,
+        content: &str,
+        source: RevisionSource,
+    ) -> Result<Option<ProfileRevision>> {
+        history::record(&self.profile_history_dir(profile), content, source).await
+    }
+
+    /// Revisions of a profile, newest first
+    pub async fn list_revisions(&self, profile: &str) -> Result<Vec<ProfileRevision>> {
+        history::list(&self.profile_history_dir(profile)).await
+    }
+
+    /// Content of a revision; `current` refers to the profile on disk
+    pub async fn load_revision(&self, profile: &str, id: &str) -> Result<String> {
+        if id == CURRENT_REVISION {
+            return self.load(profile).await;
+        }
+        history::load(&self.profile_history_dir(profile), id).await
+    }
+
+    pub async fn diff_revisions(
+        &self,
+        profile: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<YamlChange>> {
+        let before = self.load_revision(profile, from).await?;
+        let after = self.load_revision(profile, to).await?;
+        history::diff_yaml(&before, &after)
+    }
+
+    /// Write a stored revision back as the profile content
+    pub async fn restore_revision(&self, profile: &str, id: &str) -> Result<String> {
+        let content = history::load(&self.profile_history_dir(profile), id).await?;
+        self.save_with_source(profile, &content, RevisionSource::Restore)
+            .await?;
+        Ok(content)
+    }
+
+    fn profile_history_dir(&self, profile: &str) -> PathBuf {
+        self.history_dir.join(profile)
+    }
+
     pub async fn list_profiles(&self) -> Result<Vec<Profile>> {
         if !self.config_dir.exists() {
             return Ok(vec![]);
@@ -110,6 +193,12 @@ impl<S: CredentialStore> ConfigManager<S> {
         }
 
         fs::remove_file(path).await?;
+        let history_dir = self.profile_history_dir(profile);
+        if history_dir.exists()
+            && let Err(err) = fs::remove_dir_all(&history_dir).await
+        {
+            log::warn!("failed to remove profile history: {err}");
+        }
         if let Err(err) = delete_subscription_url(&self.credential_store, profile).await {
             log::warn!("failed to delete subscription entry: {err}");
         }
@@ -175,6 +264,12 @@ impl<S: CredentialStore> ConfigManager<S> {
         );
         set_optional_datetime(profile_table, "last_updated", metadata.last_updated);
         set_optional_datetime(profile_table, "next_update", metadata.next_update);
+        set_optional_u32(
+            profile_table,
+            "suggested_update_interval_hours",
+            metadata.suggested_update_interval_hours,
+        );
+        set_subscription_info(profile_table, metadata.subscription_info);
 
         let content = toml::to_string(&settings)
             .map_err(|e| MihomoError::Config(format!("Failed to serialize config: {}", e)))?;
@@ -513,6 +608,30 @@ async fn apply_profile_metadata<S: CredentialStore>(
         });
     profile.last_updated = parse_datetime(table.get("last_updated"));
     profile.next_update = parse_datetime(table.get("next_update"));
+    profile.suggested_update_interval_hours = table
+        .get("suggested_update_interval_hours")
+        .and_then(|value| value.as_integer())
+        .and_then(|value| u32::try_from(value).ok());
+    profile.subscription_info = table
+        .get("subscription_info")
+        .and_then(|value| value.as_table())
+        .map(parse_subscription_info);
+}
+
+fn parse_subscription_info(table: &toml::map::Map<String, toml::Value>) -> SubscriptionUserInfo {
+    let counter = |key: &str| {
+        table
+            .get(key)
+            .and_then(|value| value.as_integer())
+            .and_then(|value| u64::try_from(value).ok())
+            .unwrap_or(0)
+    };
+    SubscriptionUserInfo {
+        upload: counter("upload"),
+        download: counter("download"),
+        total: counter("total"),
+        expire: parse_datetime(table.get("expire")),
+    }
 }
 
 fn parse_datetime(value: Option<&toml::Value>) -> Option<DateTime<Utc>> {
@@ -619,6 +738,23 @@ fn set_optional_datetime(
     }
 }
 
+fn set_subscription_info(
+    table: &mut toml::map::Map<String, toml::Value>,
+    value: Option<SubscriptionUserInfo>,
+) {
+    let Some(info) = value else {
+        table.remove("subscription_info");
+        return;
+    };
+    let counter = |value: u64| toml::Value::Integer(i64::try_from(value).unwrap_or(i64::MAX));
+    let mut info_table = toml::map::Map::new();
+    info_table.insert("upload".to_string(), counter(info.upload));
+    info_table.insert("download".to_string(), counter(info.download));
+    info_table.insert("total".to_string(), counter(info.total));
+    set_optional_datetime(&mut info_table, "expire", info.expire);
+    table.insert("subscription_info".to_string(), toml::Value::Table(info_table));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -745,6 +881,42 @@ mod tests {
         assert!(!manager.config_dir.join("test-profile.yaml").exists());
     }
 
+    #[tokio::test]
+    async fn test_save_with_source_records_history() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = setup_test_manager(&temp_dir).await;
+
+        manager.save("test-profile", "mode: rule\n").await.unwrap();
+        manager
+            .save_with_source("test-profile", "mode: global\n", RevisionSource::AdminSave)
+            .await
+            .unwrap();
+
+        let revisions = manager.list_revisions("test-profile").await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].source, RevisionSource::AdminSave);
+        assert_eq!(revisions[1].source, RevisionSource::Baseline);
+
+        let changes = manager
+            .diff_revisions("test-profile", &revisions[1].id, CURRENT_REVISION)
+            .await
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "mode");
+
+        let restored = manager
+            .restore_revision("test-profile", &revisions[1].id)
+            .await
+            .unwrap();
+        assert_eq!(restored, "mode: rule\n");
+        assert_eq!(manager.load("test-profile").await.unwrap(), "mode: rule\n");
+        let revisions = manager.list_revisions("test-profile").await.unwrap();
+        assert_eq!(revisions[0].source, RevisionSource::Restore);
+
+        manager.delete_profile("test-profile").await.unwrap();
+        assert!(!manager.history_dir.join("test-profile").exists());
+    }
+
     #[tokio::test]
     async fn test_delete_nonexistent_profile() {
         let temp_dir = TempDir::new().unwrap();
@@ -913,6 +1085,10 @@ external-controller: http://127.0.0.1:9090
             update_interval_hours: Some(24),
             last_updated: None,
             next_update: None,
+            subscription_info: SubscriptionUserInfo::parse(
+                "upload=10; download=20; total=100; expire=1767225600",
+            ),
+            suggested_update_interval_hours: Some(12),
         };
 
         let result = manager.update_profile_metadata("test-profile", &metadata).await;
@@ -921,6 +1097,8 @@ external-controller: http://127.0.0.1:9090
         let retrieved = manager.get_profile_metadata("test-profile").await.unwrap();
         assert!(retrieved.auto_update_enabled);
         assert_eq!(retrieved.update_interval_hours, Some(24));
+        assert_eq!(retrieved.suggested_update_interval_hours, Some(12));
+        assert_eq!(retrieved.subscription_info, metadata.subscription_info);
     }
 
     #[tokio::test]
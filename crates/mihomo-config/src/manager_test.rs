@@ -293,6 +293,8 @@ external-controller: http://127.0.0.1:9090
             update_interval_hours: Some(24),
             last_updated: None,
             next_update: None,
+            subscription_info: None,
+            suggested_update_interval_hours: None,
         };
 
         let result = manager.update_profile_metadata("test-profile", &metadata).await;
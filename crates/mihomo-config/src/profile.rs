@@ -1,5 +1,6 @@
+use crate::subscription::{SubscriptionMetadata, SubscriptionUserInfo};
 use crate::yaml;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use mihomo_api::{MihomoError, Result};
 use std::path::PathBuf;
 
@@ -13,6 +14,10 @@ pub struct Profile {
     pub update_interval_hours: Option<u32>,
     pub last_updated: Option<DateTime<Utc>>,
     pub next_update: Option<DateTime<Utc>>,
+    /// Traffic counters from the last subscription response
+    pub subscription_info: Option<SubscriptionUserInfo>,
+    /// Update interval suggested by the subscription provider, in hours
+    pub suggested_update_interval_hours: Option<u32>,
 }
 
 impl Profile {
@@ -26,9 +31,36 @@ impl Profile {
             update_interval_hours: None,
             last_updated: None,
             next_update: None,
+            subscription_info: None,
+            suggested_update_interval_hours: None,
         }
     }
 
+    /// Interval the scheduler should use: the provider's suggestion wins
+    /// over the locally configured one.
+    pub fn effective_update_interval_hours(&self) -> Option<u32> {
+        self.suggested_update_interval_hours
+            .or(self.update_interval_hours)
+            .filter(|hours| *hours > 0)
+    }
+
+    /// Record the metadata of a fresh subscription response
+    pub fn apply_subscription_metadata(&mut self, metadata: &SubscriptionMetadata) {
+        self.subscription_info = metadata.user_info;
+        self.suggested_update_interval_hours = metadata.update_interval_hours;
+    }
+
+    /// Mark the profile as updated at `now` and schedule the next update
+    pub fn mark_updated(&mut self, now: DateTime<Utc>) {
+        self.last_updated = Some(now);
+        self.next_update = if self.auto_update_enabled {
+            self.effective_update_interval_hours()
+                .map(|hours| now + Duration::hours(hours as i64))
+        } else {
+            None
+        };
+    }
+
     pub async fn validate(&self) -> Result<()> {
         if !self.path.exists() {
             return Err(MihomoError::Config(format!(
@@ -72,6 +104,30 @@ mod tests {
         assert_eq!(profile.update_interval_hours, None);
         assert_eq!(profile.last_updated, None);
         assert_eq!(profile.next_update, None);
+        assert_eq!(profile.subscription_info, None);
+        assert_eq!(profile.suggested_update_interval_hours, None);
+    }
+
+    #[test]
+    fn test_mark_updated_prefers_suggested_interval() {
+        let now = Utc::now();
+        let mut profile = Profile::new("sub".to_string(), PathBuf::new(), false);
+        profile.auto_update_enabled = true;
+        profile.update_interval_hours = Some(6);
+        profile.mark_updated(now);
+        assert_eq!(profile.next_update, Some(now + Duration::hours(6)));
+
+        profile.apply_subscription_metadata(&SubscriptionMetadata {
+            user_info: None,
+            update_interval_hours: Some(24),
+        });
+        profile.mark_updated(now);
+        assert_eq!(profile.last_updated, Some(now));
+        assert_eq!(profile.next_update, Some(now + Duration::hours(24)));
+
+        profile.auto_update_enabled = false;
+        profile.mark_updated(now);
+        assert_eq!(profile.next_update, None);
     }
 
     #[tokio::test]
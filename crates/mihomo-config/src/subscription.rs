@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Traffic counters reported by the `subscription-userinfo` response header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionUserInfo {
+    pub upload: u64,
+    pub download: u64,
+    /// Total quota in bytes, `0` when the provider reports none
+    pub total: u64,
+    pub expire: Option<DateTime<Utc>>,
+}
+
+impl SubscriptionUserInfo {
+    /// Parse a header like `upload=1; download=2; total=3; expire=1700000000`.
+    /// Unknown keys are ignored; returns `None` when no known key is present.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut info = Self::default();
+        let mut found = false;
+        for pair in value.split(';') {
+            let Some((key, raw)) = pair.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let Some(number) = parse_number(raw) else {
+                continue;
+            };
+            match key.as_str() {
+                "upload" => info.upload = number,
+                "download" => info.download = number,
+                "total" => info.total = number,
+                "expire" => {
+                    info.expire = i64::try_from(number)
+                        .ok()
+                        .filter(|secs| *secs > 0)
+                        .and_then(|secs| DateTime::from_timestamp(secs, 0));
+                }
+                _ => continue,
+            }
+            found = true;
+        }
+        found.then_some(info)
+    }
+
+    pub fn used(&self) -> u64 {
+        self.upload.saturating_add(self.download)
+    }
+
+    /// Remaining quota, `None` when the provider reports no total
+    pub fn remaining(&self) -> Option<u64> {
+        (self.total > 0).then(|| self.total.saturating_sub(self.used()))
+    }
+}
+
+/// Metadata carried by the headers of a subscription response
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscriptionMetadata {
+    pub user_info: Option<SubscriptionUserInfo>,
+    /// Update interval suggested by the provider, in hours
+    pub update_interval_hours: Option<u32>,
+}
+
+impl SubscriptionMetadata {
+    pub fn from_headers(user_info: Option<&str>, update_interval: Option<&str>) -> Self {
+        Self {
+            user_info: user_info.and_then(SubscriptionUserInfo::parse),
+            update_interval_hours: update_interval.and_then(parse_update_interval),
+        }
+    }
+}
+
+/// Parse the `profile-update-interval` header, which is given in hours
+pub fn parse_update_interval(value: &str) -> Option<u32> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|hours| hours.is_finite() && *hours >= 1.0)
+        .map(|hours| hours.round().min(u32::MAX as f64) as u32)
+}
+
+fn parse_number(raw: &str) -> Option<u64> {
+    let raw = raw.trim();
+    raw.parse::<u64>().ok().or_else(|| {
+        raw.parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value >= 0.0)
+            .map(|value| value.min(u64::MAX as f64) as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_user_info() {
+        let info = SubscriptionUserInfo::parse(
+            "upload=1024; download=2048; total=10240; expire=1767225600",
+        )
+        .unwrap();
+        assert_eq!(info.used(), 3072);
+        assert_eq!(info.remaining(), Some(7168));
+        assert_eq!(
+            info.expire.unwrap().to_rfc3339(),
+            "2026-01-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_parse_user_info_lenient() {
+        let info = SubscriptionUserInfo::parse("Upload=1.5e3;download=;total=0;expire=0;foo=bar")
+            .unwrap();
+        assert_eq!(info.upload, 1500);
+        assert_eq!(info.download, 0);
+        assert_eq!(info.remaining(), None);
+        assert_eq!(info.expire, None);
+
+        assert_eq!(SubscriptionUserInfo::parse("nothing here"), None);
+        assert_eq!(SubscriptionUserInfo::parse(""), None);
+    }
+
+    #[test]
+    fn test_parse_update_interval() {
+        assert_eq!(parse_update_interval("24"), Some(24));
+        assert_eq!(parse_update_interval(" 12 "), Some(12));
+        assert_eq!(parse_update_interval("0"), None);
+        assert_eq!(parse_update_interval("soon"), None);
+    }
+}
@@ -0,0 +1,516 @@
+//! Semantic checks for profile documents.
+//!
+//! Parsing only proves the document is YAML; the core still refuses profiles
+//! whose groups point at missing proxies, whose rules target unknown policies
+//! and so on. These checks catch such problems before a profile is written.
+
+use crate::yaml;
+use mihomo_api::{MihomoError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+use yaml_rust2::Yaml;
+
+/// Policies the core provides without a definition in the profile
+const BUILTIN_POLICIES: &[&str] = &["DIRECT", "REJECT", "REJECT-DROP", "PASS", "COMPATIBLE", "GLOBAL"];
+
+const PORT_KEYS: &[&str] = &["port", "socks-port", "mixed-port", "redir-port", "tproxy-port"];
+
+/// Rule options that may follow the target
+const RULE_OPTIONS: &[&str] = &["no-resolve", "src"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Location in the document, e.g. `proxy-groups[1].proxies[0]`
+    pub path: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Run all semantic checks on a profile document
+pub fn validate_profile(content: &str) -> Result<Vec<Diagnostic>> {
+    let doc = yaml::load_yaml(content)?;
+    let mut diagnostics = vec![];
+    if doc.as_hash().is_none() {
+        if !doc.is_null() {
+            diagnostics.push(error("$", "profile must be a mapping"));
+        }
+        return Ok(diagnostics);
+    }
+
+    let proxies = collect_names(&doc, "proxies", &mut diagnostics);
+    let groups = collect_names(&doc, "proxy-groups", &mut diagnostics);
+    let mut clashes: Vec<(&String, &usize)> = groups.iter().collect();
+    clashes.sort_by_key(|(_, index)| **index);
+    for (name, group_index) in clashes {
+        if let Some(index) = proxies.get(name) {
+            diagnostics.push(error(
+                format!("proxy-groups[{group_index}].name"),
+                format!("group '{name}' clashes with proxy proxies[{index}]"),
+            ));
+        }
+    }
+
+    check_groups(&doc, &proxies, &groups, &mut diagnostics);
+    check_rules(&doc, &proxies, &groups, &mut diagnostics);
+    check_ports(&doc, &mut diagnostics);
+    check_cidrs(&doc, &mut diagnostics);
+    Ok(diagnostics)
+}
+
+/// Fail with a config error if the document has error-level diagnostics
+pub fn ensure_valid(content: &str) -> Result<()> {
+    let errors: Vec<String> = validate_profile(content)?
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(MihomoError::Config(format!(
+            "Profile validation failed: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+fn error(path: impl Into<String>, message: impl Into<String>) -> Diagnostic {
+    Diagnostic {
+        path: path.into(),
+        severity: Severity::Error,
+        message: message.into(),
+    }
+}
+
+fn warning(path: impl Into<String>, message: impl Into<String>) -> Diagnostic {
+    Diagnostic {
+        path: path.into(),
+        severity: Severity::Warning,
+        message: message.into(),
+    }
+}
+
+fn sequence<'a>(doc: &'a Yaml, key: &str) -> &'a [Yaml] {
+    doc[key].as_vec().map(Vec::as_slice).unwrap_or(&[])
+}
+
+/// Names of the entries in a list section, mapped to their index
+fn collect_names(doc: &Yaml, key: &str, out: &mut Vec<Diagnostic>) -> HashMap<String, usize> {
+    let mut names = HashMap::new();
+    for (index, entry) in sequence(doc, key).iter().enumerate() {
+        match entry["name"].as_str() {
+            Some(name) => {
+                if let Some(first) = names.insert(name.to_string(), index) {
+                    names.insert(name.to_string(), first);
+                    out.push(error(
+                        format!("{key}[{index}].name"),
+                        format!("duplicate name '{name}', first defined at {key}[{first}]"),
+                    ));
+                }
+            }
+            None => out.push(error(format!("{key}[{index}]"), "missing name")),
+        }
+    }
+    names
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTIN_POLICIES
+        .iter()
+        .any(|builtin| builtin.eq_ignore_ascii_case(name))
+}
+
+fn check_groups(
+    doc: &Yaml,
+    proxies: &HashMap<String, usize>,
+    groups: &HashMap<String, usize>,
+    out: &mut Vec<Diagnostic>,
+) {
+    let providers: HashSet<&str> = doc["proxy-providers"]
+        .as_hash()
+        .map(|hash| hash.keys().filter_map(|key| key.as_str()).collect())
+        .unwrap_or_default();
+    let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for (index, group) in sequence(doc, "proxy-groups").iter().enumerate() {
+        let Some(name) = group["name"].as_str() else {
+            continue;
+        };
+        let members = group["proxies"].as_vec().map(Vec::as_slice).unwrap_or(&[]);
+        for (member_index, member) in members.iter().enumerate() {
+            let path = format!("proxy-groups[{index}].proxies[{member_index}]");
+            let Some(member) = member.as_str() else {
+                out.push(error(path, "member must be a string"));
+                continue;
+            };
+            if groups.contains_key(member) {
+                edges.entry(name).or_default().push(member);
+            } else if !proxies.contains_key(member) && !is_builtin(member) {
+                out.push(error(path, format!("unknown proxy or group '{member}'")));
+            }
+        }
+
+        let uses = group["use"].as_vec().map(Vec::as_slice).unwrap_or(&[]);
+        for (use_index, provider) in uses.iter().enumerate() {
+            let provider = provider.as_str().unwrap_or_default();
+            if !providers.contains(provider) {
+                out.push(error(
+                    format!("proxy-groups[{index}].use[{use_index}]"),
+                    format!("unknown proxy provider '{provider}'"),
+                ));
+            }
+        }
+
+        let include_all = ["include-all", "include-all-proxies", "include-all-providers"]
+            .iter()
+            .any(|key| group[*key].as_bool() == Some(true));
+        if members.is_empty() && uses.is_empty() && !include_all {
+            out.push(warning(
+                format!("proxy-groups[{index}]"),
+                format!("group '{name}' has no members"),
+            ));
+        }
+    }
+
+    for cycle in find_cycles(&edges) {
+        let first = cycle[0];
+        out.push(error(
+            format!("proxy-groups[{}].proxies", groups[first]),
+            format!("group cycle: {}", cycle.join(" -> ")),
+        ));
+    }
+}
+
+/// Cycles in the group graph, each reported once starting from the group
+/// that was reached first
+fn find_cycles<'a>(edges: &HashMap<&'a str, Vec<&'a str>>) -> Vec<Vec<&'a str>> {
+    fn visit<'a>(
+        node: &'a str,
+        edges: &HashMap<&'a str, Vec<&'a str>>,
+        stack: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<&'a str>>,
+    ) {
+        if let Some(position) = stack.iter().position(|entry| *entry == node) {
+            let mut cycle = stack[position..].to_vec();
+            cycle.push(node);
+            cycles.push(cycle);
+            return;
+        }
+        if !done.insert(node) {
+            return;
+        }
+        stack.push(node);
+        for next in edges.get(node).into_iter().flatten() {
+            visit(next, edges, stack, done, cycles);
+        }
+        stack.pop();
+    }
+
+    let mut nodes: Vec<&str> = edges.keys().copied().collect();
+    nodes.sort_unstable();
+    let mut done = HashSet::new();
+    let mut cycles = vec![];
+    for node in nodes {
+        visit(node, edges, &mut vec![], &mut done, &mut cycles);
+    }
+    cycles
+}
+
+fn check_rules(
+    doc: &Yaml,
+    proxies: &HashMap<String, usize>,
+    groups: &HashMap<String, usize>,
+    out: &mut Vec<Diagnostic>,
+) {
+    let rule_providers: HashSet<&str> = doc["rule-providers"]
+        .as_hash()
+        .map(|hash| hash.keys().filter_map(|key| key.as_str()).collect())
+        .unwrap_or_default();
+    let sub_rules = doc["sub-rules"].as_hash();
+    let sub_rule_names: HashSet<&str> = sub_rules
+        .map(|hash| hash.keys().filter_map(|key| key.as_str()).collect())
+        .unwrap_or_default();
+    let context = RuleContext {
+        proxies,
+        groups,
+        rule_providers: &rule_providers,
+        sub_rules: &sub_rule_names,
+    };
+
+    check_rule_list(sequence(doc, "rules"), "rules", &context, out);
+    if let Some(sub_rules) = sub_rules {
+        for (name, rules) in sub_rules {
+            let (Some(name), Some(rules)) = (name.as_str(), rules.as_vec()) else {
+                continue;
+            };
+            check_rule_list(rules, &format!("sub-rules.{name}"), &context, out);
+        }
+    }
+}
+
+struct RuleContext<'a> {
+    proxies: &'a HashMap<String, usize>,
+    groups: &'a HashMap<String, usize>,
+    rule_providers: &'a HashSet<&'a str>,
+    sub_rules: &'a HashSet<&'a str>,
+}
+
+fn check_rule_list(rules: &[Yaml], base: &str, context: &RuleContext<'_>, out: &mut Vec<Diagnostic>) {
+    let mut match_index = None;
+    for (index, rule) in rules.iter().enumerate() {
+        let path = format!("{base}[{index}]");
+        let Some(rule) = rule.as_str() else {
+            out.push(error(path, "rule must be a string"));
+            continue;
+        };
+        // Rules disabled in the editor are kept as `# RULE` entries
+        if rule.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Some(match_index) = match_index {
+            out.push(warning(
+                path.clone(),
+                format!("unreachable after MATCH at {base}[{match_index}]"),
+            ));
+        }
+
+        let parts = split_rule(rule);
+        let kind = parts[0].to_ascii_uppercase();
+        let target = if kind == "MATCH" {
+            match_index.get_or_insert(index);
+            parts.get(1)
+        } else {
+            parts.get(2)
+        };
+        let Some(target) = target.filter(|target| !target.is_empty()) else {
+            out.push(error(path, format!("rule '{rule}' has no target")));
+            continue;
+        };
+
+        if kind == "RULE-SET" && !context.rule_providers.contains(parts[1]) {
+            out.push(error(
+                path.clone(),
+                format!("unknown rule provider '{}'", parts[1]),
+            ));
+        }
+        if kind == "SUB-RULE" {
+            if !context.sub_rules.contains(target) {
+                out.push(error(path, format!("unknown sub-rule '{target}'")));
+            }
+            continue;
+        }
+        if RULE_OPTIONS.contains(target) {
+            out.push(error(path, format!("rule '{rule}' has no target")));
+            continue;
+        }
+        if !context.proxies.contains_key(*target)
+            && !context.groups.contains_key(*target)
+            && !is_builtin(target)
+        {
+            out.push(error(path, format!("unknown policy '{target}'")));
+        }
+    }
+}
+
+/// Split a rule on commas that are not inside parentheses, so logic rules
+/// such as `AND,((DOMAIN,a.com),(NETWORK,UDP)),PROXY` keep their payload
+fn split_rule(rule: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (index, ch) in rule.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(rule[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(rule[start..].trim());
+    parts
+}
+
+fn check_ports(doc: &Yaml, out: &mut Vec<Diagnostic>) {
+    let mut used: Vec<(u16, String)> = vec![];
+    let mut claim = |port: u16, path: String, out: &mut Vec<Diagnostic>| {
+        if port == 0 {
+            return;
+        }
+        if let Some((_, owner)) = used.iter().find(|(existing, _)| *existing == port) {
+            out.push(error(path, format!("port {port} is already used by {owner}")));
+        } else {
+            used.push((port, path));
+        }
+    };
+
+    for key in PORT_KEYS {
+        let value = &doc[*key];
+        if value.is_badvalue() || value.is_null() {
+            continue;
+        }
+        match yaml::get_u16(doc, key) {
+            Some(port) => claim(port, key.to_string(), out),
+            None => out.push(error(*key, "invalid port")),
+        }
+    }
+    for (path, address) in [
+        ("external-controller", doc["external-controller"].as_str()),
+        ("dns.listen", doc["dns"]["listen"].as_str()),
+    ] {
+        let Some(address) = address.filter(|address| !address.is_empty()) else {
+            continue;
+        };
+        match address.rsplit_once(':').map(|(_, port)| port.parse::<u16>()) {
+            Some(Ok(port)) => claim(port, path.to_string(), out),
+            _ => out.push(error(path, format!("invalid listen address '{address}'"))),
+        }
+    }
+}
+
+fn check_cidrs(doc: &Yaml, out: &mut Vec<Diagnostic>) {
+    let dns = &doc["dns"];
+    for key in ["fake-ip-range", "fake-ip-range6"] {
+        if let Some(range) = dns[key].as_str()
+            && !is_valid_cidr(range)
+        {
+            out.push(error(format!("dns.{key}"), format!("invalid CIDR '{range}'")));
+        }
+    }
+    if let Some(ranges) = dns["fallback-filter"]["ipcidr"].as_vec() {
+        for (index, range) in ranges.iter().enumerate() {
+            let range = range.as_str().unwrap_or_default();
+            if !is_valid_cidr(range) {
+                out.push(error(
+                    format!("dns.fallback-filter.ipcidr[{index}]"),
+                    format!("invalid CIDR '{range}'"),
+                ));
+            }
+        }
+    }
+}
+
+fn is_valid_cidr(value: &str) -> bool {
+    let Some((address, prefix)) = value.split_once('/') else {
+        return false;
+    };
+    let Ok(prefix) = prefix.parse::<u8>() else {
+        return false;
+    };
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => prefix <= 32,
+        Ok(IpAddr::V6(_)) => prefix <= 128,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(content: &str, severity: Severity) -> Vec<String> {
+        validate_profile(content)
+            .unwrap()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .map(|diagnostic| diagnostic.path)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_profile_has_no_diagnostics() {
+        let content = r#"
+mixed-port: 7890
+external-controller: 127.0.0.1:9090
+proxies:
+  - { name: hk, type: socks5, server: 1.1.1.1, port: 1080 }
+proxy-providers:
+  remote: { type: http, url: "https://example.com/p.yaml" }
+proxy-groups:
+  - { name: Proxy, type: select, proxies: [Auto, hk, DIRECT] }
+  - { name: Auto, type: url-test, use: [remote] }
+rule-providers:
+  ads: { type: http, behavior: domain, url: "https://example.com/ads.yaml" }
+dns:
+  fake-ip-range: 198.18.0.1/16
+rules:
+  - "RULE-SET,ads,REJECT"
+  - "AND,((DOMAIN,a.com),(NETWORK,UDP)),Proxy"
+  - "IP-CIDR,10.0.0.0/8,DIRECT,no-resolve"
+  - "MATCH,Proxy"
+  - '# DOMAIN,b.com,Ghost'
+"#;
+        assert_eq!(validate_profile(content).unwrap(), vec![]);
+        assert!(ensure_valid(content).is_ok());
+    }
+
+    #[test]
+    fn test_reference_errors() {
+        let content = r#"
+proxies:
+  - { name: hk, type: socks5, server: 1.1.1.1, port: 1080 }
+proxy-groups:
+  - { name: A, type: select, proxies: [B, missing] }
+  - { name: B, type: select, proxies: [A], use: [nowhere] }
+rules:
+  - "RULE-SET,ads,A"
+  - "DOMAIN,a.com,Ghost"
+  - "MATCH,hk"
+  - "DOMAIN,b.com,hk"
+"#;
+        assert_eq!(
+            paths(content, Severity::Error),
+            vec![
+                "proxy-groups[0].proxies[1]",
+                "proxy-groups[1].use[0]",
+                "proxy-groups[0].proxies",
+                "rules[0]",
+                "rules[1]",
+            ]
+        );
+        assert_eq!(paths(content, Severity::Warning), vec!["rules[3]"]);
+        let message = ensure_valid(content).unwrap_err().to_string();
+        assert!(message.contains("group cycle: A -> B -> A"));
+    }
+
+    #[test]
+    fn test_ports_and_cidrs() {
+        let content = r#"
+port: 7890
+mixed-port: 7890
+external-controller: ":7890"
+dns:
+  listen: 0.0.0.0:53
+  fake-ip-range: 198.18.0.1/33
+  fallback-filter:
+    ipcidr: [240.0.0.0/4, not-a-cidr]
+"#;
+        assert_eq!(
+            paths(content, Severity::Error),
+            vec![
+                "mixed-port",
+                "external-controller",
+                "dns.fake-ip-range",
+                "dns.fallback-filter.ipcidr[1]",
+            ]
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use reqwest::{header, Client, Method};
+use reqwest::{header, Client, Method, Response, StatusCode};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use url::Url;
 
 use crate::{xml_parser, DavClient, RemoteEntry};
@@ -8,6 +11,10 @@ use crate::{xml_parser, DavClient, RemoteEntry};
 pub struct WebDavClient {
     client: Client,
     base_url: Url,
+    /// 服务器拒绝过 `Depth: infinity`，之后逐层 PROPFIND
+    finite_depth: AtomicBool,
+    /// 已确认存在的目录，如 `/rules/`，避免每次上传都 MKCOL
+    known_dirs: Mutex<HashSet<String>>,
 }
 
 impl WebDavClient {
@@ -29,53 +36,106 @@ impl WebDavClient {
             base_url.set_path(&format!("{}/", base_url.path()));
         }
 
-        Ok(Self { client, base_url })
+        Ok(Self {
+            client,
+            base_url,
+            finite_depth: AtomicBool::new(false),
+            known_dirs: Mutex::new(HashSet::new()),
+        })
     }
 
     fn full_url(&self, path: &str) -> Result<Url> {
-        let path = path.trim_start_matches('/');
-        self.base_url.join(path).map_err(|e| anyhow!("Invalid path: {}", e))
+        // 逐段编码，文件名中的 `#`、`?`、`%` 不能被当作 URL 语法
+        let path = path
+            .trim_start_matches('/')
+            .split('/')
+            .map(|segment| urlencoding::encode(segment).into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.base_url.join(&path).map_err(|e| anyhow!("Invalid path: {}", e))
     }
-}
 
-#[async_trait]
-impl DavClient for WebDavClient {
-    async fn list(&self, path: &str) -> Result<Vec<RemoteEntry>> {
-        let url = self.full_url(path)?;
+    async fn propfind(&self, dir: &str, depth: &str) -> Result<(Url, Response)> {
+        let url = self.full_url(dir)?;
         let resp = self.client
-            .request(Method::from_bytes(b"PROPFIND")?, url)
-            .header("Depth", "1")
+            .request(Method::from_bytes(b"PROPFIND")?, url.clone())
+            .header("Depth", depth)
             .send()
             .await?;
+        Ok((url, resp))
+    }
 
-        if !resp.status().is_success() {
-            return Err(anyhow!("PROPFIND failed: {}", resp.status()));
-        }
-
+    async fn parse_listing(&self, dir: &str, url: &Url, resp: Response) -> Result<Vec<RemoteEntry>> {
         let body = resp.text().await?;
-        xml_parser::parse_multistatus(&body)
+        let entries: Vec<RemoteEntry> = xml_parser::parse_multistatus_at(&body, url, &self.base_url)?
+            .into_iter()
+            .filter(|entry| entry.path != dir)
+            .collect();
+        let mut known = self.known_dirs.lock().unwrap_or_else(|e| e.into_inner());
+        known.insert(dir.to_string());
+        known.extend(entries.iter().filter(|e| e.is_dir).map(|e| e.path.clone()));
+        Ok(entries)
     }
 
-    async fn get(&self, path: &str) -> Result<Vec<u8>> {
-        let url = self.full_url(path)?;
-        let resp = self.client.get(url).send().await?;
-        
-        if !resp.status().is_success() {
-            return Err(anyhow!("GET failed: {}", resp.status()));
+    /// Walk collections one `Depth: 1` PROPFIND at a time
+    async fn walk(&self, root: &str) -> Result<Vec<RemoteEntry>> {
+        let mut pending = VecDeque::from([root.to_string()]);
+        let mut visited = HashSet::from([root.to_string()]);
+        let mut entries = Vec::new();
+        while let Some(dir) = pending.pop_front() {
+            let (url, resp) = self.propfind(&dir, "1").await?;
+            if !resp.status().is_success() {
+                return Err(anyhow!("PROPFIND failed: {}: {}", dir, resp.status()));
+            }
+            for entry in self.parse_listing(&dir, &url, resp).await? {
+                if entry.is_dir && visited.insert(entry.path.clone()) {
+                    pending.push_back(entry.path.clone());
+                }
+                entries.push(entry);
+            }
         }
-        
-        Ok(resp.bytes().await?.to_vec())
+        Ok(entries)
     }
 
-    async fn put(&self, path: &str, data: &[u8], if_match: Option<&str>) -> Result<String> {
-        let url = self.full_url(path)?;
+    /// MKCOL every missing parent collection of `path`, outermost first
+    async fn ensure_parents(&self, path: &str) -> Result<()> {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut dir = String::from("/");
+        for segment in segments.iter().take(segments.len().saturating_sub(1)) {
+            dir.push_str(segment);
+            dir.push('/');
+            let known = self.known_dirs.lock().unwrap_or_else(|e| e.into_inner()).contains(&dir);
+            if !known {
+                self.mkdir(&dir).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_put(&self, url: Url, data: &[u8], precondition: Precondition<'_>) -> Result<Response> {
         let mut req = self.client.put(url).body(data.to_owned());
         
-        if let Some(etag) = if_match {
-            req = req.header(header::IF_MATCH, format!("\"{}\"", etag));
+        match precondition {
+            Precondition::None => {}
+            Precondition::IfMatch(etag) => {
+                req = req.header(header::IF_MATCH, format!("\"{}\"", etag));
+            }
+            Precondition::IfNoneMatch => req = req.header(header::IF_NONE_MATCH, "*"),
         }
 
-        let resp = req.send().await?;
+        Ok(req.send().await?)
+    }
+
+    async fn put_with(&self, path: &str, data: &[u8], precondition: Precondition<'_>) -> Result<String> {
+        self.ensure_parents(path).await?;
+        let url = self.full_url(path)?;
+        let mut resp = self.send_put(url.clone(), data, precondition).await?;
+        if resp.status() == StatusCode::CONFLICT {
+            // 目录缓存已过期（被其它设备删除），清空后重建一次
+            self.known_dirs.lock().unwrap_or_else(|e| e.into_inner()).clear();
+            self.ensure_parents(path).await?;
+            resp = self.send_put(url, data, precondition).await?;
+        }
         
         if !resp.status().is_success() {
             return Err(anyhow!("PUT failed: {}", resp.status()));
@@ -90,6 +150,71 @@ impl DavClient for WebDavClient {
 
         Ok(etag)
     }
+}
+
+#[derive(Clone, Copy)]
+enum Precondition<'a> {
+    None,
+    IfMatch(&'a str),
+    /// Only create, never replace
+    IfNoneMatch,
+}
+
+/// `/`, `rules` or `/rules/` -> `/` or `/rules/`
+fn collection_path(path: &str) -> String {
+    let trimmed = path.trim_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{trimmed}/")
+    }
+}
+
+/// 服务器不接受无限深度时的常见回复（RFC 4918 规定为 403）
+fn depth_refused(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::FORBIDDEN | StatusCode::BAD_REQUEST | StatusCode::NOT_IMPLEMENTED
+    )
+}
+
+#[async_trait]
+impl DavClient for WebDavClient {
+    async fn list(&self, path: &str) -> Result<Vec<RemoteEntry>> {
+        let dir = collection_path(path);
+        if !self.finite_depth.load(Ordering::Relaxed) {
+            let (url, resp) = self.propfind(&dir, "infinity").await?;
+            if resp.status().is_success() {
+                return self.parse_listing(&dir, &url, resp).await;
+            }
+            if !depth_refused(resp.status()) {
+                return Err(anyhow!("PROPFIND failed: {}", resp.status()));
+            }
+            tracing::debug!("Depth: infinity refused ({}), walking collections", resp.status());
+            self.finite_depth.store(true, Ordering::Relaxed);
+        }
+        self.walk(&dir).await
+    }
+
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let url = self.full_url(path)?;
+        let resp = self.client.get(url).send().await?;
+        
+        if !resp.status().is_success() {
+            return Err(anyhow!("GET failed: {}", resp.status()));
+        }
+        
+        Ok(resp.bytes().await?.to_vec())
+    }
+
+    async fn put(&self, path: &str, data: &[u8], if_match: Option<&str>) -> Result<String> {
+        let precondition = if_match.map_or(Precondition::None, Precondition::IfMatch);
+        self.put_with(path, data, precondition).await
+    }
+
+    async fn put_new(&self, path: &str, data: &[u8]) -> Result<String> {
+        self.put_with(path, data, Precondition::IfNoneMatch).await
+    }
 
     async fn delete(&self, path: &str) -> Result<()> {
         let url = self.full_url(path)?;
@@ -97,10 +222,16 @@ impl DavClient for WebDavClient {
         if !resp.status().is_success() && resp.status() != reqwest::StatusCode::NOT_FOUND {
             return Err(anyhow!("DELETE failed: {}", resp.status()));
         }
+        let removed = collection_path(path);
+        self.known_dirs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|dir| !dir.starts_with(&removed));
         Ok(())
     }
 
     async fn move_item(&self, from: &str, to: &str) -> Result<()> {
+        self.ensure_parents(to).await?;
         let from_url = self.full_url(from)?;
         let to_url = self.full_url(to)?;
         let resp = self.client
@@ -127,6 +258,186 @@ impl DavClient for WebDavClient {
             // 405 Method Not Allowed 通常意味着目录已存在
             return Err(anyhow!("MKCOL failed: {}", resp.status()));
         }
+        self.known_dirs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(collection_path(path));
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, Method as HttpMethod, StatusCode as HttpStatus, Uri};
+    use axum::response::{IntoResponse, Response as HttpResponse};
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    /// 挂载在 `/dav/` 下的简易 WebDAV 服务器，行为参照 nginx/Apache 的默认配置
+    #[derive(Default)]
+    struct FakeDav {
+        /// 解码后的路径 -> 内容，目录以 `/` 结尾且内容为 None
+        items: Mutex<BTreeMap<String, Option<Vec<u8>>>>,
+        allow_infinity: bool,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl FakeDav {
+        fn parent_exists(items: &BTreeMap<String, Option<Vec<u8>>>, path: &str) -> bool {
+            let parent = &path[..path.trim_end_matches('/').rfind('/').unwrap() + 1];
+            items.contains_key(parent)
+        }
+
+        fn multistatus(&self, dir: &str, infinite: bool) -> String {
+            let items = self.items.lock().unwrap();
+            let mut body = String::from(r#"<?xml version="1.0"?><D:multistatus xmlns:D="DAV:">"#);
+            for (path, content) in items.range(dir.to_string()..) {
+                let Some(rest) = path.strip_prefix(dir) else { break };
+                let depth = rest.trim_end_matches('/').matches('/').count();
+                if !rest.is_empty() && !infinite && depth > 0 {
+                    continue;
+                }
+                let encoded = path.split('/').map(|s| urlencoding::encode(s).into_owned()).collect::<Vec<_>>().join("/");
+                // 文件用完整 URL，目录用绝对路径且不带结尾斜杠
+                let href = match content {
+                    Some(_) => format!("http://fake.invalid{encoded}"),
+                    None => encoded.trim_end_matches('/').to_string(),
+                };
+                let prop = match content {
+                    Some(data) => format!(
+                        "<D:resourcetype/><D:getetag>\"{:x}\"</D:getetag><D:getcontentlength>{}</D:getcontentlength>",
+                        md5::compute(data),
+                        data.len()
+                    ),
+                    None => "<D:resourcetype><D:collection/></D:resourcetype>".to_string(),
+                };
+                body.push_str(&format!(
+                    "<D:response><D:href>{href}</D:href><D:propstat><D:prop>{prop}</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>"
+                ));
+            }
+            body.push_str("</D:multistatus>");
+            body
+        }
+    }
+
+    async fn handle(
+        State(fake): State<Arc<FakeDav>>,
+        method: HttpMethod,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> HttpResponse {
+        let path = urlencoding::decode(uri.path()).unwrap().into_owned();
+        fake.requests.lock().unwrap().push(format!("{method} {path}"));
+        let mut items = fake.items.lock().unwrap();
+        match method.as_str() {
+            "PROPFIND" => {
+                let infinite = headers.get("Depth").is_some_and(|d| d == "infinity");
+                if infinite && !fake.allow_infinity {
+                    return HttpStatus::FORBIDDEN.into_response();
+                }
+                if !items.contains_key(&path) {
+                    return HttpStatus::NOT_FOUND.into_response();
+                }
+                drop(items);
+                (HttpStatus::MULTI_STATUS, fake.multistatus(&path, infinite)).into_response()
+            }
+            "MKCOL" if items.contains_key(&path) => HttpStatus::METHOD_NOT_ALLOWED.into_response(),
+            "MKCOL" if !FakeDav::parent_exists(&items, &path) => HttpStatus::CONFLICT.into_response(),
+            "MKCOL" => {
+                items.insert(path, None);
+                HttpStatus::CREATED.into_response()
+            }
+            "PUT" if !FakeDav::parent_exists(&items, &path) => HttpStatus::CONFLICT.into_response(),
+            "PUT" if headers.get("If-None-Match").is_some_and(|v| v == "*") && items.contains_key(&path) => {
+                HttpStatus::PRECONDITION_FAILED.into_response()
+            }
+            "PUT" => {
+                items.insert(path, Some(body.to_vec()));
+                HttpStatus::CREATED.into_response()
+            }
+            "GET" => match items.get(&path) {
+                Some(Some(data)) => data.clone().into_response(),
+                _ => HttpStatus::NOT_FOUND.into_response(),
+            },
+            "DELETE" => {
+                items.retain(|item, _| !item.starts_with(&path));
+                HttpStatus::NO_CONTENT.into_response()
+            }
+            _ => HttpStatus::METHOD_NOT_ALLOWED.into_response(),
+        }
+    }
+
+    async fn serve(allow_infinity: bool) -> (Arc<FakeDav>, WebDavClient) {
+        let fake = Arc::new(FakeDav { allow_infinity, ..Default::default() });
+        fake.items.lock().unwrap().insert("/dav/".to_string(), None);
+        let app = axum::Router::new().fallback(handle).with_state(fake.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let client = WebDavClient::new(&format!("http://{addr}/dav"), "user", "pass").unwrap();
+        (fake, client)
+    }
+
+    fn file_paths(entries: &[RemoteEntry]) -> Vec<&str> {
+        let mut paths: Vec<_> = entries.iter().filter(|e| !e.is_dir).map(|e| e.path.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn test_put_creates_parents_and_list_walks_collections() {
+        let (fake, client) = serve(false).await;
+        client.put("main.yaml", b"mode: rule\n", None).await.unwrap();
+        client.put("rules/geo/cn #1.yaml", b"a: 1\n", None).await.unwrap();
+        client.put("/rules/geo/us%.yaml", b"b: 2\n", None).await.unwrap();
+        {
+            let requests = fake.requests.lock().unwrap();
+            let mkcols: Vec<_> = requests.iter().filter(|r| r.starts_with("MKCOL")).collect();
+            // 已创建的目录被缓存，不会重复 MKCOL
+            assert_eq!(mkcols, ["MKCOL /dav/rules/", "MKCOL /dav/rules/geo/"]);
+        }
+
+        let entries = client.list("/").await.unwrap();
+        assert_eq!(
+            file_paths(&entries),
+            ["/main.yaml", "/rules/geo/cn #1.yaml", "/rules/geo/us%.yaml"]
+        );
+        assert!(entries.iter().any(|e| e.is_dir && e.path == "/rules/geo/"));
+        assert!(!entries.iter().any(|e| e.path == "/"));
+        assert_eq!(client.get("rules/geo/cn #1.yaml").await.unwrap(), b"a: 1\n");
+
+        let sub = client.list("rules").await.unwrap();
+        assert_eq!(file_paths(&sub), ["/rules/geo/cn #1.yaml", "/rules/geo/us%.yaml"]);
+        let propfinds = fake
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.starts_with("PROPFIND"))
+            .count();
+        // 仅首次尝试 Depth: infinity，之后 /、/rules/、/rules/geo/ 各一次，再加子树两次
+        assert_eq!(propfinds, 1 + 3 + 2);
+
+        // 目录被删除后缓存失效，上传会重新建目录
+        client.delete("rules/").await.unwrap();
+        client.put_new("rules/geo/again.yaml", b"c: 3\n").await.unwrap();
+        let err = client.put_new("rules/geo/again.yaml", b"other").await.unwrap_err();
+        assert!(err.to_string().contains("412"), "{err}");
+        assert_eq!(file_paths(&client.list("/").await.unwrap()), ["/main.yaml", "/rules/geo/again.yaml"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_uses_depth_infinity_when_allowed() {
+        let (fake, client) = serve(true).await;
+        client.put("a/b/c.yaml", b"c", None).await.unwrap();
+        fake.requests.lock().unwrap().clear();
+
+        let entries = client.list("/").await.unwrap();
+        assert_eq!(file_paths(&entries), ["/a/b/c.yaml"]);
+        assert_eq!(*fake.requests.lock().unwrap(), ["PROPFIND /dav/"]);
+    }
+}
@@ -14,7 +14,8 @@ pub struct RemoteEntry {
 
 #[async_trait]
 pub trait DavClient: Send + Sync {
-    /// Recursively list directory contents (Depth: 1)
+    /// List every file and collection below `path`, recursively, with paths
+    /// relative to the client root (e.g. `/rules/a.yaml`, dirs end with `/`)
     async fn list(&self, path: &str) -> Result<Vec<RemoteEntry>>;
     
     /// Download file content
@@ -22,6 +23,13 @@ pub trait DavClient: Send + Sync {
     
     /// Upload file content with atomicity and If-Match support
     async fn put(&self, path: &str, data: &[u8], if_match: Option<&str>) -> Result<String>;
+
+    /// Upload only if nothing exists at `path` yet (`If-None-Match: *`), so
+    /// two devices creating the same file cannot overwrite each other. Fails
+    /// with a 412 error when the file exists.
+    async fn put_new(&self, path: &str, data: &[u8]) -> Result<String> {
+        self.put(path, data, None).await
+    }
     
     /// Delete a file or directory
     async fn delete(&self, path: &str) -> Result<()>;
@@ -33,5 +41,39 @@ pub trait DavClient: Send + Sync {
     async fn mkdir(&self, path: &str) -> Result<()>;
 }
 
+/// Lets a boxed backend chosen at runtime be wrapped like a concrete one
+#[async_trait]
+impl<T: DavClient + ?Sized> DavClient for Box<T> {
+    async fn list(&self, path: &str) -> Result<Vec<RemoteEntry>> {
+        (**self).list(path).await
+    }
+
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        (**self).get(path).await
+    }
+
+    async fn put(&self, path: &str, data: &[u8], if_match: Option<&str>) -> Result<String> {
+        (**self).put(path, data, if_match).await
+    }
+
+    async fn put_new(&self, path: &str, data: &[u8]) -> Result<String> {
+        (**self).put_new(path, data).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        (**self).delete(path).await
+    }
+
+    async fn move_item(&self, from: &str, to: &str) -> Result<()> {
+        (**self).move_item(from, to).await
+    }
+
+    async fn mkdir(&self, path: &str) -> Result<()> {
+        (**self).mkdir(path).await
+    }
+}
+
 pub mod client;
+pub mod local;
+pub mod s3;
 pub mod xml_parser;
@@ -0,0 +1,224 @@
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::{DavClient, RemoteEntry};
+
+/// Suffix of in-flight uploads, never listed
+const TMP_SUFFIX: &str = ".mf-upload";
+
+/// Sync target on a local or mounted directory, e.g. a NAS share or a
+/// Syncthing folder. ETags are content hashes, so `If-Match` behaves like
+/// on a server.
+pub struct LocalDirClient {
+    root: PathBuf,
+}
+
+impl LocalDirClient {
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        if root.as_os_str().is_empty() {
+            bail!("sync directory is empty");
+        }
+        Ok(Self { root })
+    }
+
+    fn full_path(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path.trim_start_matches('/'));
+        if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            bail!("Invalid path: {path}");
+        }
+        Ok(self.root.join(relative))
+    }
+
+    async fn current_etag(path: &Path) -> Result<Option<String>> {
+        match fs::read(path).await {
+            Ok(content) => Ok(Some(etag_of(&content))),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn list_recursive(&self, dir: &Path, entries: &mut Vec<RemoteEntry>) -> Result<()> {
+        let mut reader = fs::read_dir(dir).await?;
+        while let Some(entry) = reader.next_entry().await? {
+            let path = entry.path();
+            let metadata = entry.metadata().await?;
+            let relative = path
+                .strip_prefix(&self.root)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            if relative.ends_with(TMP_SUFFIX) {
+                continue;
+            }
+            let last_modified: DateTime<Utc> = metadata
+                .modified()
+                .unwrap_or_else(|_| SystemTime::now())
+                .into();
+
+            if metadata.is_dir() {
+                entries.push(RemoteEntry {
+                    path: format!("/{relative}/"),
+                    etag: String::new(),
+                    last_modified,
+                    is_dir: true,
+                    size: 0,
+                });
+                Box::pin(self.list_recursive(&path, entries)).await?;
+            } else if metadata.is_file() {
+                entries.push(RemoteEntry {
+                    path: format!("/{relative}"),
+                    etag: etag_of(&fs::read(&path).await?),
+                    last_modified,
+                    is_dir: false,
+                    size: metadata.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn etag_of(content: &[u8]) -> String {
+    format!("{:x}", md5::compute(content))
+}
+
+#[async_trait]
+impl DavClient for LocalDirClient {
+    async fn list(&self, path: &str) -> Result<Vec<RemoteEntry>> {
+        if !self.root.is_dir() {
+            bail!("sync directory does not exist: {}", self.root.display());
+        }
+        let mut entries = Vec::new();
+        self.list_recursive(&self.full_path(path)?, &mut entries).await?;
+        Ok(entries)
+    }
+
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let full = self.full_path(path)?;
+        fs::read(&full).await.map_err(|e| anyhow!("GET failed: {}: {e}", full.display()))
+    }
+
+    async fn put(&self, path: &str, data: &[u8], if_match: Option<&str>) -> Result<String> {
+        let full = self.full_path(path)?;
+        if let Some(expected) = if_match
+            && Self::current_etag(&full).await?.as_deref() != Some(expected)
+        {
+            return Err(anyhow!("PUT failed: 412 Precondition Failed"));
+        }
+        if let Some(parent) = full.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut tmp = full.clone().into_os_string();
+        tmp.push(TMP_SUFFIX);
+        fs::write(&tmp, data).await?;
+        fs::rename(&tmp, &full).await?;
+        Ok(etag_of(data))
+    }
+
+    async fn put_new(&self, path: &str, data: &[u8]) -> Result<String> {
+        let full = self.full_path(path)?;
+        if let Some(parent) = full.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // create_new 保证并发创建时只有一方成功
+        let mut file = match fs::OpenOptions::new().write(true).create_new(true).open(&full).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                return Err(anyhow!("PUT failed: 412 Precondition Failed"));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        Ok(etag_of(data))
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        let full = self.full_path(path)?;
+        let result = if full.is_dir() {
+            fs::remove_dir_all(&full).await
+        } else {
+            fs::remove_file(&full).await
+        };
+        match result {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(anyhow!("DELETE failed: {err}")),
+            _ => Ok(()),
+        }
+    }
+
+    async fn move_item(&self, from: &str, to: &str) -> Result<()> {
+        let to = self.full_path(to)?;
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(self.full_path(from)?, to)
+            .await
+            .map_err(|e| anyhow!("MOVE failed: {e}"))
+    }
+
+    async fn mkdir(&self, path: &str) -> Result<()> {
+        fs::create_dir_all(self.full_path(path)?).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_dir_round_trip_and_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = LocalDirClient::new(dir.path()).unwrap();
+
+        let etag = client.put("rules/a.yaml", b"a: 1\n", None).await.unwrap();
+        client.put("/main.yaml", b"mode: rule\n", None).await.unwrap();
+        assert_eq!(client.get("rules/a.yaml").await.unwrap(), b"a: 1\n");
+
+        let mut files: Vec<_> = client
+            .list("/")
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| (entry.path, entry.etag))
+            .collect();
+        files.sort();
+        assert_eq!(files[0].0, "/main.yaml");
+        assert_eq!(files[1], ("/rules/a.yaml".to_string(), etag.clone()));
+
+        client.move_item("rules/a.yaml", "rules/b.yaml").await.unwrap();
+        client.delete("main.yaml").await.unwrap();
+        client.delete("main.yaml").await.unwrap();
+        let paths: Vec<_> = client.list("/").await.unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&"/rules/b.yaml".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_local_dir_conditional_put_and_path_checks() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = LocalDirClient::new(dir.path()).unwrap();
+        let first = client.put("p.yaml", b"v1", None).await.unwrap();
+        let second = client.put("p.yaml", b"v2", Some(&first)).await.unwrap();
+        assert_ne!(first, second);
+
+        let err = client.put("p.yaml", b"v3", Some(&first)).await.unwrap_err();
+        assert!(err.to_string().contains("412"));
+        assert_eq!(client.get("p.yaml").await.unwrap(), b"v2");
+        assert!(client.put("p.yaml", b"v3", Some("missing")).await.is_err());
+        assert!(client.put_new("p.yaml", b"v3").await.unwrap_err().to_string().contains("412"));
+        assert_eq!(client.put_new("n/new.yaml", b"n").await.unwrap(), etag_of(b"n"));
+
+        assert!(client.get("../outside.yaml").await.is_err());
+        assert!(LocalDirClient::new("").is_err());
+        let missing = LocalDirClient::new(dir.path().join("missing")).unwrap();
+        assert!(missing.list("/").await.is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use url::Url;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename = "multistatus", rename_all = "lowercase")]
@@ -42,7 +43,51 @@ pub struct ResourceType {
 #[derive(Debug, Deserialize)]
 pub struct Collection {}
 
+/// Parse a PROPFIND reply, keeping each href as the decoded path the server sent
 pub fn parse_multistatus(xml: &str) -> Result<Vec<crate::RemoteEntry>> {
+    parse_entries(xml, |href| Ok(Some(urlencoding::decode(href)?.into_owned())))
+}
+
+/// Parse a PROPFIND sent to `request_url`, with paths relative to `base_url`
+///
+/// Hrefs may be full URLs, absolute paths or relative to the request; entries
+/// outside `base_url` are dropped. Collections always end with `/`.
+pub fn parse_multistatus_at(
+    xml: &str,
+    request_url: &Url,
+    base_url: &Url,
+) -> Result<Vec<crate::RemoteEntry>> {
+    let mut entries = parse_entries(xml, |href| resolve_href(request_url, base_url, href))?;
+    for entry in &mut entries {
+        if entry.is_dir && !entry.path.ends_with('/') {
+            entry.path.push('/');
+        }
+    }
+    Ok(entries)
+}
+
+/// Map an href onto a decoded path below `base_url`, e.g. `/rules/a.yaml`
+pub fn resolve_href(request_url: &Url, base_url: &Url, href: &str) -> Result<Option<String>> {
+    let resolved = request_url
+        .join(href.trim())
+        .map_err(|e| anyhow!("Invalid href {:?}: {}", href, e))?;
+    // 两边都先解码再比较，服务器与本地的百分号编码风格可能不同
+    let path = urlencoding::decode(resolved.path())?;
+    let base = urlencoding::decode(base_url.path())?;
+    let base_dir = base.trim_end_matches('/');
+    let Some(rest) = path.strip_prefix(base_dir) else {
+        return Ok(None);
+    };
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return Ok(None);
+    }
+    Ok(Some(format!("/{}", rest.trim_start_matches('/'))))
+}
+
+fn parse_entries(
+    xml: &str,
+    to_path: impl Fn(&str) -> Result<Option<String>>,
+) -> Result<Vec<crate::RemoteEntry>> {
     let ms: MultiStatus = quick_xml::de::from_str(xml)
         .map_err(|e| anyhow!("Failed to parse WebDAV XML: {}", e))?;
 
@@ -50,6 +95,9 @@ pub fn parse_multistatus(xml: &str) -> Result<Vec<crate::RemoteEntry>> {
     for resp in ms.responses {
         // 提取成功的 propstat (通常是 HTTP/1.1 200 OK)
         if let Some(ok_stat) = resp.propstats.iter().find(|s| s.status.contains("200")) {
+            let Some(path) = to_path(&resp.href)? else {
+                continue;
+            };
             let is_dir = ok_stat.prop.resource_type.collection.is_some();
             
             // 解析最后修改时间
@@ -59,7 +107,7 @@ pub fn parse_multistatus(xml: &str) -> Result<Vec<crate::RemoteEntry>> {
                 .unwrap_or_else(Utc::now);
 
             entries.push(crate::RemoteEntry {
-                path: urlencoding::decode(&resp.href)?.into_owned(),
+                path,
                 etag: ok_stat.prop.etag.clone().unwrap_or_default().replace('"', ""),
                 last_modified,
                 is_dir,
@@ -178,4 +226,36 @@ mod tests {
         let entries = parse_multistatus(xml).unwrap();
         assert_eq!(entries[0].etag, "quoted-etag-value");
     }
+
+    #[test]
+    fn test_resolve_href_forms() {
+        let base = Url::parse("https://dav.example.com/remote.php/dav/files/me/").unwrap();
+        let request = base.join("mihomo/").unwrap();
+        let resolve = |href: &str| resolve_href(&request, &base, href).unwrap();
+
+        assert_eq!(
+            resolve("https://dav.example.com/remote.php/dav/files/me/mihomo/a.yaml").as_deref(),
+            Some("/mihomo/a.yaml")
+        );
+        assert_eq!(
+            resolve("/remote.php/dav/files/me/mihomo/rules/b%20c.yaml").as_deref(),
+            Some("/mihomo/rules/b c.yaml")
+        );
+        assert_eq!(resolve("rules/%E8%A7%84%E5%88%99.yaml").as_deref(), Some("/mihomo/rules/规则.yaml"));
+        assert_eq!(resolve("/remote.php/dav/files/me/").as_deref(), Some("/"));
+        assert_eq!(resolve("/remote.php/dav/files/me").as_deref(), Some("/"));
+        // 不在同步根目录之下的条目被忽略
+        assert_eq!(resolve("/remote.php/dav/files/other/a.yaml"), None);
+        assert_eq!(resolve("/remote.php/dav/files/meow/a.yaml"), None);
+    }
+
+    #[test]
+    fn test_parse_multistatus_at_base_path() {
+        let base = Url::parse("http://127.0.0.1:8080/mihomo/").unwrap();
+        let xml = SAMPLE_PROPFIND_RESPONSE.replace("<D:href>/mihomo/</D:href>", "<D:href>/mihomo</D:href>");
+        let entries = parse_multistatus_at(&xml, &base, &base).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["/", "/config.yaml", "/rules/proxy.yaml"]);
+        assert!(entries[0].is_dir);
+    }
 }
@@ -13,6 +13,20 @@ pub struct SyncStateRow {
     pub is_tombstone: i64, // SQLite 不直接支持 bool，用 i64
 }
 
+/// A file both sides changed in ways the sync engine could not merge
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct SyncConflictRow {
+    pub path: String,
+    /// Local content hash when the conflict was found
+    pub local_hash: String,
+    /// Remote ETag when the conflict was found; uploads resolving the
+    /// conflict must match it
+    pub remote_etag: String,
+    /// Overlapping edits, one per line
+    pub reason: String,
+    pub detected_at: DateTime<Utc>,
+}
+
 pub struct StateStore {
     pool: SqlitePool,
 }
@@ -46,6 +60,22 @@ impl StateStore {
                 is_tombstone INTEGER NOT NULL DEFAULT 0
             )"
         ).execute(pool).await?;
+        // 上次同步成功时的内容，作为三方合并的共同祖先
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS sync_base (
+                path TEXT PRIMARY KEY,
+                content BLOB NOT NULL
+            )"
+        ).execute(pool).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS sync_conflict (
+                path TEXT PRIMARY KEY,
+                local_hash TEXT NOT NULL,
+                remote_etag TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                detected_at DATETIME NOT NULL
+            )"
+        ).execute(pool).await?;
         Ok(())
     }
 
@@ -100,6 +130,78 @@ impl StateStore {
             .await?;
         Ok(())
     }
+
+    /// Content both sides agreed on after the last successful sync
+    pub async fn get_base(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let content = sqlx::query_scalar::<_, Vec<u8>>("SELECT content FROM sync_base WHERE path = ?")
+            .bind(path)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(content)
+    }
+
+    pub async fn set_base(&self, path: &str, content: &[u8]) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO sync_base (path, content) VALUES (?, ?)
+             ON CONFLICT(path) DO UPDATE SET content = excluded.content"
+        )
+        .bind(path)
+        .bind(content)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_base(&self, path: &str) -> Result<()> {
+        sqlx::query("DELETE FROM sync_base WHERE path = ?")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn record_conflict(&self, row: SyncConflictRow) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO sync_conflict (path, local_hash, remote_etag, reason, detected_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(path) DO UPDATE SET
+                local_hash = excluded.local_hash,
+                remote_etag = excluded.remote_etag,
+                reason = excluded.reason,
+                detected_at = excluded.detected_at"
+        )
+        .bind(row.path)
+        .bind(row.local_hash)
+        .bind(row.remote_etag)
+        .bind(row.reason)
+        .bind(row.detected_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_conflict(&self, path: &str) -> Result<Option<SyncConflictRow>> {
+        let row = sqlx::query_as::<_, SyncConflictRow>("SELECT * FROM sync_conflict WHERE path = ?")
+            .bind(path)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row)
+    }
+
+    pub async fn list_conflicts(&self) -> Result<Vec<SyncConflictRow>> {
+        let rows = sqlx::query_as::<_, SyncConflictRow>("SELECT * FROM sync_conflict ORDER BY path")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    pub async fn clear_conflict(&self, path: &str) -> Result<()> {
+        sqlx::query("DELETE FROM sync_conflict WHERE path = ?")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -207,4 +309,38 @@ mod tests {
         let retrieved = store.get_state("tombstone.yaml").await.unwrap().unwrap();
         assert_eq!(retrieved.is_tombstone, 1);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_base_content() {
+        let store = StateStore::new_in_memory().await.unwrap();
+        assert!(store.get_base("a.yaml").await.unwrap().is_none());
+
+        store.set_base("a.yaml", b"mode: rule\n").await.unwrap();
+        store.set_base("a.yaml", b"mode: global\n").await.unwrap();
+        assert_eq!(store.get_base("a.yaml").await.unwrap().as_deref(), Some(&b"mode: global\n"[..]));
+
+        store.delete_base("a.yaml").await.unwrap();
+        assert!(store.get_base("a.yaml").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_conflicts() {
+        let store = StateStore::new_in_memory().await.unwrap();
+        let row = SyncConflictRow {
+            path: "b.yaml".to_string(),
+            local_hash: "hash".to_string(),
+            remote_etag: "etag".to_string(),
+            reason: "mode".to_string(),
+            detected_at: Utc::now(),
+        };
+        store.record_conflict(row.clone()).await.unwrap();
+        store.record_conflict(SyncConflictRow { path: "a.yaml".to_string(), ..row.clone() }).await.unwrap();
+
+        let all = store.list_conflicts().await.unwrap();
+        assert_eq!(all.iter().map(|c| c.path.as_str()).collect::<Vec<_>>(), ["a.yaml", "b.yaml"]);
+        assert_eq!(store.get_conflict("b.yaml").await.unwrap().unwrap().remote_etag, "etag");
+
+        store.clear_conflict("b.yaml").await.unwrap();
+        assert!(store.get_conflict("b.yaml").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,508 @@
+//! End-to-end encryption of synced files.
+//!
+//! Remote file layout: `MFE` magic, one version byte, a 12-byte nonce, then
+//! the ChaCha20-Poly1305 ciphertext. The header and the plaintext path are
+//! bound as associated data, so a file cannot be replayed under another name.
+//! Encrypted files carry the `.mfenc` suffix; with name obfuscation the whole
+//! relative path becomes a deterministic, authenticated token instead.
+//!
+//! The key is derived from a passphrase with PBKDF2-HMAC-SHA256. Salt,
+//! iteration count and an encrypted known value live in [`KEY_CHECK_FILE`] on
+//! the remote, so a second device with a wrong passphrase fails before it
+//! touches any file.
+
+use std::num::NonZeroU32;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{hmac, pbkdf2};
+use serde::{Deserialize, Serialize};
+
+use dav_client::{DavClient, RemoteEntry};
+
+/// Remote file holding the KDF parameters and the key-verification value
+pub const KEY_CHECK_FILE: &str = ".musicfrog-e2e.json";
+/// Suffix of every encrypted remote file
+pub const ENCRYPTED_SUFFIX: &str = ".mfenc";
+
+const MAGIC: &[u8; 3] = b"MFE";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const KEY_CHECK_PLAINTEXT: &[u8] = b"musicfrog-e2e-key-check";
+
+/// Key material derived from the sync passphrase
+#[derive(Clone)]
+pub struct SyncKey {
+    master: [u8; KEY_LEN],
+}
+
+impl std::fmt::Debug for SyncKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SyncKey(..)")
+    }
+}
+
+impl SyncKey {
+    fn derive(passphrase: &str, salt: &[u8], iterations: NonZeroU32) -> Self {
+        let mut master = [0u8; KEY_LEN];
+        pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.as_bytes(), &mut master);
+        Self { master }
+    }
+
+    /// Encoded form for the credential store
+    pub fn export(&self) -> String {
+        STANDARD.encode(self.master)
+    }
+
+    pub fn import(encoded: &str) -> Result<Self> {
+        let bytes = STANDARD.decode(encoded.trim()).context("invalid sync key encoding")?;
+        let master = bytes.try_into().map_err(|_| anyhow!("invalid sync key length"))?;
+        Ok(Self { master })
+    }
+
+    fn subkey(&self, label: &str) -> [u8; KEY_LEN] {
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &self.master), label.as_bytes());
+        let mut key = [0u8; KEY_LEN];
+        key.copy_from_slice(tag.as_ref());
+        key
+    }
+
+    fn aead(&self, label: &str) -> LessSafeKey {
+        let unbound = UnboundKey::new(&CHACHA20_POLY1305, &self.subkey(label))
+            .expect("subkey has the ChaCha20-Poly1305 key length");
+        LessSafeKey::new(unbound)
+    }
+
+    /// Encrypt `plaintext` stored under the relative `path`
+    pub fn seal(&self, path: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("system random generator failed"))?;
+
+        let mut out = Vec::with_capacity(HEADER_LEN + NONCE_LEN + plaintext.len() + CHACHA20_POLY1305.tag_len());
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&nonce);
+        let mut body = plaintext.to_vec();
+        self.aead("content")
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(content_aad(path)),
+                &mut body,
+            )
+            .map_err(|_| anyhow!("encryption failed"))?;
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// Decrypt a blob written by [`SyncKey::seal`] for the same `path`
+    pub fn open(&self, path: &str, blob: &[u8]) -> Result<Vec<u8>> {
+        if blob.len() < HEADER_LEN + NONCE_LEN || &blob[..MAGIC.len()] != MAGIC {
+            bail!("{path} is not an encrypted sync file");
+        }
+        if blob[MAGIC.len()] != FORMAT_VERSION {
+            bail!("{path} uses unsupported encryption format version {}", blob[MAGIC.len()]);
+        }
+        let nonce: [u8; NONCE_LEN] = blob[HEADER_LEN..HEADER_LEN + NONCE_LEN].try_into()?;
+        let mut body = blob[HEADER_LEN + NONCE_LEN..].to_vec();
+        let plaintext = self
+            .aead("content")
+            .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(content_aad(path)), &mut body)
+            .map_err(|_| anyhow!("failed to decrypt {path}: wrong key or corrupted file"))?;
+        Ok(plaintext.to_vec())
+    }
+
+    /// Deterministic remote name for `path`; the nonce is a MAC of the path,
+    /// which is checked again when the name is decoded
+    fn encode_name(&self, path: &str) -> Result<String> {
+        let nonce = self.name_nonce(path);
+        let mut body = path.as_bytes().to_vec();
+        self.aead("name")
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut body)
+            .map_err(|_| anyhow!("name encryption failed"))?;
+        let mut token = nonce.to_vec();
+        token.extend_from_slice(&body);
+        let name = format!("{}{ENCRYPTED_SUFFIX}", URL_SAFE_NO_PAD.encode(token));
+        if name.len() > 255 {
+            bail!("path too long for name obfuscation: {path}");
+        }
+        Ok(name)
+    }
+
+    fn decode_name(&self, name: &str) -> Option<String> {
+        let token = URL_SAFE_NO_PAD.decode(name.strip_suffix(ENCRYPTED_SUFFIX)?).ok()?;
+        if token.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, body) = token.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().ok()?;
+        let mut body = body.to_vec();
+        let path = self
+            .aead("name")
+            .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut body)
+            .ok()?;
+        let path = String::from_utf8(path.to_vec()).ok()?;
+        (self.name_nonce(&path) == nonce).then_some(path)
+    }
+
+    fn name_nonce(&self, path: &str) -> [u8; NONCE_LEN] {
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &self.subkey("name-nonce")), path.as_bytes());
+        tag.as_ref()[..NONCE_LEN].try_into().expect("HMAC-SHA256 is longer than a nonce")
+    }
+}
+
+fn content_aad(path: &str) -> Vec<u8> {
+    let mut aad = MAGIC.to_vec();
+    aad.push(FORMAT_VERSION);
+    aad.extend_from_slice(path.trim_start_matches('/').as_bytes());
+    aad
+}
+
+/// Contents of [`KEY_CHECK_FILE`]
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyCheck {
+    version: u8,
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    check: String,
+}
+
+impl KeyCheck {
+    fn create(passphrase: &str, iterations: u32) -> Result<(Self, SyncKey)> {
+        let mut salt = [0u8; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| anyhow!("system random generator failed"))?;
+        let key = SyncKey::derive(passphrase, &salt, non_zero(iterations)?);
+        let check = key.seal(KEY_CHECK_FILE, KEY_CHECK_PLAINTEXT)?;
+        let file = Self {
+            version: FORMAT_VERSION,
+            kdf: "pbkdf2-sha256".to_string(),
+            iterations,
+            salt: STANDARD.encode(salt),
+            check: STANDARD.encode(check),
+        };
+        Ok((file, key))
+    }
+
+    fn derive(&self, passphrase: &str) -> Result<SyncKey> {
+        if self.kdf != "pbkdf2-sha256" {
+            bail!("unsupported key derivation {:?}", self.kdf);
+        }
+        let salt = STANDARD.decode(&self.salt).context("invalid key check salt")?;
+        Ok(SyncKey::derive(passphrase, &salt, non_zero(self.iterations)?))
+    }
+
+    fn unlock(&self, passphrase: &str) -> Result<SyncKey> {
+        let key = self.derive(passphrase)?;
+        self.verify(&key)?;
+        Ok(key)
+    }
+
+    fn verify(&self, key: &SyncKey) -> Result<()> {
+        let check = STANDARD.decode(&self.check).context("invalid key check value")?;
+        match key.open(KEY_CHECK_FILE, &check) {
+            Ok(plaintext) if plaintext == KEY_CHECK_PLAINTEXT => Ok(()),
+            _ => bail!("sync passphrase does not match the one used by other devices"),
+        }
+    }
+}
+
+fn non_zero(iterations: u32) -> Result<NonZeroU32> {
+    NonZeroU32::new(iterations).ok_or_else(|| anyhow!("key derivation needs at least one iteration"))
+}
+
+async fn read_key_check(dav: &dyn DavClient) -> Result<Option<KeyCheck>> {
+    if !is_encrypted(dav).await? {
+        return Ok(None);
+    }
+    let raw = dav.get(KEY_CHECK_FILE).await?;
+    let check = serde_json::from_slice(&raw).context("invalid key check file")?;
+    Ok(Some(check))
+}
+
+/// Whether the remote holds end-to-end encrypted files
+pub async fn is_encrypted(dav: &dyn DavClient) -> Result<bool> {
+    Ok(dav
+        .list("/")
+        .await?
+        .iter()
+        .any(|entry| !entry.is_dir && entry.path.trim_start_matches('/') == KEY_CHECK_FILE))
+}
+
+/// Derive the key for `passphrase`, checking it against the remote key check
+/// file or creating that file when this is the first encrypted device
+pub async fn unlock(dav: &dyn DavClient, passphrase: &str) -> Result<SyncKey> {
+    unlock_with_iterations(dav, passphrase, PBKDF2_ITERATIONS).await
+}
+
+async fn unlock_with_iterations(dav: &dyn DavClient, passphrase: &str, iterations: u32) -> Result<SyncKey> {
+    if passphrase.is_empty() {
+        bail!("sync passphrase must not be empty");
+    }
+    if let Some(check) = read_key_check(dav).await? {
+        return check.unlock(passphrase);
+    }
+    let (check, key) = KeyCheck::create(passphrase, iterations)?;
+    if let Err(err) = dav.put_new(KEY_CHECK_FILE, &serde_json::to_vec_pretty(&check)?).await {
+        // 另一台设备抢先创建了校验文件时，按它的盐值重新校验
+        let check = read_key_check(dav).await?.ok_or(err)?;
+        return check.unlock(passphrase);
+    }
+    Ok(key)
+}
+
+/// [`DavClient`] that encrypts on upload and decrypts on download. Paths seen
+/// by callers stay in plaintext; only `.mfenc` files are listed.
+pub struct EncryptedDav<D> {
+    inner: D,
+    key: SyncKey,
+    obfuscate_names: bool,
+}
+
+impl<D: DavClient> EncryptedDav<D> {
+    /// Wrap `inner` after checking `key` against the remote key check file
+    pub async fn open(inner: D, key: SyncKey, obfuscate_names: bool) -> Result<Self> {
+        let check = read_key_check(&inner)
+            .await?
+            .ok_or_else(|| anyhow!("remote has no {KEY_CHECK_FILE}; set the sync passphrase first"))?;
+        check.verify(&key)?;
+        Ok(Self { inner, key, obfuscate_names })
+    }
+
+    fn remote_name(&self, path: &str) -> Result<String> {
+        let path = path.trim_start_matches('/');
+        if self.obfuscate_names {
+            self.key.encode_name(path)
+        } else {
+            Ok(format!("{path}{ENCRYPTED_SUFFIX}"))
+        }
+    }
+
+    /// Plaintext path of a listed remote path, keeping its directory prefix
+    fn plain_path(&self, remote: &str) -> Option<String> {
+        let (dir, name) = remote.rsplit_once('/').unwrap_or(("", remote));
+        let decoded = self.key.decode_name(name);
+        if !self.obfuscate_names {
+            // 跳过混淆模式留下的文件
+            return match decoded {
+                Some(_) => None,
+                None => remote.strip_suffix(ENCRYPTED_SUFFIX).map(str::to_string),
+            };
+        }
+        let path = decoded?;
+        Some(if dir.is_empty() && !remote.starts_with('/') { path } else { format!("{dir}/{path}") })
+    }
+}
+
+#[async_trait]
+impl<D: DavClient> DavClient for EncryptedDav<D> {
+    async fn list(&self, path: &str) -> Result<Vec<RemoteEntry>> {
+        let entries = self.inner.list(path).await?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|mut entry| {
+                if entry.is_dir {
+                    return Some(entry);
+                }
+                entry.path = self.plain_path(&entry.path)?;
+                Some(entry)
+            })
+            .collect())
+    }
+
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let blob = self.inner.get(&self.remote_name(path)?).await?;
+        self.key.open(path, &blob)
+    }
+
+    async fn put(&self, path: &str, data: &[u8], if_match: Option<&str>) -> Result<String> {
+        let blob = self.key.seal(path, data)?;
+        self.inner.put(&self.remote_name(path)?, &blob, if_match).await
+    }
+
+    async fn put_new(&self, path: &str, data: &[u8]) -> Result<String> {
+        let blob = self.key.seal(path, data)?;
+        self.inner.put_new(&self.remote_name(path)?, &blob).await
+    }
+
+    async fn delete(&self, path: &str) -> Result<()> {
+        self.inner.delete(&self.remote_name(path)?).await
+    }
+
+    async fn move_item(&self, from: &str, to: &str) -> Result<()> {
+        self.inner.move_item(&self.remote_name(from)?, &self.remote_name(to)?).await
+    }
+
+    async fn mkdir(&self, path: &str) -> Result<()> {
+        // 混淆后所有文件都在根目录
+        if self.obfuscate_names {
+            return Ok(());
+        }
+        self.inner.mkdir(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default, Clone)]
+    struct MemoryDav {
+        files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+        /// Next listing is taken before another device wrote, so it misses files
+        stale_list: Arc<AtomicBool>,
+    }
+
+    impl MemoryDav {
+        fn raw(&self, path: &str) -> Vec<u8> {
+            self.files.lock().unwrap()[path].clone()
+        }
+    }
+
+    #[async_trait]
+    impl DavClient for MemoryDav {
+        async fn list(&self, _path: &str) -> Result<Vec<RemoteEntry>> {
+            if self.stale_list.swap(false, Ordering::SeqCst) {
+                return Ok(vec![]);
+            }
+            Ok(self.files.lock().unwrap().keys().map(|path| RemoteEntry {
+                path: format!("/{path}"),
+                etag: "e".to_string(),
+                last_modified: chrono::Utc::now(),
+                is_dir: false,
+                size: 0,
+            }).collect())
+        }
+        async fn get(&self, path: &str) -> Result<Vec<u8>> {
+            self.files.lock().unwrap().get(path).cloned().ok_or_else(|| anyhow!("GET failed: 404"))
+        }
+        async fn put(&self, path: &str, data: &[u8], _if_match: Option<&str>) -> Result<String> {
+            self.files.lock().unwrap().insert(path.to_string(), data.to_vec());
+            Ok("e".to_string())
+        }
+        async fn put_new(&self, path: &str, data: &[u8]) -> Result<String> {
+            let mut files = self.files.lock().unwrap();
+            if files.contains_key(path) {
+                return Err(anyhow!("PUT failed: 412 Precondition Failed"));
+            }
+            files.insert(path.to_string(), data.to_vec());
+            Ok("e".to_string())
+        }
+        async fn delete(&self, path: &str) -> Result<()> {
+            self.files.lock().unwrap().remove(path);
+            Ok(())
+        }
+        async fn move_item(&self, _from: &str, _to: &str) -> Result<()> { Ok(()) }
+        async fn mkdir(&self, _path: &str) -> Result<()> { Ok(()) }
+    }
+
+    const TEST_ITERATIONS: u32 = 10;
+
+    #[test]
+    fn test_seal_open_round_trip_and_binding() {
+        let key = SyncKey::derive("secret", b"0123456789abcdef", non_zero(TEST_ITERATIONS).unwrap());
+        let blob = key.seal("main.yaml", b"password: hunter2").unwrap();
+        assert_eq!(&blob[..4], b"MFE\x01");
+        assert!(!blob.windows(7).any(|window| window == b"hunter2"));
+        assert_eq!(key.open("main.yaml", &blob).unwrap(), b"password: hunter2");
+        // Fresh nonce per upload
+        assert_ne!(key.seal("main.yaml", b"password: hunter2").unwrap(), blob);
+
+        assert!(key.open("other.yaml", &blob).is_err());
+        let mut tampered = blob.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(key.open("main.yaml", &tampered).is_err());
+        let mut future = blob;
+        future[3] = 2;
+        assert!(key.open("main.yaml", &future).unwrap_err().to_string().contains("version 2"));
+
+        let imported = SyncKey::import(&key.export()).unwrap();
+        assert_eq!(imported.master, key.master);
+    }
+
+    #[test]
+    fn test_obfuscated_names_are_deterministic_and_authenticated() {
+        let key = SyncKey::derive("secret", b"0123456789abcdef", non_zero(TEST_ITERATIONS).unwrap());
+        let name = key.encode_name("rules/private.yaml").unwrap();
+        assert_eq!(name, key.encode_name("rules/private.yaml").unwrap());
+        assert!(name.ends_with(ENCRYPTED_SUFFIX) && !name.contains("private") && !name.contains('/'));
+        assert_eq!(key.decode_name(&name).as_deref(), Some("rules/private.yaml"));
+
+        let other = SyncKey::derive("other", b"0123456789abcdef", non_zero(TEST_ITERATIONS).unwrap());
+        assert_eq!(other.decode_name(&name), None);
+        assert_eq!(key.decode_name("plain.yaml"), None);
+    }
+
+    #[tokio::test]
+    async fn test_second_device_needs_matching_passphrase() {
+        let remote = MemoryDav::default();
+        let key = unlock_with_iterations(&remote, "secret", TEST_ITERATIONS).await.unwrap();
+        let check: KeyCheck = serde_json::from_slice(&remote.raw(KEY_CHECK_FILE)).unwrap();
+        assert_eq!(check.iterations, TEST_ITERATIONS);
+
+        let again = unlock_with_iterations(&remote, "secret", PBKDF2_ITERATIONS).await.unwrap();
+        assert_eq!(again.master, key.master);
+        let err = unlock_with_iterations(&remote, "wrong", TEST_ITERATIONS).await.unwrap_err();
+        assert!(err.to_string().contains("does not match"));
+
+        let stranger = SyncKey::derive("wrong", b"0123456789abcdef", non_zero(TEST_ITERATIONS).unwrap());
+        assert!(EncryptedDav::open(remote.clone(), stranger, false).await.is_err());
+        assert!(EncryptedDav::open(MemoryDav::default(), key.clone(), false).await.is_err());
+
+        // The first device wins a concurrent first unlock; the other adopts its key
+        let racing = MemoryDav { stale_list: Arc::new(AtomicBool::new(true)), ..remote.clone() };
+        let adopted = unlock_with_iterations(&racing, "secret", TEST_ITERATIONS).await.unwrap();
+        assert_eq!(adopted.master, key.master);
+        assert_eq!(serde_json::from_slice::<KeyCheck>(&remote.raw(KEY_CHECK_FILE)).unwrap().salt, check.salt);
+        racing.stale_list.store(true, Ordering::SeqCst);
+        assert!(unlock_with_iterations(&racing, "wrong", TEST_ITERATIONS).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_key_check_must_sit_at_the_root() {
+        let remote = MemoryDav::default();
+        remote.put("backup/.musicfrog-e2e.json", b"{}", None).await.unwrap();
+        assert!(!is_encrypted(&remote).await.unwrap());
+        unlock_with_iterations(&remote, "secret", TEST_ITERATIONS).await.unwrap();
+        assert!(is_encrypted(&remote).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_dav_maps_paths_and_contents() {
+        for obfuscate in [false, true] {
+            let remote = MemoryDav::default();
+            let key = unlock_with_iterations(&remote, "secret", TEST_ITERATIONS).await.unwrap();
+            remote.put("legacy.yaml", b"plaintext", None).await.unwrap();
+            // A file written in the other naming mode stays invisible
+            let other_mode = EncryptedDav::open(remote.clone(), key.clone(), !obfuscate).await.unwrap();
+            other_mode.put("old.yaml", b"mode: rule\n", None).await.unwrap();
+            let dav = EncryptedDav::open(remote.clone(), key, obfuscate).await.unwrap();
+
+            dav.put("sub/main.yaml", b"mode: rule\n", None).await.unwrap();
+            assert_eq!(dav.get("sub/main.yaml").await.unwrap(), b"mode: rule\n");
+
+            let paths: Vec<_> = dav.list("/").await.unwrap().into_iter().map(|entry| entry.path).collect();
+            // Only encrypted files are listed, under their plaintext paths
+            assert_eq!(paths, ["/sub/main.yaml"], "obfuscate={obfuscate}");
+            let stored: Vec<_> = remote.files.lock().unwrap().keys().cloned().collect();
+            assert_eq!(stored.iter().any(|name| name.contains("main")), !obfuscate);
+
+            dav.delete("sub/main.yaml").await.unwrap();
+            assert!(dav.list("/").await.unwrap().is_empty());
+            assert_eq!(other_mode.list("/").await.unwrap()[0].path, "/old.yaml");
+        }
+    }
+}
@@ -1,12 +1,24 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use chrono::Utc;
 use tracing::{info, warn};
 
 use dav_client::DavClient;
-use state_store::{StateStore, SyncStateRow};
+use state_store::{StateStore, SyncConflictRow, SyncStateRow};
+use crate::merge::{self, MergeOutcome};
+use crate::policy::ConflictStrategy;
 use crate::SyncAction;
 
+/// How a recorded conflict is settled by hand
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictResolution {
+    KeepLocal,
+    KeepRemote,
+    /// Content merged by the user, written to both sides
+    Merged(Vec<u8>),
+}
+
 pub struct SyncExecutor<'a> {
     dav: &'a dyn DavClient,
     store: &'a StateStore,
@@ -22,52 +34,29 @@ impl<'a> SyncExecutor<'a> {
             SyncAction::Upload { local, remote_path, last_etag } => {
                 info!("Uploading: {:?}", local);
                 let content = fs::read(&local).await?;
-                let hash = format!("{:x}", md5::compute(&content));
-                
-                let new_etag = self.dav.put(&remote_path, &content, last_etag.as_deref()).await?;
-                
-                self.store.upsert_state(SyncStateRow {
-                    path: remote_path,
-                    last_etag: new_etag,
-                    last_hash: hash,
-                    last_sync_at: Utc::now(),
-                    is_tombstone: 0,
-                }).await?;
-            }
-            
+                // 没有已知 ETag 说明远端本应不存在，用 If-None-Match 防止覆盖其它设备刚创建的文件
+                let new_etag = match last_etag.as_deref() {
+                    Some(etag) => self.dav.put(&remote_path, &content, Some(etag)).await?,
+                    None => self.dav.put_new(&remote_path, &content).await?,
+                };
+                self.mark_synced(&remote_path, new_etag, &content).await?;
+            }
+
             SyncAction::Download { remote_path, local, remote_etag } => {
                 info!("Downloading: {}", remote_path);
                 let content = self.dav.get(&remote_path).await?;
-                let hash = format!("{:x}", md5::compute(&content));
-                
-                let tmp_path = local.with_extension("sync-tmp");
-                if let Some(parent) = local.parent() {
-                    fs::create_dir_all(parent).await?;
-                }
-                fs::write(&tmp_path, &content).await?;
-                fs::rename(&tmp_path, &local).await?;
-
-                self.store.upsert_state(SyncStateRow {
-                    path: remote_path,
-                    last_etag: remote_etag,
-                    last_hash: hash,
-                    last_sync_at: Utc::now(),
-                    is_tombstone: 0,
-                }).await?;
+                write_local(&local, &content).await?;
+                self.mark_synced(&remote_path, remote_etag, &content).await?;
             }
 
-            SyncAction::Conflict { local, remote_path } => {
-                warn!("Conflict detected for: {}", remote_path);
-                let content = self.dav.get(&remote_path).await?;
-                let bak_path = local.with_extension(format!("remote-bak-{}", Utc::now().format("%Y%m%d%H%M%S")));
-                fs::write(&bak_path, &content).await?;
-                info!("Saved remote version to: {:?}", bak_path);
+            SyncAction::Conflict { local, remote_path, remote_etag, strategy } => {
+                self.settle_conflict(&local, &remote_path, remote_etag, strategy).await?;
             }
 
             SyncAction::DeleteRemote { remote_path, .. } => {
                 info!("Deleting remote: {}", remote_path);
                 self.dav.delete(&remote_path).await?;
-                self.store.delete_state(&remote_path).await?;
+                self.forget(&remote_path).await?;
             }
 
             SyncAction::DeleteLocal { local, remote_path } => {
@@ -75,9 +64,367 @@ impl<'a> SyncExecutor<'a> {
                 if local.exists() {
                     fs::remove_file(&local).await?;
                 }
-                self.store.delete_state(&remote_path).await?;
+                self.forget(&remote_path).await?;
+            }
+
+            SyncAction::Forget { remote_path } => {
+                info!("Forgetting deleted: {}", remote_path);
+                self.forget(&remote_path).await?;
             }
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Settle a conflict recorded by an earlier run
+    pub async fn resolve_conflict(
+        &self,
+        local: &Path,
+        remote_path: &str,
+        resolution: ConflictResolution,
+    ) -> Result<()> {
+        let conflict = self
+            .store
+            .get_conflict(remote_path)
+            .await?
+            .ok_or_else(|| anyhow!("no conflict recorded for {remote_path}"))?;
+        match resolution {
+            ConflictResolution::KeepLocal => {
+                let content = fs::read(local).await?;
+                let etag = self.dav.put(remote_path, &content, Some(&conflict.remote_etag)).await?;
+                self.mark_synced(remote_path, etag, &content).await?;
+            }
+            ConflictResolution::KeepRemote => {
+                let content = self.dav.get(remote_path).await?;
+                write_local(local, &content).await?;
+                self.mark_synced(remote_path, conflict.remote_etag, &content).await?;
+            }
+            ConflictResolution::Merged(content) => {
+                let etag = self.dav.put(remote_path, &content, Some(&conflict.remote_etag)).await?;
+                write_local(local, &content).await?;
+                self.mark_synced(remote_path, etag, &content).await?;
+            }
+        }
+        info!("Resolved conflict for: {}", remote_path);
+        Ok(())
+    }
+
+    async fn settle_conflict(
+        &self,
+        local: &Path,
+        remote_path: &str,
+        remote_etag: String,
+        strategy: ConflictStrategy,
+    ) -> Result<()> {
+        let local_content = fs::read(local).await?;
+        let local_hash = content_hash(&local_content);
+        let merging = matches!(strategy, ConflictStrategy::Merge | ConflictStrategy::NewestWins);
+        if merging
+            && let Some(known) = self.store.get_conflict(remote_path).await?
+            && known.local_hash == local_hash
+            && known.remote_etag == remote_etag
+        {
+            // 已记录且两端都没有再变化，等待手动处理
+            return Ok(());
+        }
+
+        let remote_content = self.dav.get(remote_path).await?;
+        if remote_content == local_content {
+            return self.mark_synced(remote_path, remote_etag, &local_content).await;
+        }
+        match strategy {
+            ConflictStrategy::PreferLocal => {
+                info!("Conflict for {}: keeping local", remote_path);
+                let etag = self.dav.put(remote_path, &local_content, Some(&remote_etag)).await?;
+                return self.mark_synced(remote_path, etag, &local_content).await;
+            }
+            ConflictStrategy::PreferRemote => {
+                info!("Conflict for {}: keeping remote", remote_path);
+                write_local(local, &remote_content).await?;
+                return self.mark_synced(remote_path, remote_etag, &remote_content).await;
+            }
+            ConflictStrategy::KeepBoth => {
+                // 本地版本另存为副本，下一轮作为新文件上传
+                let copy = conflict_copy_path(local);
+                info!("Conflict for {}: keeping local copy at {:?}", remote_path, copy);
+                fs::copy(local, &copy).await?;
+                write_local(local, &remote_content).await?;
+                return self.mark_synced(remote_path, remote_etag, &remote_content).await;
+            }
+            // 规划阶段已按时间决出胜者，剩下的是时间相同的情况
+            ConflictStrategy::Merge | ConflictStrategy::NewestWins => {}
+        }
+        let outcome = match self.store.get_base(remote_path).await? {
+            Some(base) => merge::merge_documents(remote_path, &base, &local_content, &remote_content)
+                .unwrap_or_else(|err| MergeOutcome::Conflict(vec![format!("{err:#}")])),
+            None => MergeOutcome::Conflict(vec!["no common base from an earlier sync".to_string()]),
+        };
+
+        match outcome {
+            MergeOutcome::Merged(merged) => {
+                info!("Merged concurrent edits of: {}", remote_path);
+                // If-Match 保证合并期间远端没有再次变化，否则下一轮重新合并
+                let etag = self.dav.put(remote_path, &merged, Some(&remote_etag)).await?;
+                write_local(local, &merged).await?;
+                self.mark_synced(remote_path, etag, &merged).await
+            }
+            MergeOutcome::Conflict(paths) => {
+                warn!("Conflict detected for: {} ({})", remote_path, paths.join(", "));
+                self.store.record_conflict(SyncConflictRow {
+                    path: remote_path.to_string(),
+                    local_hash,
+                    remote_etag,
+                    reason: paths.join("\n"),
+                    detected_at: Utc::now(),
+                }).await
+            }
+        }
+    }
+
+    /// Both sides now hold `content`; it becomes the base of the next merge
+    async fn mark_synced(&self, remote_path: &str, etag: String, content: &[u8]) -> Result<()> {
+        self.store.upsert_state(SyncStateRow {
+            path: remote_path.to_string(),
+            last_etag: etag,
+            last_hash: content_hash(content),
+            last_sync_at: Utc::now(),
+            is_tombstone: 0,
+        }).await?;
+        self.store.set_base(remote_path, content).await?;
+        self.store.clear_conflict(remote_path).await
+    }
+
+    async fn forget(&self, remote_path: &str) -> Result<()> {
+        self.store.delete_state(remote_path).await?;
+        self.store.delete_base(remote_path).await?;
+        self.store.clear_conflict(remote_path).await
+    }
+}
+
+/// Sibling path for the local side of a keep-both conflict,
+/// e.g. `main.yaml` -> `main.conflict-20240101-120000.yaml`
+fn conflict_copy_path(local: &Path) -> PathBuf {
+    let stem = local.file_stem().and_then(|stem| stem.to_str()).unwrap_or("file");
+    let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+    let name = match local.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{stem}.conflict-{stamp}.{ext}"),
+        None => format!("{stem}.conflict-{stamp}"),
+    };
+    local.with_file_name(name)
+}
+
+fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", md5::compute(content))
+}
+
+async fn write_local(local: &Path, content: &[u8]) -> Result<()> {
+    let tmp_path = local.with_extension("sync-tmp");
+    if let Some(parent) = local.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&tmp_path, content).await?;
+    fs::rename(&tmp_path, local).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use dav_client::RemoteEntry;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// In-memory remote that enforces If-Match like a WebDAV server
+    #[derive(Default)]
+    struct MemoryDav {
+        files: Mutex<HashMap<String, (Vec<u8>, u32)>>,
+    }
+
+    impl MemoryDav {
+        fn set(&self, path: &str, content: &str) -> String {
+            let mut files = self.files.lock().unwrap();
+            let version = files.get(path).map_or(1, |(_, version)| version + 1);
+            files.insert(path.to_string(), (content.as_bytes().to_vec(), version));
+            format!("v{version}")
+        }
+
+        fn content(&self, path: &str) -> String {
+            String::from_utf8(self.files.lock().unwrap()[path].0.clone()).unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl DavClient for MemoryDav {
+        async fn list(&self, _path: &str) -> Result<Vec<RemoteEntry>> { Ok(vec![]) }
+        async fn get(&self, path: &str) -> Result<Vec<u8>> {
+            Ok(self.files.lock().unwrap()[path].0.clone())
+        }
+        async fn put(&self, path: &str, data: &[u8], if_match: Option<&str>) -> Result<String> {
+            let current = self.files.lock().unwrap().get(path).map(|(_, version)| format!("v{version}"));
+            if let Some(expected) = if_match
+                && current.as_deref() != Some(expected)
+            {
+                return Err(anyhow!("PUT failed: 412 Precondition Failed"));
+            }
+            Ok(self.set(path, std::str::from_utf8(data).unwrap()))
+        }
+        async fn put_new(&self, path: &str, data: &[u8]) -> Result<String> {
+            if self.files.lock().unwrap().contains_key(path) {
+                return Err(anyhow!("PUT failed: 412 Precondition Failed"));
+            }
+            Ok(self.set(path, std::str::from_utf8(data).unwrap()))
+        }
+        async fn delete(&self, _path: &str) -> Result<()> { Ok(()) }
+        async fn move_item(&self, _from: &str, _to: &str) -> Result<()> { Ok(()) }
+        async fn mkdir(&self, _path: &str) -> Result<()> { Ok(()) }
+    }
+
+    const BASE: &str = "mode: rule\nlog-level: info\n";
+
+    /// A file synced once, then edited on both sides
+    async fn diverged(local_text: &str, remote_text: &str) -> (tempfile::TempDir, MemoryDav, StateStore, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let dav = MemoryDav::default();
+        let store = StateStore::new(":memory:").await.unwrap();
+        let local = dir.path().join("p.yaml");
+        fs::write(&local, BASE).await.unwrap();
+        let executor = SyncExecutor::new(&dav, &store);
+        executor.execute(SyncAction::Upload { local: local.clone(), remote_path: "p.yaml".into(), last_etag: None }).await.unwrap();
+
+        fs::write(&local, local_text).await.unwrap();
+        let etag = dav.set("p.yaml", remote_text);
+        (dir, dav, store, etag)
+    }
+
+    #[tokio::test]
+    async fn test_new_upload_does_not_overwrite_remote_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let dav = MemoryDav::default();
+        let store = StateStore::new(":memory:").await.unwrap();
+        let local = dir.path().join("p.yaml");
+        fs::write(&local, BASE).await.unwrap();
+        dav.set("p.yaml", "mode: global\n");
+
+        let executor = SyncExecutor::new(&dav, &store);
+        let upload = SyncAction::Upload { local, remote_path: "p.yaml".into(), last_etag: None };
+        assert!(executor.execute(upload).await.is_err());
+        assert_eq!(dav.content("p.yaml"), "mode: global\n");
+        assert!(store.get_state("p.yaml").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_conflict_merges_and_uploads() {
+        let (dir, dav, store, etag) =
+            diverged("mode: global\nlog-level: info\n", "mode: rule\nlog-level: debug\n").await;
+        let local = dir.path().join("p.yaml");
+        let executor = SyncExecutor::new(&dav, &store);
+        executor.execute(SyncAction::Conflict { local: local.clone(), remote_path: "p.yaml".into(), remote_etag: etag, strategy: ConflictStrategy::Merge }).await.unwrap();
+
+        let merged = "mode: global\nlog-level: debug\n";
+        assert_eq!(fs::read_to_string(&local).await.unwrap(), merged);
+        assert_eq!(dav.content("p.yaml"), merged);
+        let state = store.get_state("p.yaml").await.unwrap().unwrap();
+        assert_eq!(state.last_etag, "v3");
+        assert_eq!(store.get_base("p.yaml").await.unwrap().unwrap(), merged.as_bytes());
+        assert!(store.list_conflicts().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_merge_rejected_when_remote_moves() {
+        let (dir, dav, store, etag) =
+            diverged("mode: global\nlog-level: info\n", "mode: rule\nlog-level: debug\n").await;
+        dav.set("p.yaml", "mode: rule\nlog-level: warning\n");
+        let executor = SyncExecutor::new(&dav, &store);
+        let local = dir.path().join("p.yaml");
+        let result = executor.execute(SyncAction::Conflict { local: local.clone(), remote_path: "p.yaml".into(), remote_etag: etag, strategy: ConflictStrategy::Merge }).await;
+        assert!(result.is_err());
+        // Nothing written locally; the next run merges against the new remote
+        assert_eq!(fs::read_to_string(&local).await.unwrap(), "mode: global\nlog-level: info\n");
+    }
+
+    #[tokio::test]
+    async fn test_true_conflict_is_recorded_and_resolved() {
+        let (dir, dav, store, etag) =
+            diverged("mode: global\nlog-level: info\n", "mode: direct\nlog-level: info\n").await;
+        let local = dir.path().join("p.yaml");
+        let executor = SyncExecutor::new(&dav, &store);
+        let action = SyncAction::Conflict { local: local.clone(), remote_path: "p.yaml".into(), remote_etag: etag.clone(), strategy: ConflictStrategy::Merge };
+        executor.execute(action.clone()).await.unwrap();
+
+        let conflicts = store.list_conflicts().await.unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].reason, "mode");
+        assert_eq!(conflicts[0].remote_etag, etag);
+        // Re-running leaves the recorded conflict alone and writes no backups
+        executor.execute(action).await.unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        executor
+            .resolve_conflict(&local, "p.yaml", ConflictResolution::Merged(b"mode: direct\nlog-level: debug\n".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(dav.content("p.yaml"), "mode: direct\nlog-level: debug\n");
+        assert_eq!(fs::read_to_string(&local).await.unwrap(), "mode: direct\nlog-level: debug\n");
+        assert!(store.list_conflicts().await.unwrap().is_empty());
+        assert!(executor.resolve_conflict(&local, "p.yaml", ConflictResolution::KeepLocal).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_conflict_strategies_pick_a_side() {
+        let local_text = "mode: global\nlog-level: info\n";
+        let remote_text = "mode: direct\nlog-level: info\n";
+        for (strategy, expected) in [
+            (ConflictStrategy::PreferLocal, local_text),
+            (ConflictStrategy::PreferRemote, remote_text),
+        ] {
+            let (dir, dav, store, etag) = diverged(local_text, remote_text).await;
+            let local = dir.path().join("p.yaml");
+            let executor = SyncExecutor::new(&dav, &store);
+            executor.execute(SyncAction::Conflict { local: local.clone(), remote_path: "p.yaml".into(), remote_etag: etag, strategy }).await.unwrap();
+
+            assert_eq!(fs::read_to_string(&local).await.unwrap(), expected, "{strategy:?}");
+            assert_eq!(dav.content("p.yaml"), expected, "{strategy:?}");
+            assert_eq!(store.get_base("p.yaml").await.unwrap().unwrap(), expected.as_bytes());
+            assert!(store.list_conflicts().await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keep_both_saves_local_copy() {
+        let (dir, dav, store, etag) =
+            diverged("mode: global\nlog-level: info\n", "mode: direct\nlog-level: info\n").await;
+        let local = dir.path().join("p.yaml");
+        let executor = SyncExecutor::new(&dav, &store);
+        executor.execute(SyncAction::Conflict { local: local.clone(), remote_path: "p.yaml".into(), remote_etag: etag, strategy: ConflictStrategy::KeepBoth }).await.unwrap();
+
+        assert_eq!(fs::read_to_string(&local).await.unwrap(), "mode: direct\nlog-level: info\n");
+        let copies: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.starts_with("p.conflict-") && name.ends_with(".yaml"))
+            .collect();
+        assert_eq!(copies.len(), 1);
+        assert_eq!(std::fs::read_to_string(dir.path().join(&copies[0])).unwrap(), "mode: global\nlog-level: info\n");
+    }
+
+    #[tokio::test]
+    async fn test_new_on_both_sides_with_equal_content_records_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let dav = MemoryDav::default();
+        let store = StateStore::new(":memory:").await.unwrap();
+        let local = dir.path().join("p.yaml");
+        fs::write(&local, BASE).await.unwrap();
+        let etag = dav.set("p.yaml", BASE);
+
+        let executor = SyncExecutor::new(&dav, &store);
+        executor.execute(SyncAction::Conflict { local, remote_path: "p.yaml".into(), remote_etag: etag, strategy: ConflictStrategy::Merge }).await.unwrap();
+        let state = store.get_state("p.yaml").await.unwrap().unwrap();
+        assert_eq!(state.last_etag, "v1");
+        assert_eq!(state.last_hash, content_hash(BASE.as_bytes()));
+        assert!(store.list_conflicts().await.unwrap().is_empty());
+
+        executor.execute(SyncAction::Forget { remote_path: "p.yaml".into() }).await.unwrap();
+        assert!(store.get_state("p.yaml").await.unwrap().is_none());
+        assert!(store.get_base("p.yaml").await.unwrap().is_none());
+    }
+}
@@ -1,21 +1,26 @@
 use anyhow::Result;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 
+pub mod crypto;
 pub mod executor;
+pub mod merge;
+pub mod policy;
 
 use dav_client::{DavClient, RemoteEntry};
 use state_store::{StateStore, SyncStateRow};
 use indexer::{Indexer, LocalEntry};
+use policy::{ConflictPolicy, ConflictStrategy};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SyncAction {
     Upload { local: PathBuf, remote_path: String, last_etag: Option<String> },
     Download { remote_path: String, local: PathBuf, remote_etag: String },
-    Conflict { local: PathBuf, remote_path: String },
+    Conflict { local: PathBuf, remote_path: String, remote_etag: String, strategy: ConflictStrategy },
     DeleteRemote { remote_path: String, last_etag: String },
     DeleteLocal { local: PathBuf, remote_path: String },
+    /// Gone on both sides, only the sync state is left
+    Forget { remote_path: String },
 }
 
 pub struct SyncPlanner<'a> {
@@ -23,11 +28,17 @@ pub struct SyncPlanner<'a> {
     remote_base: String,
     dav: &'a dyn DavClient,
     store: &'a StateStore,
+    policy: ConflictPolicy,
 }
 
 impl<'a> SyncPlanner<'a> {
     pub fn new(local_root: PathBuf, remote_base: String, dav: &'a dyn DavClient, store: &'a StateStore) -> Self {
-        Self { local_root, remote_base, dav, store }
+        Self { local_root, remote_base, dav, store, policy: ConflictPolicy::default() }
+    }
+
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     pub async fn build_plan(&self) -> Result<Vec<SyncAction>> {
@@ -42,109 +53,153 @@ impl<'a> SyncPlanner<'a> {
         let remote_map: HashMap<String, RemoteEntry> = remotes.into_iter()
             .filter(|e| !e.is_dir)
             .map(|e| (self.normalize_remote_path(&e.path), e))
+            .filter(|(path, _)| !is_encryption_artifact(path))
             .collect();
 
         let state_map: HashMap<String, SyncStateRow> = states.into_iter()
             .map(|s| (s.path.clone(), s))
             .collect();
 
-        let mut actions = Vec::new();
-        let all_paths: HashSet<String> = local_map.keys().cloned()
-            .chain(remote_map.keys().cloned())
-            .chain(state_map.keys().cloned())
-            .collect();
+        Ok(plan(&self.local_root, &local_map, &remote_map, &state_map, &self.policy))
+    }
 
-        for path in all_paths {
-            let l = local_map.get(&path);
-            let r = remote_map.get(&path);
-            let s = state_map.get(&path);
+    fn normalize_remote_path(&self, full_path: &str) -> String {
+        full_path.trim_start_matches(&self.remote_base)
+            .trim_start_matches('/')
+            .to_string()
+    }
+}
 
-            match (l, r, s) {
-                // 1. 本地新增
-                (Some(l_val), None, None) => {
+/// Key check and ciphertext files are never synced as plaintext; in
+/// encrypted mode [`crypto::EncryptedDav`] has already mapped them away
+fn is_encryption_artifact(path: &str) -> bool {
+    path == crypto::KEY_CHECK_FILE || path.ends_with(crypto::ENCRYPTED_SUFFIX)
+}
+
+/// Decide the action of every known path, in path order
+fn plan(
+    local_root: &Path,
+    local_map: &HashMap<String, LocalEntry>,
+    remote_map: &HashMap<String, RemoteEntry>,
+    state_map: &HashMap<String, SyncStateRow>,
+    policy: &ConflictPolicy,
+) -> Vec<SyncAction> {
+    let all_paths: BTreeSet<&String> = local_map.keys()
+        .chain(remote_map.keys())
+        .chain(state_map.keys())
+        .collect();
+
+    let mut actions = Vec::new();
+    for path in all_paths {
+        let l = local_map.get(path);
+        let r = remote_map.get(path);
+        let s = state_map.get(path);
+        let strategy = policy.strategy_for(path);
+
+        match (l, r, s) {
+            // 1. 本地新增
+            (Some(l_val), None, None) => {
+                actions.push(SyncAction::Upload {
+                    local: l_val.path.clone(),
+                    remote_path: path.clone(),
+                    last_etag: None,
+                });
+            }
+            // 2. 远端新增
+            (None, Some(r_val), None) => {
+                actions.push(SyncAction::Download {
+                    remote_path: path.clone(),
+                    local: local_root.join(path),
+                    remote_etag: r_val.etag.clone(),
+                });
+            }
+            // 3. 两端同时新增，内容相同时执行器直接记录状态
+            (Some(l_val), Some(r_val), None) => {
+                actions.push(conflict(path, l_val, r_val, strategy));
+            }
+            // 4. 正常状态检查
+            (Some(l_val), Some(r_val), Some(s_val)) => {
+                let l_changed = l_val.hash != s_val.last_hash;
+                let r_changed = r_val.etag != s_val.last_etag;
+
+                if l_changed && r_changed {
+                    // 双向修改，按冲突策略处理
+                    actions.push(conflict(path, l_val, r_val, strategy));
+                } else if l_changed {
                     actions.push(SyncAction::Upload {
                         local: l_val.path.clone(),
                         remote_path: path.clone(),
-                        last_etag: None,
+                        last_etag: Some(r_val.etag.clone()),
                     });
-                }
-                // 2. 远端新增
-                (None, Some(r_val), None) => {
+                } else if r_changed {
                     actions.push(SyncAction::Download {
                         remote_path: path.clone(),
-                        local: self.local_root.join(&path),
+                        local: l_val.path.clone(),
                         remote_etag: r_val.etag.clone(),
                     });
                 }
-                // 3. 正常状态检查
-                (Some(l_val), Some(r_val), Some(s_val)) => {
-                    let l_changed = l_val.hash != s_val.last_hash;
-                    let r_changed = r_val.etag != s_val.last_etag;
-
-                    if l_changed && r_changed {
-                        // 双向修改，检查内容是否一致（哈希 vs ETag 碰撞检查）
-                        // 如果服务器不支持 Hash ETag，这里默认冲突
-                        actions.push(SyncAction::Conflict {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                        });
-                    } else if l_changed {
-                        actions.push(SyncAction::Upload {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                            last_etag: Some(r_val.etag.clone()),
-                        });
-                    } else if r_changed {
-                        actions.push(SyncAction::Download {
-                            remote_path: path.clone(),
-                            local: l_val.path.clone(),
-                            remote_etag: r_val.etag.clone(),
-                        });
-                    }
-                }
-                // 4. 本地删除
-                (None, Some(r_val), Some(s_val)) => {
-                    if r_val.etag == s_val.last_etag {
-                        actions.push(SyncAction::DeleteRemote {
-                            remote_path: path.clone(),
-                            last_etag: r_val.etag.clone(),
-                        });
-                    } else {
-                        // 远端改了，恢复下载
-                        actions.push(SyncAction::Download {
-                            remote_path: path.clone(),
-                            local: self.local_root.join(&path),
-                            remote_etag: r_val.etag.clone(),
-                        });
-                    }
+            }
+            // 5. 本地删除
+            (None, Some(r_val), Some(s_val)) => {
+                if r_val.etag == s_val.last_etag || strategy == ConflictStrategy::PreferLocal {
+                    actions.push(SyncAction::DeleteRemote {
+                        remote_path: path.clone(),
+                        last_etag: r_val.etag.clone(),
+                    });
+                } else {
+                    // 远端改了，恢复下载
+                    actions.push(SyncAction::Download {
+                        remote_path: path.clone(),
+                        local: local_root.join(path),
+                        remote_etag: r_val.etag.clone(),
+                    });
                 }
-                // 5. 远端删除
-                (Some(l_val), None, Some(s_val)) => {
-                    if l_val.hash == s_val.last_hash {
-                        actions.push(SyncAction::DeleteLocal {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                        });
-                    } else {
-                        // 本地改了，重新上传
-                        actions.push(SyncAction::Upload {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                            last_etag: None,
-                        });
-                    }
+            }
+            // 6. 远端删除
+            (Some(l_val), None, Some(s_val)) => {
+                if l_val.hash == s_val.last_hash || strategy == ConflictStrategy::PreferRemote {
+                    actions.push(SyncAction::DeleteLocal {
+                        local: l_val.path.clone(),
+                        remote_path: path.clone(),
+                    });
+                } else {
+                    // 本地改了，重新上传
+                    actions.push(SyncAction::Upload {
+                        local: l_val.path.clone(),
+                        remote_path: path.clone(),
+                        last_etag: None,
+                    });
                 }
-                _ => {}
             }
+            // 7. 两端都已删除，清理残留状态
+            (None, None, Some(_)) => {
+                actions.push(SyncAction::Forget { remote_path: path.clone() });
+            }
+            (None, None, None) => unreachable!("path comes from one of the maps"),
         }
-
-        Ok(actions)
     }
 
-    fn normalize_remote_path(&self, full_path: &str) -> String {
-        full_path.trim_start_matches(&self.remote_base)
-            .trim_start_matches('/')
-            .to_string()
+    actions
+}
+
+/// Conflict action with `NewestWins` settled by modification time
+fn conflict(path: &str, local: &LocalEntry, remote: &RemoteEntry, strategy: ConflictStrategy) -> SyncAction {
+    let strategy = match strategy {
+        ConflictStrategy::NewestWins if local.last_modified > remote.last_modified => {
+            ConflictStrategy::PreferLocal
+        }
+        ConflictStrategy::NewestWins if local.last_modified < remote.last_modified => {
+            ConflictStrategy::PreferRemote
+        }
+        // 时间相同无法判断，退回合并
+        ConflictStrategy::NewestWins => ConflictStrategy::Merge,
+        other => other,
+    };
+    SyncAction::Conflict {
+        local: local.path.clone(),
+        remote_path: path.to_string(),
+        remote_etag: remote.etag.clone(),
+        strategy,
     }
 }
 
@@ -160,6 +215,16 @@ pub mod test_utils {
         locals: Vec<LocalEntry>,
         remotes: Vec<RemoteEntry>,
         states: Vec<SyncStateRow>,
+    ) -> Vec<SyncAction> {
+        build_plan_with_policy(local_root, locals, remotes, states, &ConflictPolicy::default())
+    }
+
+    pub fn build_plan_with_policy(
+        local_root: PathBuf,
+        locals: Vec<LocalEntry>,
+        remotes: Vec<RemoteEntry>,
+        states: Vec<SyncStateRow>,
+        policy: &ConflictPolicy,
     ) -> Vec<SyncAction> {
         let local_map: HashMap<String, LocalEntry> = locals.into_iter()
             .map(|e| (e.relative_path.clone(), e))
@@ -168,94 +233,14 @@ pub mod test_utils {
         let remote_map: HashMap<String, RemoteEntry> = remotes.into_iter()
             .filter(|e| !e.is_dir)
             .map(|e| (e.path.trim_start_matches('/').to_string(), e))
+            .filter(|(path, _)| !is_encryption_artifact(path))
             .collect();
 
         let state_map: HashMap<String, SyncStateRow> = states.into_iter()
             .map(|s| (s.path.clone(), s))
             .collect();
 
-        let mut actions = Vec::new();
-        let all_paths: HashSet<String> = local_map.keys().cloned()
-            .chain(remote_map.keys().cloned())
-            .chain(state_map.keys().cloned())
-            .collect();
-
-        for path in all_paths {
-            let l = local_map.get(&path);
-            let r = remote_map.get(&path);
-            let s = state_map.get(&path);
-
-            match (l, r, s) {
-                (Some(l_val), None, None) => {
-                    actions.push(SyncAction::Upload {
-                        local: l_val.path.clone(),
-                        remote_path: path.clone(),
-                        last_etag: None,
-                    });
-                }
-                (None, Some(r_val), None) => {
-                    actions.push(SyncAction::Download {
-                        remote_path: path.clone(),
-                        local: local_root.join(&path),
-                        remote_etag: r_val.etag.clone(),
-                    });
-                }
-                (Some(l_val), Some(r_val), Some(s_val)) => {
-                    let l_changed = l_val.hash != s_val.last_hash;
-                    let r_changed = r_val.etag != s_val.last_etag;
-
-                    if l_changed && r_changed {
-                        actions.push(SyncAction::Conflict {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                        });
-                    } else if l_changed {
-                        actions.push(SyncAction::Upload {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                            last_etag: Some(r_val.etag.clone()),
-                        });
-                    } else if r_changed {
-                        actions.push(SyncAction::Download {
-                            remote_path: path.clone(),
-                            local: l_val.path.clone(),
-                            remote_etag: r_val.etag.clone(),
-                        });
-                    }
-                }
-                (None, Some(r_val), Some(s_val)) => {
-                    if r_val.etag == s_val.last_etag {
-                        actions.push(SyncAction::DeleteRemote {
-                            remote_path: path.clone(),
-                            last_etag: r_val.etag.clone(),
-                        });
-                    } else {
-                        actions.push(SyncAction::Download {
-                            remote_path: path.clone(),
-                            local: local_root.join(&path),
-                            remote_etag: r_val.etag.clone(),
-                        });
-                    }
-                }
-                (Some(l_val), None, Some(s_val)) => {
-                    if l_val.hash == s_val.last_hash {
-                        actions.push(SyncAction::DeleteLocal {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                        });
-                    } else {
-                        actions.push(SyncAction::Upload {
-                            local: l_val.path.clone(),
-                            remote_path: path.clone(),
-                            last_etag: None,
-                        });
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        actions
+        plan(&local_root, &local_map, &remote_map, &state_map, policy)
     }
 
     pub fn make_local_entry(path: &str, hash: &str) -> LocalEntry {
@@ -456,6 +441,118 @@ mod tests {
         }
     }
 
+    /// Action shape without paths, for table-driven assertions
+    fn shape(action: &SyncAction) -> String {
+        match action {
+            SyncAction::Upload { last_etag, .. } => format!("upload({})", last_etag.as_deref().unwrap_or("-")),
+            SyncAction::Download { remote_etag, .. } => format!("download({remote_etag})"),
+            SyncAction::Conflict { strategy, .. } => format!("conflict({strategy:?})"),
+            SyncAction::DeleteRemote { last_etag, .. } => format!("delete-remote({last_etag})"),
+            SyncAction::DeleteLocal { .. } => "delete-local".to_string(),
+            SyncAction::Forget { .. } => "forget".to_string(),
+        }
+    }
+
+    /// Plan a single path `f.yaml`; `None` marks a missing side
+    fn plan_one(local: Option<&str>, remote: Option<&str>, state: Option<(&str, &str)>, policy: &ConflictPolicy) -> Vec<String> {
+        let locals = local.map(|hash| make_local_entry("f.yaml", hash)).into_iter().collect();
+        let remotes = remote.map(|etag| make_remote_entry("f.yaml", etag)).into_iter().collect();
+        let states = state.map(|(etag, hash)| make_state_row("f.yaml", etag, hash)).into_iter().collect();
+        build_plan_with_policy(PathBuf::from("/configs"), locals, remotes, states, policy)
+            .iter()
+            .map(shape)
+            .collect()
+    }
+
+    /// (local hash, remote etag, synced (etag, hash), expected actions)
+    type PlanCase<'a> = (Option<&'a str>, Option<&'a str>, Option<(&'a str, &'a str)>, &'a [&'a str]);
+
+    #[test]
+    fn test_plan_covers_every_combination() {
+        let merge = ConflictPolicy::default();
+        let base = Some(("e0", "h0"));
+        let cases: &[PlanCase] = &[
+            (Some("h1"), None, None, &["upload(-)"]),
+            (None, Some("e1"), None, &["download(e1)"]),
+            (Some("h1"), Some("e1"), None, &["conflict(Merge)"]),
+            (Some("h0"), Some("e0"), base, &[]),
+            (Some("h1"), Some("e0"), base, &["upload(e0)"]),
+            (Some("h0"), Some("e1"), base, &["download(e1)"]),
+            (Some("h1"), Some("e1"), base, &["conflict(Merge)"]),
+            (None, Some("e0"), base, &["delete-remote(e0)"]),
+            (None, Some("e1"), base, &["download(e1)"]),
+            (Some("h0"), None, base, &["delete-local"]),
+            (Some("h1"), None, base, &["upload(-)"]),
+            (None, None, base, &["forget"]),
+        ];
+        for (local, remote, state, expected) in cases {
+            assert_eq!(plan_one(*local, *remote, *state, &merge), *expected, "{local:?} {remote:?} {state:?}");
+        }
+    }
+
+    #[test]
+    fn test_strategy_applies_to_conflicts_and_edit_delete_races() {
+        let base = Some(("e0", "h0"));
+        let with = |default| ConflictPolicy { default, overrides: vec![] };
+        let cases: &[(ConflictStrategy, [&str; 4])] = &[
+            (ConflictStrategy::Merge, ["conflict(Merge)", "conflict(Merge)", "download(e1)", "upload(-)"]),
+            (ConflictStrategy::KeepBoth, ["conflict(KeepBoth)", "conflict(KeepBoth)", "download(e1)", "upload(-)"]),
+            (ConflictStrategy::PreferLocal, ["conflict(PreferLocal)", "conflict(PreferLocal)", "delete-remote(e1)", "upload(-)"]),
+            (ConflictStrategy::PreferRemote, ["conflict(PreferRemote)", "conflict(PreferRemote)", "download(e1)", "delete-local"]),
+        ];
+        for (strategy, [both_new, both_changed, local_deleted, remote_deleted]) in cases {
+            let policy = with(*strategy);
+            assert_eq!(plan_one(Some("h1"), Some("e1"), None, &policy), [*both_new]);
+            assert_eq!(plan_one(Some("h1"), Some("e1"), base, &policy), [*both_changed]);
+            assert_eq!(plan_one(None, Some("e1"), base, &policy), [*local_deleted]);
+            assert_eq!(plan_one(Some("h1"), None, base, &policy), [*remote_deleted]);
+            // Non-conflicting changes ignore the strategy
+            assert_eq!(plan_one(Some("h1"), Some("e0"), base, &policy), ["upload(e0)"]);
+            assert_eq!(plan_one(None, Some("e0"), base, &policy), ["delete-remote(e0)"]);
+        }
+    }
+
+    #[test]
+    fn test_newest_wins_compares_modification_times() {
+        let policy = ConflictPolicy { default: ConflictStrategy::NewestWins, overrides: vec![] };
+        let now = chrono::Utc::now();
+        let plan = |local_age: i64, remote_age: i64| {
+            let mut local = make_local_entry("f.yaml", "h1");
+            local.last_modified = now - chrono::Duration::minutes(local_age);
+            let mut remote = make_remote_entry("f.yaml", "e1");
+            remote.last_modified = now - chrono::Duration::minutes(remote_age);
+            let states = vec![make_state_row("f.yaml", "e0", "h0")];
+            let actions = build_plan_with_policy(PathBuf::from("/configs"), vec![local], vec![remote], states, &policy);
+            actions.iter().map(shape).collect::<Vec<_>>()
+        };
+        assert_eq!(plan(1, 5), ["conflict(PreferLocal)"]);
+        assert_eq!(plan(5, 1), ["conflict(PreferRemote)"]);
+        assert_eq!(plan(3, 3), ["conflict(Merge)"]);
+    }
+
+    #[test]
+    fn test_path_overrides_pick_strategy_per_file() {
+        let policy = ConflictPolicy {
+            default: ConflictStrategy::Merge,
+            overrides: vec![policy::PathOverride {
+                pattern: "rules/**".to_string(),
+                strategy: ConflictStrategy::PreferRemote,
+            }],
+        };
+        let locals = vec![make_local_entry("rules/a.yaml", "h1"), make_local_entry("main.yaml", "h1")];
+        let remotes = vec![make_remote_entry("rules/a.yaml", "e1"), make_remote_entry("main.yaml", "e1")];
+        let states = vec![make_state_row("rules/a.yaml", "e0", "h0"), make_state_row("main.yaml", "e0", "h0")];
+        let actions = build_plan_with_policy(PathBuf::from("/configs"), locals, remotes, states, &policy);
+
+        // Sorted by path
+        let paths: Vec<_> = actions.iter().map(|action| match action {
+            SyncAction::Conflict { remote_path, .. } => remote_path.as_str(),
+            other => panic!("Expected Conflict, got {other:?}"),
+        }).collect();
+        assert_eq!(paths, ["main.yaml", "rules/a.yaml"]);
+        assert_eq!(actions.iter().map(shape).collect::<Vec<_>>(), ["conflict(Merge)", "conflict(PreferRemote)"]);
+    }
+
     #[tokio::test]
     async fn test_build_plan_async_flow() {
         use async_trait::async_trait;
@@ -498,4 +595,41 @@ mod tests {
             _ => panic!("Expected Download"),
         }
     }
+
+    #[tokio::test]
+    async fn test_two_devices_sync_through_local_directory_backend() {
+        use dav_client::local::LocalDirClient;
+
+        async fn sync(root: &std::path::Path, remote: &LocalDirClient, store: &StateStore) {
+            let planner = SyncPlanner::new(root.to_path_buf(), "/".to_string(), remote, store);
+            let executor = executor::SyncExecutor::new(remote, store);
+            for action in planner.build_plan().await.unwrap() {
+                executor.execute(action).await.unwrap();
+            }
+        }
+
+        let share = tempfile::tempdir().unwrap();
+        let remote = LocalDirClient::new(share.path()).unwrap();
+        let (laptop, phone) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let laptop_store = StateStore::new(":memory:").await.unwrap();
+        let phone_store = StateStore::new(":memory:").await.unwrap();
+
+        std::fs::create_dir(laptop.path().join("rules")).unwrap();
+        std::fs::write(laptop.path().join("rules/a.yaml"), "payload: []\n").unwrap();
+        sync(laptop.path(), &remote, &laptop_store).await;
+        sync(phone.path(), &remote, &phone_store).await;
+        assert_eq!(std::fs::read_to_string(phone.path().join("rules/a.yaml")).unwrap(), "payload: []\n");
+
+        std::fs::remove_file(phone.path().join("rules/a.yaml")).unwrap();
+        sync(phone.path(), &remote, &phone_store).await;
+        sync(laptop.path(), &remote, &laptop_store).await;
+        assert!(!laptop.path().join("rules/a.yaml").exists());
+        assert!(!share.path().join("rules/a.yaml").exists());
+
+        // Leftovers of encrypted sync are never pulled down as plaintext
+        std::fs::write(share.path().join(crypto::KEY_CHECK_FILE), "{}").unwrap();
+        std::fs::write(share.path().join("main.yaml.mfenc"), "MFE").unwrap();
+        sync(phone.path(), &remote, &phone_store).await;
+        assert!(std::fs::read_dir(phone.path()).unwrap().all(|entry| entry.unwrap().path().is_dir()));
+    }
 }
@@ -0,0 +1,471 @@
+//! Structure-aware three-way merge of YAML and TOML documents.
+//!
+//! Mappings merge key by key. Sequences merge with diff3 over item
+//! identities: mappings with a `name` (proxies, groups) are matched by name
+//! and merged recursively, everything else (rules, nameservers) by value, so
+//! edits in different places of a list combine cleanly. Only a value both
+//! sides changed differently is reported as a conflict.
+
+use anyhow::{anyhow, Context, Result};
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+/// Largest LCS table built for one list; bigger edits count as a conflict
+const MAX_LCS_CELLS: usize = 4_000_000;
+const TOML_DATETIME_TAG: &str = "toml-datetime";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeOutcome {
+    Merged(Vec<u8>),
+    /// Paths such as `dns.nameserver` or `proxies[HK].port` edited on both sides
+    Conflict(Vec<String>),
+}
+
+/// Merge `local` and `remote` edits of `base`; the format follows the file
+/// extension of `path`
+pub fn merge_documents(path: &str, base: &[u8], local: &[u8], remote: &[u8]) -> Result<MergeOutcome> {
+    let format = Format::of(path).ok_or_else(|| anyhow!("{path} is neither YAML nor TOML"))?;
+    let base = format.parse(base).context("parse base version")?;
+    let local = format.parse(local).context("parse local version")?;
+    let remote = format.parse(remote).context("parse remote version")?;
+
+    let mut conflicts = vec![];
+    let merged = merge_value(&base, &local, &remote, "", &mut conflicts);
+    if !conflicts.is_empty() {
+        return Ok(MergeOutcome::Conflict(conflicts));
+    }
+    Ok(MergeOutcome::Merged(format.render(&merged)?))
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn of(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    fn parse(self, bytes: &[u8]) -> Result<Value> {
+        let text = std::str::from_utf8(bytes).context("content is not UTF-8")?;
+        match self {
+            Self::Yaml => Ok(serde_yaml::from_str(text)?),
+            Self::Toml => Ok(toml_to_yaml(toml::from_str(text)?)),
+        }
+    }
+
+    fn render(self, value: &Value) -> Result<Vec<u8>> {
+        let text = match self {
+            Self::Yaml => serde_yaml::to_string(value)?,
+            Self::Toml => toml::to_string_pretty(&yaml_to_toml(value)?)?,
+        };
+        Ok(text.into_bytes())
+    }
+}
+
+fn merge_value(base: &Value, local: &Value, remote: &Value, path: &str, conflicts: &mut Vec<String>) -> Value {
+    if local == remote || remote == base {
+        return local.clone();
+    }
+    if local == base {
+        return remote.clone();
+    }
+    match (base, local, remote) {
+        (Value::Mapping(base), Value::Mapping(local), Value::Mapping(remote)) => {
+            Value::Mapping(merge_mapping(base, local, remote, path, conflicts))
+        }
+        (Value::Sequence(base), Value::Sequence(local), Value::Sequence(remote)) => {
+            match merge_sequence(base, local, remote, path, conflicts) {
+                Some(items) => Value::Sequence(items),
+                None => {
+                    conflicts.push(display_path(path));
+                    Value::Sequence(local.clone())
+                }
+            }
+        }
+        // Both sides added the same key with different mappings
+        (Value::Null, Value::Mapping(local), Value::Mapping(remote)) => {
+            Value::Mapping(merge_mapping(&Mapping::new(), local, remote, path, conflicts))
+        }
+        _ => {
+            conflicts.push(display_path(path));
+            local.clone()
+        }
+    }
+}
+
+fn merge_optional(
+    base: Option<&Value>,
+    local: Option<&Value>,
+    remote: Option<&Value>,
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Option<Value> {
+    match (base, local, remote) {
+        (_, Some(local), Some(remote)) => {
+            Some(merge_value(base.unwrap_or(&Value::Null), local, remote, path, conflicts))
+        }
+        (None, None, None) => None,
+        (None, one, other) => one.or(other).cloned(),
+        // Removed on one side: fine unless the other side edited it
+        (Some(base), None, Some(kept)) | (Some(base), Some(kept), None) => {
+            if kept != base {
+                conflicts.push(display_path(path));
+                return Some(kept.clone());
+            }
+            None
+        }
+        (Some(_), None, None) => None,
+    }
+}
+
+fn merge_mapping(
+    base: &Mapping,
+    local: &Mapping,
+    remote: &Mapping,
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Mapping {
+    // Local key order first, then keys only the remote side added
+    let keys = local
+        .keys()
+        .chain(remote.keys().filter(|key| !local.contains_key(*key)));
+    let mut merged = Mapping::new();
+    for key in keys {
+        let child = join_path(path, &key_label(key));
+        if let Some(value) = merge_optional(base.get(key), local.get(key), remote.get(key), &child, conflicts) {
+            merged.insert(key.clone(), value);
+        }
+    }
+    merged
+}
+
+/// diff3 over item identities; `None` when the order itself conflicts
+fn merge_sequence(
+    base: &[Value],
+    local: &[Value],
+    remote: &[Value],
+    path: &str,
+    conflicts: &mut Vec<String>,
+) -> Option<Vec<Value>> {
+    let named = [base, local, remote].iter().all(|items| unique_names(items).is_some());
+    let identity = |item: &Value| -> String {
+        match (named, item.get("name").and_then(Value::as_str)) {
+            (true, Some(name)) => format!("name:{name}"),
+            _ => format!("value:{}", serde_yaml::to_string(item).unwrap_or_default()),
+        }
+    };
+    let ids = |items: &[Value]| -> Vec<String> { items.iter().map(identity).collect() };
+    let (base_ids, local_ids, remote_ids) = (ids(base), ids(local), ids(remote));
+    let order = diff3(&base_ids, &local_ids, &remote_ids)?;
+
+    let index = |items: &'_ [Value], ids: &[String]| -> HashMap<String, Value> {
+        ids.iter().cloned().zip(items.iter().cloned()).collect()
+    };
+    let (base_items, local_items, remote_items) =
+        (index(base, &base_ids), index(local, &local_ids), index(remote, &remote_ids));
+
+    // An item one side dropped while the other side edited it
+    for id in base_ids.iter().filter(|id| !order.contains(id)) {
+        let edited = [&local_items, &remote_items]
+            .iter()
+            .any(|items| items.get(id).is_some_and(|item| Some(item) != base_items.get(id)));
+        if edited {
+            conflicts.push(item_path(path, id));
+        }
+    }
+
+    let merged = order
+        .iter()
+        .filter_map(|id| {
+            merge_optional(
+                base_items.get(id),
+                local_items.get(id),
+                remote_items.get(id),
+                &item_path(path, id),
+                conflicts,
+            )
+        })
+        .collect();
+    Some(merged)
+}
+
+fn unique_names(items: &[Value]) -> Option<()> {
+    let mut seen = std::collections::HashSet::new();
+    for item in items {
+        let name = item.get("name")?.as_str()?;
+        if !seen.insert(name) {
+            return None;
+        }
+    }
+    Some(())
+}
+
+/// Classic diff3 merge of three sequences, `None` on overlapping edits
+fn diff3<T: PartialEq + Clone>(base: &[T], local: &[T], remote: &[T]) -> Option<Vec<T>> {
+    let to_local = lcs_matches(base, local)?;
+    let to_remote = lcs_matches(base, remote)?;
+    let (mut b, mut l, mut r) = (0, 0, 0);
+    let mut merged = vec![];
+    loop {
+        let stable = (b..base.len()).find(|&i| to_local[i].is_some() && to_remote[i].is_some());
+        let (end_b, end_l, end_r) = match stable {
+            Some(i) => (i, to_local[i]?, to_remote[i]?),
+            None => (base.len(), local.len(), remote.len()),
+        };
+        let chunk_base = &base[b..end_b];
+        let chunk_local = &local[l..end_l];
+        let chunk_remote = &remote[r..end_r];
+        if chunk_local == chunk_base || chunk_local == chunk_remote {
+            merged.extend_from_slice(chunk_remote);
+        } else if chunk_remote == chunk_base {
+            merged.extend_from_slice(chunk_local);
+        } else {
+            return None;
+        }
+        match stable {
+            Some(i) => {
+                merged.push(base[i].clone());
+                (b, l, r) = (end_b + 1, end_l + 1, end_r + 1);
+            }
+            None => return Some(merged),
+        }
+    }
+}
+
+/// For each item of `base`, its index in `other` along a longest common
+/// subsequence
+fn lcs_matches<T: PartialEq>(base: &[T], other: &[T]) -> Option<Vec<Option<usize>>> {
+    let mut matches = vec![None; base.len()];
+    let prefix = base.iter().zip(other).take_while(|(a, b)| a == b).count();
+    let suffix = base[prefix..]
+        .iter()
+        .rev()
+        .zip(other[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    for (i, slot) in matches.iter_mut().enumerate().take(prefix) {
+        *slot = Some(i);
+    }
+    for k in 0..suffix {
+        matches[base.len() - 1 - k] = Some(other.len() - 1 - k);
+    }
+
+    let middle_base = &base[prefix..base.len() - suffix];
+    let middle_other = &other[prefix..other.len() - suffix];
+    let (n, m) = (middle_base.len(), middle_other.len());
+    if n == 0 || m == 0 {
+        return Some(matches);
+    }
+    if (n + 1) * (m + 1) > MAX_LCS_CELLS {
+        return None;
+    }
+    let mut table = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[at(i, j)] = if middle_base[i] == middle_other[j] {
+                table[at(i + 1, j + 1)] + 1
+            } else {
+                table[at(i + 1, j)].max(table[at(i, j + 1)])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if middle_base[i] == middle_other[j] {
+            matches[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if table[at(i + 1, j)] >= table[at(i, j + 1)] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    Some(matches)
+}
+
+fn key_label(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        other => serde_yaml::to_string(other).unwrap_or_default().trim().to_string(),
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn item_path(path: &str, id: &str) -> String {
+    let label = id
+        .strip_prefix("name:")
+        .map(str::to_string)
+        .unwrap_or_else(|| id.trim_start_matches("value:").trim().to_string());
+    format!("{}[{label}]", display_path(path))
+}
+
+fn display_path(path: &str) -> String {
+    if path.is_empty() { "<root>".to_string() } else { path.to_string() }
+}
+
+fn toml_to_yaml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(number) => Value::Number(number.into()),
+        toml::Value::Float(number) => Value::Number(number.into()),
+        toml::Value::Boolean(flag) => Value::Bool(flag),
+        // Kept tagged so it turns back into a datetime, not a string
+        toml::Value::Datetime(datetime) => Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new(TOML_DATETIME_TAG),
+            value: Value::String(datetime.to_string()),
+        })),
+        toml::Value::Array(items) => Value::Sequence(items.into_iter().map(toml_to_yaml).collect()),
+        toml::Value::Table(table) => Value::Mapping(
+            table
+                .into_iter()
+                .map(|(key, value)| (Value::String(key), toml_to_yaml(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn yaml_to_toml(value: &Value) -> Result<toml::Value> {
+    Ok(match value {
+        Value::String(text) => toml::Value::String(text.clone()),
+        Value::Bool(flag) => toml::Value::Boolean(*flag),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => toml::Value::Integer(integer),
+            None => toml::Value::Float(number.as_f64().ok_or_else(|| anyhow!("number out of range"))?),
+        },
+        Value::Sequence(items) => toml::Value::Array(items.iter().map(yaml_to_toml).collect::<Result<_>>()?),
+        Value::Mapping(mapping) => {
+            let mut table = toml::map::Map::new();
+            for (key, value) in mapping {
+                table.insert(key_label(key), yaml_to_toml(value)?);
+            }
+            toml::Value::Table(table)
+        }
+        Value::Tagged(tagged) if tagged.tag == TOML_DATETIME_TAG => {
+            let text = tagged.value.as_str().unwrap_or_default();
+            toml::Value::Datetime(text.parse().map_err(|err| anyhow!("invalid datetime {text}: {err}"))?)
+        }
+        Value::Null | Value::Tagged(_) => return Err(anyhow!("value has no TOML form")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "\
+mode: rule
+dns:
+  enable: true
+  nameserver: [223.5.5.5, 119.29.29.29]
+proxies:
+  - { name: hk, type: ss, server: a.example, port: 443 }
+  - { name: jp, type: ss, server: b.example, port: 443 }
+rules:
+  - DOMAIN-SUFFIX,a.com,hk
+  - DOMAIN-SUFFIX,b.com,jp
+  - MATCH,DIRECT
+";
+
+    fn merge(path: &str, base: &str, local: &str, remote: &str) -> MergeOutcome {
+        merge_documents(path, base.as_bytes(), local.as_bytes(), remote.as_bytes()).unwrap()
+    }
+
+    fn merged_yaml(local: &str, remote: &str) -> Value {
+        match merge("p.yaml", BASE, local, remote) {
+            MergeOutcome::Merged(bytes) => serde_yaml::from_slice(&bytes).unwrap(),
+            MergeOutcome::Conflict(paths) => panic!("unexpected conflict: {paths:?}"),
+        }
+    }
+
+    #[test]
+    fn test_non_overlapping_edits_merge() {
+        let local = BASE
+            .replace("mode: rule", "mode: global")
+            .replace("  - MATCH,DIRECT", "  - DOMAIN,local.example,DIRECT\n  - MATCH,DIRECT")
+            .replace("server: a.example", "server: a2.example");
+        let remote = BASE
+            .replace("  - DOMAIN-SUFFIX,a.com,hk\n", "  - GEOIP,CN,DIRECT\n  - DOMAIN-SUFFIX,a.com,hk\n")
+            .replace("b.example, port: 443", "b.example, port: 8443")
+            .replace("[223.5.5.5, 119.29.29.29]", "[223.5.5.5, 119.29.29.29, 1.1.1.1]")
+            + "proxy-groups:\n  - { name: auto, type: select, proxies: [hk, jp] }\n";
+        let merged = merged_yaml(&local, &remote);
+
+        assert_eq!(merged["mode"], "global");
+        assert_eq!(merged["dns"]["nameserver"].as_sequence().unwrap().len(), 3);
+        assert_eq!(merged["proxies"][0]["server"], "a2.example");
+        assert_eq!(merged["proxies"][1]["port"], 8443);
+        assert_eq!(merged["proxy-groups"][0]["name"], "auto");
+        let rules: Vec<&str> = merged["rules"].as_sequence().unwrap().iter().filter_map(Value::as_str).collect();
+        assert_eq!(
+            rules,
+            ["GEOIP,CN,DIRECT", "DOMAIN-SUFFIX,a.com,hk", "DOMAIN-SUFFIX,b.com,jp", "DOMAIN,local.example,DIRECT", "MATCH,DIRECT"]
+        );
+    }
+
+    #[test]
+    fn test_named_items_reorder_and_delete() {
+        let local = BASE.replace("  - { name: jp, type: ss, server: b.example, port: 443 }\n", "");
+        let remote = BASE.replace("a.example, port: 443", "a.example, port: 1443");
+        let merged = merged_yaml(&local, &remote);
+        let proxies = merged["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0]["port"], 1443);
+    }
+
+    #[test]
+    fn test_overlapping_edits_conflict() {
+        let local = BASE.replace("mode: rule", "mode: global").replace("b.example, port: 443", "b.example, port: 1");
+        let remote = BASE.replace("mode: rule", "mode: direct").replace("b.example, port: 443", "b.example, port: 2");
+        assert_eq!(
+            merge("p.yaml", BASE, &local, &remote),
+            MergeOutcome::Conflict(vec!["mode".to_string(), "proxies[jp].port".to_string()])
+        );
+
+        // One side drops a proxy the other side edited
+        let local = BASE.replace("  - { name: jp, type: ss, server: b.example, port: 443 }\n", "");
+        let remote = BASE.replace("b.example, port: 443", "b.example, port: 2");
+        assert_eq!(
+            merge("p.yaml", BASE, &local, &remote),
+            MergeOutcome::Conflict(vec!["proxies[jp]".to_string()])
+        );
+
+        // Both sides insert different rules at the same place
+        let local = BASE.replace("  - MATCH,DIRECT", "  - DOMAIN,x.com,hk\n  - MATCH,DIRECT");
+        let remote = BASE.replace("  - MATCH,DIRECT", "  - DOMAIN,y.com,jp\n  - MATCH,DIRECT");
+        assert_eq!(merge("p.yaml", BASE, &local, &remote), MergeOutcome::Conflict(vec!["rules".to_string()]));
+    }
+
+    #[test]
+    fn test_toml_merge_keeps_types() {
+        let base = "[webdav]\nenabled = false\nurl = \"\"\n\n[meta]\nupdated = 2024-01-01T00:00:00Z\n";
+        let local = base.replace("enabled = false", "enabled = true");
+        let remote = base.replace("url = \"\"", "url = \"https://dav.example\"");
+        let MergeOutcome::Merged(bytes) = merge("settings.toml", base, &local, &remote) else {
+            panic!("expected merge");
+        };
+        let merged: toml::Value = toml::from_str(std::str::from_utf8(&bytes).unwrap()).unwrap();
+        assert_eq!(merged["webdav"]["enabled"].as_bool(), Some(true));
+        assert_eq!(merged["webdav"]["url"].as_str(), Some("https://dav.example"));
+        assert!(merged["meta"]["updated"].is_datetime());
+
+        assert!(merge_documents("notes.txt", b"", b"", b"").is_err());
+    }
+}
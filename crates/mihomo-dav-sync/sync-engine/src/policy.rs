@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+
+/// What to do with a file changed on both sides since the last sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStrategy {
+    /// Three-way merge; what cannot be merged waits for manual resolution
+    #[default]
+    Merge,
+    /// Take the remote version and keep the local one as a sibling copy
+    KeepBoth,
+    PreferLocal,
+    PreferRemote,
+    /// The side modified last wins
+    NewestWins,
+}
+
+/// Strategy for the files matching `pattern`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathOverride {
+    /// Glob over the synced relative path, e.g. `rules/**` or `*.toml`
+    pub pattern: String,
+    pub strategy: ConflictStrategy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConflictPolicy {
+    pub default: ConflictStrategy,
+    /// Checked in order, the first matching pattern wins
+    pub overrides: Vec<PathOverride>,
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+impl ConflictPolicy {
+    pub fn validate(&self) -> Result<()> {
+        for entry in &self.overrides {
+            Pattern::new(&entry.pattern)
+                .map_err(|err| anyhow!("invalid conflict pattern {:?}: {err}", entry.pattern))?;
+        }
+        Ok(())
+    }
+
+    pub fn strategy_for(&self, path: &str) -> ConflictStrategy {
+        self.overrides
+            .iter()
+            .find(|entry| {
+                Pattern::new(&entry.pattern)
+                    .is_ok_and(|pattern| pattern.matches_with(path, MATCH_OPTIONS))
+            })
+            .map_or(self.default, |entry| entry.strategy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(overrides: &[(&str, ConflictStrategy)]) -> ConflictPolicy {
+        ConflictPolicy {
+            default: ConflictStrategy::Merge,
+            overrides: overrides
+                .iter()
+                .map(|(pattern, strategy)| PathOverride {
+                    pattern: pattern.to_string(),
+                    strategy: *strategy,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_first_matching_override_wins() {
+        let policy = policy(&[
+            ("rules/local/**", ConflictStrategy::PreferLocal),
+            ("rules/**", ConflictStrategy::PreferRemote),
+            ("*.toml", ConflictStrategy::NewestWins),
+        ]);
+        assert_eq!(policy.strategy_for("rules/local/a.yaml"), ConflictStrategy::PreferLocal);
+        assert_eq!(policy.strategy_for("rules/a.yaml"), ConflictStrategy::PreferRemote);
+        assert_eq!(policy.strategy_for("settings.toml"), ConflictStrategy::NewestWins);
+        // `*` does not cross directories
+        assert_eq!(policy.strategy_for("nested/settings.toml"), ConflictStrategy::Merge);
+        assert_eq!(policy.strategy_for("main.yaml"), ConflictStrategy::Merge);
+    }
+
+    #[test]
+    fn test_policy_serde_and_validation() {
+        let json = r#"{"default":"keep-both","overrides":[{"pattern":"**/*.toml","strategy":"prefer-remote"}]}"#;
+        let parsed: ConflictPolicy = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.default, ConflictStrategy::KeepBoth);
+        assert_eq!(parsed.strategy_for("a/b/c.toml"), ConflictStrategy::PreferRemote);
+        assert_eq!(parsed.strategy_for("c.toml"), ConflictStrategy::PreferRemote);
+        assert!(parsed.validate().is_ok());
+        assert_eq!(serde_json::from_str::<ConflictPolicy>("{}").unwrap(), ConflictPolicy::default());
+
+        assert!(policy(&[("rules/[", ConflictStrategy::PreferLocal)]).validate().is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use mihomo_api::{MihomoError, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::android_bridge::{get_android_bridge, AndroidBridge};
@@ -33,6 +33,14 @@ impl CoreController for AndroidCoreController {
     fn controller_url(&self) -> Option<String> {
         get_android_bridge().and_then(|bridge| bridge.core_controller_url())
     }
+
+    async fn test_config(&self, config_path: &Path) -> Result<()> {
+        let bridge = require_service_bridge("core test config")?;
+        match bridge.core_test_config(config_path).await? {
+            Some(message) => Err(MihomoError::Config(message)),
+            None => Ok(()),
+        }
+    }
 }
 
 pub struct AndroidCredentialStore;
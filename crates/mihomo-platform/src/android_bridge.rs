@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use mihomo_api::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, OnceLock, RwLock};
 
 #[async_trait]
@@ -9,6 +9,8 @@ pub trait AndroidBridge: Send + Sync {
     async fn core_stop(&self) -> Result<()>;
     async fn core_is_running(&self) -> Result<bool>;
     fn core_controller_url(&self) -> Option<String>;
+    /// Run the core's config check on `config_path`; `Some(message)` when it fails
+    async fn core_test_config(&self, config_path: &Path) -> Result<Option<String>>;
 
     async fn credential_get(&self, service: &str, key: &str) -> Result<Option<String>>;
     async fn credential_set(&self, service: &str, key: &str, value: &str) -> Result<()>;
@@ -42,6 +44,10 @@ impl AndroidBridge for Box<dyn AndroidBridge> {
         self.as_ref().core_controller_url()
     }
 
+    async fn core_test_config(&self, config_path: &Path) -> Result<Option<String>> {
+        self.as_ref().core_test_config(config_path).await
+    }
+
     async fn credential_get(&self, service: &str, key: &str) -> Result<Option<String>> {
         self.as_ref().credential_get(service, key).await
     }
@@ -101,6 +107,10 @@ impl AndroidBridge for Arc<dyn AndroidBridge> {
         self.as_ref().core_controller_url()
     }
 
+    async fn core_test_config(&self, config_path: &Path) -> Result<Option<String>> {
+        self.as_ref().core_test_config(config_path).await
+    }
+
     async fn credential_get(&self, service: &str, key: &str) -> Result<Option<String>> {
         self.as_ref().credential_get(service, key).await
     }
@@ -192,6 +202,10 @@ mod tests {
             Some("http://127.0.0.1:9090".to_string())
         }
 
+        async fn core_test_config(&self, _config_path: &Path) -> Result<Option<String>> {
+            Ok(None)
+        }
+
         async fn credential_get(&self, _service: &str, _key: &str) -> Result<Option<String>> {
             Ok(Some("secret".to_string()))
         }
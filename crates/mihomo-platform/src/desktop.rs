@@ -1,16 +1,26 @@
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use mihomo_api::{MihomoError, Result};
+use tokio::sync::{broadcast, Mutex};
 use tokio::time::Duration;
 
 use crate::paths::get_home_dir;
+use crate::supervisor::{
+    CoreState, CoreSupervisor, RestartPolicy, SupervisorConfig, SupervisorEvent,
+};
 use crate::traits::{CoreController, CredentialStore, DataDirProvider};
 
+pub(crate) use process::{extract_log_msg, remove_pid_file, write_pid_file};
+
 pub struct ProcessCoreController {
     binary_path: PathBuf,
     config_path: PathBuf,
     pid_file: PathBuf,
+    log_path: Option<PathBuf>,
+    policy: RestartPolicy,
+    supervisor: Mutex<Option<CoreSupervisor>>,
+    events: broadcast::Sender<SupervisorEvent>,
 }
 
 impl ProcessCoreController {
@@ -18,31 +28,54 @@ impl ProcessCoreController {
         let home = get_home_dir().unwrap_or_else(|_| PathBuf::from("."));
         let pid_file = home.join("mihomo.pid");
 
-        Self {
-            binary_path,
-            config_path,
-            pid_file,
-        }
+        Self::with_pid_file(binary_path, config_path, pid_file)
     }
 
     pub fn with_home(binary_path: PathBuf, config_path: PathBuf, home: PathBuf) -> Self {
         let pid_file = home.join("mihomo.pid");
 
-        Self {
-            binary_path,
-            config_path,
-            pid_file,
-        }
+        Self::with_pid_file(binary_path, config_path, pid_file)
     }
 
     pub fn with_pid_file(binary_path: PathBuf, config_path: PathBuf, pid_file: PathBuf) -> Self {
+        let (events, _) = broadcast::channel(crate::supervisor::EVENT_CHANNEL_SIZE);
         Self {
             binary_path,
             config_path,
             pid_file,
+            log_path: None,
+            policy: RestartPolicy::default(),
+            supervisor: Mutex::new(None),
+            events,
         }
     }
 
+    /// Write core output here instead of `<home>/logs/mihomo.log`
+    pub fn with_log_file(mut self, log_path: PathBuf) -> Self {
+        self.log_path = Some(log_path);
+        self
+    }
+
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Lifecycle events of the supervised core: starts, crashes, restarts
+    pub fn subscribe(&self) -> broadcast::Receiver<SupervisorEvent> {
+        self.events.subscribe()
+    }
+
+    /// State reported by the supervisor, or `None` when this controller did
+    /// not start the core (e.g. it was left running by an earlier session)
+    pub async fn core_state(&self) -> Option<CoreState> {
+        self.supervisor
+            .lock()
+            .await
+            .as_ref()
+            .map(CoreSupervisor::state)
+    }
+
     async fn read_running_pid(&self) -> Result<Option<u32>> {
         match process::read_pid_file(&self.pid_file).await {
             Ok(pid) => {
@@ -67,20 +100,51 @@ impl CoreController for ProcessCoreController {
             ));
         }
 
-        let pid = process::spawn_daemon(&self.binary_path, &self.config_path).await?;
-        process::write_pid_file(&self.pid_file, pid).await?;
+        let mut supervisor = self.supervisor.lock().await;
+        if let Some(previous) = supervisor.take() {
+            if matches!(previous.state(), CoreState::Restarting { .. }) {
+                *supervisor = Some(previous);
+                return Err(MihomoError::Service(
+                    "Service is restarting".to_string(),
+                ));
+            }
+            previous.stop().await;
+        }
+
+        let log_path = match &self.log_path {
+            Some(path) => path.clone(),
+            None => get_home_dir()?.join("logs").join("mihomo.log"),
+        };
+        let config = SupervisorConfig {
+            binary: self.binary_path.clone(),
+            config: self.config_path.clone(),
+            pid_file: self.pid_file.clone(),
+            log_path,
+            policy: self.policy.clone(),
+        };
+        let started = CoreSupervisor::spawn(config, self.events.clone()).await?;
 
         tokio::time::sleep(Duration::from_millis(500)).await;
 
-        if !process::is_process_alive(pid) {
-            process::remove_pid_file(&self.pid_file).await?;
+        if !matches!(started.state(), CoreState::Running(pid) if process::is_process_alive(pid)) {
+            started.stop().await;
             return Err(MihomoError::Service("Service failed to start".to_string()));
         }
 
+        *supervisor = Some(started);
         Ok(())
     }
 
     async fn stop(&self) -> Result<()> {
+        if let Some(supervisor) = self.supervisor.lock().await.take() {
+            supervisor.stop().await;
+            // A crashed or restarting core has no child left once the
+            // supervisor is gone; only fall through for a stray process
+            if self.read_running_pid().await?.is_none() {
+                return Ok(());
+            }
+        }
+
         let pid = process::read_pid_file(&self.pid_file).await?;
 
         if !process::is_process_alive(pid) {
@@ -111,6 +175,10 @@ impl CoreController for ProcessCoreController {
             }
         }
     }
+
+    async fn test_config(&self, config_path: &Path) -> Result<()> {
+        process::test_config(&self.binary_path, config_path).await
+    }
 }
 
 pub struct KeyringCredentialStore;
@@ -175,91 +243,92 @@ impl DataDirProvider for DesktopDataDirProvider {
 
 mod process {
     use mihomo_api::{MihomoError, Result};
-    use std::fs::OpenOptions;
-    use std::path::{Path, PathBuf};
-    use std::process::{Command, Stdio};
+    use std::path::Path;
+    use std::process::Stdio;
     use sysinfo::{Pid, ProcessesToUpdate, System};
     use tokio::fs;
+    use tokio::time::Duration;
 
     #[cfg(windows)]
     use std::os::windows::process::CommandExt;
     #[cfg(windows)]
     use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
 
-    use crate::paths::get_home_dir;
+    const CONFIG_TEST_TIMEOUT: Duration = Duration::from_secs(30);
 
-    pub async fn spawn_daemon(binary: &Path, config: &Path) -> Result<u32> {
+    /// Run the core with `-t` against `config` and surface its complaint
+    pub async fn test_config(binary: &Path, config: &Path) -> Result<()> {
         if !binary.exists() {
             return Err(MihomoError::NotFound(format!(
                 "Binary not found: {}",
                 binary.display()
             )));
         }
-
-        if !config.exists() {
-            return Err(MihomoError::NotFound(format!(
-                "Config not found: {}",
-                config.display()
-            )));
-        }
-
-        let log_path = prepare_log_file().await?;
-        let stdout = open_log_file(&log_path)?;
-        let stderr = open_log_file(&log_path)?;
-        log::info!("mihomo log file: {}", log_path.display());
-
         let config_dir = config
             .parent()
             .ok_or_else(|| MihomoError::Config("Config file has no parent directory".to_string()))?;
 
-        let mut command = Command::new(binary);
+        let mut command = tokio::process::Command::new(binary);
         command
+            .arg("-t")
             .arg("-d")
             .arg(config_dir)
             .arg("-f")
             .arg(config)
             .stdin(Stdio::null())
-            .stdout(Stdio::from(stdout))
-            .stderr(Stdio::from(stderr));
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         #[cfg(windows)]
         {
             command.creation_flags(CREATE_NO_WINDOW);
         }
 
-        let mut child = command
-            .spawn()
-            .map_err(|e| MihomoError::Service(format!("Failed to spawn process: {}", e)))?;
-
-        if let Ok(Some(status)) = child.try_wait() {
-            return Err(MihomoError::Service(format!(
-                "Process exited immediately with status: {}",
-                status
-            )));
+        let output = tokio::time::timeout(CONFIG_TEST_TIMEOUT, command.output())
+            .await
+            .map_err(|_| MihomoError::Service("Config test timed out".to_string()))?
+            .map_err(|e| MihomoError::Service(format!("Failed to run config test: {}", e)))?;
+        if output.status.success() {
+            return Ok(());
         }
 
-        Ok(child.id())
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(MihomoError::Config(summarize_test_output(&stdout, &stderr)))
     }
 
-    async fn prepare_log_file() -> Result<PathBuf> {
-        let home = get_home_dir()?;
-        let log_dir = home.join("logs");
-        fs::create_dir_all(&log_dir).await?;
-        Ok(log_dir.join("mihomo.log"))
+    /// Pick the error lines out of the core's log output, falling back to
+    /// the raw tail when nothing is tagged as an error
+    pub(super) fn summarize_test_output(stdout: &str, stderr: &str) -> String {
+        let lines: Vec<&str> = stderr
+            .lines()
+            .chain(stdout.lines())
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let errors: Vec<String> = lines
+            .iter()
+            .filter(|line| line.contains("level=error") || line.contains("level=fatal"))
+            .map(|line| extract_log_msg(line).unwrap_or(line).to_string())
+            .collect();
+        if !errors.is_empty() {
+            return errors.join("\n");
+        }
+        let tail = lines.len().saturating_sub(5);
+        let summary = lines[tail..].join("\n");
+        if summary.is_empty() {
+            "Config test failed".to_string()
+        } else {
+            summary
+        }
     }
 
-    fn open_log_file(path: &Path) -> Result<std::fs::File> {
-        OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path)
-            .map_err(|e| {
-                MihomoError::Service(format!(
-                    "Failed to open log file {}: {}",
-                    path.display(),
-                    e
-                ))
-            })
+    pub(crate) fn extract_log_msg(line: &str) -> Option<&str> {
+        let start = line.find("msg=\"")? + 5;
+        let rest = &line[start..];
+        let end = rest.rfind('"')?;
+        Some(&rest[..end])
     }
 
     pub fn kill_process(pid: u32) -> Result<()> {
@@ -313,3 +382,104 @@ mod process {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::process::summarize_test_output;
+    use super::ProcessCoreController;
+    use crate::traits::CoreController;
+    use mihomo_api::MihomoError;
+
+    #[test]
+    fn test_summarize_test_output_prefers_error_lines() {
+        let stderr = concat!(
+            "time=\"2026-01-01T00:00:00Z\" level=info msg=\"Start initial configuration in progress\"\n",
+            "time=\"2026-01-01T00:00:00Z\" level=error msg=\"proxy group[0]: 'missing' not found\"\n",
+        );
+        let summary = summarize_test_output("configuration file test failed", stderr);
+        assert_eq!(summary, "proxy group[0]: 'missing' not found");
+    }
+
+    #[test]
+    fn test_summarize_test_output_falls_back_to_tail() {
+        let summary = summarize_test_output("", "parse config error: yaml: line 3\n");
+        assert_eq!(summary, "parse config error: yaml: line 3");
+        assert_eq!(summarize_test_output("", ""), "Config test failed");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_config_runs_core_in_test_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary = temp_dir.path().join("mihomo");
+        std::fs::write(
+            &binary,
+            "#!/bin/sh\n[ \"$1\" = \"-t\" ] || exit 2\ngrep -q broken \"$5\" || exit 0\necho 'time=\"x\" level=error msg=\"rules[0] [MATCH,Ghost] error: proxy [Ghost] not found\"' >&2\nexit 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = temp_dir.path().join("config.yaml");
+        let controller = ProcessCoreController::with_pid_file(
+            binary,
+            config.clone(),
+            temp_dir.path().join("mihomo.pid"),
+        );
+
+        std::fs::write(&config, "mode: rule\n").unwrap();
+        controller.test_config(&config).await.unwrap();
+
+        std::fs::write(&config, "# broken\nrules: [MATCH,Ghost]\n").unwrap();
+        let err = controller.test_config(&config).await.unwrap_err();
+        assert!(
+            matches!(err, MihomoError::Config(ref message) if message == "rules[0] [MATCH,Ghost] error: proxy [Ghost] not found"),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stop_after_crash_succeeds() {
+        use crate::supervisor::{CoreState, RestartPolicy, SupervisorEvent};
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::Duration;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary = temp_dir.path().join("mihomo");
+        std::fs::write(&binary, "#!/bin/sh\nsleep 1\nexit 3\n").unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = temp_dir.path().join("config.yaml");
+        std::fs::write(&config, "mode: rule\n").unwrap();
+        let controller = ProcessCoreController::with_pid_file(
+            binary,
+            config,
+            temp_dir.path().join("mihomo.pid"),
+        )
+        .with_log_file(temp_dir.path().join("mihomo.log"))
+        .with_restart_policy(RestartPolicy {
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(20),
+            max_restarts: 0,
+            crash_window: Duration::from_secs(60),
+            healthy_after: Duration::from_secs(60),
+        });
+        let mut events = controller.subscribe();
+
+        controller.start().await.unwrap();
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(10), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if matches!(event, SupervisorEvent::GaveUp { .. }) {
+                break;
+            }
+        }
+        assert!(matches!(controller.core_state().await, Some(CoreState::Crashed(_))));
+
+        controller.stop().await.unwrap();
+        assert_eq!(controller.core_state().await, None);
+        assert!(!controller.is_running().await);
+    }
+}
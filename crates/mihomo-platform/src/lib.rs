@@ -15,6 +15,10 @@ pub use traits::{
 pub mod desktop;
 #[cfg(not(target_os = "android"))]
 pub use desktop::{DesktopDataDirProvider, KeyringCredentialStore, ProcessCoreController};
+#[cfg(not(target_os = "android"))]
+pub mod supervisor;
+#[cfg(not(target_os = "android"))]
+pub use supervisor::{CoreState, RestartPolicy, SupervisorEvent};
 
 #[cfg(target_os = "android")]
 pub mod android;
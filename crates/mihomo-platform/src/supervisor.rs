@@ -0,0 +1,549 @@
+//! Watch a spawned core process, keep its output in a rotating log file and
+//! restart it with exponential backoff when it exits on its own.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+
+use mihomo_api::{MihomoError, Result};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+use crate::desktop::{extract_log_msg, remove_pid_file, write_pid_file};
+
+#[cfg(windows)]
+use windows_sys::Win32::System::Threading::CREATE_NO_WINDOW;
+
+/// Output lines kept in memory to explain an exit
+const TAIL_LINES: usize = 20;
+pub(crate) const EVENT_CHANNEL_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Crashes tolerated inside `crash_window` before giving up
+    pub max_restarts: usize,
+    pub crash_window: Duration,
+    /// A run longer than this resets the backoff
+    pub healthy_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_restarts: 5,
+            crash_window: Duration::from_secs(120),
+            healthy_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreState {
+    Running(u32),
+    Restarting { attempt: u32 },
+    /// Gave up after repeated crashes; carries the last exit reason
+    Crashed(String),
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupervisorEvent {
+    Started { pid: u32, restarts: u32 },
+    Exited { reason: String },
+    Restarting { attempt: u32, delay: Duration },
+    GaveUp { reason: String },
+    Stopped,
+}
+
+/// Append-only log file that rolls over to `<name>.1`, `<name>.2`, ...
+pub struct RotatingLog {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingLog {
+    pub const DEFAULT_MAX_BYTES: u64 = 5 * 1024 * 1024;
+    pub const DEFAULT_KEEP: usize = 3;
+
+    pub fn open(path: &Path, max_bytes: u64, keep: usize) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = open_append(path)?;
+        let written = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            keep,
+            file,
+            written,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.written > 0 && self.written + len > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.written += len;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        if self.keep == 0 {
+            self.file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)?;
+            self.written = 0;
+            return Ok(());
+        }
+        for index in (1..self.keep).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))?;
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| {
+            MihomoError::Service(format!("Failed to open log file {}: {}", path.display(), e))
+        })
+}
+
+pub struct SupervisorConfig {
+    pub binary: PathBuf,
+    pub config: PathBuf,
+    pub pid_file: PathBuf,
+    pub log_path: PathBuf,
+    pub policy: RestartPolicy,
+}
+
+/// Owns the core child process until `stop` is called
+pub struct CoreSupervisor {
+    state: Arc<Mutex<CoreState>>,
+    stop_tx: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl CoreSupervisor {
+    /// Spawn the core and start watching it. Failing to launch the first
+    /// process is reported here rather than retried.
+    pub async fn spawn(
+        config: SupervisorConfig,
+        events: broadcast::Sender<SupervisorEvent>,
+    ) -> Result<Self> {
+        if !config.binary.exists() {
+            return Err(MihomoError::NotFound(format!(
+                "Binary not found: {}",
+                config.binary.display()
+            )));
+        }
+        if !config.config.exists() {
+            return Err(MihomoError::NotFound(format!(
+                "Config not found: {}",
+                config.config.display()
+            )));
+        }
+
+        let log = RotatingLog::open(
+            &config.log_path,
+            RotatingLog::DEFAULT_MAX_BYTES,
+            RotatingLog::DEFAULT_KEEP,
+        )?;
+        log::info!("mihomo log file: {}", config.log_path.display());
+        let log = Arc::new(Mutex::new(log));
+        let child = spawn_child(&config.binary, &config.config)?;
+        let pid = child.id().unwrap_or(0);
+        write_pid_file(&config.pid_file, pid).await?;
+
+        let state = Arc::new(Mutex::new(CoreState::Running(pid)));
+        let _ = events.send(SupervisorEvent::Started { pid, restarts: 0 });
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let task = tokio::spawn(supervise(
+            config,
+            child,
+            log,
+            Arc::clone(&state),
+            events,
+            stop_rx,
+        ));
+        Ok(Self {
+            state,
+            stop_tx,
+            task,
+        })
+    }
+
+    pub fn state(&self) -> CoreState {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Kill the child without restarting it and wait for the watcher to exit
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(true);
+        if let Err(err) = self.task.await {
+            log::warn!("core supervisor task failed: {err}");
+        }
+    }
+}
+
+fn set_state(state: &Mutex<CoreState>, next: CoreState) {
+    if let Ok(mut guard) = state.lock() {
+        *guard = next;
+    }
+}
+
+async fn supervise(
+    config: SupervisorConfig,
+    mut child: Child,
+    log: Arc<Mutex<RotatingLog>>,
+    state: Arc<Mutex<CoreState>>,
+    events: broadcast::Sender<SupervisorEvent>,
+    mut stop_rx: watch::Receiver<bool>,
+) {
+    let mut crashes: VecDeque<Instant> = VecDeque::new();
+    let mut attempt = 0u32;
+    let mut restarts = 0u32;
+    loop {
+        let started = Instant::now();
+        let tail = Arc::new(Mutex::new(VecDeque::with_capacity(TAIL_LINES)));
+        let readers = capture_output(&mut child, &log, &tail);
+
+        let status = tokio::select! {
+            status = child.wait() => status,
+            _ = stop_rx.changed() => {
+                if let Err(err) = child.kill().await {
+                    log::warn!("failed to kill mihomo process: {err}");
+                }
+                finish(&config, &state, &events).await;
+                return;
+            }
+        };
+        for reader in readers {
+            let _ = tokio::time::timeout(Duration::from_secs(1), reader).await;
+        }
+        let _ = remove_pid_file(&config.pid_file).await;
+
+        let reason = describe_exit(status, &tail);
+        log::warn!("mihomo exited unexpectedly: {reason}");
+        let _ = events.send(SupervisorEvent::Exited {
+            reason: reason.clone(),
+        });
+
+        if started.elapsed() >= config.policy.healthy_after {
+            attempt = 0;
+        }
+        let now = Instant::now();
+        crashes.push_back(now);
+        while crashes
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > config.policy.crash_window)
+        {
+            crashes.pop_front();
+        }
+        if crashes.len() > config.policy.max_restarts {
+            log::error!("mihomo crash loop detected, giving up: {reason}");
+            set_state(&state, CoreState::Crashed(reason.clone()));
+            let _ = events.send(SupervisorEvent::GaveUp { reason });
+            return;
+        }
+
+        attempt += 1;
+        let delay = config.policy.backoff(attempt);
+        set_state(&state, CoreState::Restarting { attempt });
+        let _ = events.send(SupervisorEvent::Restarting { attempt, delay });
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stop_rx.changed() => {
+                finish(&config, &state, &events).await;
+                return;
+            }
+        }
+
+        child = match spawn_child(&config.binary, &config.config) {
+            Ok(child) => child,
+            Err(err) => {
+                let reason = err.to_string();
+                log::error!("failed to restart mihomo: {reason}");
+                set_state(&state, CoreState::Crashed(reason.clone()));
+                let _ = events.send(SupervisorEvent::GaveUp { reason });
+                return;
+            }
+        };
+        restarts += 1;
+        let pid = child.id().unwrap_or(0);
+        if let Err(err) = write_pid_file(&config.pid_file, pid).await {
+            log::warn!("failed to write pid file: {err}");
+        }
+        set_state(&state, CoreState::Running(pid));
+        let _ = events.send(SupervisorEvent::Started { pid, restarts });
+    }
+}
+
+async fn finish(
+    config: &SupervisorConfig,
+    state: &Mutex<CoreState>,
+    events: &broadcast::Sender<SupervisorEvent>,
+) {
+    let _ = remove_pid_file(&config.pid_file).await;
+    set_state(state, CoreState::Stopped);
+    let _ = events.send(SupervisorEvent::Stopped);
+}
+
+fn spawn_child(binary: &Path, config: &Path) -> Result<Child> {
+    let config_dir = config
+        .parent()
+        .ok_or_else(|| MihomoError::Config("Config file has no parent directory".to_string()))?;
+
+    let mut command = Command::new(binary);
+    command
+        .arg("-d")
+        .arg(config_dir)
+        .arg("-f")
+        .arg(config)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    #[cfg(windows)]
+    {
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+        .spawn()
+        .map_err(|e| MihomoError::Service(format!("Failed to spawn process: {}", e)))
+}
+
+fn capture_output(
+    child: &mut Child,
+    log: &Arc<Mutex<RotatingLog>>,
+    tail: &Arc<Mutex<VecDeque<String>>>,
+) -> Vec<JoinHandle<()>> {
+    let mut readers = vec![];
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(tokio::spawn(pump_lines(stdout, Arc::clone(log), Arc::clone(tail))));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(tokio::spawn(pump_lines(stderr, Arc::clone(log), Arc::clone(tail))));
+    }
+    readers
+}
+
+async fn pump_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    log: Arc<Mutex<RotatingLog>>,
+    tail: Arc<Mutex<VecDeque<String>>>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Ok(mut log) = log.lock()
+            && let Err(err) = log.write_line(&line)
+        {
+            log::warn!("failed to write mihomo log: {err}");
+        }
+        if let Ok(mut tail) = tail.lock() {
+            if tail.len() == TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    }
+}
+
+/// Combine the exit status with the last error the core printed
+fn describe_exit(status: std::io::Result<ExitStatus>, tail: &Mutex<VecDeque<String>>) -> String {
+    let status = match status {
+        Ok(status) => status.to_string(),
+        Err(err) => format!("wait failed: {err}"),
+    };
+    let tail = tail.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let last_error = tail
+        .iter()
+        .rev()
+        .find(|line| line.contains("level=error") || line.contains("level=fatal"))
+        .or_else(|| tail.back());
+    match last_error {
+        Some(line) => format!("{status}: {}", extract_log_msg(line).unwrap_or(line)),
+        None => status,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let policy = RestartPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..RestartPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_rotating_log_rolls_over() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("logs").join("mihomo.log");
+        let mut log = RotatingLog::open(&path, 10, 2).unwrap();
+        for line in ["first", "second", "third", "fourth"] {
+            log.write_line(line).unwrap();
+        }
+        let read = |suffix: &str| {
+            std::fs::read_to_string(format!("{}{suffix}", path.display())).unwrap()
+        };
+        assert_eq!(read(""), "fourth\n");
+        assert_eq!(read(".1"), "third\n");
+        assert_eq!(read(".2"), "second\n");
+        assert!(!temp_dir.path().join("logs").join("mihomo.log.3").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_supervisor_restarts_then_gives_up() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary = temp_dir.path().join("mihomo");
+        std::fs::write(
+            &binary,
+            "#!/bin/sh\necho 'time=\"x\" level=info msg=\"starting\"'\necho 'time=\"x\" level=fatal msg=\"listen tcp :7890: bind: address already in use\"' >&2\nexit 3\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = temp_dir.path().join("config.yaml");
+        std::fs::write(&config, "mode: rule\n").unwrap();
+        let log_path = temp_dir.path().join("logs").join("mihomo.log");
+
+        let (events, mut receiver) = broadcast::channel(64);
+        let supervisor = CoreSupervisor::spawn(
+            SupervisorConfig {
+                binary,
+                config,
+                pid_file: temp_dir.path().join("mihomo.pid"),
+                log_path: log_path.clone(),
+                policy: RestartPolicy {
+                    initial_backoff: Duration::from_millis(10),
+                    max_backoff: Duration::from_millis(20),
+                    max_restarts: 2,
+                    crash_window: Duration::from_secs(60),
+                    healthy_after: Duration::from_secs(60),
+                },
+            },
+            events,
+        )
+        .await
+        .unwrap();
+
+        let mut seen = vec![];
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            let done = matches!(event, SupervisorEvent::GaveUp { .. });
+            seen.push(event);
+            if done {
+                break;
+            }
+        }
+        let restarts = seen
+            .iter()
+            .filter(|event| matches!(event, SupervisorEvent::Restarting { .. }))
+            .count();
+        assert_eq!(restarts, 2);
+        let reason = "exit status: 3: listen tcp :7890: bind: address already in use";
+        assert_eq!(seen.last(), Some(&SupervisorEvent::GaveUp { reason: reason.to_string() }));
+        assert_eq!(supervisor.state(), CoreState::Crashed(reason.to_string()));
+        assert!(!temp_dir.path().join("mihomo.pid").exists());
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert_eq!(log.matches("address already in use").count(), 3);
+        supervisor.stop().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_supervisor_stop_does_not_restart() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary = temp_dir.path().join("mihomo");
+        std::fs::write(&binary, "#!/bin/sh\nexec sleep 30\n").unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = temp_dir.path().join("config.yaml");
+        std::fs::write(&config, "mode: rule\n").unwrap();
+        let pid_file = temp_dir.path().join("mihomo.pid");
+
+        let (events, mut receiver) = broadcast::channel(64);
+        let supervisor = CoreSupervisor::spawn(
+            SupervisorConfig {
+                binary,
+                config,
+                pid_file: pid_file.clone(),
+                log_path: temp_dir.path().join("mihomo.log"),
+                policy: RestartPolicy::default(),
+            },
+            events,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(supervisor.state(), CoreState::Running(_)));
+        assert!(pid_file.exists());
+
+        supervisor.stop().await;
+        assert!(matches!(receiver.recv().await, Ok(SupervisorEvent::Started { .. })));
+        assert_eq!(receiver.recv().await, Ok(SupervisorEvent::Stopped));
+        assert!(!pid_file.exists());
+    }
+}
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use mihomo_api::Result;
 
@@ -13,6 +13,12 @@ pub trait CoreController: Send + Sync {
     async fn pid(&self) -> Option<u32> {
         None
     }
+
+    /// Ask the core to check `config_path` without starting it. Fails with
+    /// `MihomoError::Config` carrying the core's own error message.
+    async fn test_config(&self, _config_path: &Path) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[async_trait]
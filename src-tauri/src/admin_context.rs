@@ -3,8 +3,9 @@ use tauri::{async_runtime, AppHandle};
 
 use crate::{app_state::AppState, platform, runtime::rebuild_runtime};
 use infiltrator_desktop::editor;
-use infiltrator_admin::AdminApiContext;
-use infiltrator_core::AppSettings;
+use infiltrator_admin::{AdminApiContext, AdminEvent};
+use infiltrator_core::{usage::UsageLedger, AppSettings};
+use mihomo_api::{ConnectionAnalytics, DelayHistoryStore, MihomoClient};
 
 #[derive(Clone)]
 pub(crate) struct TauriAdminContext {
@@ -63,4 +64,34 @@ impl AdminApiContext for TauriAdminContext {
     async fn save_app_settings(&self, settings: AppSettings) -> anyhow::Result<()> {
         self.app_state.set_app_settings(settings).await
     }
+
+    async fn controller_client(&self) -> anyhow::Result<MihomoClient> {
+        Ok(self.app_state.runtime().await?.client())
+    }
+
+    async fn usage_ledger(&self) -> anyhow::Result<UsageLedger> {
+        self.app_state.usage_ledger().await
+    }
+
+    fn runtime_generation(&self) -> u64 {
+        self.app_state.runtime_generation()
+    }
+
+    async fn profile_hot_applied(&self, content: String) {
+        if let Ok(runtime) = self.app_state.runtime().await {
+            runtime.set_loaded_config(content);
+        }
+    }
+
+    fn delay_history(&self) -> DelayHistoryStore {
+        self.app_state.delay_history()
+    }
+
+    fn connection_analytics(&self) -> ConnectionAnalytics {
+        self.app_state.connection_analytics()
+    }
+
+    fn emit_admin_event(&self, event: AdminEvent) {
+        self.app_state.emit_admin_event(event);
+    }
 }
@@ -1,4 +1,10 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use anyhow::anyhow;
 use infiltrator_admin::{
@@ -7,10 +13,13 @@ use infiltrator_admin::{
     SubscriptionScheduler,
     servers::{AdminServerHandle, StaticServerHandle},
 };
-use infiltrator_core::AppSettings;
+use infiltrator_core::{
+    AppSettings,
+    usage::{QuotaAlert, UsageAccumulator, UsageLedger},
+};
 use infiltrator_desktop::{MihomoRuntime, SystemProxyState};
 use log::warn;
-use mihomo_api::ProxyInfo;
+use mihomo_api::{ConnectionAnalytics, DelayHistoryStore, ProxyInfo};
 use mihomo_version::VersionManager;
 use tauri::{
     menu::{CheckMenuItem, MenuItem, Submenu},
@@ -20,6 +29,7 @@ use tauri_plugin_notification::NotificationExt;
 use tokio::sync::RwLock;
 
 use crate::{
+    core_update::format_bytes,
     locales::{Lang, Localizer},
     settings::save_settings,
     system_proxy::{apply_system_proxy, read_system_proxy_state},
@@ -29,12 +39,17 @@ use crate::{
 #[derive(Clone, Default)]
 pub(crate) struct AppState {
     runtime: Arc<RwLock<Option<Arc<MihomoRuntime>>>>,
+    runtime_generation: Arc<AtomicU64>,
     static_server: Arc<RwLock<Option<StaticServerHandle>>>,
     admin_server: Arc<RwLock<Option<AdminServerHandle>>>,
     tray_info: Arc<RwLock<Option<TrayInfoItems>>>,
     system_proxy: Arc<RwLock<SystemProxyState>>,
     current_mode: Arc<RwLock<Option<String>>>,
     proxy_groups: Arc<RwLock<HashMap<String, ProxyInfo>>>,
+    delay_history: DelayHistoryStore,
+    connection_analytics: ConnectionAnalytics,
+    usage: UsageAccumulator,
+    usage_ledger: Arc<RwLock<Option<UsageLedger>>>,
     tun_enabled: Arc<RwLock<bool>>,
     subscription_scheduler: Arc<RwLock<Option<SubscriptionScheduler>>>,
     tray_profile_map: Arc<RwLock<HashMap<String, String>>>,
@@ -104,6 +119,12 @@ impl AppState {
     pub(crate) async fn set_runtime(&self, runtime: MihomoRuntime) {
         let mut guard = self.runtime.write().await;
         *guard = Some(Arc::new(runtime));
+        self.runtime_generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 每注册一个新的内核实例就递增一次
+    pub(crate) fn runtime_generation(&self) -> u64 {
+        self.runtime_generation.load(Ordering::SeqCst)
     }
 
     pub(crate) async fn runtime(&self) -> anyhow::Result<Arc<MihomoRuntime>> {
@@ -227,6 +248,32 @@ impl AppState {
         Ok(proxies)
     }
 
+    pub(crate) fn delay_history(&self) -> DelayHistoryStore {
+        self.delay_history.clone()
+    }
+
+    pub(crate) fn connection_analytics(&self) -> ConnectionAnalytics {
+        self.connection_analytics.clone()
+    }
+
+    pub(crate) fn usage(&self) -> UsageAccumulator {
+        self.usage.clone()
+    }
+
+    /// 流量账本只打开一次，之后复用同一个连接池
+    pub(crate) async fn usage_ledger(&self) -> anyhow::Result<UsageLedger> {
+        if let Some(ledger) = self.usage_ledger.read().await.as_ref() {
+            return Ok(ledger.clone());
+        }
+        let mut guard = self.usage_ledger.write().await;
+        if let Some(ledger) = guard.as_ref() {
+            return Ok(ledger.clone());
+        }
+        let ledger = UsageLedger::open_default().await?;
+        *guard = Some(ledger.clone());
+        Ok(ledger)
+    }
+
     pub(crate) async fn set_tun_enabled(&self, enabled: bool) {
         let mut guard = self.tun_enabled.write().await;
         *guard = enabled;
@@ -569,7 +616,11 @@ impl AppState {
         };
         let body = if success {
             // Need to support formatting in locales, but simple replacement works for now
-            lang.tr("sub_updated").replace("{0}", profile)
+            let body = lang.tr("sub_updated").replace("{0}", profile);
+            match message {
+                Some(note) => format!("{body}\n{note}"),
+                None => body,
+            }
         } else {
             let reason = message.unwrap_or_else(|| lang.tr("unknown").into_owned());
             lang.tr("sub_failed_reason").replace("{0}", profile).replace("{1}", &reason)
@@ -621,6 +672,22 @@ impl AppState {
         self.show_notification(&title, &body).await;
     }
 
+    pub(crate) async fn notify_usage_quota(&self, alert: &QuotaAlert) {
+        let lang_code = self.get_lang_code().await;
+        let lang = Lang(lang_code.as_str());
+        let key = if alert.threshold >= 100 {
+            "usage_quota_exceeded"
+        } else {
+            "usage_quota_warning"
+        };
+        let body = lang.tr(key)
+            .replace("{0}", &alert.profile)
+            .replace("{1}", &format_bytes(alert.used))
+            .replace("{2}", &format_bytes(alert.quota))
+            .replace("{3}", &alert.threshold.to_string());
+        self.show_notification(&lang.tr("usage_quota_title"), &body).await;
+    }
+
     async fn show_notification(&self, title: &str, body: &str) {
         let app_handle = self.app_handle.read().await.clone();
         if let Some(handle) = app_handle
@@ -146,7 +146,7 @@ pub(crate) async fn delete_core_version(version: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn format_bytes(bytes: u64) -> String {
+pub(crate) fn format_bytes(bytes: u64) -> String {
     const KB: f64 = 1024.0;
     const MB: f64 = KB * 1024.0;
     const GB: f64 = MB * 1024.0;
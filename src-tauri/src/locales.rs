@@ -113,6 +113,9 @@ fn translate_zh_cn(key: &str) -> Cow<'static, str> {
         "more_profiles" => "更多配置".into(),
         "update_all_subs" => "立即更新所有订阅".into(),
         "auto_update_sub" => "自动更新当前订阅".into(),
+        "sub_traffic" => "已用 {0} / {1}，剩余 {2}".into(),
+        "sub_traffic_unlimited" => "已用 {0}".into(),
+        "sub_expire" => "到期: {0}".into(),
         "subscription" => "订阅".into(),
         "proxy_groups" => "代理组".into(),
         "proxy_groups_read_failed" => "代理组读取失败".into(),
@@ -163,6 +166,9 @@ fn translate_zh_cn(key: &str) -> Cow<'static, str> {
         "toggle_auto_update_failed" => "切换自动更新失败".into(),
         "core_startup_failed" => "无法启动 mihomo 服务".into(),
         "control_startup_failed" => "控制接口: 启动失败".into(),
+        "usage_quota_title" => "流量配额提醒".into(),
+        "usage_quota_warning" => "配置 \"{0}\" 本月已用 {1}，达到配额 {2} 的 {3}%".into(),
+        "usage_quota_exceeded" => "配置 \"{0}\" 本月已用 {1}，已超出配额 {2}".into(),
 
         _ => key.to_string().into(),
     }
@@ -242,6 +248,9 @@ fn translate_en(key: &str) -> Cow<'static, str> {
         "more_profiles" => "More Profiles".into(),
         "update_all_subs" => "Update All Subscriptions".into(),
         "auto_update_sub" => "Auto-update Current".into(),
+        "sub_traffic" => "Used {0} / {1}, {2} left".into(),
+        "sub_traffic_unlimited" => "Used {0}".into(),
+        "sub_expire" => "Expires: {0}".into(),
         "subscription" => "Sub".into(),
         "proxy_groups" => "Proxy Groups".into(),
         "proxy_groups_read_failed" => "Failed to load groups".into(),
@@ -292,6 +301,9 @@ fn translate_en(key: &str) -> Cow<'static, str> {
         "toggle_auto_update_failed" => "Toggle Auto-update Failed".into(),
         "core_startup_failed" => "Mihomo startup failed".into(),
         "control_startup_failed" => "Controller: Startup Failed".into(),
+        "usage_quota_title" => "Traffic Quota".into(),
+        "usage_quota_warning" => "Profile \"{0}\" used {1} this month, {3}% of its {2} quota".into(),
+        "usage_quota_exceeded" => "Profile \"{0}\" used {1} this month, exceeding its {2} quota".into(),
 
         _ => key.to_string().into(),
     }
@@ -1,17 +1,18 @@
 use anyhow::anyhow;
-use infiltrator_desktop::MihomoRuntime;
+use infiltrator_desktop::{MihomoRuntime, SupervisorEvent};
 use infiltrator_admin::{
-    AdminEvent, EVENT_REBUILD_FAILED, EVENT_REBUILD_FINISHED, EVENT_REBUILD_STARTED,
+    AdminEvent, EVENT_CORE_CHANGED, EVENT_REBUILD_FAILED, EVENT_REBUILD_FINISHED, EVENT_REBUILD_STARTED,
+    EVENT_USAGE_QUOTA_REACHED,
 };
+use infiltrator_core::usage::UsageLedger;
 use log::{info, warn};
-use mihomo_api::TrafficData;
+use mihomo_api::{StreamOptions, TrafficData};
 use mihomo_config::ConfigManager;
 use mihomo_version::VersionManager;
 use serde::Serialize;
 use tauri::{AppHandle, Emitter};
 use tokio::time::{sleep, Duration, Instant};
 use async_trait::async_trait;
-use std::path::{Path, PathBuf};
 
 use crate::{
     app_state::AppState,
@@ -158,6 +159,8 @@ pub(crate) fn spawn_runtime(app: AppHandle, state: AppState) {
                 let runtime = handle.into_runtime();
                 register_runtime(&app, &state, runtime).await;
                 spawn_traffic_stream(app.clone(), state.clone());
+                spawn_connection_analytics(state.clone());
+                spawn_usage_ledger(state.clone());
                 spawn_config_monitor(state.clone());
                 if let Err(err) = refresh_tray_menu(&app_handle_for_refresh, &state_for_refresh).await {
                     warn!("initial tray menu refresh failed: {err}");
@@ -217,6 +220,10 @@ pub(crate) async fn register_runtime(
 ) {
     let controller = runtime.controller_url.clone();
     let config_path = runtime.config_path.to_string_lossy().to_string();
+    if let Err(err) = runtime.restore_proxy_selections().await {
+        warn!("failed to restore proxy selections: {err:#}");
+    }
+    spawn_core_watcher(app.clone(), state.clone(), runtime.subscribe_core_events());
     state.set_runtime(runtime).await;
     state.update_controller_info_text(format!("控制接口: {controller}")).await;
     if let Err(err) = app.emit("mihomo://ready", ReadyPayload { controller, config_path }) {
@@ -224,6 +231,62 @@ pub(crate) async fn register_runtime(
     }
 }
 
+/// Follow the supervisor of one runtime until it is replaced or dropped
+fn spawn_core_watcher(
+    app: AppHandle,
+    state: AppState,
+    mut events: tokio::sync::broadcast::Receiver<SupervisorEvent>,
+) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            match event {
+                SupervisorEvent::Exited { reason } => {
+                    warn!("mihomo core exited: {reason}");
+                    state.update_controller_info_text("控制接口: 内核异常退出，正在重启".to_string()).await;
+                    state.emit_admin_event(
+                        AdminEvent::new(EVENT_CORE_CHANGED).with_detail(format!("内核异常退出: {reason}")),
+                    );
+                }
+                SupervisorEvent::Started { restarts, .. } if restarts > 0 => {
+                    info!("mihomo core restarted (attempt {restarts})");
+                    if let Ok(runtime) = state.runtime().await {
+                        if let Err(err) = wait_for_controller_ready(&runtime).await {
+                            warn!("restarted core is not ready: {err:#}");
+                        } else if let Err(err) = runtime.restore_proxy_selections().await {
+                            warn!("failed to restore proxy selections: {err:#}");
+                        }
+                        state
+                            .update_controller_info_text(format!("控制接口: {}", runtime.controller_url))
+                            .await;
+                    }
+                    state.emit_admin_event(
+                        AdminEvent::new(EVENT_CORE_CHANGED).with_detail("内核已自动重启"),
+                    );
+                    let _ = refresh_tray_menu(&app, &state).await;
+                }
+                SupervisorEvent::GaveUp { reason } => {
+                    log::error!("mihomo core keeps crashing, giving up: {reason}");
+                    state.update_controller_info_text(format!("控制接口: 内核已崩溃 ({reason})")).await;
+                    state.emit_admin_event(
+                        AdminEvent::new(EVENT_CORE_CHANGED)
+                            .with_detail(format!("内核反复崩溃，已停止自动重启: {reason}")),
+                    );
+                    if let Err(err) = app.emit("mihomo://error", reason) {
+                        warn!("failed to emit runtime error event: {err}");
+                    }
+                    let _ = refresh_tray_menu(&app, &state).await;
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
 pub(crate) async fn rebuild_runtime(app: &AppHandle, state: &AppState) -> anyhow::Result<()> {
     let _guard = state.rebuild_lock.lock().await;
     rebuild_runtime_without_lock(app, state).await
@@ -234,6 +297,13 @@ pub(crate) async fn rebuild_runtime_without_lock(app: &AppHandle, state: &AppSta
     info!("runtime rebuild start");
     let result: anyhow::Result<()> = async {
     let previous_runtime = state.runtime().await.ok();
+    if let Some(runtime) = previous_runtime.as_ref()
+        && let Err(err) = preflight_runtime(app, state).await
+    {
+        // Keep the running core and its profile when the candidate is rejected
+        restore_running_profile(runtime).await;
+        return Err(err);
+    }
     let previous_controller_port = previous_runtime
         .as_ref()
         .and_then(|runtime| extract_port_from_url(&runtime.controller_url));
@@ -294,6 +364,37 @@ pub(crate) async fn rebuild_runtime_without_lock(app: &AppHandle, state: &AppSta
     result
 }
 
+async fn preflight_runtime(app: &AppHandle, state: &AppState) -> anyhow::Result<()> {
+    let vm = VersionManager::new()?;
+    let data_dir = app_data_dir(app)?;
+    let bundled_candidates = bundled_core_candidates(app);
+    let installed = vm.list_installed().await.unwrap_or_default();
+    let use_bundled = state.use_bundled_core().await || installed.is_empty();
+    MihomoRuntime::preflight(&vm, use_bundled, &bundled_candidates, &data_dir).await?;
+    Ok(())
+}
+
+async fn restore_running_profile(runtime: &MihomoRuntime) {
+    if let Err(err) = runtime.restore_loaded_config().await {
+        warn!("failed to restore running config: {err}");
+    }
+    let Some(profile) = runtime.config_path.file_stem().and_then(|stem| stem.to_str()) else {
+        return;
+    };
+    let manager = match ConfigManager::new() {
+        Ok(manager) => manager,
+        Err(err) => {
+            warn!("failed to restore running profile: {err}");
+            return;
+        }
+    };
+    if manager.get_current().await.ok().as_deref() != Some(profile)
+        && let Err(err) = manager.set_current(profile).await
+    {
+        warn!("failed to restore running profile {profile}: {err}");
+    }
+}
+
 async fn wait_for_controller_ready(runtime: &MihomoRuntime) -> anyhow::Result<()> {
     let deadline = Instant::now() + Duration::from_secs(15);
     let mut last_err = None;
@@ -313,6 +414,80 @@ async fn wait_for_controller_ready(runtime: &MihomoRuntime) -> anyhow::Result<()
     Err(anyhow!("控制接口未就绪: {}", last_err.map(|e| e.to_string()).unwrap_or_default()))
 }
 
+fn spawn_connection_analytics(state: AppState) {
+    tauri::async_runtime::spawn(async move {
+        let analytics = state.connection_analytics();
+        loop {
+            let client = match state.runtime().await {
+                Ok(runtime) => runtime.client(),
+                Err(_) => {
+                    sleep(Duration::from_secs(2)).await;
+                    continue;
+                }
+            };
+
+            let options = StreamOptions {
+                max_retries: Some(5),
+                ..StreamOptions::default()
+            };
+            if let Ok(mut rx) = client.stream_connections_with(options).await {
+                let usage = state.usage();
+                while let Some(snapshot) = rx.recv().await {
+                    analytics.ingest(&snapshot);
+                    usage.record_totals(snapshot.upload_total, snapshot.download_total);
+                }
+            }
+            sleep(Duration::from_secs(3)).await;
+        }
+    });
+}
+
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+fn spawn_usage_ledger(state: AppState) {
+    tauri::async_runtime::spawn(async move {
+        let ledger = loop {
+            match state.usage_ledger().await {
+                Ok(ledger) => break ledger,
+                Err(err) => {
+                    warn!("failed to open usage ledger: {err:#}");
+                    sleep(USAGE_FLUSH_INTERVAL).await;
+                }
+            }
+        };
+        loop {
+            sleep(USAGE_FLUSH_INTERVAL).await;
+            if let Err(err) = flush_usage(&state, &ledger).await {
+                warn!("failed to record traffic usage: {err:#}");
+            }
+        }
+    });
+}
+
+async fn flush_usage(state: &AppState, ledger: &UsageLedger) -> anyhow::Result<()> {
+    let totals = state.usage().take();
+    if totals.total() == 0 {
+        return Ok(());
+    }
+    let profile = ConfigManager::new()?.get_current().await?;
+    let day = chrono::Local::now().date_naive();
+    ledger
+        .add_usage(&profile, day, totals.upload, totals.download)
+        .await?;
+    for alert in ledger.check_quota(&profile, day).await? {
+        info!(
+            "profile {} reached {}% of its monthly quota",
+            alert.profile, alert.threshold
+        );
+        state.notify_usage_quota(&alert).await;
+        state.emit_admin_event(
+            AdminEvent::new(EVENT_USAGE_QUOTA_REACHED)
+                .with_detail(format!("{}: {}%", alert.profile, alert.threshold)),
+        );
+    }
+    Ok(())
+}
+
 fn spawn_traffic_stream(app: AppHandle, state: AppState) {
     tauri::async_runtime::spawn(async move {
         loop {
@@ -324,7 +499,12 @@ fn spawn_traffic_stream(app: AppHandle, state: AppState) {
                 }
             };
 
-            if let Ok(mut rx) = client.stream_traffic().await {
+            // Bounded retries let a rebuilt runtime on a new port hand us a fresh client.
+            let options = StreamOptions {
+                max_retries: Some(5),
+                ..StreamOptions::default()
+            };
+            if let Ok(mut rx) = client.stream_traffic_with(options).await {
                 while let Some(message) = rx.recv().await {
                     let _ = app.emit("mihomo://traffic", &TrafficEvent { message });
                 }
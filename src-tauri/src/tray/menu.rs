@@ -5,7 +5,7 @@ use std::{
 
 use infiltrator_core::profiles as core_profiles;
 use log::warn;
-use mihomo_api::ProxyInfo;
+use mihomo_api::{DelayHistoryStore, DelayTrend, ProxyInfo};
 use mihomo_version::{manager::VersionInfo, VersionManager};
 use tauri::{
     include_image,
@@ -18,6 +18,7 @@ use tokio::time::Duration;
 use crate::{
     app_state::{AppState, TrayInfoItems},
     autostart::is_autostart_enabled,
+    core_update::format_bytes,
     platform::{is_running_as_admin},
     frontend::open_frontend,
     locales::{Lang, Localizer},
@@ -434,7 +435,7 @@ async fn build_profile_switch_items(
     )?;
     items.push(Box::new(update_all_item));
 
-    if let Some(active) = active_profile
+    if let Some(active) = active_profile.as_ref()
         && active.subscription_url.is_some() {
             let auto_update_item = CheckMenuItem::with_id(
                 app,
@@ -447,6 +448,32 @@ async fn build_profile_switch_items(
             items.push(Box::new(auto_update_item));
         }
 
+    if let Some(info) = active_profile.as_ref().and_then(|p| p.subscription_info) {
+        items.push(Box::new(PredefinedMenuItem::separator(app)?));
+        let traffic = match info.remaining() {
+            Some(remaining) => lang
+                .tr("sub_traffic")
+                .replace("{0}", &format_bytes(info.used()))
+                .replace("{1}", &format_bytes(info.total))
+                .replace("{2}", &format_bytes(remaining)),
+            None => lang
+                .tr("sub_traffic_unlimited")
+                .replace("{0}", &format_bytes(info.used())),
+        };
+        let traffic_item =
+            MenuItem::with_id(app, "profile-sub-traffic", traffic, false, None::<&str>)?;
+        items.push(Box::new(traffic_item));
+        if let Some(expire) = info.expire {
+            let label = lang.tr("sub_expire").replace(
+                "{0}",
+                &expire.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string(),
+            );
+            let expire_item =
+                MenuItem::with_id(app, "profile-sub-expire", label, false, None::<&str>)?;
+            items.push(Box::new(expire_item));
+        }
+    }
+
     Ok(items)
 }
 
@@ -609,11 +636,12 @@ async fn build_proxy_groups_items(
         return Ok(vec![Box::new(empty_item)]);
     }
     groups.sort_by(|a, b| a.0.cmp(&b.0));
+    let history = state.delay_history();
 
     let max_groups = 5usize;
     let mut items: Vec<Box<dyn IsMenuItem<Wry>>> = Vec::new();
     for (name, info) in groups.iter().take(max_groups) {
-        let submenu = build_proxy_group_submenu(app, &proxies, name, info, proxy_map, lang)?;
+        let submenu = build_proxy_group_submenu(app, &proxies, &history, name, info, proxy_map, lang)?;
         items.push(Box::new(submenu));
     }
 
@@ -621,7 +649,7 @@ async fn build_proxy_groups_items(
         let mut overflow_submenus: Vec<Submenu<Wry>> = Vec::new();
         let mut overflow_items: Vec<&dyn IsMenuItem<Wry>> = Vec::new();
         for (name, info) in groups.iter().skip(max_groups) {
-            let submenu = build_proxy_group_submenu(app, &proxies, name, info, proxy_map, lang)?;
+            let submenu = build_proxy_group_submenu(app, &proxies, &history, name, info, proxy_map, lang)?;
             overflow_submenus.push(submenu);
         }
         for submenu in &overflow_submenus {
@@ -638,6 +666,7 @@ async fn build_proxy_groups_items(
 fn build_proxy_group_submenu(
     app: &AppHandle,
     proxies: &std::collections::HashMap<String, ProxyInfo>,
+    history: &DelayHistoryStore,
     group_name: &str,
     group_info: &ProxyInfo,
     proxy_map: &mut HashMap<String, (String, String)>,
@@ -660,7 +689,7 @@ fn build_proxy_group_submenu(
 
     let mut node_items: Vec<CheckMenuItem<Wry>> = Vec::new();
     for node in nodes.iter().take(max_nodes) {
-        let label = truncate_label(&build_proxy_node_label(proxies, node), 60);
+        let label = truncate_label(&build_proxy_node_label(proxies, history, node), 60);
         let menu_id = insert_proxy_menu_id(proxy_map, group_name, node);
         let item = CheckMenuItem::with_id(
             app,
@@ -679,7 +708,7 @@ fn build_proxy_group_submenu(
     if nodes.len() > max_nodes {
         let mut overflow_items: Vec<CheckMenuItem<Wry>> = Vec::new();
         for node in nodes.iter().skip(max_nodes) {
-            let label = truncate_label(&build_proxy_node_label(proxies, node), 60);
+            let label = truncate_label(&build_proxy_node_label(proxies, history, node), 60);
             let menu_id = insert_proxy_menu_id(proxy_map, group_name, node);
             let item = CheckMenuItem::with_id(
                 app,
@@ -708,8 +737,21 @@ fn build_proxy_group_submenu(
 
 pub(crate) fn build_proxy_node_label(
     proxies: &std::collections::HashMap<String, ProxyInfo>,
+    history: &DelayHistoryStore,
     node: &str,
 ) -> String {
+    // Prefer our own rolling history, which carries a trend, over the core's last probe.
+    if let Some(stats) = history.stats(node) {
+        let arrow = match stats.trend {
+            DelayTrend::Improving => " ↓",
+            DelayTrend::Stable => "",
+            DelayTrend::Degrading => " ↑",
+        };
+        return match stats.latest {
+            Some(delay) => format!("{node} ({delay}ms{arrow})"),
+            None => format!("{node} (timeout)"),
+        };
+    }
     if let Some(delay) = proxies
         .get(node)
         .and_then(|info| info.history.last().map(|entry| entry.delay))
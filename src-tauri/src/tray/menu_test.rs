@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::tray::menu::*;
-    use mihomo_api::{ProxyInfo, DelayHistory};
+    use mihomo_api::{DelayHistory, DelayHistoryStore, DelaySample, ProxyInfo};
 
     #[tokio::test]
     async fn test_build_menu_id() {
@@ -182,16 +182,30 @@ mod tests {
         };
         proxies.insert(node2.clone(), info2);
 
+        let history = DelayHistoryStore::default();
+
         // Test with delay
-        let label1 = build_proxy_node_label(&proxies, &node1);
+        let label1 = build_proxy_node_label(&proxies, &history, &node1);
         assert_eq!(label1, "node1 (150ms)");
 
         // Test without delay
-        let label2 = build_proxy_node_label(&proxies, &node2);
+        let label2 = build_proxy_node_label(&proxies, &history, &node2);
         assert_eq!(label2, "node2");
 
         // Test with non-existent node
-        let label3 = build_proxy_node_label(&proxies, "nonexistent");
+        let label3 = build_proxy_node_label(&proxies, &history, "nonexistent");
         assert_eq!(label3, "nonexistent");
     }
+
+    #[test]
+    fn test_build_proxy_node_label_with_history_trend() {
+        let proxies = std::collections::HashMap::new();
+        let history = DelayHistoryStore::default();
+        history.record_at("node1", DelaySample { timestamp: 1, delay: Some(100) });
+        history.record_at("node1", DelaySample { timestamp: 2, delay: Some(300) });
+        history.record_at("node2", DelaySample { timestamp: 1, delay: None });
+
+        assert_eq!(build_proxy_node_label(&proxies, &history, "node1"), "node1 (300ms ↑)");
+        assert_eq!(build_proxy_node_label(&proxies, &history, "node2"), "node2 (timeout)");
+    }
 }
\ No newline at end of file
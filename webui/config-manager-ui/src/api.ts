@@ -1,15 +1,29 @@
 ﻿import type {
   AppSettings,
   CacheFlushResponse,
+  ConflictKeep,
   CoreVersionsResponse,
   DnsConfig,
   FakeIpConfig,
   ProfileActionResponse,
   ProfileDetail,
   ProfileInfo,
+  ProxiesPayload,
+  ProxyDefinition,
+  ProxyGroupDefinition,
+  ProxyGroupsPayload,
   RebuildStatusResponse,
+  RuleProviderCacheResponse,
+  RuleProviderSearch,
+  RuleProviderStatus,
   RuleProvidersPayload,
   RulesPayload,
+  RuleTestQuery,
+  RuleTestResult,
+  ShareLinksResponse,
+  SyncConflict,
+  SyncConflictDetail,
+  SyncEncryptionStatus,
   SyncResult,
   TunConfig,
   WebDavConfig,
@@ -18,6 +32,14 @@
 const API_BASE = `${window.location.origin}/admin/api`;
 export const adminEventsUrl = `${API_BASE}/events`;
 
+/** Subscription URL serving the base64 share links of a profile */
+export const shareSubscriptionUrl = (profile: string) =>
+  `${API_BASE}/profiles/${encodeURIComponent(profile)}/share/subscription`;
+
+/** QR code image of one proxy, usable directly as an `<img>` source */
+export const shareQrUrl = (profile: string, proxy: string, format: 'svg' | 'png' = 'svg') =>
+  `${API_BASE}/profiles/${encodeURIComponent(profile)}/share/qr?proxy=${encodeURIComponent(proxy)}&format=${format}`;
+
 type RequestOptions = {
   method?: string;
   body?: unknown;
@@ -75,6 +97,15 @@ export const api = {
   syncWebDavNow: () => request<SyncResult>('webdav/sync', { method: 'POST' }),
   testWebDav: (config: WebDavConfig) =>
     request<void>('webdav/test', { method: 'POST', body: config }),
+  getSyncEncryption: () => request<SyncEncryptionStatus>('webdav/encryption'),
+  setSyncPassphrase: (passphrase: string) =>
+    request<void>('webdav/encryption', { method: 'POST', body: { passphrase } }),
+  clearSyncPassphrase: () => request<void>('webdav/encryption', { method: 'DELETE' }),
+  listSyncConflicts: () => request<{ conflicts: SyncConflict[] }>('webdav/conflicts'),
+  getSyncConflict: (path: string) =>
+    request<SyncConflictDetail>(`webdav/conflicts/detail?path=${encodeURIComponent(path)}`),
+  resolveSyncConflict: (path: string, keep: ConflictKeep, content?: string) =>
+    request<void>('webdav/conflicts/resolve', { method: 'POST', body: { path, keep, content } }),
   listProfiles: () => request<ProfileInfo[]>('profiles'),
   getProfile: (name: string) => request<ProfileDetail>(`profiles/${encodeURIComponent(name)}`),
   switchProfile: (name: string) =>
@@ -129,9 +160,50 @@ export const api = {
   getRuleProviders: () => request<RuleProvidersPayload>('rule-providers'),
   saveRuleProviders: (payload: RuleProvidersPayload) =>
     request<RuleProvidersPayload>('rule-providers', { method: 'POST', body: payload }),
+  getRuleProviderCache: () => request<RuleProviderCacheResponse>('rule-providers/cache'),
+  refreshRuleProviderCache: () =>
+    request<RuleProviderCacheResponse>('rule-providers/cache/refresh', {
+      method: 'POST',
+      timeoutMs: 120000,
+    }),
+  downloadRuleProvider: (name: string) =>
+    request<RuleProviderStatus>(`rule-providers/${encodeURIComponent(name)}/cache`, {
+      method: 'POST',
+      timeoutMs: 60000,
+    }),
+  searchRuleProvider: (name: string, query: string, limit = 200) =>
+    request<RuleProviderSearch>(
+      `rule-providers/${encodeURIComponent(name)}/search?q=${encodeURIComponent(query)}&limit=${limit}`,
+    ),
+  setRuleProviderOffline: (name: string, offline: boolean) =>
+    request<RuleProviderStatus>(`rule-providers/${encodeURIComponent(name)}/offline`, {
+      method: 'POST',
+      body: { offline },
+    }),
   getRules: () => request<RulesPayload>('rules'),
   saveRules: (payload: RulesPayload) =>
     request<RulesPayload>('rules', { method: 'POST', body: payload }),
+  testRules: (query: RuleTestQuery) =>
+    request<RuleTestResult>('rules/test', { method: 'POST', body: query }),
+  getShareLinks: (profile: string) =>
+    request<ShareLinksResponse>(`profiles/${encodeURIComponent(profile)}/share`),
+  getProxies: () => request<ProxiesPayload>('proxies'),
+  createProxy: (proxy: ProxyDefinition) =>
+    request<ProxyDefinition>('proxies', { method: 'POST', body: proxy }),
+  updateProxy: (name: string, proxy: ProxyDefinition) =>
+    request<ProxyDefinition>(`proxies/${encodeURIComponent(name)}`, { method: 'PUT', body: proxy }),
+  deleteProxy: (name: string) =>
+    request<void>(`proxies/${encodeURIComponent(name)}`, { method: 'DELETE' }),
+  getProxyGroups: () => request<ProxyGroupsPayload>('proxy-groups'),
+  createProxyGroup: (group: ProxyGroupDefinition) =>
+    request<ProxyGroupDefinition>('proxy-groups', { method: 'POST', body: group }),
+  updateProxyGroup: (name: string, group: ProxyGroupDefinition) =>
+    request<ProxyGroupDefinition>(`proxy-groups/${encodeURIComponent(name)}`, {
+      method: 'PUT',
+      body: group,
+    }),
+  deleteProxyGroup: (name: string) =>
+    request<void>(`proxy-groups/${encodeURIComponent(name)}`, { method: 'DELETE' }),
   getTunConfig: () => request<TunConfig>('tun'),
   saveTunConfig: (config: TunConfig) =>
     request<TunConfig>('tun', { method: 'POST', body: config }),
@@ -182,6 +182,12 @@ export function useProfileManager(options: ProfileManagerOptions) {
       importForm.url = '';
       importForm.name = '';
       options.setStatus(t('app.import_success'), result.profile.name);
+      if (result.unparsed_links?.length) {
+        options.pushToast(
+          t('app.unparsed_links', { count: result.unparsed_links.length }),
+          'warning',
+        );
+      }
       if (result.rebuild_scheduled) {
         options.busy.updateBusyDetail(t('app.switch_rebuild'));
         await options.waitForRebuild(t('app.importing_busy'));
@@ -324,6 +330,12 @@ export function useProfileManager(options: ProfileManagerOptions) {
     try {
       const result = await api.updateProfileNow(name);
       options.setStatus(t('app.update_sub_success'), result.profile.name);
+      if (result.unparsed_links?.length) {
+        options.pushToast(
+          t('app.unparsed_links', { count: result.unparsed_links.length }),
+          'warning',
+        );
+      }
       if (result.rebuild_scheduled) {
         options.busy.updateBusyDetail(t('app.switch_rebuild'));
         await options.waitForRebuild(t('app.update_sub_busy'));
@@ -23,9 +23,16 @@ export interface ProfileDetail {
   next_update?: string | null;
 }
 
+export interface UnparsedLink {
+  line: number;
+  scheme: string;
+  reason: string;
+}
+
 export interface ProfileActionResponse {
   profile: ProfileInfo;
   rebuild_scheduled: boolean;
+  unparsed_links?: UnparsedLink[];
 }
 
 export interface CoreVersionsResponse {
@@ -37,6 +44,8 @@ export interface RebuildStatusResponse {
   in_progress: boolean;
   last_error?: string | null;
   last_reason?: string | null;
+  last_method?: 'unchanged' | 'patch' | 'reload' | 'rebuild' | null;
+  last_detail?: string | null;
 }
 
 export interface WebDavConfig {
@@ -46,14 +55,68 @@ export interface WebDavConfig {
   password: string;
   sync_interval_mins: number;
   sync_on_startup: boolean;
+  conflict_policy?: ConflictPolicy;
+  encryption?: SyncEncryption;
+  backend?: SyncBackend;
+}
+
+export type SyncBackend =
+  | { type: 'webdav' }
+  | { type: 'local'; path: string }
+  | {
+      type: 's3';
+      endpoint: string;
+      region: string;
+      bucket: string;
+      prefix: string;
+      path_style: boolean;
+    };
+
+export interface SyncEncryption {
+  enabled: boolean;
+  obfuscate_names: boolean;
+}
+
+export interface SyncEncryptionStatus extends SyncEncryption {
+  passphrase_set: boolean;
+}
+
+export type ConflictStrategy =
+  | 'merge'
+  | 'keep-both'
+  | 'prefer-local'
+  | 'prefer-remote'
+  | 'newest-wins';
+
+export interface ConflictPolicy {
+  default: ConflictStrategy;
+  overrides: { pattern: string; strategy: ConflictStrategy }[];
 }
 
 export interface SyncResult {
   success_count: number;
   failed_count: number;
   total_actions: number;
+  conflict_count: number;
+}
+
+export interface SyncConflict {
+  path: string;
+  local_hash: string;
+  remote_etag: string;
+  reason: string;
+  detected_at: string;
+}
+
+export interface SyncConflictDetail {
+  conflict: SyncConflict;
+  base: string | null;
+  local: string | null;
+  remote: string;
 }
 
+export type ConflictKeep = 'local' | 'remote' | 'merged';
+
 export interface AppSettings {
   open_webui_on_startup: boolean;
   editor_path: string | null;
@@ -107,6 +170,30 @@ export interface RuleProvidersPayload {
 
 export interface RulesPayload {
   rules: RuleEntry[];
+  /** Rules kept verbatim because they could not be parsed, with the reason */
+  unrecognized?: string[];
+}
+
+export interface RuleTestQuery {
+  host?: string | null;
+  ip?: string | null;
+  src_ip?: string | null;
+  port?: number | null;
+  network?: string | null;
+  process?: string | null;
+}
+
+export interface SkippedRule {
+  index: number;
+  rule: string;
+  reason: string;
+}
+
+export interface RuleTestResult {
+  index: number | null;
+  rule: string | null;
+  policy: string;
+  skipped: SkippedRule[];
 }
 
 export interface RuleProvider {
@@ -118,6 +205,84 @@ export interface RuleProvider {
   format?: string;
 }
 
+export interface RuleProviderStatus {
+  name: string;
+  type: string;
+  behavior: 'domain' | 'ipcidr' | 'classical';
+  format: 'yaml' | 'text' | 'mrs';
+  url: string | null;
+  path: string | null;
+  offline: boolean;
+  entries: number | null;
+  size: number | null;
+  updated_at: string | null;
+  error: string | null;
+}
+
+export interface RuleProviderCacheResponse {
+  providers: RuleProviderStatus[];
+}
+
+export interface RuleProviderSearch {
+  name: string;
+  total: number;
+  matches: string[];
+}
+
+export type ProxyType =
+  | 'ss'
+  | 'vmess'
+  | 'vless'
+  | 'trojan'
+  | 'hysteria2'
+  | 'tuic'
+  | 'wireguard'
+  | 'socks5'
+  | 'http';
+
+/** One `proxies` entry; protocol fields use the profile's own keys */
+export interface ProxyDefinition {
+  name: string;
+  type: ProxyType | string;
+  server?: string;
+  port?: number;
+  [key: string]: unknown;
+}
+
+export interface ProxiesPayload {
+  proxies: ProxyDefinition[];
+}
+
+export interface ProxyGroupDefinition {
+  name: string;
+  type: 'select' | 'url-test' | 'fallback' | 'load-balance' | string;
+  proxies?: string[];
+  use?: string[];
+  url?: string;
+  interval?: number;
+  tolerance?: number;
+  timeout?: number;
+  lazy?: boolean;
+  strategy?: 'consistent-hashing' | 'round-robin' | 'sticky-sessions';
+  [key: string]: unknown;
+}
+
+export interface ProxyGroupsPayload {
+  groups: ProxyGroupDefinition[];
+}
+
+export interface ShareLink {
+  name: string;
+  type: string;
+  link: string | null;
+  error: string | null;
+}
+
+export interface ShareLinksResponse {
+  links: ShareLink[];
+  subscription: string;
+}
+
 export interface TunConfig {
   enable?: boolean;
   stack?: string;